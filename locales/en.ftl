@@ -0,0 +1,58 @@
+# Messages shown by the CLI, in English. The other files in this directory
+# translate these messages (by their ID); any message which a translation is
+# missing is shown in English instead.
+
+## Shared
+
+room = Room
+team = Team
+debate = Debate { $id }
+
+## view-draw
+
+draw-none = No draw for this round
+draw-id = id
+draw-nb = Nb
+draw-prop = Prop
+draw-opp = Opp
+draw-og = OG
+draw-oo = OO
+draw-cg = CG
+draw-co = CO
+draw-panel = Panel
+draw-strength = Strength
+draw-sides-not-confirmed = Sides not confirmed!
+draw-conflicts-with = conflicts with { $names }
+draw-judged-before = has judged { $names } before
+draw-bracket = Bracket
+draw-room-rank = Room rank
+draw-importance = Importance
+draw-result-status = Result status
+draw-sides-confirmed = Sides confirmed
+draw-flags = Flags
+draw-team-flags = Team flags
+
+## break live
+
+break-rank = Rank
+break-points = Points
+break-best-case = Best case
+break-status = Status
+break-safe = safe
+break-live = live
+break-dead = dead
+break-remaining = { $count } preliminary round(s) remain
+break-summary = { $category } (breaking { $size }): { $safe } safe, { $live } live, { $dead } dead
+
+## feedback missing
+
+feedback-institution = Institution
+feedback-participant = Participant
+feedback-role = Role
+feedback-role-team = team
+feedback-role-chair = chair
+feedback-role-adjudicator = adjudicator
+feedback-missing-on = Missing feedback on
+feedback-oral = the oral adjudication
+feedback-all-in = All { $total } required feedback submission(s) for { $round } are in
+feedback-rate = { $submitted } of { $total } required feedback submission(s) for { $round } are in ({ $percent }%)
@@ -0,0 +1,56 @@
+# Mensajes de la CLI en español (ver en.ftl).
+
+## Shared
+
+room = Sala
+team = Equipo
+debate = Debate { $id }
+
+## view-draw
+
+draw-none = No hay emparejamientos para esta ronda
+draw-id = id
+draw-nb = Nota
+draw-prop = Gob.
+draw-opp = Opos.
+draw-og = GA
+draw-oo = OA
+draw-cg = GB
+draw-co = OB
+draw-panel = Panel
+draw-strength = Nivel
+draw-sides-not-confirmed = ¡Posiciones sin confirmar!
+draw-conflicts-with = en conflicto con { $names }
+draw-judged-before = ya ha juzgado a { $names }
+draw-bracket = Grupo
+draw-room-rank = Rango de sala
+draw-importance = Importancia
+draw-result-status = Estado del resultado
+draw-sides-confirmed = Posiciones confirmadas
+draw-flags = Avisos
+draw-team-flags = Avisos de equipos
+
+## break live
+
+break-rank = Puesto
+break-points = Puntos
+break-best-case = Mejor caso
+break-status = Estado
+break-safe = clasificado
+break-live = en juego
+break-dead = eliminado
+break-remaining = Quedan { $count } ronda(s) preliminar(es)
+break-summary = { $category } (clasifican { $size }): { $safe } clasificado(s), { $live } en juego, { $dead } eliminado(s)
+
+## feedback missing
+
+feedback-institution = Institución
+feedback-participant = Participante
+feedback-role = Función
+feedback-role-team = equipo
+feedback-role-chair = presidente
+feedback-role-adjudicator = juez
+feedback-missing-on = Evaluación pendiente sobre
+feedback-oral = la adjudicación oral
+feedback-all-in = Se han recibido las { $total } evaluaciones requeridas para { $round }
+feedback-rate = Se han recibido { $submitted } de { $total } evaluaciones requeridas para { $round } ({ $percent } %)
@@ -0,0 +1,56 @@
+# Messages de la CLI en français (voir en.ftl).
+
+## Shared
+
+room = Salle
+team = Équipe
+debate = Débat { $id }
+
+## view-draw
+
+draw-none = Aucun tirage pour cette manche
+draw-id = id
+draw-nb = Nb
+draw-prop = Gouv.
+draw-opp = Opp.
+draw-og = PG
+draw-oo = PO
+draw-cg = SG
+draw-co = SO
+draw-panel = Jury
+draw-strength = Niveau
+draw-sides-not-confirmed = Côtés non confirmés !
+draw-conflicts-with = en conflit avec { $names }
+draw-judged-before = a déjà jugé { $names }
+draw-bracket = Groupe
+draw-room-rank = Rang de la salle
+draw-importance = Importance
+draw-result-status = État du résultat
+draw-sides-confirmed = Côtés confirmés
+draw-flags = Alertes
+draw-team-flags = Alertes des équipes
+
+## break live
+
+break-rank = Rang
+break-points = Points
+break-best-case = Meilleur cas
+break-status = Statut
+break-safe = qualifiée
+break-live = en lice
+break-dead = éliminée
+break-remaining = Il reste { $count } manche(s) préliminaire(s)
+break-summary = { $category } ({ $size } qualifiées) : { $safe } qualifiée(s), { $live } en lice, { $dead } éliminée(s)
+
+## feedback missing
+
+feedback-institution = Institution
+feedback-participant = Participant
+feedback-role = Rôle
+feedback-role-team = équipe
+feedback-role-chair = président
+feedback-role-adjudicator = juge
+feedback-missing-on = Évaluation manquante sur
+feedback-oral = l'adjudication orale
+feedback-all-in = Les { $total } évaluations requises pour { $round } ont toutes été reçues
+feedback-rate = { $submitted } des { $total } évaluations requises pour { $round } ont été reçues ({ $percent } %)
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, process::exit};
+
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use tabbycat_api::types::Adjudicator;
+use tracing::{Level, error, info, span};
+
+use crate::{
+    Auth, api_utils::get_judges, import::optional_bool_from_str, names::names_match, open_csv_file,
+    request_manager::RequestManager,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+struct AdjCoreRow {
+    /// The name (or ID) of the judge.
+    name: String,
+    /// Whether the judge is on the adjudication core (defaults to true).
+    #[serde(default, deserialize_with = "optional_bool_from_str")]
+    is_ca: Option<bool>,
+    /// Whether the judge is an independent adjudicator (left unchanged if
+    /// empty).
+    #[serde(default, deserialize_with = "optional_bool_from_str")]
+    is_ia: Option<bool>,
+}
+
+/// The flags which should be set on a judge (`None` leaves the flag as it
+/// is).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Flags {
+    adj_core: Option<bool>,
+    independent: Option<bool>,
+}
+
+/// The body of the request which changes a judge's flags from `current` to
+/// `wanted`, or `None` if nothing needs to change.
+fn payload(current: Flags, wanted: Flags) -> Option<Value> {
+    let mut payload = Map::new();
+    if let Some(adj_core) = wanted
+        .adj_core
+        .filter(|&value| current.adj_core != Some(value))
+    {
+        payload.insert("adj_core".to_string(), json!(adj_core));
+    }
+    if let Some(independent) = wanted
+        .independent
+        .filter(|&value| current.independent != Some(value))
+    {
+        payload.insert("independent".to_string(), json!(independent));
+    }
+    (!payload.is_empty()).then_some(Value::Object(payload))
+}
+
+fn find_judge<'j>(judges: &'j [Adjudicator], name: &str) -> &'j Adjudicator {
+    judges
+        .iter()
+        .find(|judge| names_match(&judge.name, name) || judge.id.to_string() == name.trim())
+        .unwrap_or_else(|| {
+            error!("No adjudicator named {name} exists");
+            exit(1);
+        })
+}
+
+/// Sets (or, if `unset` is given, unsets) the adj core flag of the judges
+/// named in `names`, or sets the flags given in the `is_ca` and `is_ia`
+/// columns of a CSV file. With `independent`, judges in `names` are also
+/// marked as independent adjudicators, and with `reset` every judge who is
+/// not listed is removed from the adj core.
+pub async fn set_adj_core(
+    auth: Auth,
+    names: Vec<String>,
+    csv: Option<String>,
+    unset: bool,
+    independent: bool,
+    reset: bool,
+) {
+    let manager = RequestManager::new(&auth.api_key);
+    let judges = get_judges(&auth, manager.clone()).await;
+
+    let mut flags: HashMap<String, Flags> = HashMap::new();
+    match open_csv_file(csv, true) {
+        Some(mut csv) => {
+            for row in csv.deserialize::<AdjCoreRow>() {
+                let row = row.unwrap();
+                let judge = find_judge(&judges, &row.name);
+                flags.insert(
+                    judge.url.clone(),
+                    Flags {
+                        adj_core: Some(row.is_ca.unwrap_or(true)),
+                        independent: row.is_ia,
+                    },
+                );
+            }
+        }
+        None => {
+            for name in names.iter().filter(|name| !name.trim().is_empty()) {
+                let judge = find_judge(&judges, name);
+                flags.insert(
+                    judge.url.clone(),
+                    Flags {
+                        adj_core: Some(!unset),
+                        independent: independent.then_some(!unset),
+                    },
+                );
+            }
+        }
+    }
+    if reset {
+        for judge in &judges {
+            flags.entry(judge.url.clone()).or_insert(Flags {
+                adj_core: Some(false),
+                independent: None,
+            });
+        }
+    }
+
+    let span = span!(Level::INFO, "set_adj_core");
+    let _guard = span.enter();
+
+    let mut changed = 0;
+    for judge in &judges {
+        let Some(payload) = flags.get(&judge.url).and_then(|wanted| {
+            let current = Flags {
+                adj_core: judge.adj_core,
+                independent: judge.independent,
+            };
+            self::payload(current, *wanted)
+        }) else {
+            continue;
+        };
+
+        let resp = manager
+            .send_request(|| {
+                manager
+                    .client
+                    .patch(&judge.url)
+                    .json(&payload)
+                    .build()
+                    .unwrap()
+            })
+            .await;
+        if !resp.status().is_success() {
+            error!(
+                "Failed to update {}: {} {}",
+                judge.name,
+                resp.status(),
+                resp.text().await.unwrap()
+            );
+            exit(1);
+        }
+
+        changed += 1;
+        info!("Updated {} ({payload})", judge.name);
+    }
+
+    info!("{changed} judge(s) updated");
+}
+
+#[cfg(test)]
+#[test]
+fn test_payload() {
+    let current = Flags {
+        adj_core: Some(true),
+        independent: None,
+    };
+
+    assert_eq!(
+        payload(
+            current,
+            Flags {
+                adj_core: Some(true),
+                independent: None
+            }
+        ),
+        None
+    );
+    assert_eq!(
+        payload(
+            current,
+            Flags {
+                adj_core: Some(false),
+                independent: Some(true)
+            }
+        ),
+        Some(json!({ "adj_core": false, "independent": true }))
+    );
+}
@@ -0,0 +1,102 @@
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+/// The environment variable holding the key used to derive pseudonyms. With
+/// the same key, the same participant receives the same pseudonym, so exports
+/// made at different times can still be joined.
+pub const KEY_VARIABLE: &str = "TABBYCAT_ANONYMISE_KEY";
+
+/// Replaces the names (and API URLs, which can be used to look the names up)
+/// of participants with pseudonyms, so that exported data can be shared
+/// without leaking personal data. Pseudonyms are derived from the URL with a
+/// keyed hash, so they cannot be reversed (e.g. by hashing the URLs of every
+/// participant) without the key.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Anonymiser {
+    /// The key, if anonymisation is enabled.
+    key: Option<[u8; 32]>,
+}
+
+impl Anonymiser {
+    /// Uses the key in [`KEY_VARIABLE`] if it is set, or otherwise a random
+    /// key (so the pseudonyms only match within this export).
+    pub fn new(enabled: bool) -> Self {
+        if !enabled {
+            return Self::default();
+        }
+        let key = match std::env::var(KEY_VARIABLE) {
+            Ok(key) if !key.is_empty() => Sha256::digest(key.as_bytes()).into(),
+            _ => {
+                info!(
+                    "{KEY_VARIABLE} is not set, so the pseudonyms in this export will not \
+                    match those in other exports."
+                );
+                rand::rng().random()
+            }
+        };
+        Self::with_key(key)
+    }
+
+    fn with_key(key: [u8; 32]) -> Self {
+        Self { key: Some(key) }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// HMAC-SHA256 of `url`, under `key`.
+    fn digest(key: &[u8; 32], url: &str) -> [u8; 32] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(url.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    /// Returns a pseudonym such as `Judge 1a2b3c4d` for the participant with
+    /// the given URL, or `name` unchanged if anonymisation is disabled.
+    pub fn name(&self, kind: &str, url: &str, name: &str) -> String {
+        match &self.key {
+            Some(key) => format!("{kind} {}", hex(&Self::digest(key, url)[..4])),
+            None => name.to_string(),
+        }
+    }
+
+    /// Replaces an API URL with an opaque identifier.
+    pub fn url(&self, url: &str) -> String {
+        match &self.key {
+            Some(key) => format!("anonymous:{}", hex(&Self::digest(key, url)[..8])),
+            None => url.to_string(),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test_pseudonyms_are_stable() {
+    let anon = Anonymiser::with_key([7; 32]);
+    let url = "https://example.calicotab.com/api/v1/tournaments/t/adjudicators/1";
+
+    assert_eq!(
+        anon.name("Judge", url, "Jana Fuchs"),
+        anon.name("Judge", url, "Jana Fuchs")
+    );
+    assert_ne!(
+        anon.name("Judge", url, "Jana Fuchs"),
+        anon.name("Judge", &format!("{url}0"), "Jana Fuchs")
+    );
+    // pseudonyms depend on the key
+    assert_ne!(
+        anon.name("Judge", url, "Jana Fuchs"),
+        Anonymiser::with_key([8; 32]).name("Judge", url, "Jana Fuchs")
+    );
+    assert_eq!(
+        Anonymiser::new(false).name("Judge", url, "Jana Fuchs"),
+        "Jana Fuchs"
+    );
+}
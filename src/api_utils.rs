@@ -1,6 +1,14 @@
+use std::process::exit;
+
 use tabbycat_api::types::RoundPairing;
+use tracing::error;
 
-use crate::{Auth, dispatch_req::json_of_resp, request_manager::RequestManager};
+use crate::{
+    Auth,
+    dispatch_req::json_of_resp,
+    names::{names_match, normalise},
+    request_manager::{AVAILABILITIES_API, BALLOTS_API, PREFERENCES_API, RequestManager},
+};
 
 pub async fn get_feedback_questions(
     Auth {
@@ -17,7 +25,7 @@ pub async fn get_feedback_questions(
         .send_request(|| manager.client.get(&base_url).build().unwrap())
         .await;
 
-    resp.json().await.unwrap()
+    json_of_resp(resp).await
 }
 
 pub async fn get_feedbacks(
@@ -35,7 +43,20 @@ pub async fn get_feedbacks(
         .send_request(|| manager.client.get(&base_url).build().unwrap())
         .await;
 
-    resp.json().await.unwrap()
+    json_of_resp(resp).await
+}
+
+/// Like [`get_feedbacks`], but returns the error instead of stopping if the
+/// feedback could not be fetched, for commands which poll Tabbycat.
+pub async fn try_get_feedbacks(
+    auth: &Auth,
+    manager: RequestManager,
+) -> Result<Vec<tabbycat_api::types::Feedback>, String> {
+    let base_url = format!(
+        "{}/api/v1/tournaments/{}/feedback",
+        auth.tabbycat_url, auth.tournament_slug
+    );
+    try_json_of(&manager, || manager.client.get(&base_url).build().unwrap()).await
 }
 
 pub async fn get_rounds(
@@ -53,7 +74,7 @@ pub async fn get_rounds(
         .send_request(|| manager.client.get(&base_url).build().unwrap())
         .await;
 
-    resp.json().await.unwrap()
+    json_of_resp(resp).await
 }
 
 pub async fn get_teams(
@@ -71,7 +92,7 @@ pub async fn get_teams(
         .send_request(|| manager.client.get(&base_url).build().unwrap())
         .await;
 
-    resp.json().await.unwrap()
+    json_of_resp(resp).await
 }
 
 pub async fn get_judges(
@@ -89,23 +110,93 @@ pub async fn get_judges(
         .send_request(|| manager.client.get(&base_url).build().unwrap())
         .await;
 
-    resp.json().await.unwrap()
+    json_of_resp(resp).await
+}
+
+/// Like [`get_judges`], but returns the error instead of stopping if the
+/// judges could not be fetched, for commands which poll Tabbycat.
+pub async fn try_get_judges(
+    auth: &Auth,
+    manager: RequestManager,
+) -> Result<Vec<tabbycat_api::types::Adjudicator>, String> {
+    let base_url = format!(
+        "{}/api/v1/tournaments/{}/adjudicators",
+        auth.tabbycat_url, auth.tournament_slug
+    );
+    try_json_of(&manager, || manager.client.get(&base_url).build().unwrap()).await
+}
+
+/// Finds the round a user means by `query`: the round with that name or
+/// abbreviation, or failing that with that sequence number (e.g. `4`), or
+/// failing that the only round whose name or abbreviation starts with it.
+/// Names are compared as by [`normalise`]. `keys` gives the sequence number,
+/// name and abbreviation of a round.
+fn find_round<'r, R>(
+    query: &str,
+    rounds: &'r [R],
+    keys: impl Fn(&R) -> [String; 3],
+) -> Result<&'r R, String> {
+    let original = query;
+    let query = normalise(query);
+    let keyed = rounds
+        .iter()
+        .map(|round| (round, keys(round).map(|key| normalise(&key))))
+        .collect::<Vec<_>>();
+
+    if let Some((round, _)) = keyed
+        .iter()
+        .find(|(_, [_, name, abbreviation])| *name == query || *abbreviation == query)
+    {
+        return Ok(round);
+    }
+    if let Some((round, _)) = keyed.iter().find(|(_, [seq, _, _])| *seq == query) {
+        return Ok(round);
+    }
+
+    let prefixed = keyed
+        .iter()
+        .filter(|(_, [_, name, abbreviation])| {
+            !query.is_empty() && (name.starts_with(&query) || abbreviation.starts_with(&query))
+        })
+        .collect::<Vec<_>>();
+    match prefixed.as_slice() {
+        [(round, _)] => Ok(round),
+        [] => Err(format!(
+            "There is no round called `{original}` (give its name, abbreviation or number)"
+        )),
+        candidates => Err(format!(
+            "`{original}` could mean any of {}",
+            candidates
+                .iter()
+                .map(|(round, _)| keys(round)[1].clone())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
 }
 
+/// Fetches the round given by the user (by name, abbreviation, sequence number
+/// or an unambiguous prefix of its name), exiting if there is no such round.
 pub async fn get_round(
     round: &str,
     auth: &Auth,
     manager: RequestManager,
 ) -> tabbycat_api::types::Round {
     let rounds = get_rounds(auth, manager.clone()).await;
-    let round = rounds
-        .iter()
-        .find(|r| {
-            r.abbreviation.as_str().eq_ignore_ascii_case(round)
-                || r.name.as_str().eq_ignore_ascii_case(round)
-        })
-        .expect("the round you specified does not exist");
-    round.clone()
+    let found = find_round(round, &rounds, |r| {
+        [
+            r.seq.to_string(),
+            r.name.as_str().to_string(),
+            r.abbreviation.as_str().to_string(),
+        ]
+    });
+    match found {
+        Ok(round) => round.clone(),
+        Err(e) => {
+            error!("{e}");
+            exit(1);
+        }
+    }
 }
 
 pub async fn pairings_of_round(
@@ -127,6 +218,49 @@ pub async fn pairings_of_round(
     json_of_resp(resp).await
 }
 
+/// Like [`pairings_of_round`], but returns the error instead of stopping if
+/// the pairings could not be fetched, for commands which poll Tabbycat.
+pub async fn try_pairings_of_round(
+    round: &tabbycat_api::types::Round,
+    manager: RequestManager,
+) -> Result<Vec<RoundPairing>, String> {
+    try_json_of(&manager, || {
+        manager.client.get(&round.links.pairing).build().unwrap()
+    })
+    .await
+}
+
+/// Fetches a round and its pairings (the pairings can only be requested once
+/// the round is known, so this is useful for fetching both concurrently with
+/// other data, e.g. in a `tokio::join!`).
+pub async fn get_round_and_pairings(
+    round: &str,
+    auth: &Auth,
+    manager: RequestManager,
+) -> (tabbycat_api::types::Round, Vec<RoundPairing>) {
+    let round = get_round(round, auth, manager.clone()).await;
+    let pairings = pairings_of_round(auth, &round, manager).await;
+    (round, pairings)
+}
+
+/// Finds the debate in `room`, which is either the name of a room or the ID
+/// of a debate.
+pub fn pairing_in_room<'r>(
+    pairings: &'r [RoundPairing],
+    venues: &[tabbycat_api::types::Venue],
+    room: &str,
+) -> Option<&'r RoundPairing> {
+    pairings.iter().find(|pairing| {
+        pairing.id.to_string() == room.trim()
+            || pairing
+                .venue
+                .as_ref()
+                .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+                .map(|venue| names_match(&venue.name, room))
+                .unwrap_or(false)
+    })
+}
+
 pub async fn get_institutions(
     auth: &Auth,
     manager: RequestManager,
@@ -141,5 +275,238 @@ pub async fn get_institutions(
         })
         .await;
 
-    resp.json().await.unwrap()
+    json_of_resp(resp).await
+}
+
+/// Marks the participants (adjudicators, teams or venues, identified by their
+/// API URLs) as available or unavailable for the given round.
+pub async fn set_availability(
+    auth: &Auth,
+    round: &tabbycat_api::types::Round,
+    urls: &[String],
+    available: bool,
+    manager: RequestManager,
+) -> reqwest::Response {
+    manager
+        .require_version(
+            &auth.tabbycat_url,
+            AVAILABILITIES_API,
+            "Setting availability",
+        )
+        .await;
+
+    let url = format!(
+        "{}/api/v1/tournaments/{}/rounds/{}/availabilities",
+        auth.tabbycat_url, auth.tournament_slug, round.seq
+    );
+
+    manager
+        .send_request(|| {
+            let req = if available {
+                manager.client.put(&url)
+            } else {
+                manager.client.post(&url)
+            };
+            req.json(&urls).build().unwrap()
+        })
+        .await
+}
+
+pub async fn get_speaker_categories(
+    auth: &Auth,
+    manager: RequestManager,
+) -> Vec<tabbycat_api::types::SpeakerCategory> {
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .get(format!(
+                    "{}/api/v1/tournaments/{}/speaker-categories",
+                    auth.tabbycat_url, auth.tournament_slug
+                ))
+                .build()
+                .unwrap()
+        })
+        .await;
+
+    json_of_resp(resp).await
+}
+
+pub async fn get_venues(auth: &Auth, manager: RequestManager) -> Vec<tabbycat_api::types::Venue> {
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .get(format!(
+                    "{}/api/v1/tournaments/{}/venues",
+                    auth.tabbycat_url, auth.tournament_slug
+                ))
+                .build()
+                .unwrap()
+        })
+        .await;
+
+    json_of_resp(resp).await
+}
+
+pub async fn get_break_categories(
+    auth: &Auth,
+    manager: RequestManager,
+) -> Vec<tabbycat_api::types::BreakCategory> {
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .get(format!(
+                    "{}/api/v1/tournaments/{}/break-categories",
+                    auth.tabbycat_url, auth.tournament_slug
+                ))
+                .build()
+                .unwrap()
+        })
+        .await;
+
+    json_of_resp(resp).await
+}
+
+/// Fetches the teams which broke in a break category (empty if the break has
+/// not been generated yet). These are returned as JSON, with the `team`'s URL
+/// and its `rank` and `break_rank`.
+pub async fn get_breaking_teams(
+    category: &tabbycat_api::types::BreakCategory,
+    manager: RequestManager,
+) -> Vec<serde_json::Value> {
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .get(format!("{}/break", category.url))
+                .build()
+                .unwrap()
+        })
+        .await;
+
+    json_of_resp(resp).await
+}
+
+/// Fetches the value of one of the tournament's preferences (e.g.
+/// `debate_rules__teams_in_debate`).
+pub async fn get_preference(auth: &Auth, name: &str, manager: RequestManager) -> serde_json::Value {
+    manager
+        .require_version(&auth.tabbycat_url, PREFERENCES_API, "Reading preferences")
+        .await;
+
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .get(format!(
+                    "{}/api/v1/tournaments/{}/preferences/{name}",
+                    auth.tabbycat_url, auth.tournament_slug
+                ))
+                .build()
+                .unwrap()
+        })
+        .await;
+
+    let preference: tabbycat_api::types::Preference = json_of_resp(resp).await;
+    preference.value
+}
+
+/// Returns the API URLs of the participants (adjudicators, teams and venues)
+/// which are available for the given round.
+pub async fn get_availabilities(
+    auth: &Auth,
+    round: &tabbycat_api::types::Round,
+    manager: RequestManager,
+) -> Vec<String> {
+    manager
+        .require_version(
+            &auth.tabbycat_url,
+            AVAILABILITIES_API,
+            "Reading availability",
+        )
+        .await;
+
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .get(format!(
+                    "{}/api/v1/tournaments/{}/rounds/{}/availabilities",
+                    auth.tabbycat_url, auth.tournament_slug, round.seq
+                ))
+                .build()
+                .unwrap()
+        })
+        .await;
+
+    json_of_resp(resp).await
+}
+
+/// Fetches the ballots submitted for a debate. These are returned as JSON, as
+/// their structure depends on the format of the tournament.
+pub async fn get_ballots(
+    auth: &Auth,
+    pairing: &RoundPairing,
+    manager: RequestManager,
+) -> Vec<serde_json::Value> {
+    manager
+        .require_version(&auth.tabbycat_url, BALLOTS_API, "Reading ballots")
+        .await;
+
+    let resp = manager
+        .send_request(|| manager.client.get(&pairing.links.ballots).build().unwrap())
+        .await;
+
+    json_of_resp(resp).await
+}
+
+/// Like [`get_ballots`], but returns the error instead of stopping if the
+/// ballots could not be fetched, for commands which poll Tabbycat (and so can
+/// try again later).
+pub async fn try_get_ballots(
+    auth: &Auth,
+    pairing: &RoundPairing,
+    manager: RequestManager,
+) -> Result<Vec<serde_json::Value>, String> {
+    manager
+        .require_version(&auth.tabbycat_url, BALLOTS_API, "Reading ballots")
+        .await;
+
+    try_json_of(&manager, || {
+        manager.client.get(&pairing.links.ballots).build().unwrap()
+    })
+    .await
+}
+
+/// Sends the request, and parses the response as JSON, returning an error
+/// (rather than stopping) if either fails.
+async fn try_json_of<T: serde::de::DeserializeOwned>(
+    manager: &RequestManager,
+    get_request: impl Fn() -> reqwest::Request,
+) -> Result<T, String> {
+    let resp = manager.try_send_request(get_request).await?;
+    resp.json().await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+#[test]
+fn test_find_round() {
+    let rounds = [
+        ["1", "Round 1", "R1"],
+        ["2", "Round 2", "R2"],
+        ["3", "Octofinals", "OF"],
+        ["4", "Quarterfinals", "QF"],
+        ["5", "Grand Final", "GF"],
+    ];
+    let keys = |round: &[&str; 3]| round.map(str::to_string);
+    let find = |query| find_round(query, &rounds, keys).map(|round| round[0]);
+
+    assert_eq!(find("r2"), Ok("2"));
+    assert_eq!(find("grand  FINAL"), Ok("5"));
+    assert_eq!(find("4"), Ok("4"));
+    assert_eq!(find("Oct"), Ok("3"));
+    assert!(find("Round").unwrap_err().contains("Round 1, Round 2"));
+    assert!(find("Semifinals").is_err());
 }
@@ -0,0 +1,773 @@
+use std::{
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use tabbycat_api::types::{Round, RoundPairing};
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    anonymise::Anonymiser,
+    api_utils::{
+        get_ballots, get_break_categories, get_institutions, get_judges, get_rounds,
+        get_speaker_categories, get_teams, get_venues, pairings_of_round,
+    },
+    dispatch_req::json_of_resp,
+    finals::debate_winners,
+    request_manager::RequestManager,
+    standings::{fetch_standings_table, get_speaker_standings, speaker_tab},
+    tabular::column_names,
+};
+
+/// The template every page of the archive is rendered with. Templates whose
+/// name ends in `.html` are escaped by Tera.
+const PAGE_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{ title }} – {{ tournament }}</title>
+<style>
+body { font-family: sans-serif; margin: 2em auto; max-width: 70em; padding: 0 1em; }
+nav a { margin-right: 1em; }
+table { border-collapse: collapse; margin-bottom: 2em; width: 100%; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #f3f3f3; }
+</style>
+</head>
+<body>
+<h1>{{ tournament }}</h1>
+<nav>{% for link in nav %}<a href="{{ link.href }}">{{ link.title }}</a>{% endfor %}</nav>
+<h2>{{ title }}</h2>
+{% for section in sections %}
+{% if section.title %}<h3>{{ section.title }}</h3>{% endif %}
+{% if section.rows %}
+<table>
+<thead><tr>{% for cell in section.header %}<th>{{ cell }}</th>{% endfor %}</tr></thead>
+<tbody>
+{% for row in section.rows %}<tr>{% for cell in row %}<td>{{ cell | escape | linebreaksbr | safe }}</td>{% endfor %}</tr>
+{% endfor %}</tbody>
+</table>
+{% else %}
+<p>Nothing to show.</p>
+{% endif %}
+{% endfor %}
+<footer><p>Archived on {{ archived }}.</p></footer>
+</body>
+</html>
+"#;
+
+/// The directory (within the archive) containing the data the archive was
+/// made from, as returned by the API, which `tabbycat import --from-archive`
+/// reads.
+const DATA_DIR: &str = "data";
+
+#[derive(Serialize, Debug, Clone)]
+struct Link {
+    href: String,
+    title: String,
+}
+
+/// A single table on a page.
+#[derive(Serialize, Debug, Clone)]
+struct Section {
+    title: String,
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct Page {
+    tournament: String,
+    title: String,
+    nav: Vec<Link>,
+    sections: Vec<Section>,
+    archived: String,
+}
+
+/// The file name of the page for a round.
+fn round_page(round: &Round) -> String {
+    format!("round-{}.html", round.seq)
+}
+
+/// Summarises the result of a debate from its confirmed ballot: the teams in
+/// order of points (in formats where teams receive points) or the winner.
+fn result_summary(
+    ballot: &Value,
+    pairing: &RoundPairing,
+    team_name: impl Fn(&str) -> String,
+) -> String {
+    let teams = ballot["result"]["sheets"][0]["teams"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let team_of = |team: &Value| {
+        team["team"]
+            .as_str()
+            .map(|url| url.to_string())
+            .or_else(|| {
+                pairing
+                    .teams
+                    .iter()
+                    .find(|debate_team| {
+                        serde_json::to_value(&debate_team.side).ok().as_ref() == Some(&team["side"])
+                    })
+                    .map(|debate_team| debate_team.team.clone())
+            })
+    };
+
+    if teams.len() > 2 && teams.iter().all(|team| team["points"].is_number()) {
+        let mut ranked = teams
+            .iter()
+            .filter_map(|team| Some((team_of(team)?, team["points"].as_f64()?)))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        return ranked
+            .iter()
+            .map(|(url, points)| format!("{} ({points})", team_name(url)))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    let winners = debate_winners(ballot, pairing);
+    if winners.is_empty() {
+        return String::new();
+    }
+    format!(
+        "{} won",
+        winners
+            .iter()
+            .map(|url| team_name(url))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// The fields of each judge kept in the archive's data (for `tabbycat import
+/// --from-archive` and `tabbycat clashes infer --prior`).
+const JUDGE_FIELDS: &[&str] = &[
+    "url",
+    "id",
+    "name",
+    "institution",
+    "institution_conflicts",
+    "team_conflicts",
+    "adjudicator_conflicts",
+    "adj_core",
+    "independent",
+    "anonymous",
+    "base_score",
+];
+/// The fields of each team kept in the archive's data.
+const TEAM_FIELDS: &[&str] = &[
+    "url",
+    "id",
+    "reference",
+    "short_name",
+    "long_name",
+    "code_name",
+    "emoji",
+    "institution",
+    "use_institution_prefix",
+    "break_categories",
+    "institution_conflicts",
+    "speakers",
+];
+/// The fields of each speaker kept in the archive's data.
+const SPEAKER_FIELDS: &[&str] = &["url", "id", "name", "categories"];
+/// Contact details and other private fields of judges and speakers, which
+/// are only kept in the archive's data with `--include-private`.
+const PRIVATE_FIELDS: &[&str] = &["email", "phone", "gender", "pronoun", "url_key"];
+
+/// Keeps only the given fields (and the private fields, if `include_private`
+/// is set) of each object in `objects`.
+fn only_fields(objects: Value, fields: &[&str], include_private: bool) -> Value {
+    let keep = |field: &str| {
+        fields.contains(&field) || (include_private && PRIVATE_FIELDS.contains(&field))
+    };
+    match objects {
+        Value::Array(objects) => Value::Array(
+            objects
+                .into_iter()
+                .map(|object| match object {
+                    Value::Object(map) => {
+                        Value::Object(map.into_iter().filter(|(field, _)| keep(field)).collect())
+                    }
+                    other => other,
+                })
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// The teams (and their speakers) as saved in the archive's data.
+fn archived_teams(teams: Value, include_private: bool) -> Value {
+    let mut teams = only_fields(teams, TEAM_FIELDS, include_private);
+    for team in teams.as_array_mut().into_iter().flatten() {
+        let speakers = team["speakers"].take();
+        team["speakers"] = only_fields(speakers, SPEAKER_FIELDS, include_private);
+    }
+    teams
+}
+
+/// Writes a static HTML archive of the tournament (draws and results of each
+/// round, standings, motions and the break) to the directory `out`, so that
+/// the record of the tournament survives the Tabbycat site being deleted.
+/// The data the pages are made from is saved in `data/`, without the
+/// participants' contact details and private URLs unless `include_private`
+/// is set.
+pub async fn archive_html(auth: Auth, out: &str, include_private: bool) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let get = |path: &str| {
+        let url = format!(
+            "{}/api/v1/tournaments/{}{path}",
+            auth.tabbycat_url, auth.tournament_slug
+        );
+        let manager = manager.clone();
+        async move {
+            let resp = manager
+                .send_request(|| manager.client.get(&url).build().unwrap())
+                .await;
+            json_of_resp::<Value>(resp).await
+        }
+    };
+    let (
+        tournament_data,
+        venue_categories,
+        rounds,
+        teams,
+        judges,
+        venues,
+        institutions,
+        break_cats,
+        speaker_cats,
+    ) = tokio::join!(
+        get(""),
+        get("/venue-categories"),
+        get_rounds(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+        get_break_categories(&auth, manager.clone()),
+        get_speaker_categories(&auth, manager.clone()),
+    );
+    let tournament = tournament_data["name"]
+        .as_str()
+        .unwrap_or(&auth.tournament_slug)
+        .to_string();
+
+    let mut rounds = rounds;
+    rounds.sort_by_key(|round| round.seq);
+
+    let team_name = |url: &str| {
+        teams
+            .iter()
+            .find(|team| team.url == url)
+            .map(|team| team.short_name.to_string())
+            .unwrap_or_default()
+    };
+    let judge_name = |url: &String| {
+        judges
+            .iter()
+            .find(|judge| &judge.url == url)
+            .map(|judge| judge.name.clone())
+            .unwrap_or_default()
+    };
+
+    let mut nav = vec![Link {
+        href: "index.html".to_string(),
+        title: "Home".to_string(),
+    }];
+    nav.extend(rounds.iter().map(|round| Link {
+        href: round_page(round),
+        title: round.abbreviation.as_str().to_string(),
+    }));
+    for (href, title) in [
+        ("standings.html", "Standings"),
+        ("speakers.html", "Speakers"),
+        ("motions.html", "Motions"),
+        ("break.html", "Break"),
+    ] {
+        nav.push(Link {
+            href: href.to_string(),
+            title: title.to_string(),
+        });
+    }
+
+    let mut tera = tera::Tera::default();
+    tera.add_raw_template("page.html", PAGE_TEMPLATE).unwrap();
+    std::fs::create_dir_all(out).unwrap_or_else(|e| {
+        error!("Could not create {out}: {e}");
+        exit(1);
+    });
+    let data_dir = Path::new(out).join(DATA_DIR);
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let write_data = |name: &str, data: Value| {
+        let path = data_dir.join(format!("{name}.json"));
+        std::fs::write(&path, serde_json::to_string_pretty(&data).unwrap()).unwrap();
+        info!("Wrote {}", path.display());
+    };
+    write_data("tournament", tournament_data);
+    write_data("institutions", serde_json::to_value(&institutions).unwrap());
+    write_data(
+        "adjudicators",
+        only_fields(
+            serde_json::to_value(&judges).unwrap(),
+            JUDGE_FIELDS,
+            include_private,
+        ),
+    );
+    write_data("venues", serde_json::to_value(&venues).unwrap());
+    write_data("venue-categories", venue_categories);
+    write_data(
+        "teams",
+        archived_teams(serde_json::to_value(&teams).unwrap(), include_private),
+    );
+    write_data("rounds", serde_json::to_value(&rounds).unwrap());
+
+    let archived = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let write_page = |file: &str, title: &str, sections: Vec<Section>| {
+        let page = Page {
+            tournament: tournament.clone(),
+            title: title.to_string(),
+            nav: nav.clone(),
+            sections,
+            archived: archived.clone(),
+        };
+        let rendered = tera
+            .render("page.html", &tera::Context::from_serialize(&page).unwrap())
+            .unwrap();
+        let path = Path::new(out).join(file);
+        std::fs::write(&path, rendered).unwrap();
+        info!("Wrote {}", path.display());
+    };
+
+    // the draw and results of each round (and the teams which debated in
+    // each elimination round, for the break)
+    let mut round_rows = Vec::new();
+    let mut motion_rows = Vec::new();
+    let mut first_elims: Vec<(String, Vec<String>)> = Vec::new();
+    for round in &rounds {
+        let pairings = pairings_of_round(&auth, round, manager.clone()).await;
+        let round_json = serde_json::to_value(round).unwrap();
+
+        for motion in round_json["motions"].as_array().into_iter().flatten() {
+            motion_rows.push(vec![
+                round.name.as_str().to_string(),
+                motion["text"].as_str().unwrap_or_default().to_string(),
+                motion["info_slide"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            ]);
+        }
+
+        let first_elim_of = round_json["break_category"]
+            .as_str()
+            .filter(|_| round_json["stage"].as_str() == Some("E"))
+            .filter(|category| !first_elims.iter().any(|(url, _)| url == category));
+        if let Some(category) = first_elim_of {
+            first_elims.push((
+                category.to_string(),
+                pairings
+                    .iter()
+                    .flat_map(|pairing| &pairing.teams)
+                    .map(|debate_team| debate_team.team.clone())
+                    .collect(),
+            ));
+        }
+
+        let mut rows = Vec::new();
+        for pairing in &pairings {
+            let ballot = get_ballots(&auth, pairing, manager.clone())
+                .await
+                .into_iter()
+                .find(|ballot| ballot["confirmed"].as_bool() == Some(true));
+            if ballot.is_none() {
+                warn!(
+                    "{} has no confirmed ballot for debate {}",
+                    round.name.as_str(),
+                    pairing.id
+                );
+            }
+
+            let room = pairing
+                .venue
+                .as_ref()
+                .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+                .map(|venue| venue.name.as_str().to_string())
+                .unwrap_or_default();
+            let teams = pairing
+                .teams
+                .iter()
+                .map(|debate_team| {
+                    let side = serde_json::to_value(&debate_team.side)
+                        .ok()
+                        .and_then(|side| side.as_str().map(|side| side.to_uppercase()))
+                        .map(|side| format!("{side}: "))
+                        .unwrap_or_default();
+                    format!("{side}{}", team_name(&debate_team.team))
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let adjudicators = pairing
+                .adjudicators
+                .as_ref()
+                .map(|panel| {
+                    panel
+                        .chair
+                        .iter()
+                        .map(|chair| format!("{} (c)", judge_name(chair)))
+                        .chain(panel.panellists.iter().map(judge_name))
+                        .chain(
+                            panel
+                                .trainees
+                                .iter()
+                                .map(|trainee| format!("{} (t)", judge_name(trainee))),
+                        )
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+            let result = ballot
+                .map(|ballot| result_summary(&ballot, pairing, team_name))
+                .unwrap_or_default();
+            rows.push(vec![room, teams, adjudicators, result]);
+        }
+        rows.sort();
+        round_rows.push((round, rows));
+    }
+
+    for (round, rows) in round_rows {
+        write_page(
+            &round_page(round),
+            round.name.as_str(),
+            vec![Section {
+                title: String::new(),
+                header: ["Room", "Teams", "Adjudicators", "Result"]
+                    .map(|h| h.to_string())
+                    .to_vec(),
+                rows,
+            }],
+        );
+    }
+
+    let (header, rows) =
+        fetch_standings_table(&auth, false, false, Anonymiser::default(), manager.clone()).await;
+    write_page(
+        "standings.html",
+        "Team standings",
+        vec![Section {
+            title: String::new(),
+            header: column_names(&header),
+            rows,
+        }],
+    );
+
+    let standings = get_speaker_standings(&auth, manager.clone()).await;
+    let mut sections = Vec::new();
+    let (header, rows) = speaker_tab(standings.clone(), &teams, None, Anonymiser::default());
+    sections.push(Section {
+        title: "Open".to_string(),
+        header: column_names(&header),
+        rows,
+    });
+    for category in &speaker_cats {
+        let (header, rows) = speaker_tab(
+            standings.clone(),
+            &teams,
+            Some(category.url.as_str()),
+            Anonymiser::default(),
+        );
+        sections.push(Section {
+            title: category.name.as_str().to_string(),
+            header: column_names(&header),
+            rows,
+        });
+    }
+    write_page("speakers.html", "Speaker standings", sections);
+
+    write_page(
+        "motions.html",
+        "Motions",
+        vec![Section {
+            title: String::new(),
+            header: ["Round", "Motion", "Info slide"]
+                .map(|h| h.to_string())
+                .to_vec(),
+            rows: motion_rows,
+        }],
+    );
+
+    let break_sections = break_cats
+        .iter()
+        .map(|category| {
+            let mut rows = first_elims
+                .iter()
+                .find(|(url, _)| *url == category.url)
+                .map(|(_, teams)| {
+                    teams
+                        .iter()
+                        .map(|team| vec![team_name(team)])
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            rows.sort();
+            Section {
+                title: category.name.as_str().to_string(),
+                header: vec!["Team".to_string()],
+                rows,
+            }
+        })
+        .collect();
+    write_page("break.html", "Break", break_sections);
+
+    write_page(
+        "index.html",
+        "Rounds",
+        vec![Section {
+            title: String::new(),
+            header: ["Round", "Motions"].map(|h| h.to_string()).to_vec(),
+            rows: rounds
+                .iter()
+                .map(|round| {
+                    let motions = serde_json::to_value(round).unwrap()["motions"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|motion| motion["text"].as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    vec![round.name.as_str().to_string(), motions]
+                })
+                .collect(),
+        }],
+    );
+
+    info!("Archived {tournament} to {out}");
+}
+
+/// Reads one of the JSON files in the data directory of an archive.
+pub fn read_data(dir: &str, name: &str) -> Vec<Value> {
+    let path = Path::new(dir).join(DATA_DIR).join(format!("{name}.json"));
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        error!(
+            "Could not read {} ({e}); is {dir} an archive made by `tabbycat archive`?",
+            path.display()
+        );
+        exit(1);
+    });
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        error!("{} is malformed: {e}", path.display());
+        exit(1);
+    })
+}
+
+/// Writes rows to a temporary CSV file for the import, returning its path.
+fn write_csv(name: &str, header: &[&str], rows: &[Vec<String>]) -> String {
+    let path: PathBuf = std::env::temp_dir().join(format!(
+        "tabbycat-{}-archive-{name}.csv",
+        std::process::id()
+    ));
+    let mut writer = csv::Writer::from_path(&path).unwrap();
+    writer.write_record(header).unwrap();
+    for row in rows {
+        writer.write_record(row).unwrap();
+    }
+    writer.flush().unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// The institutions, judges and rooms CSV files (in the formats read by
+/// `tabbycat import`) recreated from an archive. The (temporary) files are
+/// deleted when this is dropped.
+pub struct ArchiveCsvs {
+    pub institutions: String,
+    pub judges: String,
+    pub rooms: String,
+}
+
+impl Drop for ArchiveCsvs {
+    fn drop(&mut self) {
+        for path in [&self.institutions, &self.judges, &self.rooms] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Converts the institutions, adjudicators and venues saved in an archive
+/// (by `tabbycat archive`) into CSV files which can be imported into a new
+/// tournament. Judges are referred to by their institution's code.
+pub fn archive_csvs(dir: &str) -> ArchiveCsvs {
+    let institutions = read_data(dir, "institutions");
+    let judges = read_data(dir, "adjudicators");
+    let venues = read_data(dir, "venues");
+    let venue_categories = read_data(dir, "venue-categories");
+
+    let institution_code = |url: &Value| {
+        institutions
+            .iter()
+            .find(|inst| inst["url"] == *url)
+            .map(|inst| text(&inst["code"]))
+    };
+
+    let institution_rows = institutions
+        .iter()
+        .map(|inst| {
+            vec![
+                text(&inst["region"]),
+                text(&inst["code"]),
+                text(&inst["name"]),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let judge_rows = judges
+        .iter()
+        .map(|judge| {
+            let flag = |field: &str| (judge[field].as_bool() == Some(true)).to_string();
+            vec![
+                text(&judge["name"]),
+                institution_code(&judge["institution"]).unwrap_or_default(),
+                judge["institution_conflicts"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(institution_code)
+                    .collect::<Vec<_>>()
+                    .join(","),
+                text(&judge["email"]),
+                flag("adj_core"),
+                flag("independent"),
+                text(&judge["base_score"]),
+                text(&judge["gender"]),
+                text(&judge["pronoun"]),
+                text(&judge["phone"]),
+                flag("anonymous"),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let room_rows = venues
+        .iter()
+        .map(|venue| {
+            let categories = venue_categories
+                .iter()
+                .filter(|cat| {
+                    cat["venues"]
+                        .as_array()
+                        .is_some_and(|venues| venues.contains(&venue["url"]))
+                })
+                .map(|cat| text(&cat["name"]))
+                .collect::<Vec<_>>()
+                .join(",");
+            vec![
+                text(&venue["name"]),
+                venue["priority"].as_i64().unwrap_or(0).to_string(),
+                categories,
+                text(&venue["external_url"]),
+                text(&venue["barcode"]),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    info!(
+        "Read {} institution(s), {} judge(s) and {} room(s) from the archive in {dir}",
+        institution_rows.len(),
+        judge_rows.len(),
+        room_rows.len()
+    );
+
+    ArchiveCsvs {
+        institutions: write_csv(
+            "institutions",
+            &["region", "short_code", "full_name"],
+            &institution_rows,
+        ),
+        judges: write_csv(
+            "judges",
+            &[
+                "name",
+                "institution",
+                "institution_clashes",
+                "email",
+                "is_ca",
+                "is_ia",
+                "base_score",
+                "gender",
+                "pronoun",
+                "phone",
+                "anonymous",
+            ],
+            &judge_rows,
+        ),
+        rooms: write_csv(
+            "rooms",
+            &["name", "priority", "categories", "external_url", "barcode"],
+            &room_rows,
+        ),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_archived_teams() {
+    let teams = serde_json::json!([{
+        "url": "/teams/1",
+        "short_name": "Oxford A",
+        "institution": "/institutions/1",
+        "speakers": [{
+            "name": "Sam",
+            "email": "sam@example.com",
+            "phone": "0123",
+            "url_key": "abc",
+        }],
+    }]);
+
+    let public = archived_teams(teams.clone(), false);
+    assert_eq!(public[0]["short_name"], "Oxford A");
+    assert_eq!(
+        public[0]["speakers"],
+        serde_json::json!([{ "name": "Sam" }])
+    );
+
+    let private = archived_teams(teams, true);
+    assert_eq!(private[0]["speakers"][0]["email"], "sam@example.com");
+    assert_eq!(private[0]["speakers"][0]["url_key"], "abc");
+}
+
+#[cfg(test)]
+#[test]
+fn test_page_template() {
+    let mut tera = tera::Tera::default();
+    tera.add_raw_template("page.html", PAGE_TEMPLATE).unwrap();
+    let page = Page {
+        tournament: "Open <2025>".to_string(),
+        title: "Round 1".to_string(),
+        nav: vec![],
+        sections: vec![Section {
+            title: String::new(),
+            header: vec!["Room".to_string()],
+            rows: vec![vec!["A\nB".to_string()]],
+        }],
+        archived: "2025-10-16".to_string(),
+    };
+    let rendered = tera
+        .render("page.html", &tera::Context::from_serialize(&page).unwrap())
+        .unwrap();
+    assert!(rendered.contains("Open &lt;2025&gt;"));
+    assert!(rendered.contains("A<br>B"));
+}
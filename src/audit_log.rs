@@ -0,0 +1,386 @@
+use std::{
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::exit,
+};
+
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime};
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+
+use crate::{Auth, request_manager::RequestManager};
+
+/// A single mutating request (i.e. anything other than a `GET`) which the CLI
+/// sent to Tabbycat.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// RFC 3339, in local time.
+    pub timestamp: String,
+    /// The command line which made the request (with API keys removed).
+    pub command: String,
+    pub method: String,
+    pub endpoint: String,
+    /// SHA-256 of the request body, if there was one.
+    pub payload_digest: Option<String>,
+    /// The HTTP status code of the response.
+    pub status: u16,
+    /// For `PATCH` requests, the values of the changed fields before the
+    /// request was made.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_image: Option<Value>,
+}
+
+/// The audit log, which has one JSON object ([`AuditEntry`]) per line and is
+/// only ever appended to. The ID of an entry is its line number.
+fn log_path() -> PathBuf {
+    dirs::home_dir()
+        .expect("Could not determine home directory")
+        .join(".tabbycat_audit.jsonl")
+}
+
+/// The arguments the CLI was run with, with the value of `--api-key` removed.
+pub fn command_line() -> String {
+    let mut args = Vec::new();
+    let mut redact_next = false;
+    for arg in std::env::args().skip(1) {
+        if redact_next {
+            args.push("<redacted>".to_string());
+            redact_next = false;
+        } else if arg == "--api-key" {
+            args.push(arg);
+            redact_next = true;
+        } else if arg.starts_with("--api-key=") {
+            args.push("--api-key=<redacted>".to_string());
+        } else {
+            args.push(arg);
+        }
+    }
+    format!("tabbycat {}", args.join(" "))
+}
+
+fn digest(payload: &[u8]) -> String {
+    Sha256::digest(payload)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Appends a request to the audit log. Failing to write the log is reported,
+/// but does not stop the command.
+pub fn record(
+    method: &str,
+    endpoint: &str,
+    payload: Option<&[u8]>,
+    status: u16,
+    pre_image: Option<Value>,
+) {
+    let entry = AuditEntry {
+        timestamp: Local::now().to_rfc3339(),
+        command: command_line(),
+        method: method.to_string(),
+        endpoint: endpoint.to_string(),
+        payload_digest: payload.map(digest),
+        status,
+        pre_image,
+    };
+
+    let path = log_path();
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", serde_json::to_string(&entry).unwrap()));
+    if let Err(e) = written {
+        warn!("Could not write to the audit log ({}): {e}", path.display());
+    }
+}
+
+/// The fields of `current` which `payload` (the body of a `PATCH` request)
+/// would change, with their current values.
+pub fn pre_image(current: &Value, payload: &Value) -> Option<Value> {
+    let fields = payload
+        .as_object()?
+        .keys()
+        .map(|key| {
+            (
+                key.clone(),
+                current.get(key).cloned().unwrap_or(Value::Null),
+            )
+        })
+        .collect();
+    Some(Value::Object(fields))
+}
+
+/// Sends a `PATCH` request with the blocking client, and records it in the
+/// audit log.
+pub fn patch_json(url: &str, auth: &Auth, payload: &Value) -> attohttpc::Response {
+    let current = attohttpc::get(url)
+        .header("Authorization", format!("Token {}", auth.api_key))
+        .send()
+        .ok()
+        .and_then(|resp| resp.json::<Value>().ok());
+    let response = attohttpc::patch(url)
+        .header("Authorization", format!("Token {}", auth.api_key))
+        .json(payload)
+        .unwrap()
+        .send()
+        .unwrap();
+    record(
+        "PATCH",
+        url,
+        Some(serde_json::to_vec(payload).unwrap().as_slice()),
+        response.status().as_u16(),
+        current.and_then(|current| pre_image(&current, payload)),
+    );
+    response
+}
+
+/// Parses the argument to `--since`: either a time relative to now (e.g.
+/// `30m`, `2h` or `1d`), a date (`2025-10-16`), a date and time
+/// (`2025-10-16 13:30`) or an RFC 3339 timestamp.
+pub fn parse_since(since: &str, now: DateTime<Local>) -> Option<DateTime<FixedOffset>> {
+    let since = since.trim();
+
+    let relative = since.find(|c: char| !c.is_ascii_digit()).and_then(|i| {
+        let (amount, unit) = since.split_at(i);
+        let amount = amount.parse::<i64>().ok()?;
+        match unit {
+            "m" | "min" => Some(Duration::minutes(amount)),
+            "h" => Some(Duration::hours(amount)),
+            "d" => Some(Duration::days(amount)),
+            _ => None,
+        }
+    });
+    if let Some(duration) = relative {
+        return Some((now - duration).fixed_offset());
+    }
+
+    parse_absolute(since)
+}
+
+fn parse_absolute(since: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Some(time);
+    }
+    let naive = NaiveDateTime::parse_from_str(since, "%Y-%m-%d %H:%M")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(since, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|time| time.fixed_offset())
+}
+
+/// Reads every entry in the audit log, along with its ID.
+pub fn read_entries() -> Vec<(usize, AuditEntry)> {
+    let path = log_path();
+    let file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            error!("Could not read the audit log ({}): {e}", path.display());
+            exit(1)
+        }
+    };
+
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| match serde_json::from_str(&line.unwrap()) {
+            Ok(entry) => Some((i + 1, entry)),
+            Err(e) => {
+                warn!("Skipping entry {} of the audit log: {e}", i + 1);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Shows the requests in the audit log (optionally only those made since
+/// `since`).
+pub fn show_log(since: Option<String>) {
+    let since = since.map(|since| {
+        parse_since(&since, Local::now()).unwrap_or_else(|| {
+            error!("Could not understand `--since {since}` (try e.g. `2h` or `2025-10-16 13:30`)");
+            exit(1)
+        })
+    });
+
+    let entries = read_entries();
+    if entries.is_empty() {
+        info!("Nothing has been recorded in the audit log yet");
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec![
+            "id", "time", "command", "method", "endpoint", "status", "payload",
+        ]);
+
+    let mut shown = 0;
+    for (id, entry) in entries {
+        let timestamp = DateTime::parse_from_rfc3339(&entry.timestamp).ok();
+        if since.is_some_and(|since| timestamp.is_none_or(|timestamp| timestamp < since)) {
+            continue;
+        }
+
+        shown += 1;
+        table.add_row(vec![
+            id.to_string(),
+            timestamp
+                .map(|timestamp| timestamp.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or(entry.timestamp),
+            entry.command,
+            entry.method,
+            entry.endpoint,
+            entry.status.to_string(),
+            entry
+                .payload_digest
+                .map(|digest| digest.chars().take(12).collect())
+                .unwrap_or_default(),
+        ]);
+    }
+
+    println!("{table}");
+    info!("{shown} request(s) shown from {}", log_path().display());
+}
+
+/// Whether `endpoint` is an object of the tournament `auth` refers to (or a
+/// site-wide object, such as an institution, on the same Tabbycat site).
+fn belongs_to(endpoint: &str, auth: &Auth) -> bool {
+    let Some(path) = endpoint.strip_prefix(auth.tabbycat_url.trim_end_matches('/')) else {
+        return false;
+    };
+    match path.strip_prefix("/api/v1/tournaments/") {
+        Some(rest) => rest.split('/').next() == Some(auth.tournament_slug.as_str()),
+        None => path.starts_with('/'),
+    }
+}
+
+/// Undoes the `PATCH` request recorded in entry `id` of the audit log, by
+/// setting the fields it changed back to their previous values. The revert is
+/// itself recorded in the audit log.
+pub async fn revert(auth: Auth, id: usize) {
+    let entries = read_entries();
+    let Some((_, entry)) = entries.iter().find(|(entry_id, _)| *entry_id == id) else {
+        error!("There is no entry {id} in the audit log");
+        exit(1)
+    };
+    let Some(pre_image) = &entry.pre_image else {
+        error!(
+            "Entry {id} ({} {}) cannot be reverted, as the previous state was not recorded \
+            (only PATCH requests can be reverted)",
+            entry.method, entry.endpoint
+        );
+        exit(1)
+    };
+    if !belongs_to(&entry.endpoint, &auth) {
+        error!(
+            "Entry {id} was made against {}, which is not the current tournament",
+            entry.endpoint
+        );
+        exit(1)
+    }
+
+    for (later, _) in entries
+        .iter()
+        .filter(|(later, later_entry)| *later > id && later_entry.endpoint == entry.endpoint)
+    {
+        warn!("Entry {later} changed the same object later on; its changes may be undone");
+    }
+
+    let manager = RequestManager::new(&auth.api_key);
+    manager
+        .send_request(|| {
+            manager
+                .client
+                .patch(&entry.endpoint)
+                .json(pre_image)
+                .build()
+                .unwrap()
+        })
+        .await;
+
+    info!("Reverted entry {id} ({} {})", entry.method, entry.endpoint);
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_since() {
+    let now = DateTime::parse_from_rfc3339("2025-10-16T14:00:00+01:00")
+        .unwrap()
+        .with_timezone(&Local);
+
+    assert_eq!(
+        parse_since("2h", now),
+        Some((now - Duration::hours(2)).fixed_offset())
+    );
+    assert_eq!(
+        parse_since("2025-10-16T12:30:00+01:00", now),
+        DateTime::parse_from_rfc3339("2025-10-16T12:30:00+01:00").ok()
+    );
+    assert!(parse_since("2025-10-16", now).is_some());
+    assert!(parse_since("2025-10-16 13:30", now).is_some());
+    assert_eq!(parse_since("yesterday", now), None);
+}
+
+#[cfg(test)]
+#[test]
+fn test_pre_image() {
+    let current = serde_json::json!({
+        "id": 1,
+        "name": "Oxford A",
+        "break_categories": ["open"],
+    });
+
+    assert_eq!(
+        pre_image(
+            &current,
+            &serde_json::json!({ "break_categories": ["open", "esl"], "emoji": "🦆" })
+        ),
+        Some(serde_json::json!({ "break_categories": ["open"], "emoji": null }))
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_belongs_to() {
+    let auth = Auth {
+        tabbycat_url: "https://example.com/".to_string(),
+        tournament_slug: "wudc".to_string(),
+        api_key: String::new(),
+    };
+
+    assert!(belongs_to(
+        "https://example.com/api/v1/tournaments/wudc/teams/1",
+        &auth
+    ));
+    assert!(belongs_to(
+        "https://example.com/api/v1/institutions/3",
+        &auth
+    ));
+    assert!(!belongs_to(
+        "https://example.com/api/v1/tournaments/wudc2/teams/1",
+        &auth
+    ));
+    assert!(!belongs_to(
+        "https://example.com.evil/api/v1/tournaments/wudc/teams/1",
+        &auth
+    ));
+    assert!(!belongs_to(
+        "https://other.com/api/v1/tournaments/wudc/teams/1",
+        &auth
+    ));
+}
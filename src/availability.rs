@@ -0,0 +1,216 @@
+use std::{collections::BTreeMap, process::exit};
+
+use chrono::NaiveTime;
+use tracing::{error, info};
+
+use crate::{
+    Auth, SetAvailability,
+    api_utils::{get_institutions, get_judges, get_round, set_availability},
+    names::names_match,
+    release::parse_time,
+    request_manager::RequestManager,
+};
+
+/// Whether a judge or room is available for a round, going by the entries in
+/// its `availability` column. An entry is either the name or abbreviation of a
+/// round (`round_names`) which it is available for, the same prefixed with `-`
+/// (e.g. `-R3`) for a round it is unavailable for, or `before 13:00` or `after
+/// 13:00`, for which only rounds starting (at `start`) in that part of the day
+/// are available (with several of these, rounds starting in any of them are
+/// available). Rounds which are not covered by any entry are available if
+/// `default_available` is set.
+pub fn is_available(
+    availability: &[String],
+    round_names: &[&str],
+    start: Option<NaiveTime>,
+    default_available: bool,
+) -> bool {
+    let listed = |entry: &str| {
+        round_names
+            .iter()
+            .any(|name| name.trim().eq_ignore_ascii_case(entry))
+    };
+    // whether the round starts inside the time window given by `entry`
+    let window = |entry: &str| {
+        let (before, time) = match entry.split_once(char::is_whitespace) {
+            Some((word, time)) if word.eq_ignore_ascii_case("before") => (true, time),
+            Some((word, time)) if word.eq_ignore_ascii_case("after") => (false, time),
+            _ => return None,
+        };
+        let time = parse_time(time)?;
+        Some(start.map(|start| if before { start < time } else { start >= time }))
+    };
+
+    let entries = availability
+        .iter()
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .collect::<Vec<_>>();
+    if entries.iter().any(|entry| {
+        entry
+            .strip_prefix('-')
+            .is_some_and(|entry| listed(entry.trim()))
+    }) {
+        return false;
+    }
+    // (the windows which could not be checked, because the start time of the
+    // round is not known, are left out)
+    let windows = entries
+        .iter()
+        .filter_map(|entry| window(entry).flatten())
+        .collect::<Vec<_>>();
+    if !windows.is_empty() && !windows.contains(&true) {
+        return false;
+    }
+    if !windows.is_empty() || entries.iter().any(|entry| listed(entry)) {
+        return true;
+    }
+    default_available
+}
+
+/// Marks a whole group of adjudicators (e.g. all judges from an institution,
+/// or the entire adjudication core) as available or unavailable for a round.
+pub async fn do_set_availability(auth: Auth, args: SetAvailability) {
+    if args.institution.is_none() && !args.adj_core && !args.independent {
+        error!("Please specify a group (`--institution`, `--adj-core` or `--independent`).");
+        exit(1);
+    }
+
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (round, judges, institutions) = tokio::join!(
+        get_round(&args.round, &auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+    );
+
+    let institution = args.institution.as_ref().map(|key| {
+        institutions
+            .iter()
+            .find(|inst| {
+                names_match(inst.name.as_str(), key) || names_match(inst.code.as_str(), key)
+            })
+            .unwrap_or_else(|| {
+                error!("No institution found matching {key}");
+                exit(1);
+            })
+    });
+
+    let selected = judges
+        .iter()
+        .filter(|judge| {
+            institution
+                .map(|inst| judge.institution.as_ref() == Some(&inst.url))
+                .unwrap_or(true)
+        })
+        .filter(|judge| !args.adj_core || judge.adj_core == Some(true))
+        .filter(|judge| !args.independent || judge.independent == Some(true))
+        .collect::<Vec<_>>();
+
+    if selected.is_empty() {
+        info!("No adjudicators matched the group you specified.");
+        return;
+    }
+
+    let available = !args.unavailable;
+    let urls = selected
+        .iter()
+        .map(|judge| judge.url.clone())
+        .collect::<Vec<_>>();
+
+    set_availability(&auth, &round, &urls, available, manager).await;
+
+    // the judges are listed by their pool: the adjudication core, the
+    // independent adjudicators, or otherwise their institution
+    let mut pools = BTreeMap::<String, Vec<&str>>::new();
+    for judge in &selected {
+        let pool = if judge.adj_core == Some(true) {
+            "Adjudication core".to_string()
+        } else if judge.independent == Some(true) {
+            "Independent adjudicators".to_string()
+        } else {
+            judge
+                .institution
+                .as_ref()
+                .and_then(|url| institutions.iter().find(|inst| &inst.url == url))
+                .map(|inst| inst.name.as_str().to_string())
+                .unwrap_or_else(|| "No institution".to_string())
+        };
+        pools.entry(pool).or_default().push(&judge.name);
+    }
+
+    info!(
+        "Marked {} judge(s) as {} for round {}:",
+        selected.len(),
+        if available {
+            "available"
+        } else {
+            "unavailable"
+        },
+        round.name.as_str()
+    );
+    for (pool, names) in pools {
+        info!("  {pool} ({}): {}", names.len(), names.join(", "));
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_is_available() {
+    let entries = |entries: &[&str]| {
+        entries
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect::<Vec<_>>()
+    };
+    let morning = parse_time("09:00");
+    let afternoon = parse_time("14:00");
+
+    assert!(is_available(
+        &entries(&["R1", "r2"]),
+        &["Round 2", "R2"],
+        morning,
+        false
+    ));
+    assert!(!is_available(
+        &entries(&["-R2"]),
+        &["Round 2", "R2"],
+        morning,
+        true
+    ));
+    assert!(!is_available(
+        &entries(&["R3"]),
+        &["Round 2", "R2"],
+        morning,
+        false
+    ));
+    assert!(is_available(
+        &entries(&[]),
+        &["Round 2", "R2"],
+        morning,
+        true
+    ));
+
+    let morning_only = entries(&["before 13:00"]);
+    assert!(is_available(&morning_only, &["R1"], morning, false));
+    assert!(!is_available(&morning_only, &["R3"], afternoon, true));
+    // the start time of the round is not known
+    assert!(is_available(&morning_only, &["R5"], None, true));
+    assert!(is_available(
+        &entries(&["after 13:00"]),
+        &["R3"],
+        afternoon,
+        false
+    ));
+
+    // either window is enough
+    let not_midday = entries(&["before 13:00", "after 17:00"]);
+    assert!(is_available(&not_midday, &["R1"], morning, false));
+    assert!(!is_available(&not_midday, &["R3"], afternoon, true));
+    assert!(is_available(
+        &not_midday,
+        &["R5"],
+        parse_time("18:00"),
+        false
+    ));
+}
@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde_json::Value;
+use tracing::info;
+
+use crate::{
+    Auth,
+    api_utils::{get_ballots, get_round_and_pairings, get_teams, get_venues},
+    request_manager::RequestManager,
+};
+
+/// Identifies a speech within a ballot: the index of the sheet (there is one
+/// per adjudicator in formats with individual ballots), the side, and the
+/// position of the speech.
+type SpeechKey = (usize, String, usize);
+/// The speaker (URL) and score recorded for a speech.
+type Speech = (Option<String>, Option<f64>);
+
+/// The speaker and score of each speech in a ballot.
+fn speeches(ballot: &Value) -> BTreeMap<SpeechKey, Speech> {
+    let mut speeches = BTreeMap::new();
+    let sheets = ballot["result"]["sheets"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    for (i, sheet) in sheets.iter().enumerate() {
+        for team in sheet["teams"].as_array().into_iter().flatten() {
+            let side = team["side"].as_str().unwrap_or_default().to_string();
+            for (position, speech) in team["speeches"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .enumerate()
+            {
+                speeches.insert(
+                    (i, side.clone(), position + 1),
+                    (
+                        speech["speaker"].as_str().map(|s| s.to_string()),
+                        speech["score"].as_f64(),
+                    ),
+                );
+            }
+        }
+    }
+    speeches
+}
+
+/// The speeches which differ between ballots, along with the speaker and score
+/// recorded in each ballot (in the same order as `ballots`).
+pub fn differing_speeches(ballots: &[Value]) -> Vec<(SpeechKey, Vec<Speech>)> {
+    let speeches = ballots.iter().map(speeches).collect::<Vec<_>>();
+    let keys = speeches
+        .iter()
+        .flat_map(|speeches| speeches.keys().cloned())
+        .collect::<BTreeSet<_>>();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let versions = speeches
+                .iter()
+                .map(|speeches| speeches.get(&key).cloned().unwrap_or((None, None)))
+                .collect::<Vec<_>>();
+            if versions.iter().all(|version| *version == versions[0]) {
+                None
+            } else {
+                Some((key, versions))
+            }
+        })
+        .collect()
+}
+
+/// For tournaments which use double ballot entry: finds the debates in which
+/// the unconfirmed ballots disagree with one another, and shows the speeches
+/// on which they differ side-by-side.
+pub async fn compare_ballots(auth: Auth, round: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((round, pairings), teams, venues) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+    );
+
+    let speaker_name = |url: &str| {
+        teams
+            .iter()
+            .flat_map(|team| &team.speakers)
+            .find(|speaker| speaker.url == url)
+            .map(|speaker| speaker.name.to_string())
+            .unwrap_or_else(|| url.to_string())
+    };
+
+    let mut mismatches = 0;
+    for pairing in &pairings {
+        let ballots = get_ballots(&auth, pairing, manager.clone())
+            .await
+            .into_iter()
+            .filter(|ballot| {
+                ballot["confirmed"].as_bool() != Some(true)
+                    && ballot["discarded"].as_bool() != Some(true)
+            })
+            .collect::<Vec<_>>();
+        if ballots.len() < 2 {
+            continue;
+        }
+
+        let differences = differing_speeches(&ballots);
+        if differences.is_empty() {
+            continue;
+        }
+        mismatches += 1;
+
+        let room = pairing
+            .venue
+            .as_ref()
+            .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+            .map(|venue| venue.name.as_str().to_string())
+            .unwrap_or_else(|| format!("Debate {}", pairing.id));
+
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+            .apply_modifier(UTF8_ROUND_CORNERS);
+        let mut header = vec![
+            "sheet".to_string(),
+            "side".to_string(),
+            "speech".to_string(),
+        ];
+        header.extend(ballots.iter().map(|ballot| {
+            format!(
+                "ballot {} ({})",
+                ballot["id"],
+                ballot["submitter"].as_str().unwrap_or("unknown submitter")
+            )
+        }));
+        table.set_header(header);
+
+        for ((sheet, side, position), versions) in differences {
+            let mut row = vec![(sheet + 1).to_string(), side, position.to_string()];
+            row.extend(versions.into_iter().map(|(speaker, score)| {
+                format!(
+                    "{}: {}",
+                    speaker.as_deref().map(speaker_name).unwrap_or_default(),
+                    score.map(|s| s.to_string()).unwrap_or_default()
+                )
+            }));
+            table.add_row(row);
+        }
+
+        println!("{room}");
+        println!("{table}");
+    }
+
+    info!(
+        "{mismatches} debate(s) in {} have mismatching ballots",
+        round.name.as_str()
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_differing_speeches() {
+    let ballot = |score: f64| {
+        serde_json::json!({
+            "result": {
+                "sheets": [{
+                    "teams": [
+                        {
+                            "side": "aff",
+                            "speeches": [
+                                { "speaker": "a1", "score": 75.0 },
+                                { "speaker": "a2", "score": score },
+                            ]
+                        },
+                        {
+                            "side": "neg",
+                            "speeches": [{ "speaker": "n1", "score": 74.0 }]
+                        }
+                    ]
+                }]
+            }
+        })
+    };
+
+    assert!(differing_speeches(&[ballot(76.0), ballot(76.0)]).is_empty());
+    assert_eq!(
+        differing_speeches(&[ballot(76.0), ballot(67.0)]),
+        vec![(
+            (0, "aff".to_string(), 2),
+            vec![
+                (Some("a2".to_string()), Some(76.0)),
+                (Some("a2".to_string()), Some(67.0))
+            ]
+        )]
+    );
+}
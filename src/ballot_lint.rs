@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_ballots, get_preference, get_round_and_pairings, get_teams, get_venues},
+    request_manager::RequestManager,
+};
+
+/// The tournament's scoring rules (from its preferences).
+#[derive(Debug, Clone)]
+pub struct ScoreRules {
+    pub score_min: f64,
+    pub score_max: f64,
+    pub reply_score_min: f64,
+    pub reply_score_max: f64,
+    /// Zero if there is no maximum margin.
+    pub maximum_margin: f64,
+    /// Speeches after these (in each team) are replies.
+    pub substantive_speakers: usize,
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+impl ScoreRules {
+    async fn fetch(auth: &Auth, manager: RequestManager) -> ScoreRules {
+        let (min, max, reply_min, reply_max, margin, substantive) = tokio::join!(
+            get_preference(auth, "scoring__score_min", manager.clone()),
+            get_preference(auth, "scoring__score_max", manager.clone()),
+            get_preference(auth, "scoring__reply_score_min", manager.clone()),
+            get_preference(auth, "scoring__reply_score_max", manager.clone()),
+            get_preference(auth, "scoring__maximum_margin", manager.clone()),
+            get_preference(auth, "debate_rules__substantive_speakers", manager.clone()),
+        );
+
+        ScoreRules {
+            score_min: as_f64(&min).unwrap_or(f64::MIN),
+            score_max: as_f64(&max).unwrap_or(f64::MAX),
+            reply_score_min: as_f64(&reply_min).unwrap_or(f64::MIN),
+            reply_score_max: as_f64(&reply_max).unwrap_or(f64::MAX),
+            maximum_margin: as_f64(&margin).unwrap_or(0.0),
+            substantive_speakers: as_f64(&substantive)
+                .map(|n| n as usize)
+                .unwrap_or(usize::MAX),
+        }
+    }
+}
+
+/// Checks a single sheet of a ballot against `rules`. `speaker_name` is used
+/// to describe speakers in the problems which are returned.
+pub fn lint_sheet(
+    sheet: &Value,
+    rules: &ScoreRules,
+    speaker_name: impl Fn(&str) -> String,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    // (side, total score, whether the team won, points)
+    let mut totals = Vec::new();
+
+    for team in sheet["teams"].as_array().into_iter().flatten() {
+        let side = team["side"].as_str().unwrap_or_default();
+        let mut total = 0.0;
+        let mut seen = HashSet::new();
+
+        for (i, speech) in team["speeches"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let speaker = speech["speaker"].as_str().unwrap_or_default();
+            let reply = i >= rules.substantive_speakers;
+            let (min, max) = if reply {
+                (rules.reply_score_min, rules.reply_score_max)
+            } else {
+                (rules.score_min, rules.score_max)
+            };
+
+            if let Some(score) = speech["score"].as_f64() {
+                total += score;
+                if score < min || score > max {
+                    problems.push(format!(
+                        "{side}: {} was given {score}, which is outside the range {min}-{max}",
+                        speaker_name(speaker)
+                    ));
+                }
+            }
+
+            // iron-person speeches are marked as ghosts
+            if !reply
+                && speech["ghost"].as_bool() != Some(true)
+                && !seen.insert(speaker.to_string())
+            {
+                problems.push(format!(
+                    "{side}: {} gave more than one substantive speech",
+                    speaker_name(speaker)
+                ));
+            }
+        }
+
+        totals.push((
+            side.to_string(),
+            total,
+            team["win"].as_bool(),
+            team["points"].as_f64(),
+        ));
+    }
+
+    for (side, total, win, points) in &totals {
+        for (other_side, other_total, other_win, other_points) in &totals {
+            let ranked_above = match (win, other_win, points, other_points) {
+                (Some(true), Some(false), _, _) => true,
+                (None, None, Some(a), Some(b)) => a > b,
+                _ => false,
+            };
+            if ranked_above && total < other_total {
+                problems.push(format!(
+                    "{side} beat {other_side} despite scoring fewer points ({total} vs {other_total})"
+                ));
+            }
+        }
+    }
+
+    if let [(side_a, a, ..), (side_b, b, ..)] = totals.as_slice() {
+        let margin = (a - b).abs();
+        if margin == 0.0 {
+            problems.push(format!("{side_a} and {side_b} scored the same total ({a})"));
+        } else if rules.maximum_margin > 0.0 && margin > rules.maximum_margin {
+            problems.push(format!(
+                "The margin ({margin}) is greater than the maximum margin ({})",
+                rules.maximum_margin
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Checks every ballot (which has not been discarded) submitted for `round`
+/// for implausible scores: scores outside the tournament's range, speakers
+/// who speak twice, margins which are too large, and low-point wins.
+pub async fn lint_ballots(auth: Auth, round: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((round, pairings), teams, venues, rules) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+        ScoreRules::fetch(&auth, manager.clone()),
+    );
+
+    let speaker_name = |url: &str| {
+        teams
+            .iter()
+            .flat_map(|team| &team.speakers)
+            .find(|speaker| speaker.url == url)
+            .map(|speaker| speaker.name.to_string())
+            .unwrap_or_else(|| url.to_string())
+    };
+
+    let mut flagged = 0;
+    for pairing in &pairings {
+        let room = pairing
+            .venue
+            .as_ref()
+            .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+            .map(|venue| venue.name.as_str().to_string())
+            .unwrap_or_else(|| format!("Debate {}", pairing.id));
+
+        for ballot in get_ballots(&auth, pairing, manager.clone()).await {
+            if ballot["discarded"].as_bool() == Some(true) {
+                continue;
+            }
+            let sheets = ballot["result"]["sheets"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            for (i, sheet) in sheets.iter().enumerate() {
+                for problem in lint_sheet(sheet, &rules, speaker_name) {
+                    flagged += 1;
+                    warn!(
+                        "{room} (ballot {}{}): {problem}",
+                        ballot["id"],
+                        if sheets.len() > 1 {
+                            format!(", sheet {}", i + 1)
+                        } else {
+                            String::new()
+                        }
+                    );
+                }
+            }
+        }
+    }
+
+    info!(
+        "Found {flagged} problem(s) with the ballots for {}",
+        round.name.as_str()
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_lint_sheet() {
+    let rules = ScoreRules {
+        score_min: 68.0,
+        score_max: 82.0,
+        reply_score_min: 34.0,
+        reply_score_max: 41.0,
+        maximum_margin: 15.0,
+        substantive_speakers: 2,
+    };
+    let sheet = serde_json::json!({
+        "teams": [
+            {
+                "side": "aff",
+                "win": true,
+                "speeches": [
+                    { "speaker": "a1", "score": 70.0 },
+                    { "speaker": "a1", "score": 70.0 },
+                    { "speaker": "a1", "score": 36.0 },
+                ]
+            },
+            {
+                "side": "neg",
+                "win": false,
+                "speeches": [
+                    { "speaker": "n1", "score": 85.0 },
+                    { "speaker": "n2", "score": 75.0 },
+                    { "speaker": "n1", "score": 37.0 },
+                ]
+            }
+        ]
+    });
+
+    assert_eq!(
+        lint_sheet(&sheet, &rules, |s| s.to_string()),
+        vec![
+            "aff: a1 gave more than one substantive speech".to_string(),
+            "neg: n1 was given 85, which is outside the range 68-82".to_string(),
+            "aff beat neg despite scoring fewer points (176 vs 197)".to_string(),
+            "The margin (21) is greater than the maximum margin (15)".to_string(),
+        ]
+    );
+}
@@ -0,0 +1,100 @@
+use std::{fs::File, io::BufWriter, process::exit};
+
+use printpdf::{BuiltinFont, Mm, PdfDocument};
+use tracing::{error, info};
+
+use crate::{Auth, request_manager::RequestManager, signage::room_contexts};
+
+/// The template used when `--template` is not provided.
+const DEFAULT_BALLOT_TEMPLATE: &str = r#"{{ round }}: {{ room }}
+{% for motion in motions %}
+Motion: {{ motion }}{% endfor %}
+{% if chair %}
+Chair: {{ chair }}{% endif %}{% if panellists %}
+Panellists: {{ panellists | join(sep=", ") }}{% endif %}{% if trainees %}
+Trainees: {{ trainees | join(sep=", ") }}{% endif %}
+{% for team in teams %}
+{{ team.name }}{% if team.side %} ({{ team.side }}){% endif %}
+{% for speaker in team.speakers %}    {{ speaker }}: ______
+{% endfor %}    Total: ______
+{% endfor %}
+Winner: ______________________
+
+Adjudicator signature: ______________________
+"#;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const FONT_SIZE: f32 = 11.0;
+const LINE_HEIGHT: f32 = 6.0;
+
+/// Generates a PDF containing a pre-filled paper ballot for every debate in
+/// the round. Each ballot is rendered as plain text from a Tera template
+/// (which has the same variables as the signage templates) and then laid out
+/// line-by-line, starting a new page for each debate.
+///
+/// Note: the PDF uses the built-in Helvetica font, which cannot display
+/// characters outside of the Windows-1252 character set.
+pub async fn export_ballots(auth: Auth, round: &str, pdf: &str, template: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let template_source = match template {
+        Some(template) => std::fs::read_to_string(&template).unwrap_or_else(|e| {
+            error!("Could not read template {template}: {e}");
+            exit(1);
+        }),
+        None => DEFAULT_BALLOT_TEMPLATE.to_string(),
+    };
+    let mut tera = tera::Tera::default();
+    if let Err(e) = tera.add_raw_template("ballot", &template_source) {
+        error!("Could not parse ballot template: {e}");
+        exit(1);
+    }
+
+    let rooms = room_contexts(&auth, round, manager).await;
+    if rooms.is_empty() {
+        error!("There are no debates in round {round}");
+        exit(1);
+    }
+
+    let (doc, first_page, first_layer) =
+        PdfDocument::new("Ballots", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Ballot");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+
+    let mut next_page = Some((first_page, first_layer));
+    for room in &rooms {
+        let rendered = tera
+            .render("ballot", &tera::Context::from_serialize(room).unwrap())
+            .unwrap_or_else(|e| {
+                error!("Could not render ballot for {}: {e}", room.room);
+                exit(1);
+            });
+
+        let (page, layer) = next_page
+            .take()
+            .unwrap_or_else(|| doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Ballot"));
+        let mut layer = doc.get_page(page).get_layer(layer);
+        let mut y = PAGE_HEIGHT - MARGIN;
+        for line in rendered.lines() {
+            if y < MARGIN {
+                let (page, new_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Ballot");
+                layer = doc.get_page(page).get_layer(new_layer);
+                y = PAGE_HEIGHT - MARGIN;
+            }
+            layer.use_text(line, FONT_SIZE, Mm(MARGIN), Mm(y), &font);
+            y -= LINE_HEIGHT;
+        }
+    }
+
+    let file = File::create(pdf).unwrap_or_else(|e| {
+        error!("Could not create {pdf}: {e}");
+        exit(1);
+    });
+    if let Err(e) = doc.save(&mut BufWriter::new(file)) {
+        error!("Could not write ballots to {pdf}: {e}");
+        exit(1);
+    }
+
+    info!("Saved {} ballots to {pdf}", rooms.len());
+}
@@ -0,0 +1,443 @@
+//! A chat bot for the tab room, which listens for commands (such as `!draw R3`
+//! or `!swap R3 "Team A" "Team B"`) in a Slack or Discord channel, carries them
+//! out by running the CLI itself, and replies with the output.
+
+use std::{process::exit, time::Duration};
+
+use serde_json::{Value, json};
+use tracing::{error, info, warn};
+
+/// A command which can be given to the bot, and the CLI command it runs (the
+/// rest of the message is passed on as its arguments).
+struct BotCommand {
+    name: &'static str,
+    args: &'static [&'static str],
+    /// Whether the command changes anything on Tabbycat (so only the users in
+    /// the allowlist may run it).
+    mutates: bool,
+    usage: &'static str,
+}
+
+const BOT_COMMANDS: &[BotCommand] = &[
+    BotCommand {
+        name: "draw",
+        args: &["view-draw"],
+        mutates: false,
+        usage: "!draw <round> [--room <room>] [--team <team>] [--judge <judge>]",
+    },
+    BotCommand {
+        name: "schedule",
+        args: &["schedule", "show"],
+        mutates: false,
+        usage: "!schedule",
+    },
+    BotCommand {
+        name: "swap",
+        args: &["draw-swap"],
+        mutates: true,
+        usage: "!swap <round> <team or judge> <team or judge> [--swap-sides]",
+    },
+    BotCommand {
+        name: "swap-chairs",
+        args: &["draw", "swap-chairs"],
+        mutates: true,
+        usage: "!swap-chairs <round> <room> <room>",
+    },
+    BotCommand {
+        name: "add-judge",
+        args: &["add-judge"],
+        mutates: true,
+        usage: "!add-judge <round> <judge> <room> <role> [--force]",
+    },
+    BotCommand {
+        name: "remove-judge",
+        args: &["remove-judge"],
+        mutates: true,
+        usage: "!remove-judge <round> <judge>",
+    },
+    BotCommand {
+        name: "release",
+        args: &["draw", "release"],
+        mutates: true,
+        usage: "!release <round> [--motions]",
+    },
+];
+
+/// The chat service the bot is connected to.
+#[derive(Debug, Clone)]
+pub enum Platform {
+    Slack { token: String },
+    Discord { token: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ChatMessage {
+    id: String,
+    user: String,
+    text: String,
+}
+
+const SLACK_API: &str = "https://slack.com/api";
+const DISCORD_API: &str = "https://discord.com/api/v10";
+
+impl Platform {
+    /// The longest message which can be posted.
+    fn max_len(&self) -> usize {
+        match self {
+            Platform::Slack { .. } => 4000,
+            Platform::Discord { .. } => 2000,
+        }
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            Platform::Slack { token } => request.bearer_auth(token),
+            Platform::Discord { token } => request.header("Authorization", format!("Bot {token}")),
+        }
+    }
+
+    /// The messages posted (by people, not bots) in `channel` after the
+    /// message `after`, oldest first. Without `after`, only the latest message
+    /// is returned, so that the bot knows where to start from.
+    async fn poll(
+        &self,
+        client: &reqwest::Client,
+        channel: &str,
+        after: Option<&str>,
+    ) -> Result<Vec<ChatMessage>, String> {
+        let limit = if after.is_some() { "100" } else { "1" };
+        let request = match self {
+            Platform::Slack { .. } => {
+                let mut query = vec![("channel", channel), ("limit", limit)];
+                if let Some(after) = after {
+                    query.push(("oldest", after));
+                }
+                client
+                    .get(format!("{SLACK_API}/conversations.history"))
+                    .query(&query)
+            }
+            Platform::Discord { .. } => {
+                let mut query = vec![("limit", limit)];
+                if let Some(after) = after {
+                    query.push(("after", after));
+                }
+                client
+                    .get(format!("{DISCORD_API}/channels/{channel}/messages"))
+                    .query(&query)
+            }
+        };
+
+        let resp = self
+            .authorize(request)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = resp.status();
+        let body: Value = resp.json().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!("{status}: {body}"));
+        }
+
+        // both APIs list the newest message first
+        let messages = match self {
+            Platform::Slack { .. } => {
+                if body["ok"] != json!(true) {
+                    return Err(body["error"]
+                        .as_str()
+                        .unwrap_or("unknown error")
+                        .to_string());
+                }
+                body["messages"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default()
+                    .iter()
+                    .rev()
+                    .map(|message| ChatMessage {
+                        id: message["ts"].as_str().unwrap_or_default().to_string(),
+                        // messages from bots (including this one) have no user
+                        user: if message["bot_id"].is_null() {
+                            message["user"].as_str().unwrap_or_default().to_string()
+                        } else {
+                            String::new()
+                        },
+                        text: unescape_slack(message["text"].as_str().unwrap_or_default()),
+                    })
+                    .collect()
+            }
+            Platform::Discord { .. } => body
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+                .iter()
+                .rev()
+                .map(|message| ChatMessage {
+                    id: message["id"].as_str().unwrap_or_default().to_string(),
+                    user: match message["author"]["bot"].as_bool() {
+                        Some(true) => String::new(),
+                        _ => message["author"]["id"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                    },
+                    text: message["content"].as_str().unwrap_or_default().to_string(),
+                })
+                .collect(),
+        };
+        Ok(messages)
+    }
+
+    /// Posts `text` to `channel`.
+    async fn reply(&self, client: &reqwest::Client, channel: &str, text: &str) {
+        let request = match self {
+            Platform::Slack { .. } => client
+                .post(format!("{SLACK_API}/chat.postMessage"))
+                .json(&json!({ "channel": channel, "text": text })),
+            Platform::Discord { .. } => client
+                .post(format!("{DISCORD_API}/channels/{channel}/messages"))
+                .json(&json!({ "content": text })),
+        };
+
+        let res = self.authorize(request).send().await;
+        match res {
+            Ok(res) if res.status().is_success() => {
+                // Slack reports errors in the body
+                let body: Value = res.json().await.unwrap_or_default();
+                if body["ok"] == json!(false) {
+                    error!("Could not post to the channel: {}", body["error"]);
+                }
+            }
+            Ok(res) => error!(
+                "Could not post to the channel ({}): {}",
+                res.status(),
+                res.text().await.unwrap_or_default()
+            ),
+            Err(e) => error!("Could not post to the channel: {e}"),
+        }
+    }
+}
+
+/// Undoes the escaping Slack applies to `&`, `<` and `>` in messages.
+fn unescape_slack(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Splits a command into words, keeping quoted phrases (in straight or curly
+/// quotes, which chat apps often substitute) together.
+fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (None, '"' | '\'' | '“' | '‘') => {
+                quote = Some(match c {
+                    '“' => '”',
+                    '‘' => '’',
+                    _ => c,
+                });
+                word.get_or_insert_with(String::new);
+            }
+            (Some(end), c) if c == end => quote = None,
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("the command has an unclosed quote".to_string());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// The command in a message (if the message is a command, i.e. starts with
+/// `!`), and the arguments to run the CLI with.
+fn command_of(text: &str) -> Option<Result<(&'static BotCommand, Vec<String>), String>> {
+    let text = text.trim().strip_prefix('!')?;
+    let words = match split_words(text) {
+        Ok(words) => words,
+        Err(e) => return Some(Err(e)),
+    };
+    let (name, rest) = words.split_first()?;
+
+    let Some(command) = BOT_COMMANDS
+        .iter()
+        .find(|command| command.name.eq_ignore_ascii_case(name))
+    else {
+        return Some(Err(format!("unknown command `!{name}` (try `!help`)")));
+    };
+    let args = command
+        .args
+        .iter()
+        .map(|arg| arg.to_string())
+        .chain(rest.iter().cloned())
+        .collect();
+    Some(Ok((command, args)))
+}
+
+fn help() -> String {
+    let usage = BOT_COMMANDS
+        .iter()
+        .map(|command| {
+            let restricted = if command.mutates {
+                " (allowlist only)"
+            } else {
+                ""
+            };
+            format!("{}{restricted}", command.usage)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("```\n{usage}\n```")
+}
+
+/// Removes the terminal colour codes from the output of the CLI.
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip to the end of the escape sequence, e.g. `\x1b[32m`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Runs the CLI with `args`, returning its output (formatted as a code block,
+/// and cut down to at most `max_len` characters).
+async fn run(args: &[String], max_len: usize) -> String {
+    let exe = std::env::current_exe().unwrap();
+    let output = tokio::process::Command::new(exe)
+        .args(args)
+        .env("RUST_LOG", "tabbycat=info")
+        .output()
+        .await;
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => return format!("Could not run the command: {e}"),
+    };
+
+    let text = strip_ansi(&format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ));
+    let status = if output.status.success() {
+        "Done"
+    } else {
+        "The command failed"
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        return format!("{status}.");
+    }
+
+    // leave room for the status and the code block
+    let room = max_len.saturating_sub(status.len() + 32);
+    let text = match text.char_indices().nth(room) {
+        Some((end, _)) => format!("{}\n… (cut short)", &text[..end]),
+        None => text.to_string(),
+    };
+    format!("{status}:\n```\n{text}\n```")
+}
+
+/// Listens for commands in `channel` (checking every `interval` seconds) and
+/// carries them out, replying with the output. Commands which change the draw
+/// may only be run by the users (IDs) in `allow`. Runs until interrupted.
+pub async fn run_bot(platform: Platform, channel: &str, allow: Vec<String>, interval: u64) {
+    // note: this deliberately doesn't use `RequestManager`, which would attach
+    // the Tabbycat API key to the requests
+    let client = reqwest::Client::new();
+
+    let mut last = match platform.poll(&client, channel, None).await {
+        Ok(messages) => messages.last().map(|message| message.id.clone()),
+        Err(e) => {
+            error!("Could not read the channel {channel}: {e}");
+            exit(1);
+        }
+    };
+    if allow.is_empty() {
+        warn!("No users are allowed to change the draw (add them with `--allow <user ID>`)");
+    }
+    info!("Listening for commands in {channel} (checking every {interval} seconds)");
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        let messages = match platform.poll(&client, channel, last.as_deref()).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                error!("Could not read the channel {channel}: {e}");
+                continue;
+            }
+        };
+        for message in messages {
+            last = Some(message.id.clone());
+            if message.user.is_empty() {
+                continue;
+            }
+
+            let reply = match command_of(&message.text) {
+                None => continue,
+                Some(_) if message.text.trim().eq_ignore_ascii_case("!help") => help(),
+                Some(Err(e)) => format!("Sorry, {e}."),
+                Some(Ok((command, _))) if command.mutates && !allow.contains(&message.user) => {
+                    warn!(
+                        "{} tried to run `!{}`, but is not on the allowlist",
+                        message.user, command.name
+                    );
+                    format!("Sorry, you are not allowed to run `!{}`.", command.name)
+                }
+                Some(Ok((command, args))) => {
+                    info!("{} ran `{}`", message.user, message.text.trim());
+                    if command.mutates {
+                        platform
+                            .reply(&client, channel, &format!("Running `!{}`…", command.name))
+                            .await;
+                    }
+                    run(&args, platform.max_len()).await
+                }
+            };
+            platform.reply(&client, channel, &reply).await;
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_command_of() {
+    assert_eq!(
+        split_words(r#"swap R3 "Team A" “Team B”"#).unwrap(),
+        vec!["swap", "R3", "Team A", "Team B"]
+    );
+    assert_eq!(split_words("  a  ''  b ").unwrap(), vec!["a", "", "b"]);
+    assert!(split_words("swap \"Team A").is_err());
+
+    let (command, args) = command_of(r#"!swap R3 "Team A" "Team B""#)
+        .unwrap()
+        .unwrap();
+    assert!(command.mutates);
+    assert_eq!(args, vec!["draw-swap", "R3", "Team A", "Team B"]);
+
+    let (command, args) = command_of("!Draw R3").unwrap().unwrap();
+    assert!(!command.mutates);
+    assert_eq!(args, vec!["view-draw", "R3"]);
+
+    assert!(command_of("the draw is out").is_none());
+    assert!(
+        command_of("!import --teams-csv teams.csv")
+            .unwrap()
+            .is_err()
+    );
+
+    assert_eq!(strip_ansi("\x1b[32m INFO\x1b[0m done"), " INFO done");
+}
@@ -1,13 +1,172 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    process::exit,
+};
 
+use serde::Deserialize;
 use serde_json::json;
-use tracing::{Level, error, info, span};
+use tabbycat_api::types::{BreakCategory, Team};
+use tracing::{Level, error, info, span, warn};
 
-use crate::Auth;
+use crate::{
+    Auth, audit_log, check_stdin_paths,
+    import::{bool_from_str, read_rows},
+    names::names_match,
+};
+
+/// A row of the overrides CSV file, which decides a team's eligibility for a
+/// category regardless of what the preset computes.
+#[derive(Deserialize, Debug, Clone)]
+struct OverrideRow {
+    team: String,
+    /// The name or slug of the break category.
+    category: String,
+    #[serde(deserialize_with = "bool_from_str")]
+    eligible: bool,
+}
+
+/// An override, as (team URL, break category URL, eligible).
+type Override = (String, String, bool);
+
+/// Reads the overrides CSV file (if one was given), resolving the names of
+/// teams and categories.
+fn read_overrides(
+    path: &Option<String>,
+    teams: &[Team],
+    break_categories: &[BreakCategory],
+) -> Vec<Override> {
+    read_rows::<OverrideRow>(path)
+        .into_iter()
+        .map(|row| {
+            let team = teams
+                .iter()
+                .find(|team| {
+                    names_match(&team.long_name, &row.team)
+                        || names_match(&team.short_name, &row.team)
+                })
+                .unwrap_or_else(|| {
+                    error!("The overrides refer to {}, which is not a team", row.team);
+                    exit(1);
+                });
+            let category = break_categories
+                .iter()
+                .find(|cat| {
+                    cat.name.trim().eq_ignore_ascii_case(row.category.trim())
+                        || cat.slug.as_str().eq_ignore_ascii_case(row.category.trim())
+                })
+                .unwrap_or_else(|| {
+                    error!(
+                        "The overrides refer to {}, which is not a break category",
+                        row.category
+                    );
+                    exit(1);
+                });
+            (team.url.clone(), category.url.clone(), row.eligible)
+        })
+        .collect()
+}
+
+/// Applies the overrides for the team to the break categories it is eligible
+/// for.
+fn apply_overrides(break_cats: &mut HashSet<String>, team_url: &str, overrides: &[Override]) {
+    for (_, category, eligible) in overrides.iter().filter(|(team, ..)| team == team_url) {
+        if *eligible {
+            break_cats.insert(category.clone());
+        } else {
+            break_cats.remove(category);
+        }
+    }
+}
+
+/// A row of the certifications CSV file, which records the language status
+/// each speaker has been certified with.
+#[derive(Deserialize, Debug, Clone)]
+struct CertificationRow {
+    speaker: String,
+    status: String,
+    certifying_body: Option<String>,
+}
+
+/// Normalises a certified language status to `esl` or `efl` (any other
+/// status, e.g. `epl`, is not a language category).
+fn language_status(status: &str) -> Option<&'static str> {
+    match status.trim().to_lowercase().as_str() {
+        "esl" | "english as a second language" => Some("esl"),
+        "efl" | "english as a foreign language" => Some("efl"),
+        _ => None,
+    }
+}
+
+/// A speaker's certified language status.
+#[derive(Debug, Clone)]
+struct Certification {
+    status: String,
+    certifying_body: Option<String>,
+    /// The break category (URL) the speaker counts towards, if any.
+    category: Option<String>,
+}
+
+/// Reads the certifications CSV file, returning the certification of each
+/// speaker (by URL) listed in it.
+fn read_certifications(
+    path: &str,
+    teams: &[Team],
+    break_categories: &[BreakCategory],
+) -> HashMap<String, Certification> {
+    read_rows::<CertificationRow>(&Some(path.to_string()))
+        .into_iter()
+        .map(|row| {
+            let speaker = teams
+                .iter()
+                .flat_map(|team| &team.speakers)
+                .find(|speaker| names_match(&speaker.name, &row.speaker))
+                .unwrap_or_else(|| {
+                    error!(
+                        "The certifications refer to {}, who is not a speaker",
+                        row.speaker
+                    );
+                    exit(1);
+                });
+            let category = language_status(&row.status).map(|status| {
+                break_categories
+                    .iter()
+                    .find(|cat| cat.name.to_ascii_lowercase().contains(status))
+                    .map(|cat| cat.url.clone())
+                    .unwrap_or_else(|| {
+                        error!(
+                            "There is no {status} break category (needed for {})",
+                            row.speaker
+                        );
+                        exit(1);
+                    })
+            });
+
+            (
+                speaker.url.clone(),
+                Certification {
+                    status: row.status,
+                    certifying_body: row.certifying_body,
+                    category,
+                },
+            )
+        })
+        .collect()
+}
 
 /// Computes whether each team should be break eligible according to the rules
-/// of the specified format.
-pub fn do_compute_break_eligibility(auth: Auth, format: String) {
+/// of the specified format, and then applies the overrides (if a CSV file with
+/// `team`, `category` and `eligible` columns is given).
+///
+/// If a certifications CSV file is given, the ESL/EFL status of speakers is
+/// taken from it (rather than from their speaker categories).
+pub fn do_compute_break_eligibility(
+    auth: Auth,
+    format: String,
+    overrides: Option<String>,
+    certifications: Option<String>,
+) {
+    check_stdin_paths(&[overrides.as_deref(), certifications.as_deref()]);
+
     let break_categories: Vec<tabbycat_api::types::BreakCategory> = attohttpc::get(format!(
         "{}/api/v1/tournaments/{}/break-categories",
         auth.tabbycat_url, auth.tournament_slug
@@ -36,6 +195,8 @@ pub fn do_compute_break_eligibility(auth: Auth, format: String) {
     .json()
     .unwrap();
 
+    let overrides = read_overrides(&overrides, &teams, &break_categories);
+
     let span = span!(Level::INFO, "break_eligibility");
     let _guard = span.enter();
 
@@ -59,17 +220,76 @@ pub fn do_compute_break_eligibility(auth: Auth, format: String) {
         map.insert(speaker_cat.url.clone(), break_cat.url.clone());
     }
 
+    let language_categories = break_categories
+        .iter()
+        .filter(|cat| {
+            let name = cat.name.to_ascii_lowercase();
+            name.contains("esl") || name.contains("efl")
+        })
+        .map(|cat| cat.url.clone())
+        .collect::<Vec<_>>();
+    let category_names = |urls: &[String]| {
+        urls.iter()
+            .filter_map(|url| break_categories.iter().find(|cat| &cat.url == url))
+            .map(|cat| cat.name.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let certifications =
+        certifications.map(|path| read_certifications(&path, &teams, &break_categories));
+
     let mut team_breaking_counts = HashMap::new();
 
     for team in &teams {
         let mut n_breaking_per_category: HashMap<String, usize> = HashMap::new();
 
         for speaker in &team.speakers {
-            for category in &speaker.categories {
-                let break_cat = map.get(category).unwrap();
+            let mut categories = speaker
+                .categories
+                .iter()
+                .map(|category| map.get(category).unwrap().clone())
+                .collect::<Vec<_>>();
+
+            if let Some(certifications) = &certifications {
+                let current = categories
+                    .iter()
+                    .filter(|cat| language_categories.contains(cat))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                categories.retain(|cat| !language_categories.contains(cat));
 
+                match certifications.get(&speaker.url) {
+                    Some(certification) => {
+                        let certified = certification.category.iter().cloned().collect::<Vec<_>>();
+                        if certified != current {
+                            warn!(
+                                "{} ({}) is certified as {}{}, but their speaker categories are [{}]",
+                                speaker.name,
+                                team.short_name,
+                                certification.status,
+                                certification
+                                    .certifying_body
+                                    .as_ref()
+                                    .map(|body| format!(" by {body}"))
+                                    .unwrap_or_default(),
+                                category_names(&current)
+                            );
+                        }
+                        categories.extend(certified);
+                    }
+                    None if !current.is_empty() => warn!(
+                        "{} ({}) is in [{}] but has no certification, so does not count towards it",
+                        speaker.name,
+                        team.short_name,
+                        category_names(&current)
+                    ),
+                    None => (),
+                }
+            }
+
+            for break_cat in categories {
                 n_breaking_per_category
-                    .entry(break_cat.clone())
+                    .entry(break_cat)
                     .and_modify(|count| *count += 1)
                     .or_insert(1);
             }
@@ -115,15 +335,15 @@ pub fn do_compute_break_eligibility(auth: Auth, format: String) {
             }
 
             break_cats.insert(open.url.clone());
+            apply_overrides(&mut break_cats, &team_url, &overrides);
 
-            attohttpc::patch(&team_url)
-                .header("Authorization", format!("Token {}", auth.api_key))
-                .json(&json!({
+            audit_log::patch_json(
+                &team_url,
+                &auth,
+                &json!({
                     "break_categories": break_cats
-                }))
-                .unwrap()
-                .send()
-                .unwrap();
+                }),
+            );
             info!(
                 "Set team {} break eligibility to {:?}",
                 team.short_name,
@@ -177,15 +397,15 @@ pub fn do_compute_break_eligibility(auth: Auth, format: String) {
             }
 
             break_cats.insert(open.url.clone());
+            apply_overrides(&mut break_cats, &team_url, &overrides);
 
-            attohttpc::patch(&team_url)
-                .header("Authorization", format!("Token {}", auth.api_key))
-                .json(&json!({
+            audit_log::patch_json(
+                &team_url,
+                &auth,
+                &json!({
                     "break_categories": break_cats
-                }))
-                .unwrap()
-                .send()
-                .unwrap();
+                }),
+            );
             info!(
                 "Set team {} break eligibility to {:?}",
                 team.short_name,
@@ -206,3 +426,27 @@ pub fn do_compute_break_eligibility(auth: Auth, format: String) {
         error!("Unrecognised format {}", c)
     }
 }
+
+#[cfg(test)]
+#[test]
+fn test_apply_overrides() {
+    let overrides = vec![
+        ("team-a".to_string(), "esl".to_string(), true),
+        ("team-a".to_string(), "open".to_string(), false),
+        ("team-b".to_string(), "efl".to_string(), true),
+    ];
+    let mut break_cats = HashSet::from(["open".to_string()]);
+    apply_overrides(&mut break_cats, "team-a", &overrides);
+    assert_eq!(break_cats, HashSet::from(["esl".to_string()]));
+}
+
+#[cfg(test)]
+#[test]
+fn test_language_status() {
+    assert_eq!(language_status("ESL"), Some("esl"));
+    assert_eq!(
+        language_status(" English as a foreign language"),
+        Some("efl")
+    );
+    assert_eq!(language_status("EPL"), None);
+}
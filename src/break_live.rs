@@ -0,0 +1,283 @@
+use std::{collections::HashSet, process::exit};
+
+use comfy_table::{Cell, Color, ContentArrangement, Table};
+use serde_json::Value;
+use tabbycat_api::types::{Round, Team};
+use tracing::{error, info};
+
+use crate::{
+    Auth,
+    api_utils::{get_break_categories, get_preference, get_rounds, get_teams},
+    i18n::{tr, tr_args},
+    request_manager::RequestManager,
+    standings::{Standing, get_team_standings},
+};
+
+/// Whether a team can still break in a category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    /// The team breaks however the remaining rounds go.
+    Safe,
+    /// The team may or may not break.
+    Live,
+    /// The team cannot break, even if it wins every remaining round.
+    Dead,
+}
+
+impl Liveness {
+    fn cell(self) -> Cell {
+        match self {
+            Liveness::Safe => Cell::new(tr("break-safe")).fg(Color::Green),
+            Liveness::Live => Cell::new(tr("break-live")).fg(Color::Yellow),
+            Liveness::Dead => Cell::new(tr("break-dead")).fg(Color::Red),
+        }
+    }
+}
+
+/// Classifies each team (given the points of every team eligible for the
+/// category) with `remaining` preliminary rounds left to go, winning at most
+/// `max_points` in each.
+///
+/// This is deliberately conservative: teams are assumed to be able to win (or
+/// lose) every remaining debate regardless of who they meet, and ties are
+/// assumed to go against the team in question. A team is safe if fewer than
+/// `break_size` other teams could reach its current points, and dead if at
+/// least `break_size` other teams already have more points than it could
+/// reach.
+pub fn liveness(
+    points: &[f64],
+    remaining: usize,
+    max_points: f64,
+    break_size: usize,
+) -> Vec<Liveness> {
+    let best_case = |points: f64| points + remaining as f64 * max_points;
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, &own)| {
+            let others = points
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, &other)| other);
+            let can_catch_up = others
+                .clone()
+                .filter(|&other| best_case(other) >= own)
+                .count();
+            let already_ahead = others.filter(|&other| other > best_case(own)).count();
+            if break_size == 0 || already_ahead >= break_size {
+                Liveness::Dead
+            } else if can_catch_up < break_size {
+                Liveness::Safe
+            } else {
+                Liveness::Live
+            }
+        })
+        .collect()
+}
+
+/// The most points (or wins) a team can win in a single debate, given the
+/// `debate_rules__teams_in_debate` preference.
+pub fn max_points(teams_in_debate: &Value) -> f64 {
+    // in British Parliamentary a team can win at most 3 points in a debate
+    if teams_in_debate.as_i64() == Some(4) {
+        3.0
+    } else {
+        1.0
+    }
+}
+
+pub fn is_prelim(round: &Round) -> bool {
+    serde_json::to_value(round).unwrap()["stage"].as_str() != Some("E")
+}
+
+/// The number of preliminary rounds which have not been completed (only
+/// counting `from` and the rounds after it, if given).
+pub fn remaining_prelims(rounds: &[Round], from: Option<&Round>) -> usize {
+    rounds
+        .iter()
+        .filter(|round| is_prelim(round))
+        .filter(|round| serde_json::to_value(round).unwrap()["completed"].as_bool() != Some(true))
+        .filter(|round| from.is_none_or(|from| round.seq >= from.seq))
+        .count()
+}
+
+/// The teams eligible for the break category with the given URL, with their
+/// points, in the order of the standings. The first metric of the standings
+/// is the one teams are primarily ranked by (i.e. wins or points).
+fn eligible<'a>(standings: &[Standing], teams: &'a [Team], category: &str) -> Vec<(&'a Team, f64)> {
+    standings
+        .iter()
+        .filter_map(|standing| {
+            let team = teams
+                .iter()
+                .find(|team| standing.team.as_deref() == Some(team.url.as_str()))?;
+            let points = standing
+                .metrics
+                .first()
+                .and_then(|metric| metric.value)
+                .unwrap_or(0.0);
+            team.break_categories
+                .iter()
+                .any(|url| url == category)
+                .then_some((team, points))
+        })
+        .collect()
+}
+
+/// The URLs of the teams which are live (see [`Liveness::Live`]) in at least
+/// one of the break categories they are eligible for, with `remaining`
+/// preliminary rounds left to go. `break_size` overrides the size of the
+/// general break category.
+pub async fn live_teams(
+    auth: &Auth,
+    teams: &[Team],
+    remaining: usize,
+    break_size: Option<usize>,
+    manager: RequestManager,
+) -> HashSet<String> {
+    let (standings, break_categories, teams_in_debate) = tokio::join!(
+        get_team_standings(auth, manager.clone()),
+        get_break_categories(auth, manager.clone()),
+        get_preference(auth, "debate_rules__teams_in_debate", manager.clone()),
+    );
+    if break_categories.is_empty() {
+        error!("The tournament has no break categories, so no team is live.");
+        exit(1);
+    }
+
+    let mut live = HashSet::new();
+    for category in &break_categories {
+        let eligible = eligible(&standings, teams, &category.url);
+        let points = eligible
+            .iter()
+            .map(|(_, points)| *points)
+            .collect::<Vec<_>>();
+        let break_size = match (category.is_general, break_size) {
+            (true, Some(break_size)) => break_size,
+            _ => category.break_size as usize,
+        };
+        for ((team, _), status) in eligible.iter().zip(liveness(
+            &points,
+            remaining,
+            max_points(&teams_in_debate),
+            break_size,
+        )) {
+            if status == Liveness::Live {
+                live.insert(team.url.clone());
+            }
+        }
+    }
+    live
+}
+
+/// Prints, for each break category (or only `category`, if provided), which
+/// teams are safe, live or dead based on the current standings and the
+/// number of preliminary rounds which have not yet been completed.
+pub async fn break_live(auth: Auth, category: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (rounds, teams, standings, break_categories, teams_in_debate) = tokio::join!(
+        get_rounds(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_team_standings(&auth, manager.clone()),
+        get_break_categories(&auth, manager.clone()),
+        get_preference(&auth, "debate_rules__teams_in_debate", manager.clone()),
+    );
+
+    let categories = break_categories
+        .iter()
+        .filter(|cat| {
+            category.as_ref().is_none_or(|wanted| {
+                cat.name.as_str().eq_ignore_ascii_case(wanted)
+                    || cat.slug.as_str().eq_ignore_ascii_case(wanted)
+            })
+        })
+        .collect::<Vec<_>>();
+    if categories.is_empty() {
+        match category {
+            Some(category) => error!("No break category is called {category}"),
+            None => error!("The tournament has no break categories"),
+        }
+        exit(1);
+    }
+
+    let remaining = remaining_prelims(&rounds, None);
+    let max_points = max_points(&teams_in_debate);
+
+    info!(
+        "{}",
+        tr_args("break-remaining", [("count", remaining.into())])
+    );
+
+    for category in categories {
+        let eligible = eligible(&standings, &teams, &category.url);
+
+        let break_size = category.break_size as usize;
+        let points = eligible
+            .iter()
+            .map(|(_, points)| *points)
+            .collect::<Vec<_>>();
+        let statuses = liveness(&points, remaining, max_points, break_size);
+
+        let mut table = Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                tr("break-rank"),
+                tr("team"),
+                tr("break-points"),
+                tr("break-best-case"),
+                tr("break-status"),
+            ]);
+        for (i, ((team, points), status)) in eligible.iter().zip(&statuses).enumerate() {
+            table.add_row(vec![
+                Cell::new(i + 1),
+                Cell::new(team.short_name.as_str()),
+                Cell::new(points),
+                Cell::new(points + remaining as f64 * max_points),
+                status.cell(),
+            ]);
+        }
+
+        let count = |wanted: Liveness| statuses.iter().filter(|&&s| s == wanted).count();
+        println!(
+            "{}",
+            tr_args(
+                "break-summary",
+                [
+                    ("category", category.name.as_str().into()),
+                    ("size", break_size.into()),
+                    ("safe", count(Liveness::Safe).into()),
+                    ("live", count(Liveness::Live).into()),
+                    ("dead", count(Liveness::Dead).into()),
+                ]
+            )
+        );
+        println!("{table}");
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_liveness() {
+    use Liveness::*;
+
+    // four teams, two breaking, one round to go
+    assert_eq!(
+        liveness(&[4.0, 3.0, 2.0, 1.0], 1, 1.0, 2),
+        vec![Safe, Live, Live, Dead]
+    );
+    // no rounds left: the standings are final (ties are still live)
+    assert_eq!(
+        liveness(&[3.0, 2.0, 2.0, 1.0], 0, 1.0, 2),
+        vec![Safe, Live, Live, Dead]
+    );
+    // BP: a team 3 points behind the break can still draw level
+    assert_eq!(
+        liveness(&[9.0, 9.0, 6.0, 3.0], 1, 3.0, 2),
+        vec![Live, Live, Live, Dead]
+    );
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, process::exit};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde::Deserialize;
+use serde_json::json;
+use tabbycat_api::types::{Adjudicator, Feedback};
+use tracing::{Level, error, info, span};
+
+use crate::{
+    Auth,
+    api_utils::{get_feedbacks, get_judges},
+    names::{names_match, sort_key},
+    open_csv_file,
+    request_manager::RequestManager,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+struct BreakingJudgeRow {
+    /// The name (or ID) of the judge.
+    name: String,
+}
+
+/// The feedback scores given to each adjudicator (by URL).
+pub fn feedback_scores(feedbacks: &[Feedback]) -> HashMap<String, Vec<f64>> {
+    let mut scores: HashMap<String, Vec<f64>> = HashMap::new();
+    for feedback in feedbacks {
+        scores
+            .entry(feedback.adjudicator.clone())
+            .or_default()
+            .push(feedback.score);
+    }
+    scores
+}
+
+/// The mean of `scores`, or `None` if there are none.
+pub fn mean(scores: &[f64]) -> Option<f64> {
+    (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64)
+}
+
+/// The average feedback score of each adjudicator (by URL), along with the
+/// number of pieces of feedback it is the average of.
+pub fn average_feedback(feedbacks: &[Feedback]) -> HashMap<String, (f64, usize)> {
+    averages(feedback_scores(feedbacks))
+}
+
+fn averages(scores: HashMap<String, Vec<f64>>) -> HashMap<String, (f64, usize)> {
+    scores
+        .into_iter()
+        .filter_map(|(url, scores)| Some((url, (mean(&scores)?, scores.len()))))
+        .collect()
+}
+
+/// Whether the adjudicator with the given URL has an average feedback score
+/// of at least `threshold` (judges with no feedback never do).
+fn meets_threshold(averages: &HashMap<String, (f64, usize)>, url: &str, threshold: f64) -> bool {
+    averages
+        .get(url)
+        .is_some_and(|(average, _)| *average >= threshold)
+}
+
+fn is_breaking(judge: &Adjudicator) -> bool {
+    serde_json::to_value(judge).unwrap()["breaking"].as_bool() == Some(true)
+}
+
+async fn set_breaking(judge: &Adjudicator, breaking: bool, manager: &RequestManager) {
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .patch(&judge.url)
+                .json(&json!({ "breaking": breaking }))
+                .build()
+                .unwrap()
+        })
+        .await;
+    if !resp.status().is_success() {
+        error!(
+            "Failed to update {}: {} {}",
+            judge.name,
+            resp.status(),
+            resp.text().await.unwrap()
+        );
+        exit(1);
+    }
+}
+
+/// Marks judges as breaking (i.e. eligible to judge outrounds), either the
+/// judges listed in a CSV file (with a `name` column) or those whose average
+/// feedback is at least `threshold`. If `reset` is set, every other judge is
+/// marked as not breaking.
+pub async fn mark_breaking(auth: Auth, csv: Option<String>, threshold: Option<f64>, reset: bool) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (judges, feedbacks) = tokio::join!(
+        get_judges(&auth, manager.clone()),
+        get_feedbacks(&auth, manager.clone()),
+    );
+
+    let breaking: Vec<&Adjudicator> = match (open_csv_file(csv, true), threshold) {
+        (Some(mut csv), _) => csv
+            .deserialize::<BreakingJudgeRow>()
+            .map(|row| {
+                let row = row.unwrap();
+                judges
+                    .iter()
+                    .find(|judge| {
+                        names_match(&judge.name, &row.name)
+                            || judge.id.to_string() == row.name.trim()
+                    })
+                    .unwrap_or_else(|| {
+                        error!("No adjudicator named {} exists", row.name);
+                        exit(1);
+                    })
+            })
+            .collect(),
+        (None, Some(threshold)) => {
+            let averages = average_feedback(&feedbacks);
+            judges
+                .iter()
+                .filter(|judge| meets_threshold(&averages, &judge.url, threshold))
+                .collect()
+        }
+        (None, None) => {
+            error!("Please provide either `--csv` or `--from-feedback-threshold`.");
+            exit(1);
+        }
+    };
+
+    let span = span!(Level::INFO, "mark_breaking");
+    let _guard = span.enter();
+
+    for judge in &judges {
+        let should_break = breaking.iter().any(|b| b.url == judge.url);
+        if should_break == is_breaking(judge) || (!should_break && !reset) {
+            continue;
+        }
+        set_breaking(judge, should_break, &manager).await;
+        if should_break {
+            info!("Marked {} as breaking", judge.name);
+        } else {
+            info!("Marked {} as not breaking", judge.name);
+        }
+    }
+
+    info!("{} judge(s) should be breaking", breaking.len());
+}
+
+/// Lists the judges who are currently marked as breaking, along with their
+/// base score and average feedback.
+pub async fn list_breaking(auth: Auth) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (judges, feedbacks) = tokio::join!(
+        get_judges(&auth, manager.clone()),
+        get_feedbacks(&auth, manager.clone()),
+    );
+    let averages = average_feedback(&feedbacks);
+
+    let mut breaking = judges
+        .iter()
+        .filter(|judge| is_breaking(judge))
+        .collect::<Vec<_>>();
+    breaking.sort_by_cached_key(|judge| sort_key(&judge.name));
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec!["judge", "base score", "average feedback", "feedback"]);
+    for judge in &breaking {
+        let base_score = serde_json::to_value(judge).unwrap()["base_score"]
+            .as_f64()
+            .map(|score| score.to_string())
+            .unwrap_or_default();
+        let (average, count) = averages
+            .get(&judge.url)
+            .map(|(average, count)| (format!("{average:.2}"), *count))
+            .unwrap_or_default();
+        table.add_row(vec![
+            judge.name.clone(),
+            base_score,
+            average,
+            count.to_string(),
+        ]);
+    }
+
+    println!("{table}");
+    info!(
+        "{} of {} judge(s) are breaking",
+        breaking.len(),
+        judges.len()
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_average_feedback() {
+    assert_eq!(mean(&[]), None);
+    assert_eq!(mean(&[2.0, 3.0, 4.0]), Some(3.0));
+
+    let averages = averages(HashMap::from([
+        ("/adjudicators/1".to_string(), vec![3.0, 4.0]),
+        ("/adjudicators/2".to_string(), vec![2.5]),
+        ("/adjudicators/3".to_string(), vec![]),
+    ]));
+    assert_eq!(averages["/adjudicators/1"], (3.5, 2));
+    assert_eq!(averages["/adjudicators/2"], (2.5, 1));
+    assert!(!averages.contains_key("/adjudicators/3"));
+
+    // the threshold is inclusive, and judges without feedback are excluded
+    assert!(meets_threshold(&averages, "/adjudicators/1", 3.5));
+    assert!(!meets_threshold(&averages, "/adjudicators/2", 3.0));
+    assert!(!meets_threshold(&averages, "/adjudicators/3", 0.0));
+    assert!(!meets_threshold(&averages, "/adjudicators/4", 0.0));
+}
@@ -0,0 +1,195 @@
+//! A copy of the responses fetched from Tabbycat by the read-only commands
+//! (`view-draw`, `schedule show`, the reports, …), so that they can still be
+//! run if the venue's internet goes down, either with `--offline` or
+//! automatically when Tabbycat cannot be reached. Commands which change
+//! anything in Tabbycat neither use nor update the cache, so they never act on
+//! out of date data.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Once,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use chrono::{DateTime, Duration, Local};
+use reqwest::{StatusCode, header::HeaderMap};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static BANNER: Once = Once::new();
+
+/// Cached responses older than this are not used (and are deleted).
+const MAX_AGE_DAYS: i64 = 7;
+
+/// Turns the cache on for this command (which must be read-only).
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Sets whether requests are answered from the cache instead of being sent to
+/// Tabbycat (set by `--offline`).
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// A response to a `GET` request, as stored in the cache.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct CachedResponse {
+    url: String,
+    /// When the response was fetched (RFC 3339, in local time).
+    fetched_at: String,
+    status: u16,
+    body: String,
+}
+
+fn cache_dir() -> PathBuf {
+    dirs::home_dir()
+        .expect("Could not determine home directory")
+        .join(".tabbycat_cache")
+}
+
+/// The file the response to a request for `url` is cached in. The credentials
+/// the request is sent with are part of the key, so that responses fetched for
+/// different profiles (or API keys with different permissions) are kept
+/// apart.
+fn path_of(authorization: &str, url: &str) -> PathBuf {
+    let digest = Sha256::digest(format!("{authorization}\n{url}").as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    cache_dir().join(format!("{digest}.json"))
+}
+
+fn response(status: StatusCode, headers: HeaderMap, body: Vec<u8>) -> reqwest::Response {
+    let mut resp = http::Response::new(body);
+    *resp.status_mut() = status;
+    *resp.headers_mut() = headers;
+    reqwest::Response::from(resp)
+}
+
+/// Writes `contents` to `path` so that only the current user can read it (the
+/// responses include participants' contact details and private URLs).
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // (the mode is only used when the file is created)
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+fn create_cache_dir() -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(cache_dir())
+}
+
+/// Saves the response to a `GET` request for `url` in the cache (replacing the
+/// previous one), and returns it so that it can be read as usual. Failing to
+/// write the cache does not stop the command.
+pub async fn store(authorization: &str, url: &str, resp: reqwest::Response) -> reqwest::Response {
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let body = resp.bytes().await.unwrap().to_vec();
+
+    let cached = CachedResponse {
+        url: url.to_string(),
+        fetched_at: Local::now().to_rfc3339(),
+        status: status.as_u16(),
+        body: String::from_utf8_lossy(&body).to_string(),
+    };
+    let written = create_cache_dir().and_then(|()| {
+        write_private(
+            &path_of(authorization, url),
+            &serde_json::to_string(&cached).unwrap(),
+        )
+    });
+    if let Err(e) = written {
+        debug!("Could not cache the response from {url}: {e}");
+    }
+
+    response(status, headers, body)
+}
+
+/// Whether a response fetched at `fetched_at` is too old to be used.
+fn is_expired(fetched_at: &str, now: DateTime<Local>) -> bool {
+    DateTime::parse_from_rfc3339(fetched_at)
+        .ok()
+        .is_none_or(|fetched_at| now - fetched_at > Duration::days(MAX_AGE_DAYS))
+}
+
+/// The cached response to a `GET` request for `url`, if there is one (and it
+/// is recent enough). The first time a cached response is used, a banner is
+/// shown saying how old it is.
+pub fn cached(authorization: &str, url: &str) -> Option<reqwest::Response> {
+    let path = path_of(authorization, url);
+    let text = std::fs::read_to_string(&path).ok()?;
+    let cached: CachedResponse = serde_json::from_str(&text).ok()?;
+    if is_expired(&cached.fetched_at, Local::now()) {
+        let _ = std::fs::remove_file(&path);
+        return None;
+    }
+
+    BANNER.call_once(|| {
+        warn!(
+            "OFFLINE as of {}: showing data cached from Tabbycat, which may be out of date",
+            cached.fetched_at
+        );
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        reqwest::header::CONTENT_TYPE,
+        "application/json".parse().unwrap(),
+    );
+    Some(response(
+        StatusCode::from_u16(cached.status).unwrap_or(StatusCode::OK),
+        headers,
+        cached.body.into_bytes(),
+    ))
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_cached_response() {
+    assert_eq!(
+        path_of("Token a", "https://a/api"),
+        path_of("Token a", "https://a/api")
+    );
+    assert_ne!(
+        path_of("Token a", "https://a/api"),
+        path_of("Token a", "https://a/api/")
+    );
+    assert_ne!(
+        path_of("Token a", "https://a/api"),
+        path_of("Token b", "https://a/api")
+    );
+
+    let now = Local::now();
+    assert!(!is_expired(&(now - Duration::days(1)).to_rfc3339(), now));
+    assert!(is_expired(&(now - Duration::days(8)).to_rfc3339(), now));
+    assert!(is_expired("yesterday", now));
+
+    let resp = response(StatusCode::OK, HeaderMap::new(), b"[1, 2]".to_vec());
+    assert!(resp.status().is_success());
+    assert_eq!(resp.json::<Vec<i32>>().await.unwrap(), vec![1, 2]);
+}
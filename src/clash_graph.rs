@@ -0,0 +1,271 @@
+use std::{
+    collections::{HashMap, HashSet},
+    process::exit,
+};
+
+use tracing::{error, info};
+
+use crate::{
+    Auth,
+    api_utils::{get_institutions, get_judges, get_teams},
+    clashes_csv::SoftClashes,
+    request_manager::RequestManager,
+};
+
+struct Node {
+    id: String,
+    label: String,
+    kind: &'static str,
+}
+
+struct Edge {
+    from: String,
+    to: String,
+    /// Either `conflict`, `soft-conflict` (for a clash imported as soft) or
+    /// `member` (for a team or judge's own institution).
+    kind: &'static str,
+}
+
+/// Reads a list of URLs (e.g. `team_conflicts`) from an API object.
+pub fn urls(value: &serde_json::Value, field: &str) -> Vec<String> {
+    value[field]
+        .as_array()
+        .map(|urls| {
+            urls.iter()
+                .filter_map(|url| url.as_str().map(|url| url.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn to_dot(nodes: &[Node], edges: &[Edge]) -> String {
+    let mut out = String::from("graph clashes {\n");
+    for node in nodes {
+        let shape = match node.kind {
+            "judge" => "ellipse",
+            "team" => "box",
+            _ => "diamond",
+        };
+        out.push_str(&format!(
+            "  \"{}\" [label=\"{}\", shape={shape}];\n",
+            escape_dot(&node.id),
+            escape_dot(&node.label)
+        ));
+    }
+    for edge in edges {
+        let style = match edge.kind {
+            "member" => "dashed",
+            "soft-conflict" => "dotted",
+            _ => "solid",
+        };
+        out.push_str(&format!(
+            "  \"{}\" -- \"{}\" [style={style}];\n",
+            escape_dot(&edge.from),
+            escape_dot(&edge.to)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn to_graphml(nodes: &[Node], edges: &[Edge]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n\
+        \x20 <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n\
+        \x20 <key id=\"kind\" for=\"all\" attr.name=\"kind\" attr.type=\"string\"/>\n\
+        \x20 <graph id=\"clashes\" edgedefault=\"undirected\">\n",
+    );
+    for node in nodes {
+        out.push_str(&format!(
+            "    <node id=\"{}\"><data key=\"label\">{}</data><data key=\"kind\">{}</data></node>\n",
+            escape_xml(&node.id),
+            escape_xml(&node.label),
+            node.kind
+        ));
+    }
+    for edge in edges {
+        out.push_str(&format!(
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"kind\">{}</data></edge>\n",
+            escape_xml(&edge.from),
+            escape_xml(&edge.to),
+            edge.kind
+        ));
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+/// Exports the conflicts between judges, teams and institutions as a graph
+/// (in either Graphviz DOT or GraphML format), to `out` or standard output.
+/// Conflicts are drawn as solid edges (or dotted, for soft clashes), and the
+/// institution each team and judge belongs to as dashed edges.
+pub async fn export_clash_graph(auth: Auth, format: &str, out: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, judges, institutions) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+    );
+
+    // the names each object may be given in the clashes file, to tell which
+    // conflicts were imported as soft clashes
+    let soft_clashes = SoftClashes::load();
+    let mut names: HashMap<&str, Vec<String>> = HashMap::new();
+    for judge in &judges {
+        names.insert(&judge.url, vec![judge.name.clone()]);
+    }
+    for team in &teams {
+        let mut team_names = vec![team.short_name.clone(), team.long_name.clone()];
+        team_names.extend(team.speakers.iter().map(|speaker| speaker.name.to_string()));
+        names.insert(&team.url, team_names);
+    }
+    for institution in &institutions {
+        names.insert(
+            &institution.url,
+            vec![
+                institution.code.as_str().to_string(),
+                institution.name.as_str().to_string(),
+            ],
+        );
+    }
+    let names_of = |url: &str| {
+        names
+            .get(url)
+            .map(|names| names.iter().map(String::as_str).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    let mut seen_edges = HashSet::new();
+    let mut add_edge = |from: &str, to: &str, kind: &'static str| {
+        let kind = if kind == "conflict" && soft_clashes.is_soft(&names_of(from), &names_of(to)) {
+            "soft-conflict"
+        } else {
+            kind
+        };
+        // conflicts are often recorded on both objects
+        let key = if from < to { (from, to) } else { (to, from) };
+        if seen_edges.insert((key.0.to_string(), key.1.to_string(), kind)) {
+            edges.push(Edge {
+                from: from.to_string(),
+                to: to.to_string(),
+                kind,
+            });
+        }
+    };
+
+    for judge in &judges {
+        nodes.push(Node {
+            id: judge.url.clone(),
+            label: judge.name.clone(),
+            kind: "judge",
+        });
+        let value = serde_json::to_value(judge).unwrap();
+        if let Some(institution) = value["institution"].as_str() {
+            add_edge(&judge.url, institution, "member");
+        }
+        for field in [
+            "team_conflicts",
+            "adjudicator_conflicts",
+            "institution_conflicts",
+        ] {
+            for url in urls(&value, field) {
+                add_edge(&judge.url, &url, "conflict");
+            }
+        }
+    }
+
+    for team in &teams {
+        nodes.push(Node {
+            id: team.url.clone(),
+            label: team.short_name.clone(),
+            kind: "team",
+        });
+        let value = serde_json::to_value(team).unwrap();
+        if let Some(institution) = value["institution"].as_str() {
+            add_edge(&team.url, institution, "member");
+        }
+        for url in urls(&value, "institution_conflicts") {
+            add_edge(&team.url, &url, "conflict");
+        }
+    }
+
+    // only include institutions which take part in the graph
+    let connected = edges
+        .iter()
+        .flat_map(|edge| [edge.from.as_str(), edge.to.as_str()])
+        .collect::<HashSet<_>>();
+    let institution_nodes = institutions
+        .iter()
+        .filter(|institution| connected.contains(institution.url.as_str()))
+        .map(|institution| Node {
+            id: institution.url.clone(),
+            label: institution.code.as_str().to_string(),
+            kind: "institution",
+        })
+        .collect::<Vec<_>>();
+    nodes.extend(institution_nodes);
+
+    let graph = match format.to_ascii_lowercase().as_str() {
+        "dot" => to_dot(&nodes, &edges),
+        "graphml" => to_graphml(&nodes, &edges),
+        _ => {
+            error!("Invalid format `{format}` expected either dot or graphml");
+            exit(1);
+        }
+    };
+
+    match out {
+        Some(out) => {
+            std::fs::write(&out, graph).unwrap();
+            info!(
+                "Saved clash graph ({} nodes, {} edges) to {out}",
+                nodes.len(),
+                edges.len()
+            );
+        }
+        None => print!("{graph}"),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_to_dot() {
+    let nodes = vec![
+        Node {
+            id: "a".to_string(),
+            label: "Judge \"A\"".to_string(),
+            kind: "judge",
+        },
+        Node {
+            id: "b".to_string(),
+            label: "Team B".to_string(),
+            kind: "team",
+        },
+    ];
+    let edges = vec![Edge {
+        from: "a".to_string(),
+        to: "b".to_string(),
+        kind: "conflict",
+    }];
+
+    assert_eq!(
+        to_dot(&nodes, &edges),
+        "graph clashes {\n  \"a\" [label=\"Judge \\\"A\\\"\", shape=ellipse];\n  \
+        \"b\" [label=\"Team B\", shape=box];\n  \"a\" -- \"b\" [style=solid];\n}\n"
+    );
+}
@@ -0,0 +1,354 @@
+use std::{collections::BTreeMap, process::exit};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde_json::Value;
+use tracing::{error, info};
+
+use crate::{
+    Auth,
+    api_utils::{get_judges, get_teams},
+    archive::read_data,
+    clashes_csv,
+    conflicts::Conflicts,
+    contacts::email,
+    names::{names_match, normalise},
+    request_manager::RequestManager,
+};
+
+/// Email domains used by so many people that sharing one says nothing about
+/// whether two people know each other.
+const COMMON_EMAIL_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "googlemail.com",
+    "outlook.com",
+    "hotmail.com",
+    "hotmail.co.uk",
+    "live.com",
+    "msn.com",
+    "yahoo.com",
+    "yahoo.co.uk",
+    "icloud.com",
+    "me.com",
+    "aol.com",
+    "proton.me",
+    "protonmail.com",
+    "gmx.de",
+    "gmx.net",
+    "web.de",
+    "mail.ru",
+    "yandex.ru",
+    "qq.com",
+    "163.com",
+];
+
+/// A judge or speaker who might have an unrecorded conflict.
+#[derive(Debug, Clone, PartialEq)]
+struct Participant {
+    name: String,
+    /// The URL of the judge, or of the speaker's team.
+    url: String,
+    is_judge: bool,
+    email: String,
+    /// The institution the participant belonged to in a previous tournament.
+    prior_institution: Option<String>,
+}
+
+/// A proposed clash between two participants (by name).
+#[derive(Debug, Clone, PartialEq)]
+struct Proposal {
+    a: String,
+    b: String,
+    reasons: Vec<String>,
+}
+
+/// The (normalised) surname of a person, taken to be the last word of their
+/// name. People with a single name have no surname.
+fn surname(name: &str) -> Option<String> {
+    let name = normalise(name);
+    let mut words = name.split(' ');
+    let last = words.next_back()?;
+    words.next().map(|_| last.to_string())
+}
+
+/// The domain of an email address, unless it belongs to a common email
+/// provider.
+fn email_domain(email: &str) -> Option<String> {
+    let (_, domain) = email.rsplit_once('@')?;
+    let domain = domain.trim().to_lowercase();
+    (!domain.is_empty() && !COMMON_EMAIL_DOMAINS.contains(&domain.as_str())).then_some(domain)
+}
+
+/// Proposes a clash between each judge and every other participant who has
+/// the same key (if any), as long as at most `max_group` participants share
+/// that key and the two do not already conflict.
+fn propose(
+    participants: &[Participant],
+    key: impl Fn(&Participant) -> Option<String>,
+    max_group: usize,
+    reason: impl Fn(&str) -> String,
+    conflicted: &impl Fn(&Participant, &Participant) -> bool,
+    proposals: &mut Vec<Proposal>,
+) {
+    let mut groups: BTreeMap<String, Vec<&Participant>> = BTreeMap::new();
+    for participant in participants {
+        if let Some(key) = key(participant) {
+            groups.entry(key).or_default().push(participant);
+        }
+    }
+
+    for (key, group) in groups {
+        if group.len() < 2 || group.len() > max_group {
+            continue;
+        }
+        for (i, a) in group.iter().enumerate() {
+            for b in &group[i + 1..] {
+                // speakers are never allocated to judge one another
+                if !(a.is_judge || b.is_judge) || a.url == b.url || conflicted(a, b) {
+                    continue;
+                }
+                let reason = reason(&key);
+                match proposals
+                    .iter_mut()
+                    .find(|p| (p.a == a.name && p.b == b.name) || (p.a == b.name && p.b == a.name))
+                {
+                    Some(proposal) => proposal.reasons.push(reason),
+                    None => proposals.push(Proposal {
+                        a: a.name.clone(),
+                        b: b.name.clone(),
+                        reasons: vec![reason],
+                    }),
+                }
+            }
+        }
+    }
+}
+
+/// The institution (by name) each participant of a previous tournament
+/// belonged to, as (participant name, institution name) pairs, read from an
+/// archive made by `tabbycat archive`.
+fn prior_institutions(dir: &str) -> Vec<(String, String)> {
+    let institutions = read_data(dir, "institutions");
+    let institution_name = |url: &Value| {
+        institutions
+            .iter()
+            .find(|inst| inst["url"] == *url)
+            .and_then(|inst| inst["name"].as_str())
+            .map(|name| name.to_string())
+    };
+
+    let mut members = Vec::new();
+    for judge in read_data(dir, "adjudicators") {
+        if let (Some(name), Some(inst)) = (
+            judge["name"].as_str(),
+            institution_name(&judge["institution"]),
+        ) {
+            members.push((name.to_string(), inst));
+        }
+    }
+    for team in read_data(dir, "teams") {
+        let Some(inst) = institution_name(&team["institution"]) else {
+            continue;
+        };
+        for speaker in team["speakers"].as_array().into_iter().flatten() {
+            if let Some(name) = speaker["name"].as_str() {
+                members.push((name.to_string(), inst.clone()));
+            }
+        }
+    }
+    members
+}
+
+/// Proposes clashes which are likely but have not been recorded: judges who
+/// share a rare surname (`by_surname`) or an uncommon email domain
+/// (`by_email_domain`) with another judge or a speaker (where at most
+/// `max_group` participants share it), and judges who were at the same
+/// institution as another judge or a speaker in a previous tournament (from
+/// the archive in `prior`). The proposals are written to `out`, in the format
+/// read by `tabbycat import --clashes` (with the reason for each as its
+/// comment), so that they can be reviewed before being imported.
+pub async fn infer_clashes(
+    auth: Auth,
+    by_surname: bool,
+    by_email_domain: bool,
+    prior: Option<String>,
+    max_group: usize,
+    out: &str,
+) {
+    if !by_surname && !by_email_domain && prior.is_none() {
+        error!("Please choose at least one of `--by-surname`, `--by-email-domain` or `--prior`.");
+        exit(1);
+    }
+
+    let manager = RequestManager::new(&auth.api_key);
+    let (teams, judges) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+    );
+    let conflicts = Conflicts::new(&teams, &judges);
+    let prior = prior.as_deref().map(prior_institutions).unwrap_or_default();
+    let prior_institution = |name: &str| {
+        prior
+            .iter()
+            .find(|(member, _)| names_match(member, name))
+            .map(|(_, inst)| inst.clone())
+    };
+
+    let mut participants = judges
+        .iter()
+        .map(|judge| Participant {
+            name: judge.name.clone(),
+            url: judge.url.clone(),
+            is_judge: true,
+            email: email(&serde_json::to_value(judge).unwrap()),
+            prior_institution: prior_institution(&judge.name),
+        })
+        .collect::<Vec<_>>();
+    for team in &teams {
+        for speaker in &team.speakers {
+            participants.push(Participant {
+                name: speaker.name.to_string(),
+                url: team.url.clone(),
+                is_judge: false,
+                email: email(&serde_json::to_value(speaker).unwrap()),
+                prior_institution: prior_institution(&speaker.name),
+            });
+        }
+    }
+
+    let conflicted = |a: &Participant, b: &Participant| match (a.is_judge, b.is_judge) {
+        (true, true) => conflicts.judge_judge(&a.url, &b.url),
+        (true, false) => conflicts.judge_team(&a.url, &b.url),
+        (false, true) => conflicts.judge_team(&b.url, &a.url),
+        (false, false) => true,
+    };
+
+    let mut proposals = Vec::new();
+    if by_surname {
+        propose(
+            &participants,
+            |p| surname(&p.name),
+            max_group,
+            |surname| format!("same surname ({surname})"),
+            &conflicted,
+            &mut proposals,
+        );
+    }
+    if by_email_domain {
+        propose(
+            &participants,
+            |p| email_domain(&p.email),
+            max_group,
+            |domain| format!("same email domain ({domain})"),
+            &conflicted,
+            &mut proposals,
+        );
+    }
+    if !prior.is_empty() {
+        propose(
+            &participants,
+            |p| p.prior_institution.clone(),
+            usize::MAX,
+            |inst| format!("both previously at {inst}"),
+            &conflicted,
+            &mut proposals,
+        );
+    }
+
+    if proposals.is_empty() {
+        println!("No likely clashes were found.");
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["Participant", "Participant", "Reason"]);
+    let mut writer = csv::Writer::from_path(out).unwrap();
+    writer.write_record(clashes_csv::HEADER).unwrap();
+    for proposal in &proposals {
+        let reasons = proposal.reasons.join("; ");
+        table.add_row(vec![&proposal.a, &proposal.b, &reasons]);
+        writer
+            .write_record([proposal.a.as_str(), &proposal.b, "hard", &reasons])
+            .unwrap();
+    }
+    writer.flush().unwrap();
+
+    println!("{table}");
+    info!(
+        "Saved {} proposed clash(es) to {out}; remove any which are wrong, then \
+        import the rest with `tabbycat import --clashes {out}`",
+        proposals.len()
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_propose_clashes() {
+    assert_eq!(surname("Ana  María GARCÍA"), Some("garcía".to_string()));
+    assert_eq!(surname("Prince"), None);
+    assert_eq!(
+        email_domain("a@Smith-Family.net"),
+        Some("smith-family.net".to_string())
+    );
+    assert_eq!(email_domain("a@gmail.com"), None);
+
+    let participant = |name: &str, url: &str, is_judge: bool| Participant {
+        name: name.to_string(),
+        url: url.to_string(),
+        is_judge,
+        email: String::new(),
+        prior_institution: None,
+    };
+    let participants = [
+        participant("Jo Okonkwo", "j1", true),
+        participant("Sam Okonkwo", "t1", false),
+        participant("Alex Okonkwo", "t1", false),
+        participant("Kim Lee", "j2", true),
+        participant("Pat Lee", "j3", true),
+    ];
+    let mut proposals = Vec::new();
+    propose(
+        &participants,
+        |p| surname(&p.name),
+        3,
+        |surname| format!("same surname ({surname})"),
+        &|a: &Participant, b: &Participant| a.url == "j2" || b.url == "j2",
+        &mut proposals,
+    );
+    // "lee" is already a conflict, and speakers aren't clashed with each
+    // other
+    assert_eq!(
+        proposals,
+        vec![
+            Proposal {
+                a: "Jo Okonkwo".to_string(),
+                b: "Sam Okonkwo".to_string(),
+                reasons: vec!["same surname (okonkwo)".to_string()],
+            },
+            Proposal {
+                a: "Jo Okonkwo".to_string(),
+                b: "Alex Okonkwo".to_string(),
+                reasons: vec!["same surname (okonkwo)".to_string()],
+            },
+        ]
+    );
+
+    // too common to be meaningful
+    let mut proposals = Vec::new();
+    propose(
+        &participants,
+        |p| surname(&p.name),
+        2,
+        |surname| surname.to_string(),
+        &|_: &Participant, _: &Participant| false,
+        &mut proposals,
+    );
+    assert_eq!(proposals.len(), 1);
+    assert_eq!(
+        (proposals[0].a.as_str(), proposals[0].b.as_str()),
+        ("Kim Lee", "Pat Lee")
+    );
+}
@@ -0,0 +1,249 @@
+//! Reading the clashes CSV file, which is a list of pairs of names (of judges,
+//! speakers, teams or institutions) to clash, optionally with a header row, a
+//! `type` (hard or soft) and a comment for each clash. Lines starting with `#`
+//! are ignored.
+
+use std::process::exit;
+
+use csv::{StringRecord, Trim};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{import::Clash, names::names_match, open_csv_file_with};
+
+/// The file (in the working directory) in which the soft clashes are kept,
+/// since Tabbycat does not record whether a clash is hard or soft.
+pub const SOFT_CLASHES_FILE: &str = "soft_clashes.csv";
+
+/// The header row of the clashes files written by the CLI.
+pub const HEADER: [&str; 4] = ["object_1", "object_2", "type", "comment"];
+
+/// The names of the columns which may appear in a header row.
+const COLUMNS: &[&str] = &["object_1", "object_2", "type", "severity", "comment"];
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The two may never meet (Tabbycat's allocator avoids all clashes).
+    #[default]
+    Hard,
+    /// The clash should be avoided where possible, and is flagged in reports.
+    Soft,
+}
+
+impl Severity {
+    fn parse(severity: &str) -> Option<Severity> {
+        match severity.trim().to_ascii_lowercase().as_str() {
+            "" | "hard" => Some(Severity::Hard),
+            "soft" => Some(Severity::Soft),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `record` is a header row, i.e. names only the known columns.
+fn is_header(record: &StringRecord) -> bool {
+    record.iter().any(|field| !field.is_empty())
+        && record
+            .iter()
+            .filter(|field| !field.is_empty())
+            .all(|field| COLUMNS.contains(&field.to_ascii_lowercase().as_str()))
+}
+
+/// Rearranges the rows of a clashes file into the columns of a [`Clash`]
+/// (`object_1`, `object_2`, `severity` and `comment`), using the header row if
+/// there is one, and otherwise taking the columns in that order. Returns an
+/// error (with the line number) for rows with a missing name or an unknown
+/// type of clash.
+fn normalise_records(records: Vec<StringRecord>) -> Result<Vec<StringRecord>, String> {
+    let mut records = records.into_iter().peekable();
+    let columns = match records.next_if(is_header) {
+        Some(header) => {
+            let position = |names: &[&str]| {
+                header
+                    .iter()
+                    .position(|field| names.contains(&field.to_ascii_lowercase().as_str()))
+            };
+            [
+                position(&["object_1"]),
+                position(&["object_2"]),
+                position(&["type", "severity"]),
+                position(&["comment"]),
+            ]
+        }
+        None => [Some(0), Some(1), Some(2), Some(3)],
+    };
+    if columns[0].is_none() || columns[1].is_none() {
+        return Err("the header row must name the object_1 and object_2 columns".to_string());
+    }
+
+    records
+        .map(|record| {
+            let line = record
+                .position()
+                .map(|position| position.line())
+                .unwrap_or_default();
+            let [object_1, object_2, severity, comment] =
+                columns.map(|column| column.and_then(|i| record.get(i)).unwrap_or_default());
+            if object_1.is_empty() || object_2.is_empty() {
+                return Err(format!("line {line} does not name two things to clash"));
+            }
+            let Some(severity) = Severity::parse(severity) else {
+                return Err(format!(
+                    "line {line} has the clash type {severity:?} (expected hard or soft)"
+                ));
+            };
+            let severity = match severity {
+                Severity::Hard => "hard",
+                Severity::Soft => "soft",
+            };
+            Ok(StringRecord::from(vec![
+                object_1, object_2, severity, comment,
+            ]))
+        })
+        .collect()
+}
+
+/// Reads the clashes file at `path` (if given), returning its rows in the
+/// columns of a [`Clash`].
+pub fn read_clashes(path: Option<String>) -> Option<Vec<StringRecord>> {
+    let path_name = path.clone().unwrap_or_default();
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .trim(Trim::All);
+    let mut csv = open_csv_file_with(path, &builder)?;
+
+    let records = csv
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| {
+            error!("Could not read the clashes file {path_name}: {e}");
+            exit(1)
+        });
+    match normalise_records(records) {
+        Ok(records) => Some(records),
+        Err(e) => {
+            error!("The clashes file {path_name} is invalid: {e}");
+            exit(1)
+        }
+    }
+}
+
+/// The clashes which have been imported as soft clashes.
+#[derive(Debug, Clone, Default)]
+pub struct SoftClashes {
+    clashes: Vec<Clash>,
+}
+
+impl SoftClashes {
+    /// Loads the soft clashes from [`SOFT_CLASHES_FILE`], if it exists.
+    pub fn load() -> SoftClashes {
+        if !std::path::Path::new(SOFT_CLASHES_FILE).exists() {
+            return SoftClashes::default();
+        }
+        let clashes = read_clashes(Some(SOFT_CLASHES_FILE.to_string()))
+            .unwrap_or_default()
+            .iter()
+            .map(|record| record.deserialize::<Clash>(None).unwrap())
+            .filter(|clash| clash.severity == Severity::Soft)
+            .collect();
+        SoftClashes { clashes }
+    }
+
+    /// Adds the soft clashes among `clashes` to [`SOFT_CLASHES_FILE`] (unless
+    /// they are already there).
+    pub fn record(clashes: &[Clash]) {
+        let mut soft = SoftClashes::load();
+        let mut added = 0;
+        for clash in clashes
+            .iter()
+            .filter(|clash| clash.severity == Severity::Soft)
+        {
+            if !soft.is_soft(&[&clash.object_1], &[&clash.object_2]) {
+                soft.clashes.push(clash.clone());
+                added += 1;
+            }
+        }
+        if added == 0 {
+            return;
+        }
+
+        let mut writer = csv::Writer::from_path(SOFT_CLASHES_FILE).unwrap();
+        writer.write_record(HEADER).unwrap();
+        for clash in &soft.clashes {
+            writer
+                .write_record([
+                    clash.object_1.as_str(),
+                    clash.object_2.as_str(),
+                    "soft",
+                    clash.comment.as_deref().unwrap_or_default(),
+                ])
+                .unwrap();
+        }
+        writer.flush().unwrap();
+        info!("Recorded {added} soft clash(es) in {SOFT_CLASHES_FILE}");
+    }
+
+    /// Whether there is a soft clash between something known by any of the
+    /// names `a` and something known by any of the names `b`.
+    pub fn is_soft(&self, a: &[&str], b: &[&str]) -> bool {
+        let known_as = |names: &[&str], name: &str| names.iter().any(|n| names_match(n, name));
+        self.clashes.iter().any(|clash| {
+            (known_as(a, &clash.object_1) && known_as(b, &clash.object_2))
+                || (known_as(a, &clash.object_2) && known_as(b, &clash.object_1))
+        })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_normalise_records() {
+    let records = |rows: &[&[&str]]| {
+        rows.iter()
+            .map(|row| StringRecord::from(row.to_vec()))
+            .collect::<Vec<_>>()
+    };
+
+    // no header row
+    let rows = normalise_records(records(&[
+        &["Jo", "Oxford"],
+        &["Sam", "Kim", "soft", "exes"],
+    ]))
+    .unwrap();
+    assert_eq!(
+        rows[0],
+        StringRecord::from(vec!["Jo", "Oxford", "hard", ""])
+    );
+    assert_eq!(
+        rows[1],
+        StringRecord::from(vec!["Sam", "Kim", "soft", "exes"])
+    );
+
+    // a header row, with the columns in a different order
+    let rows = normalise_records(records(&[
+        &["comment", "Severity", "object_1", "object_2"],
+        &["same school", "SOFT", "Jo", "Sam"],
+    ]))
+    .unwrap();
+    assert_eq!(
+        rows,
+        vec![StringRecord::from(vec!["Jo", "Sam", "soft", "same school"])]
+    );
+
+    assert!(normalise_records(records(&[&["Jo", "Sam", "maybe"]])).is_err());
+    assert!(normalise_records(records(&[&["Jo", ""]])).is_err());
+
+    let soft = SoftClashes {
+        clashes: vec![Clash {
+            object_1: "Jo".to_string(),
+            object_2: "Sam".to_string(),
+            severity: Severity::Soft,
+            comment: None,
+        }],
+    };
+    assert!(soft.is_soft(&["Team A", "sam"], &["JO"]));
+    assert!(!soft.is_soft(&["Jo"], &["Kim"]));
+}
@@ -1,7 +1,7 @@
 use serde_json::json;
 use tracing::{Level, error, span};
 
-use crate::Auth;
+use crate::{Auth, audit_log};
 
 pub fn do_clear_room_urls(auth: Auth) {
     let mut rooms: Vec<tabbycat_api::types::Venue> = attohttpc::get(format!(
@@ -18,14 +18,13 @@ pub fn do_clear_room_urls(auth: Auth) {
     let _guard = span.enter();
 
     for (i, room) in rooms.clone().into_iter().enumerate() {
-        let response = attohttpc::patch(room.url.clone())
-            .header("Authorization", format!("Token {}", auth.api_key))
-            .json(&json!({
+        let response = audit_log::patch_json(
+            &room.url,
+            &auth,
+            &json!({
                 "external_url": ""
-            }))
-            .unwrap()
-            .send()
-            .unwrap();
+            }),
+        );
 
         if !response.is_success() {
             error!(
@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+use tabbycat_api::types::{Adjudicator, Team};
+
+use crate::clash_graph::urls;
+
+/// The conflicts (clashes) recorded on Tabbycat between judges, teams and
+/// institutions, following the rules Tabbycat's allocator uses: a judge
+/// conflicts with a team if the team is one of the judge's team conflicts, or
+/// if they share an institution conflict; and with another judge if either
+/// lists the other as an adjudicator conflict, or they share an institution
+/// conflict.
+#[derive(Debug, Clone, Default)]
+pub struct Conflicts {
+    judge_teams: HashMap<String, HashSet<String>>,
+    judge_judges: HashMap<String, HashSet<String>>,
+    institutions: HashMap<String, HashSet<String>>,
+}
+
+impl Conflicts {
+    pub fn new(teams: &[Team], judges: &[Adjudicator]) -> Conflicts {
+        Conflicts::from_values(
+            teams
+                .iter()
+                .map(|team| (team.url.clone(), serde_json::to_value(team).unwrap())),
+            judges
+                .iter()
+                .map(|judge| (judge.url.clone(), serde_json::to_value(judge).unwrap())),
+        )
+    }
+
+    /// Reads the conflicts from the teams and judges (as URL and JSON object
+    /// pairs) returned by the API.
+    fn from_values(
+        teams: impl Iterator<Item = (String, Value)>,
+        judges: impl Iterator<Item = (String, Value)>,
+    ) -> Conflicts {
+        let mut conflicts = Conflicts::default();
+        for (url, team) in teams {
+            conflicts.institutions.insert(
+                url,
+                urls(&team, "institution_conflicts").into_iter().collect(),
+            );
+        }
+        for (url, judge) in judges {
+            conflicts.judge_teams.insert(
+                url.clone(),
+                urls(&judge, "team_conflicts").into_iter().collect(),
+            );
+            conflicts.judge_judges.insert(
+                url.clone(),
+                urls(&judge, "adjudicator_conflicts").into_iter().collect(),
+            );
+            conflicts.institutions.insert(
+                url,
+                urls(&judge, "institution_conflicts").into_iter().collect(),
+            );
+        }
+        conflicts
+    }
+
+    fn share_institution(&self, a: &str, b: &str) -> bool {
+        match (self.institutions.get(a), self.institutions.get(b)) {
+            (Some(a), Some(b)) => !a.is_disjoint(b),
+            _ => false,
+        }
+    }
+
+    /// Whether the judge (by URL) conflicts with the team (by URL).
+    pub fn judge_team(&self, judge: &str, team: &str) -> bool {
+        self.judge_teams
+            .get(judge)
+            .is_some_and(|teams| teams.contains(team))
+            || self.share_institution(judge, team)
+    }
+
+    /// Whether two judges (by URL) conflict with each other.
+    pub fn judge_judge(&self, a: &str, b: &str) -> bool {
+        self.judge_judges
+            .get(a)
+            .is_some_and(|judges| judges.contains(b))
+            || self
+                .judge_judges
+                .get(b)
+                .is_some_and(|judges| judges.contains(a))
+            || self.share_institution(a, b)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_conflicts() {
+    use serde_json::json;
+
+    let conflicts = Conflicts::from_values(
+        [
+            (
+                "oxford-a".to_string(),
+                json!({ "institution_conflicts": ["oxford"] }),
+            ),
+            (
+                "durham-a".to_string(),
+                json!({ "institution_conflicts": ["durham"] }),
+            ),
+        ]
+        .into_iter(),
+        [
+            (
+                "alex".to_string(),
+                json!({
+                    "institution_conflicts": ["oxford"],
+                    "team_conflicts": [],
+                    "adjudicator_conflicts": ["sam"],
+                }),
+            ),
+            ("sam".to_string(), json!({ "team_conflicts": ["durham-a"] })),
+        ]
+        .into_iter(),
+    );
+
+    assert!(conflicts.judge_team("alex", "oxford-a"));
+    assert!(!conflicts.judge_team("alex", "durham-a"));
+    assert!(conflicts.judge_team("sam", "durham-a"));
+    assert!(conflicts.judge_judge("sam", "alex"));
+    assert!(!conflicts.judge_team("unknown", "oxford-a"));
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use tracing::{info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_institutions, get_judges, get_teams},
+    request_manager::RequestManager,
+    tabular::{Column, TableFormat, write_table},
+};
+
+/// A single participant's contact details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contact {
+    pub name: String,
+    /// Either `speaker` or `judge`.
+    pub role: String,
+    pub email: String,
+    pub team: String,
+    pub institution: String,
+    pub private_url: String,
+}
+
+/// Checks that an email address is plausible (this deliberately does not try
+/// to implement the full grammar of addresses).
+pub fn valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && !email.chars().any(char::is_whitespace)
+        && domain.split('.').filter(|part| !part.is_empty()).count() >= 2
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+}
+
+/// Removes contacts without a valid email address, and all but the first
+/// contact with each email address (ignoring case), warning about each one
+/// which is removed.
+pub fn dedup_contacts(contacts: Vec<Contact>) -> Vec<Contact> {
+    let mut seen: HashMap<String, String> = HashMap::new();
+    let mut kept = Vec::new();
+    for contact in contacts {
+        if contact.email.is_empty() {
+            warn!("{} ({}) has no email address", contact.name, contact.role);
+            continue;
+        }
+        if !valid_email(&contact.email) {
+            warn!(
+                "{} ({}) has a malformed email address: {:?}",
+                contact.name, contact.role, contact.email
+            );
+            continue;
+        }
+        match seen.get(&contact.email.to_lowercase()) {
+            Some(name) if *name == contact.name => continue,
+            Some(name) => {
+                warn!(
+                    "{} and {name} share the email address {}; only {name} was exported",
+                    contact.name, contact.email
+                );
+                continue;
+            }
+            None => {
+                seen.insert(contact.email.to_lowercase(), contact.name.clone());
+                kept.push(contact);
+            }
+        }
+    }
+    kept
+}
+
+/// The private URL of a participant (a speaker or judge, as JSON), or an
+/// empty string if they do not have one.
+pub fn private_url(auth: &Auth, participant: &serde_json::Value) -> String {
+    participant["url_key"]
+        .as_str()
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            format!(
+                "{}/{}/privateurls/{key}/",
+                auth.tabbycat_url.trim_end_matches('/'),
+                auth.tournament_slug
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// The email address of a participant (a speaker or judge, as JSON).
+pub fn email(participant: &serde_json::Value) -> String {
+    participant["email"]
+        .as_str()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Writes `contacts` to a CSV file at `to`.
+pub fn write_contacts(to: &str, contacts: &[Contact]) {
+    let header = [
+        "name",
+        "role",
+        "email",
+        "team",
+        "institution",
+        "private_url",
+    ]
+    .map(Column::text);
+    let rows = contacts
+        .iter()
+        .map(|contact| {
+            vec![
+                contact.name.clone(),
+                contact.role.clone(),
+                contact.email.clone(),
+                contact.team.clone(),
+                contact.institution.clone(),
+                contact.private_url.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    write_table(to, TableFormat::Csv, &header, &rows);
+}
+
+/// Exports the speakers and judges along with their email address, team,
+/// institution and private URL as a single CSV file (for a mail merge).
+pub async fn export_contacts(auth: Auth, to: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, judges, institutions) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+    );
+
+    let institution_name = |url: Option<&String>| {
+        url.and_then(|url| institutions.iter().find(|inst| &inst.url == url))
+            .map(|inst| inst.name.as_str().to_string())
+            .unwrap_or_default()
+    };
+    let mut contacts = Vec::new();
+    for team in &teams {
+        for speaker in &team.speakers {
+            let speaker_json = serde_json::to_value(speaker).unwrap();
+            contacts.push(Contact {
+                name: speaker.name.to_string(),
+                role: "speaker".to_string(),
+                email: email(&speaker_json),
+                team: team.long_name.clone(),
+                institution: institution_name(team.institution.as_ref()),
+                private_url: private_url(&auth, &speaker_json),
+            });
+        }
+    }
+    for judge in &judges {
+        let judge_json = serde_json::to_value(judge).unwrap();
+        contacts.push(Contact {
+            name: judge.name.clone(),
+            role: "judge".to_string(),
+            email: email(&judge_json),
+            team: String::new(),
+            institution: institution_name(judge.institution.as_ref()),
+            private_url: private_url(&auth, &judge_json),
+        });
+    }
+
+    let total = contacts.len();
+    let contacts = dedup_contacts(contacts);
+    write_contacts(to, &contacts);
+
+    info!(
+        "Exported {} of {total} participant(s) to {to}",
+        contacts.len()
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_dedup_contacts() {
+    assert!(valid_email("a.person@example.ac.uk"));
+    assert!(!valid_email("a person@example.com"));
+    assert!(!valid_email("someone@localhost"));
+    assert!(!valid_email("someone.example.com"));
+
+    let contact = |name: &str, email: &str| Contact {
+        name: name.to_string(),
+        role: "speaker".to_string(),
+        email: email.to_string(),
+        team: String::new(),
+        institution: String::new(),
+        private_url: String::new(),
+    };
+
+    assert_eq!(
+        dedup_contacts(vec![
+            contact("Alex", "alex@example.com"),
+            contact("Alex", "Alex@example.com"),
+            contact("Sam", "alex@example.com"),
+            contact("Jo", "jo@example"),
+            contact("Kim", ""),
+            contact("Lee", "lee@example.com"),
+        ]),
+        vec![
+            contact("Alex", "alex@example.com"),
+            contact("Lee", "lee@example.com"),
+        ]
+    );
+}
@@ -0,0 +1,184 @@
+use std::{collections::BTreeMap, process::exit};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_round_and_pairings, get_venues, pairing_in_room},
+    names::names_match,
+    request_manager::RequestManager,
+};
+
+/// The file (in the current working directory) in which the flags set on
+/// debates (e.g. "needs runner") of each tournament are kept. Tabbycat only
+/// stores the flags its draw generator sets, so these are kept locally.
+pub const DEBATE_FLAGS_FILE: &str = "debate-flags.json";
+
+/// The flags set on each debate of a tournament, by the ID of the debate
+/// (which is unique across rounds, but not across tournaments).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct DebateFlags {
+    debates: BTreeMap<i64, Vec<String>>,
+}
+
+/// The flags of every tournament (by [`tournament_key`]) which have been
+/// flagged from the working directory.
+type FlagsFile = BTreeMap<String, DebateFlags>;
+
+/// Identifies the tournament `auth` refers to, as debate IDs are only unique
+/// within a single Tabbycat site.
+fn tournament_key(auth: &Auth) -> String {
+    format!(
+        "{}/{}",
+        auth.tabbycat_url.trim_end_matches('/'),
+        auth.tournament_slug
+    )
+}
+
+fn load_file() -> FlagsFile {
+    let text = match std::fs::read_to_string(DEBATE_FLAGS_FILE) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return FlagsFile::default();
+        }
+        Err(e) => {
+            error!("Could not read {DEBATE_FLAGS_FILE}: {e}");
+            exit(1)
+        }
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        error!("Your {DEBATE_FLAGS_FILE} file is malformed: {e}");
+        exit(1)
+    })
+}
+
+impl DebateFlags {
+    /// Loads the flags of the tournament `auth` refers to from the working
+    /// directory, or returns no flags if none have been set yet.
+    pub fn load(auth: &Auth) -> DebateFlags {
+        load_file()
+            .remove(&tournament_key(auth))
+            .unwrap_or_default()
+    }
+
+    /// Saves the flags of the tournament `auth` refers to, keeping those of
+    /// other tournaments.
+    pub fn save(&self, auth: &Auth) {
+        let mut file = load_file();
+        if self.debates.is_empty() {
+            file.remove(&tournament_key(auth));
+        } else {
+            file.insert(tournament_key(auth), self.clone());
+        }
+        let json = serde_json::to_string_pretty(&file).unwrap();
+        if let Err(e) = std::fs::write(DEBATE_FLAGS_FILE, json) {
+            error!("Could not write {DEBATE_FLAGS_FILE}: {e}");
+            exit(1);
+        }
+    }
+
+    /// The flags set on the debate with the given ID.
+    pub fn get(&self, debate: i64) -> &[String] {
+        self.debates
+            .get(&debate)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Sets `flag` on the debate, returning false if it was already set
+    /// (compared using [`names_match`]).
+    fn set(&mut self, debate: i64, flag: &str) -> bool {
+        let flags = self.debates.entry(debate).or_default();
+        if flags.iter().any(|f| names_match(f, flag)) {
+            return false;
+        }
+        flags.push(flag.trim().to_string());
+        true
+    }
+
+    /// Removes `flag` from the debate, returning false if it was not set.
+    fn unset(&mut self, debate: i64, flag: &str) -> bool {
+        let Some(flags) = self.debates.get_mut(&debate) else {
+            return false;
+        };
+        let before = flags.len();
+        flags.retain(|f| !names_match(f, flag));
+        let removed = flags.len() < before;
+        if flags.is_empty() {
+            self.debates.remove(&debate);
+        }
+        removed
+    }
+}
+
+/// Sets (or, with `unset`, removes) `flag` on the debate in `room` (either the
+/// name of the room, or the ID of the debate) in `round`.
+pub async fn flag(auth: Auth, round: &str, room: &str, flag: &str, unset: bool) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    if flag.trim().is_empty() {
+        error!("The flag cannot be empty.");
+        exit(1);
+    }
+
+    let ((round, pairings), venues) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_venues(&auth, manager.clone())
+    );
+    let pairing = pairing_in_room(&pairings, &venues, room).unwrap_or_else(|| {
+        error!(
+            "No debate in round {} matches room {room}",
+            round.name.as_str()
+        );
+        exit(1);
+    });
+
+    let mut flags = DebateFlags::load(&auth);
+    if unset {
+        if !flags.unset(pairing.id, flag) {
+            warn!("{room} is not flagged {flag:?}");
+            return;
+        }
+        info!("Removed the flag {flag:?} from {room}");
+    } else {
+        if !flags.set(pairing.id, flag) {
+            warn!("{room} is already flagged {flag:?}");
+            return;
+        }
+        info!("Flagged {room} as {flag:?}");
+    }
+    flags.save(&auth);
+}
+
+#[cfg(test)]
+#[test]
+fn test_debate_flags() {
+    let mut flags: DebateFlags = serde_json::from_str(r#"{ "12": ["needs runner"] }"#).unwrap();
+    assert_eq!(flags.get(12), ["needs runner"]);
+    assert!(flags.get(13).is_empty());
+
+    assert!(!flags.set(12, " Needs Runner"));
+    assert!(flags.set(12, "late start"));
+    assert!(flags.unset(12, "NEEDS RUNNER"));
+    assert!(!flags.unset(13, "late start"));
+    assert_eq!(flags.get(12), ["late start"]);
+
+    assert!(flags.unset(12, "late start"));
+    assert_eq!(serde_json::to_value(&flags).unwrap(), serde_json::json!({}));
+
+    let auth = |url: &str, slug: &str| Auth {
+        tabbycat_url: url.to_string(),
+        tournament_slug: slug.to_string(),
+        api_key: String::new(),
+    };
+    assert_eq!(
+        tournament_key(&auth("https://example.com/", "wudc")),
+        tournament_key(&auth("https://example.com", "wudc"))
+    );
+    assert_ne!(
+        tournament_key(&auth("https://example.com", "wudc")),
+        tournament_key(&auth("https://example.com", "eudc"))
+    );
+}
@@ -0,0 +1,189 @@
+use std::process::exit;
+
+use tracing::{error, info};
+
+/// The name of the file (in the current working directory) from which default
+/// command options are read.
+pub const DEFAULTS_FILE: &str = "tabbycat.toml";
+
+/// Options which cannot be given defaults, as the defaults file may have been
+/// put in the working directory by someone else (e.g. checked out with a
+/// repository), and these must only ever be turned on explicitly.
+const DENIED_OPTIONS: [&str; 2] = ["hooks", "offline"];
+
+/// Loads the default command options from `tabbycat.toml` in the working
+/// directory, if there is one. Each table is named after a (sub)command and
+/// sets defaults for its options, which are used unless the option is given on
+/// the command line. For example
+///
+/// ```toml
+/// ignore-accents = true
+///
+/// [export.standings]
+/// format = "parquet"
+///
+/// [export.results]
+/// by-debate = true
+/// format = "csv"
+/// ```
+pub fn load() -> Option<toml::Table> {
+    let text = match std::fs::read_to_string(DEFAULTS_FILE) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            error!("Could not read {DEFAULTS_FILE}: {e}");
+            exit(1)
+        }
+    };
+
+    match text.parse::<toml::Table>() {
+        Ok(table) => {
+            info!("Using default options from {DEFAULTS_FILE}");
+            Some(table)
+        }
+        Err(e) => {
+            error!("Your {DEFAULTS_FILE} file is malformed: {e}");
+            exit(1)
+        }
+    }
+}
+
+/// The value(s) of an option, as they would be written on the command line.
+fn option_values(value: &toml::Value) -> Option<Vec<String>> {
+    match value {
+        toml::Value::String(value) => Some(vec![value.clone()]),
+        toml::Value::Integer(value) => Some(vec![value.to_string()]),
+        toml::Value::Float(value) => Some(vec![value.to_string()]),
+        toml::Value::Boolean(value) => Some(vec![value.to_string()]),
+        toml::Value::Array(values) => values
+            .iter()
+            .map(|value| match option_values(value).as_deref() {
+                Some([value]) => Some(value.clone()),
+                _ => None,
+            })
+            .collect(),
+        toml::Value::Datetime(_) | toml::Value::Table(_) => None,
+    }
+}
+
+/// Makes the options in `table` the defaults of `command` (whose name, for
+/// error messages, is `path`), and those in its sub-tables the defaults of its
+/// subcommands. Options given on the command line still take precedence.
+pub fn apply(mut command: clap::Command, table: &toml::Table, path: &str) -> clap::Command {
+    for (key, value) in table {
+        if let toml::Value::Table(subtable) = value {
+            if command.find_subcommand(key).is_none() {
+                error!("{DEFAULTS_FILE}: `{path}` has no subcommand `{key}`");
+                exit(1);
+            }
+            let subpath = format!("{path} {key}");
+            command = command.mut_subcommand(key, |sub| apply(sub, subtable, &subpath));
+            continue;
+        }
+
+        if is_denied(key) {
+            error!(
+                "{DEFAULTS_FILE}: `--{key}` cannot be set in the defaults file; pass it on the \
+                command line instead"
+            );
+            exit(1);
+        }
+
+        let id = key.replace('-', "_");
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_id().as_str() == id)
+        else {
+            error!("{DEFAULTS_FILE}: `{path}` has no option `--{key}`");
+            exit(1);
+        };
+        if arg.get_long().is_none() {
+            error!("{DEFAULTS_FILE}: `{key}` is not an option of `{path}`, so it has no default");
+            exit(1);
+        }
+        let Some(values) = option_values(value) else {
+            error!("{DEFAULTS_FILE}: the default for `{path} --{key}` is not a valid value");
+            exit(1);
+        };
+        // the default is enough for options which must otherwise be given
+        command = command.mut_arg(id, |arg| arg.default_values(values).required(false));
+    }
+    command
+}
+
+/// Whether `key` names one of the [`DENIED_OPTIONS`] (in either spelling).
+fn is_denied(key: &str) -> bool {
+    DENIED_OPTIONS.contains(&key.replace('_', "-").as_str())
+}
+
+#[cfg(test)]
+#[test]
+fn test_apply_defaults() {
+    use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+    #[derive(Parser, Debug, PartialEq)]
+    struct Args {
+        #[arg(long, global = true)]
+        #[clap(default_value_t = false)]
+        ignore_accents: bool,
+        #[clap(subcommand)]
+        command: Command,
+    }
+
+    #[derive(Subcommand, Debug, PartialEq)]
+    enum Command {
+        ExportStandings {
+            #[arg(long)]
+            #[clap(default_value = "csv")]
+            format: String,
+            #[arg(long)]
+            to: String,
+            #[arg(long)]
+            rounds: Vec<String>,
+        },
+    }
+
+    let table = r#"
+        ignore-accents = true
+
+        [export-standings]
+        format = "parquet"
+        rounds = ["R1", "R2"]
+        to = "standings.parquet"
+    "#
+    .parse::<toml::Table>()
+    .unwrap();
+    let parse = |argv: &[&str]| {
+        let matches = apply(Args::command(), &table, "tabbycat").get_matches_from(argv);
+        Args::from_arg_matches(&matches).unwrap()
+    };
+
+    assert_eq!(
+        parse(&["tabbycat", "export-standings"]),
+        Args {
+            ignore_accents: true,
+            command: Command::ExportStandings {
+                format: "parquet".to_string(),
+                to: "standings.parquet".to_string(),
+                rounds: vec!["R1".to_string(), "R2".to_string()],
+            },
+        }
+    );
+    assert_eq!(
+        parse(&["tabbycat", "export-standings", "--format", "csv"]).command,
+        Command::ExportStandings {
+            format: "csv".to_string(),
+            to: "standings.parquet".to_string(),
+            rounds: vec!["R1".to_string(), "R2".to_string()],
+        }
+    );
+
+    assert_eq!(
+        option_values(&toml::Value::Integer(4)),
+        Some(vec!["4".to_string()])
+    );
+
+    assert!(is_denied("hooks"));
+    assert!(is_denied("offline"));
+    assert!(!is_denied("ignore-accents"));
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    process::exit,
+    sync::Mutex,
+};
+
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::TempPath;
+use tracing::{error, info};
+
+use crate::open_csv_file;
+
+/// The hashes of the rows of each CSV file (by kind, e.g. `teams`) at the
+/// last import.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct ImportState {
+    rows: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Support for `tabbycat import --since-state state.json`, which only imports
+/// the rows of each CSV file which are new (or have changed) since the last
+/// import that used the same state file.
+pub struct DeltaImport {
+    path: String,
+    previous: ImportState,
+    next: ImportState,
+}
+
+/// The temporary copies of CSV files written by [`write_temp_csv`], which are
+/// deleted by [`remove_temp_files`].
+static TEMP_FILES: Mutex<Vec<TempPath>> = Mutex::new(Vec::new());
+
+/// Writes `rows` (after `header`, if given) to a temporary copy of the `kind`
+/// CSV file, and returns its path. The copy is kept until
+/// [`remove_temp_files`] is called.
+pub fn write_temp_csv<'r>(
+    kind: &str,
+    header: Option<&StringRecord>,
+    rows: impl IntoIterator<Item = &'r StringRecord>,
+) -> String {
+    let file = tempfile::Builder::new()
+        .prefix(&format!("tabbycat-{kind}-"))
+        .suffix(".csv")
+        .tempfile()
+        .unwrap();
+    let mut writer = csv::Writer::from_writer(file.as_file());
+    if let Some(header) = header {
+        writer.write_record(header).unwrap();
+    }
+    for row in rows {
+        writer.write_record(row).unwrap();
+    }
+    writer.flush().unwrap();
+    drop(writer);
+
+    let out = file.into_temp_path();
+    let path = out.to_string_lossy().to_string();
+    TEMP_FILES.lock().unwrap().push(out);
+    path
+}
+
+/// Deletes the temporary copies of CSV files written during the import. This
+/// must be called before the import finishes (or exits early), since they are
+/// not deleted otherwise.
+pub fn remove_temp_files() {
+    TEMP_FILES.lock().unwrap().clear();
+}
+
+fn row_hash(record: &StringRecord) -> String {
+    let mut hasher = Sha256::new();
+    for field in record {
+        hasher.update(field.as_bytes());
+        // separate the fields, so that `a,bc` and `ab,c` differ
+        hasher.update([0x1f]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Splits `rows` into those which are not in `previous`, and the hashes of
+/// every row.
+fn changed_rows<'r>(
+    previous: Option<&BTreeSet<String>>,
+    rows: &'r [StringRecord],
+) -> (Vec<&'r StringRecord>, BTreeSet<String>) {
+    let mut changed = Vec::new();
+    let mut hashes = BTreeSet::new();
+    for row in rows {
+        let hash = row_hash(row);
+        if !previous.is_some_and(|previous| previous.contains(&hash)) {
+            changed.push(row);
+        }
+        hashes.insert(hash);
+    }
+    (changed, hashes)
+}
+
+impl DeltaImport {
+    /// Reads the state left by the last import (if there was one).
+    pub fn load(path: &str) -> DeltaImport {
+        let previous = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                error!("The import state in {path} is malformed: {e}");
+                exit(1)
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("No previous import state found in {path}, so every row will be imported");
+                ImportState::default()
+            }
+            Err(e) => {
+                error!("Could not read {path}: {e}");
+                exit(1)
+            }
+        };
+
+        DeltaImport {
+            path: path.to_string(),
+            previous,
+            next: ImportState::default(),
+        }
+    }
+
+    /// Copies the rows of the CSV file at `csv` which have changed since the
+    /// last import into a temporary file, and returns its path.
+    pub fn filter(&mut self, kind: &str, csv: Option<String>, headers: bool) -> Option<String> {
+        let csv = csv?;
+        let mut reader = open_csv_file(Some(csv.clone()), headers).unwrap();
+        let rows = reader.records().map(|row| row.unwrap()).collect::<Vec<_>>();
+        let header = headers.then(|| reader.headers().unwrap().clone());
+
+        Some(self.filter_records(kind, &csv, header.as_ref(), &rows))
+    }
+
+    /// Like [`DeltaImport::filter`], for rows which have already been read
+    /// from `csv` (and are written out after `header`, if given).
+    pub fn filter_records(
+        &mut self,
+        kind: &str,
+        csv: &str,
+        header: Option<&StringRecord>,
+        rows: &[StringRecord],
+    ) -> String {
+        let (changed, hashes) = changed_rows(self.previous.rows.get(kind), rows);
+        if !self.previous.rows.is_empty() {
+            info!(
+                "{} of the {} row(s) in {csv} are new or have changed since the last import",
+                changed.len(),
+                rows.len()
+            );
+        }
+
+        self.next.rows.insert(kind.to_string(), hashes);
+        write_temp_csv(kind, header, changed)
+    }
+
+    /// Removes a row which failed to import, so that it is imported again
+    /// next time.
+    pub fn forget(&mut self, kind: &str, row: &StringRecord) {
+        if let Some(hashes) = self.next.rows.get_mut(kind) {
+            hashes.remove(&row_hash(row));
+        }
+    }
+
+    /// The path the state is saved to.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Records the rows which were imported, for the next import. Kinds of
+    /// CSV file which were not part of this import keep their previous state.
+    pub fn save(self) {
+        let mut state = self.previous;
+        state.rows.extend(self.next.rows);
+        self.write(&state);
+    }
+
+    /// Records the rows of the `completed` kinds of CSV file (the other kinds
+    /// keep their previous state), for an import which was stopped part of
+    /// the way through.
+    pub fn checkpoint(&self, completed: &[&str]) {
+        let mut state = self.previous.clone();
+        for (kind, hashes) in &self.next.rows {
+            if completed.contains(&kind.as_str()) {
+                state.rows.insert(kind.clone(), hashes.clone());
+            }
+        }
+        self.write(&state);
+    }
+
+    fn write(&self, state: &ImportState) {
+        std::fs::write(&self.path, serde_json::to_string_pretty(state).unwrap()).unwrap();
+        info!("Saved the import state to {}", self.path);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_changed_rows() {
+    let old = [
+        StringRecord::from(vec!["Oxford A", "Oxford"]),
+        StringRecord::from(vec!["Cambridge A", "Cambridge"]),
+    ];
+    let (_, previous) = changed_rows(None, &old);
+
+    let new = [
+        StringRecord::from(vec!["Oxford A", "Oxford"]),
+        StringRecord::from(vec!["Cambridge A", "Cambridge Union"]),
+        StringRecord::from(vec!["Durham A", "Durham"]),
+    ];
+    let (changed, hashes) = changed_rows(Some(&previous), &new);
+    assert_eq!(changed, vec![&new[1], &new[2]]);
+    assert_eq!(hashes.len(), 3);
+
+    let mut delta = DeltaImport {
+        path: "state.json".to_string(),
+        previous: ImportState::default(),
+        next: ImportState::default(),
+    };
+    delta.next.rows.insert("teams".to_string(), hashes);
+    delta.forget("teams", &new[2]);
+    let (changed, _) = changed_rows(delta.next.rows.get("teams"), &new);
+    assert_eq!(changed, vec![&new[2]]);
+}
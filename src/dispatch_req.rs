@@ -1,6 +1,7 @@
-use std::process::exit;
+use std::{collections::BTreeSet, process::exit};
 
 use serde::de::DeserializeOwned;
+use serde_json::Value;
 
 pub async fn json_of_resp<T: DeserializeOwned>(res: reqwest::Response) -> T {
     if !res.status().is_success() {
@@ -10,7 +11,7 @@ pub async fn json_of_resp<T: DeserializeOwned>(res: reqwest::Response) -> T {
 
     let text = res.text().await.unwrap();
 
-    match serde_json::from_str(&text) {
+    match parse_json(&text) {
         Ok(t) => t,
         Err(e) => {
             tracing::error!(
@@ -23,3 +24,202 @@ pub async fn json_of_resp<T: DeserializeOwned>(res: reqwest::Response) -> T {
         }
     }
 }
+
+/// Fields which the CLI can do without: if Tabbycat leaves one of these out
+/// of an object (which happens when fields are added, renamed or removed in a
+/// new release), it is filled in with an empty value. Any other missing field
+/// (e.g. a `url` or `id`) is an error.
+const OPTIONAL_FIELDS: &[&str] = &[
+    "adj_core",
+    "adjudicator_conflicts",
+    "anonymous",
+    "base_score",
+    "break_categories",
+    "breaking",
+    "categories",
+    "code_name",
+    "email",
+    "emoji",
+    "gender",
+    "independent",
+    "institution_conflicts",
+    "phone",
+    "pronoun",
+    "team_conflicts",
+    "trainee",
+    "url_key",
+    "use_institution_prefix",
+    "venue_constraints",
+];
+
+/// A step along the path to a value in a JSON document.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    Index(usize),
+    Key(String),
+}
+
+fn steps_of(path: &serde_path_to_error::Path) -> Option<Vec<Step>> {
+    path.iter()
+        .map(|segment| match segment {
+            serde_path_to_error::Segment::Seq { index } => Some(Step::Index(*index)),
+            serde_path_to_error::Segment::Map { key } => Some(Step::Key(key.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn value_at<'v>(value: &'v mut Value, steps: &[Step]) -> Option<&'v mut Value> {
+    steps.iter().try_fold(value, |value, step| match step {
+        Step::Index(index) => value.get_mut(*index),
+        Step::Key(key) => value.get_mut(key.as_str()),
+    })
+}
+
+/// An empty value of the type serde expected (as described in an "invalid
+/// type" error).
+fn empty_value(expected: &str) -> Option<Value> {
+    Some(match expected {
+        e if e.contains("string") => Value::String(String::new()),
+        e if e.contains("sequence") => Value::Array(vec![]),
+        e if e.contains("bool") => Value::Bool(false),
+        e if e.contains("map") || e.starts_with("struct") => Value::Object(Default::default()),
+        e if e.contains("number")
+            || e.starts_with('i')
+            || e.starts_with('u')
+            || e.starts_with('f') =>
+        {
+            Value::from(0)
+        }
+        _ => return None,
+    })
+}
+
+/// Parses a response from the Tabbycat API, tolerating [`OPTIONAL_FIELDS`]
+/// which are missing from the response. Only the object the field is missing
+/// from is changed: the field is filled in with null (or, if the field is not
+/// an `Option`, an empty value of the expected type) and a warning is printed.
+/// Values which are present (including nulls) are never changed. Unknown
+/// fields are already ignored when deserializing.
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, serde_json::Error> {
+    let mut value: Value = serde_json::from_str(text)?;
+    let mut filled = BTreeSet::new();
+    // the paths of the fields which have been filled in with null
+    let mut nulls: BTreeSet<Vec<Step>> = BTreeSet::new();
+
+    loop {
+        let err = match serde_path_to_error::deserialize::<_, T>(&value) {
+            Ok(t) => {
+                if !filled.is_empty() {
+                    tracing::warn!(
+                        "The response from Tabbycat was missing the field(s) {}, which were \
+                        filled in with defaults. Your version of Tabbycat may not be fully \
+                        supported by this version of tabbycat-cli.",
+                        filled.iter().cloned().collect::<Vec<_>>().join(", ")
+                    );
+                }
+                return Ok(t);
+            }
+            Err(err) => err,
+        };
+        let message = err.inner().to_string();
+        let Some(steps) = steps_of(err.path()) else {
+            return Err(err.into_inner());
+        };
+
+        // the path of a missing field is that of the object it is missing from
+        if let Some(field) = message
+            .strip_prefix("missing field `")
+            .and_then(|rest| rest.split('`').next())
+            .filter(|field| OPTIONAL_FIELDS.contains(field))
+        {
+            let object = value_at(&mut value, &steps).and_then(Value::as_object_mut);
+            if let Some(object) = object
+                && !object.contains_key(field)
+            {
+                object.insert(field.to_string(), Value::Null);
+                filled.insert(field.to_string());
+                let mut path = steps;
+                path.push(Step::Key(field.to_string()));
+                nulls.insert(path);
+                continue;
+            }
+        }
+
+        // a field which was filled in with null is not optional, so fill it
+        // in with an empty value of the expected type instead
+        if let Some(default) = message
+            .strip_prefix("invalid type: null, expected ")
+            .and_then(empty_value)
+            && nulls.remove(&steps)
+            && let Some(null) = value_at(&mut value, &steps)
+        {
+            *null = default;
+            continue;
+        }
+
+        return Err(err.into_inner());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_json_fills_missing_fields() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Team {
+        name: String,
+        emoji: Option<String>,
+        team_conflicts: Vec<String>,
+    }
+
+    let teams: Vec<Team> = parse_json(r#"[{"name": "A", "new_field": 1}]"#).unwrap();
+    assert_eq!(
+        teams,
+        vec![Team {
+            name: "A".to_string(),
+            emoji: None,
+            team_conflicts: vec![],
+        }]
+    );
+
+    assert!(parse_json::<Vec<Team>>(r#"[{"name": 1}]"#).is_err());
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Judge {
+        url: String,
+        institution: Option<String>,
+        emoji: Option<String>,
+        break_categories: Vec<String>,
+        team_conflicts: Vec<String>,
+    }
+
+    // only the object with the missing field is filled in, and nulls which
+    // were already there are left alone
+    let judges: Vec<Judge> = parse_json(
+        r#"[
+            {"url": "/1", "institution": null, "emoji": "x", "break_categories": ["/b"], "team_conflicts": []},
+            {"url": "/2", "institution": "/i", "team_conflicts": ["/t"]}
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(judges[0].institution, None);
+    assert_eq!(judges[0].emoji.as_deref(), Some("x"));
+    assert_eq!(judges[0].break_categories, vec!["/b".to_string()]);
+    assert_eq!(judges[1].emoji, None);
+    assert!(judges[1].break_categories.is_empty());
+    assert_eq!(judges[1].team_conflicts, vec!["/t".to_string()]);
+
+    // critical fields are not filled in, and existing nulls are not replaced
+    assert!(
+        parse_json::<Vec<Judge>>(
+            r#"[{"institution": null, "break_categories": [], "team_conflicts": []}]"#
+        )
+        .is_err()
+    );
+    assert!(
+        parse_json::<Vec<Judge>>(
+            r#"[{"url": "/1", "institution": null, "break_categories": null, "team_conflicts": []}]"#
+        )
+        .is_err()
+    );
+}
@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashSet};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde_json::Value;
+use tabbycat_api::types::DrawStatusEnum;
+use tracing::info;
+
+use crate::{
+    Auth,
+    api_utils::{
+        get_break_categories, get_breaking_teams, get_judges, get_rounds, get_teams,
+        pairings_of_round,
+    },
+    request_manager::RequestManager,
+};
+
+pub const NOT_GIVEN: &str = "not given";
+
+/// The gender recorded for a participant (Tabbycat stores `M`, `F` or `O`).
+pub fn gender(participant: &Value) -> &'static str {
+    match participant["gender"].as_str().map(str::trim) {
+        Some("M") => "male",
+        Some("F") => "female",
+        Some("O") => "other",
+        _ => NOT_GIVEN,
+    }
+}
+
+/// The pronoun recorded for a participant, lower-cased so that e.g. "They"
+/// and "they" are counted together.
+fn pronoun(participant: &Value) -> String {
+    participant["pronoun"]
+        .as_str()
+        .map(|pronoun| pronoun.trim().to_lowercase())
+        .filter(|pronoun| !pronoun.is_empty())
+        .unwrap_or_else(|| NOT_GIVEN.to_string())
+}
+
+/// Counts the number of times each value occurs.
+fn tally<T: Ord>(values: impl IntoIterator<Item = T>) -> BTreeMap<T, usize> {
+    let mut counts = BTreeMap::new();
+    for value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// The columns of a table of counts: every value which occurs in any group,
+/// most common first, with `not given` last.
+fn columns(groups: &[(&str, BTreeMap<String, usize>)]) -> Vec<String> {
+    let mut totals: BTreeMap<&String, usize> = BTreeMap::new();
+    for (value, count) in groups.iter().flat_map(|(_, counts)| counts) {
+        *totals.entry(value).or_insert(0) += count;
+    }
+    let mut columns = totals
+        .into_iter()
+        .filter(|(value, _)| *value != NOT_GIVEN)
+        .collect::<Vec<_>>();
+    columns.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    columns
+        .into_iter()
+        .map(|(value, _)| value.clone())
+        .chain([NOT_GIVEN.to_string()])
+        .collect()
+}
+
+fn print_table(title: &str, groups: &[(&str, BTreeMap<String, usize>)]) {
+    let columns = columns(groups);
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(
+            ["Group", "Total"]
+                .into_iter()
+                .map(str::to_string)
+                .chain(columns.iter().cloned()),
+        );
+    for (group, counts) in groups {
+        let total = counts.values().sum::<usize>();
+        let mut row = vec![group.to_string(), total.to_string()];
+        for column in &columns {
+            let count = counts.get(column).copied().unwrap_or(0);
+            row.push(if total == 0 {
+                "0".to_string()
+            } else {
+                format!("{count} ({:.0}%)", 100.0 * count as f64 / total as f64)
+            });
+        }
+        table.add_row(row);
+    }
+    println!("{title}");
+    println!("{table}");
+}
+
+/// Summarises the genders and pronouns of the speakers, judges, chairs (judges
+/// who have chaired a debate in a released draw) and breaking participants.
+/// Only aggregate counts are shown, never who the participants are.
+pub async fn diversity_report(auth: Auth) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, judges, rounds, categories) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_rounds(&auth, manager.clone()),
+        get_break_categories(&auth, manager.clone()),
+    );
+
+    let mut chairs = HashSet::new();
+    for round in rounds
+        .iter()
+        .filter(|round| matches!(round.draw_status, Some(DrawStatusEnum::R)))
+    {
+        for pairing in pairings_of_round(&auth, round, manager.clone()).await {
+            chairs.extend(pairing.adjudicators.and_then(|adjs| adjs.chair));
+        }
+    }
+
+    let mut breaking_teams = HashSet::new();
+    for category in &categories {
+        for team in get_breaking_teams(category, manager.clone()).await {
+            if team["break_rank"].is_null() {
+                continue;
+            }
+            if let Some(url) = team["team"].as_str() {
+                breaking_teams.insert(url.to_string());
+            }
+        }
+    }
+
+    let speakers = teams
+        .iter()
+        .flat_map(|team| {
+            team.speakers
+                .iter()
+                .map(move |speaker| (team, serde_json::to_value(speaker).unwrap()))
+        })
+        .collect::<Vec<_>>();
+    let breaking_speakers = speakers
+        .iter()
+        .filter(|(team, _)| breaking_teams.contains(&team.url))
+        .map(|(_, speaker)| speaker)
+        .collect::<Vec<_>>();
+    let speakers = speakers
+        .iter()
+        .map(|(_, speaker)| speaker)
+        .collect::<Vec<_>>();
+    let judges = judges
+        .iter()
+        .map(|judge| (judge.url.clone(), serde_json::to_value(judge).unwrap()))
+        .collect::<Vec<_>>();
+    let chairs = judges
+        .iter()
+        .filter(|(url, _)| chairs.contains(url))
+        .map(|(_, judge)| judge)
+        .collect::<Vec<_>>();
+    let breaking_judges = judges
+        .iter()
+        .filter(|(_, judge)| judge["breaking"].as_bool() == Some(true))
+        .map(|(_, judge)| judge)
+        .collect::<Vec<_>>();
+    let judges = judges.iter().map(|(_, judge)| judge).collect::<Vec<_>>();
+
+    let mut groups = vec![("Speakers", speakers), ("Judges", judges)];
+    if !chairs.is_empty() {
+        groups.push(("Chairs", chairs));
+    }
+    if breaking_speakers.is_empty() {
+        info!("No teams have broken yet, so breaking speakers are not shown");
+    } else {
+        groups.push(("Breaking speakers", breaking_speakers));
+    }
+    if !breaking_judges.is_empty() {
+        groups.push(("Breaking judges", breaking_judges));
+    }
+
+    let genders = groups
+        .iter()
+        .map(|(group, participants)| {
+            let counts = tally(participants.iter().map(|p| gender(p).to_string()));
+            (*group, counts)
+        })
+        .collect::<Vec<_>>();
+    let pronouns = groups
+        .iter()
+        .map(|(group, participants)| (*group, tally(participants.iter().map(|p| pronoun(p)))))
+        .collect::<Vec<_>>();
+
+    print_table("Gender", &genders);
+    print_table("Pronouns", &pronouns);
+}
+
+#[cfg(test)]
+#[test]
+fn test_diversity_counts() {
+    use serde_json::json;
+
+    let participants = [
+        json!({ "gender": "F", "pronoun": "she/her" }),
+        json!({ "gender": "M", "pronoun": "He/Him " }),
+        json!({ "gender": "O", "pronoun": "they/them" }),
+        json!({ "gender": null, "pronoun": "" }),
+        json!({ "gender": "F", "pronoun": "She/Her" }),
+    ];
+    let genders = tally(participants.iter().map(|p| gender(p).to_string()));
+    assert_eq!(genders["female"], 2);
+    assert_eq!(genders[NOT_GIVEN], 1);
+
+    let pronouns = tally(participants.iter().map(pronoun));
+    assert_eq!(pronouns["she/her"], 2);
+    assert_eq!(pronouns["he/him"], 1);
+    assert_eq!(
+        columns(&[("Speakers", pronouns)]),
+        vec!["she/her", "he/him", "they/them", NOT_GIVEN]
+    );
+}
@@ -0,0 +1,373 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use resvg::{tiny_skia, usvg};
+use serde::Deserialize;
+use tabbycat_api::types::DrawStatusEnum;
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    api_utils::get_round,
+    clash_graph::escape_xml,
+    request_manager::RequestManager,
+    rooms::natural_cmp,
+    signage::{RoomContext, room_contexts},
+};
+
+const FONT_SIZE: f64 = 24.0;
+const LINE_HEIGHT: f64 = 34.0;
+const HEADER_HEIGHT: f64 = 150.0;
+const MARGIN: f64 = 40.0;
+
+/// The branding applied to the draw image, read from a TOML file. For example
+///
+/// ```toml
+/// title = "Oxford IV 2026"
+/// logo = "logo.png"
+/// background = "#102a43"
+/// foreground = "#ffffff"
+/// accent = "#f0b429"
+/// font = "Inter"
+/// ```
+///
+/// The logo is resolved relative to the theme file. Every field is optional.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Shown above the name of the round.
+    pub title: Option<String>,
+    /// A PNG, JPEG or SVG image shown in the top left corner.
+    pub logo: Option<String>,
+    pub background: String,
+    pub foreground: String,
+    pub accent: String,
+    /// The font family (which must be installed on this computer).
+    pub font: String,
+    /// The width of the image in pixels (the height depends on the number of
+    /// debates).
+    pub width: u32,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            title: None,
+            logo: None,
+            background: "#ffffff".to_string(),
+            foreground: "#1a1a1a".to_string(),
+            accent: "#2b6cb0".to_string(),
+            font: "sans-serif".to_string(),
+            width: 1600,
+        }
+    }
+}
+
+/// Whether `colour` is a hex colour (`#rgb` or `#rrggbb`).
+fn is_hex_colour(colour: &str) -> bool {
+    colour.strip_prefix('#').is_some_and(|hex| {
+        (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+impl Theme {
+    /// Loads the theme from `path`, or returns the default theme if no path is
+    /// given.
+    pub fn load(path: Option<&str>) -> Theme {
+        let Some(path) = path else {
+            return Theme::default();
+        };
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            error!("Could not read theme {path}: {e}");
+            exit(1)
+        });
+        let theme: Theme = toml::from_str(&text).unwrap_or_else(|e| {
+            error!("Your theme {path} is malformed: {e}");
+            exit(1)
+        });
+
+        for (field, colour) in [
+            ("background", &theme.background),
+            ("foreground", &theme.foreground),
+            ("accent", &theme.accent),
+        ] {
+            if !is_hex_colour(colour) {
+                error!("The {field} colour in {path} should look like #1a2b3c (found {colour:?})");
+                exit(1);
+            }
+        }
+        info!("Using the theme in {path}");
+        theme
+    }
+}
+
+/// Splits `items` into lines of at most `max_chars` characters (except where
+/// a single item is longer than that), joined by `sep`.
+fn wrap(items: &[String], sep: &str, max_chars: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for item in items {
+        match lines.last_mut() {
+            Some(line)
+                if line.chars().count() + sep.chars().count() + item.chars().count()
+                    <= max_chars =>
+            {
+                line.push_str(sep);
+                line.push_str(item);
+            }
+            _ => lines.push(item.clone()),
+        }
+    }
+    lines
+}
+
+/// Roughly how many characters of text fit in `width` pixels.
+fn chars_in(width: f64) -> usize {
+    (width / (FONT_SIZE * 0.55)) as usize
+}
+
+fn text(out: &mut String, x: f64, y: f64, fill: &str, weight: &str, size: f64, content: &str) {
+    writeln!(
+        out,
+        r#"<text x="{x}" y="{y}" fill="{fill}" font-weight="{weight}" font-size="{size}">{}</text>"#,
+        escape_xml(content)
+    )
+    .unwrap();
+}
+
+/// Lays out the draw as an SVG document.
+fn draw_svg(theme: &Theme, round: &str, rooms: &[RoomContext]) -> String {
+    let width = theme.width as f64;
+    let inner = width - 2.0 * MARGIN;
+    let columns = [
+        (MARGIN, inner * 0.18),
+        (MARGIN + inner * 0.18, inner * 0.47),
+        (MARGIN + inner * 0.65, inner * 0.35),
+    ];
+
+    let rows = rooms
+        .iter()
+        .map(|room| {
+            let teams = room
+                .teams
+                .iter()
+                .map(|team| match &team.side {
+                    Some(side) => format!("{} {}", side.to_uppercase(), team.name),
+                    None => team.name.clone(),
+                })
+                .collect::<Vec<_>>();
+            let adjudicators = room
+                .chair
+                .iter()
+                .map(|chair| format!("{chair} (c)"))
+                .chain(room.panellists.iter().cloned())
+                .chain(room.trainees.iter().map(|trainee| format!("{trainee} (t)")))
+                .collect::<Vec<_>>();
+            (
+                wrap(&[room.room.clone()], "", chars_in(columns[0].1)),
+                wrap(&teams, " vs ", chars_in(columns[1].1)),
+                wrap(&adjudicators, ", ", chars_in(columns[2].1)),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let row_height = |(room, teams, adjs): &(Vec<String>, Vec<String>, Vec<String>)| {
+        room.len().max(teams.len()).max(adjs.len()).max(1) as f64 * LINE_HEIGHT + 16.0
+    };
+    let height =
+        HEADER_HEIGHT + LINE_HEIGHT + 16.0 + rows.iter().map(row_height).sum::<f64>() + MARGIN;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{width}" height="{height}" font-family="{}">"#,
+        escape_xml(&theme.font)
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        theme.background
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect width="100%" height="{HEADER_HEIGHT}" fill="{}"/>"#,
+        theme.accent
+    )
+    .unwrap();
+
+    let mut title_x = MARGIN;
+    if let Some(logo) = &theme.logo {
+        writeln!(
+            svg,
+            r#"<image x="{MARGIN}" y="25" width="100" height="100" preserveAspectRatio="xMidYMid meet" xlink:href="{}"/>"#,
+            escape_xml(logo)
+        )
+        .unwrap();
+        title_x += 130.0;
+    }
+    match &theme.title {
+        Some(title) => {
+            text(
+                &mut svg,
+                title_x,
+                65.0,
+                &theme.background,
+                "bold",
+                40.0,
+                title,
+            );
+            text(
+                &mut svg,
+                title_x,
+                110.0,
+                &theme.background,
+                "normal",
+                32.0,
+                round,
+            );
+        }
+        None => text(
+            &mut svg,
+            title_x,
+            90.0,
+            &theme.background,
+            "bold",
+            44.0,
+            round,
+        ),
+    }
+
+    let mut y = HEADER_HEIGHT + LINE_HEIGHT;
+    for ((x, _), heading) in columns.iter().zip(["Room", "Teams", "Adjudicators"]) {
+        text(&mut svg, *x, y, &theme.accent, "bold", FONT_SIZE, heading);
+    }
+    y += 16.0;
+
+    for (i, row) in rows.iter().enumerate() {
+        let height = row_height(row);
+        if i % 2 == 0 {
+            writeln!(
+                svg,
+                r#"<rect x="{}" y="{y}" width="{}" height="{height}" fill="{}" fill-opacity="0.12"/>"#,
+                MARGIN / 2.0,
+                width - MARGIN,
+                theme.accent
+            )
+            .unwrap();
+        }
+        let (room, teams, adjs) = row;
+        for ((x, _), (lines, weight)) in
+            columns
+                .iter()
+                .zip([(room, "bold"), (teams, "normal"), (adjs, "normal")])
+        {
+            for (j, line) in lines.iter().enumerate() {
+                let line_y = y + 8.0 + LINE_HEIGHT * (j as f64 + 0.75);
+                text(
+                    &mut svg,
+                    *x,
+                    line_y,
+                    &theme.foreground,
+                    weight,
+                    FONT_SIZE,
+                    line,
+                );
+            }
+        }
+        y += height;
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Rasterises `svg` to a PNG at `png`, loading images (i.e. the logo) relative
+/// to `resources_dir`.
+fn render_png(svg: &str, resources_dir: PathBuf, font: &str, png: &str) {
+    let mut options = usvg::Options {
+        resources_dir: Some(resources_dir),
+        font_family: font.to_string(),
+        ..usvg::Options::default()
+    };
+    options.fontdb_mut().load_system_fonts();
+    if options.fontdb.is_empty() {
+        warn!("No fonts are installed on this computer, so the image will not contain any text");
+    }
+
+    let tree = usvg::Tree::from_str(svg, &options).unwrap_or_else(|e| {
+        error!("Could not lay out the draw image: {e}");
+        exit(1)
+    });
+    let size = tree.size().to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height()).unwrap();
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    if let Err(e) = pixmap.save_png(png) {
+        error!("Could not write {png}: {e}");
+        exit(1);
+    }
+}
+
+/// Renders the released draw for `round` to a PNG image (for posting on social
+/// media), styled using the theme at `theme` if provided.
+pub async fn export_draw_image(auth: Auth, round: &str, png: &str, theme: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+    let theme_config = Theme::load(theme.as_deref());
+
+    let round_info = get_round(round, &auth, manager.clone()).await;
+    if !matches!(round_info.draw_status, Some(DrawStatusEnum::R)) {
+        error!(
+            "The draw for {} has not been released, so it should not be posted yet",
+            round_info.name.as_str()
+        );
+        exit(1);
+    }
+
+    let mut rooms = room_contexts(&auth, round, manager).await;
+    rooms.sort_by(|a, b| natural_cmp(&a.room, &b.room));
+
+    let svg = draw_svg(&theme_config, round_info.name.as_str(), &rooms);
+    let resources_dir = theme
+        .as_deref()
+        .and_then(|theme| Path::new(theme).parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    render_png(&svg, resources_dir, &theme_config.font, png);
+    info!("Wrote the draw for {} debate(s) to {png}", rooms.len());
+}
+
+#[cfg(test)]
+#[test]
+fn test_draw_image_layout() {
+    assert!(is_hex_colour("#fff"));
+    assert!(is_hex_colour("#102a43"));
+    assert!(!is_hex_colour("102a43"));
+    assert!(!is_hex_colour("#102a4"));
+
+    let teams = ["OG Oxford A", "OO Durham B", "CG LSE A"].map(|team| team.to_string());
+    assert_eq!(
+        wrap(&teams, " vs ", 28),
+        vec!["OG Oxford A vs OO Durham B", "CG LSE A"]
+    );
+    assert_eq!(wrap(&teams, " vs ", 5), teams.to_vec());
+
+    let theme: Theme = toml::from_str(r##"accent = "#f0b429""##).unwrap();
+    let rooms = [RoomContext {
+        debate: 1,
+        round: "Round 1".to_string(),
+        room: "Room <1>".to_string(),
+        teams: vec![],
+        chair: Some("Alex".to_string()),
+        panellists: vec![],
+        trainees: vec!["Sam".to_string()],
+        motions: vec![],
+        flags: vec![],
+    }];
+    let svg = draw_svg(&theme, "Round 1", &rooms);
+    assert!(svg.contains("Room &lt;1&gt;"));
+    assert!(svg.contains("Alex (c), Sam (t)"));
+    assert!(svg.contains(r##"fill="#f0b429""##));
+}
@@ -1,9 +1,13 @@
-use serde_json::json;
-use tabbycat_api::types::DebateAdjudicator;
+use serde_json::{Value, json};
+use tabbycat_api::types::{DebateAdjudicator, DebateTeamSide, DebateTeamSideVariant1};
 
 use crate::{
     Auth,
-    api_utils::{get_judges, get_round, get_teams, pairings_of_round},
+    api_utils::{get_judges, get_round_and_pairings, get_teams, get_venues, pairing_in_room},
+    audit_log,
+    dispatch_req::json_of_resp,
+    names::names_match,
+    panel_policy::PanelPolicy,
     request_manager::RequestManager,
 };
 
@@ -26,15 +30,15 @@ fn kind(
     teams: &[tabbycat_api::types::Team],
     judges: &[tabbycat_api::types::Adjudicator],
 ) -> Kind {
-    if let Some(team) = teams.iter().find(|team| {
-        team.long_name.to_lowercase().trim().to_string() == a.to_lowercase().trim().to_string()
-            || team.short_name.to_lowercase().trim() == a.to_lowercase().trim()
-    }) {
+    if let Some(team) = teams
+        .iter()
+        .find(|team| names_match(&team.long_name, a) || names_match(&team.short_name, a))
+    {
         Kind::Team(team.clone())
-    } else if let Some(judge) = judges.iter().find(|judge| {
-        judge.name.to_lowercase().trim() == a.to_lowercase().trim()
-            || judge.id.to_string().trim() == a.to_lowercase().trim()
-    }) {
+    } else if let Some(judge) = judges
+        .iter()
+        .find(|judge| names_match(&judge.name, a) || judge.id.to_string().trim() == a.trim())
+    {
         Kind::Judge(judge.clone())
     } else {
         println!("Error: {a} is not a team or judge!");
@@ -42,15 +46,14 @@ fn kind(
     }
 }
 
-pub async fn swap(round: &str, a: &str, b: &str, auth: Auth) {
+pub async fn swap(round: &str, a: &str, b: &str, swap_sides: bool, auth: Auth) {
     let manager = RequestManager::new(&auth.api_key);
 
-    let (teams, judges, round) = tokio::join! {
+    let (teams, judges, (round, pairings)) = tokio::join! {
         get_teams(&auth, manager.clone()),
         get_judges(&auth, manager.clone()),
-        get_round(round, &auth, manager.clone()),
+        get_round_and_pairings(round, &auth, manager.clone()),
     };
-    let pairings = pairings_of_round(&auth, &round, manager.clone()).await;
 
     let a = (kind)(a, &teams, &judges);
     let b = (kind)(b, &teams, &judges);
@@ -61,6 +64,10 @@ pub async fn swap(round: &str, a: &str, b: &str, auth: Auth) {
     }
 
     match (a, b) {
+        (Kind::Judge(_), Kind::Judge(_)) if swap_sides => {
+            println!("Only teams have sides!");
+            std::process::exit(1);
+        }
         (Kind::Judge(adj1), Kind::Judge(adj2)) => {
             let mut pairing_a = get_adj_pairing(&pairings, adj1.clone()).clone();
             let mut pairing_b = get_adj_pairing(&pairings, adj2.clone()).clone();
@@ -90,71 +97,141 @@ pub async fn swap(round: &str, a: &str, b: &str, auth: Auth) {
             std::process::exit(1);
         }
         (Kind::Team(team1), Kind::Team(team2)) => {
-            let mut pairings = pairings;
-            replace_team_url(&mut pairings, &team1.url, "tmp");
-            replace_team_url(&mut pairings, &team2.url, &team1.url);
-            replace_team_url(&mut pairings, "tmp", &team2.url);
-            let pairing_a = pairing_of_team(&pairings, &team1.url);
-            let pairing_b = pairing_of_team(&pairings, &team2.url);
-
-            if pairing_a.url != pairing_b.url {
-                patch_teams_in_pairing(&auth, pairing_a);
-                patch_teams_in_pairing(&auth, pairing_b);
-            } else {
-                patch_teams_in_pairing(&auth, pairing_a);
-            }
+            swap_teams(&auth, &round, &team1, &team2, swap_sides, manager).await;
         }
     };
 }
 
-fn pairing_of_team<'r>(
-    pairings: &'r [tabbycat_api::types::RoundPairing],
-    team_url: &str,
-) -> &'r tabbycat_api::types::RoundPairing {
-    pairings
-        .iter()
-        .find(|pairing| pairing.teams.iter().any(|team| team.team == team_url))
-        .unwrap()
-}
-
-fn replace_team_url(
-    pairings: &mut [tabbycat_api::types::RoundPairing],
-    team_url: &str,
-    new_team_url: &str,
+/// Swaps two teams on the draw. Each team keeps its own flags (and any other
+/// per-team data), and takes the side (and room) the other team had. With
+/// `swap_sides`, the teams must be in the same debate, and only exchange
+/// their sides.
+async fn swap_teams(
+    auth: &Auth,
+    round: &tabbycat_api::types::Round,
+    team1: &tabbycat_api::types::Team,
+    team2: &tabbycat_api::types::Team,
+    swap_sides: bool,
+    manager: RequestManager,
 ) {
-    for pairing in pairings {
-        for team in &mut pairing.teams {
-            if team.team == team_url {
-                team.team = new_team_url.to_string();
-            }
+    // the pairings are fetched as JSON, as the typed pairings do not include
+    // all the fields of each team (e.g. its flags)
+    let resp = manager
+        .send_request(|| manager.client.get(&round.links.pairing).build().unwrap())
+        .await;
+    let mut pairings: Vec<Value> = json_of_resp(resp).await;
+
+    let position = |team: &tabbycat_api::types::Team| {
+        pairings
+            .iter()
+            .enumerate()
+            .find_map(|(i, pairing)| {
+                pairing["teams"]
+                    .as_array()?
+                    .iter()
+                    .position(|debate_team| debate_team["team"] == team.url)
+                    .map(|j| (i, j))
+            })
+            .unwrap_or_else(|| {
+                println!("Team `{}` is not on the draw", team.long_name);
+                std::process::exit(1);
+            })
+    };
+    let (pairing_a, slot_a) = position(team1);
+    let (pairing_b, slot_b) = position(team2);
+
+    if swap_sides && pairing_a != pairing_b {
+        println!("Error: --swap-sides can only be used for teams in the same debate");
+        std::process::exit(1);
+    }
+
+    let mut debate_team_a = pairings[pairing_a]["teams"][slot_a].clone();
+    let mut debate_team_b = pairings[pairing_b]["teams"][slot_b].clone();
+    let side_a = debate_team_a["side"].clone();
+    debate_team_a["side"] = debate_team_b["side"].clone();
+    debate_team_b["side"] = side_a;
+    pairings[pairing_a]["teams"][slot_a] = debate_team_b;
+    pairings[pairing_b]["teams"][slot_b] = debate_team_a;
+
+    let mut changed = vec![pairing_a];
+    if pairing_b != pairing_a {
+        changed.push(pairing_b);
+    }
+    for i in changed {
+        let url = pairings[i]["url"].as_str().unwrap().to_string();
+        let resp = manager
+            .send_request(|| {
+                manager
+                    .client
+                    .patch(&url)
+                    .json(&json!({ "teams": pairings[i]["teams"] }))
+                    .build()
+                    .unwrap()
+            })
+            .await;
+        if !resp.status().is_success() {
+            println!(
+                "Error: failed to swap teams: {} {}",
+                resp.status(),
+                resp.text().await.unwrap()
+            );
+            std::process::exit(1);
         }
     }
 }
 
-fn patch_teams_in_pairing(auth: &Auth, pairing_a: &tabbycat_api::types::RoundPairing) {
-    attohttpc::patch(pairing_a.url.clone())
-        .header("Authorization", format!("Token {}", auth.api_key))
-        .json(&json! ({
-            "teams": pairing_a.teams.clone()
-        }))
-        .unwrap()
-        .send()
-        .unwrap();
+/// Exchanges the chairs of the debates in `room_a` and `room_b` (each either
+/// the name of a room or the ID of a debate), leaving the panellists and
+/// trainees where they are.
+pub async fn swap_chairs(round: &str, room_a: &str, room_b: &str, auth: Auth) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((_, pairings), venues) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+    );
+
+    let find = |room: &str| {
+        pairing_in_room(&pairings, &venues, room)
+            .cloned()
+            .unwrap_or_else(|| {
+                println!("Error: no debate in this round matches room `{room}`");
+                std::process::exit(1);
+            })
+    };
+    let mut pairing_a = find(room_a);
+    let mut pairing_b = find(room_b);
+
+    if pairing_a.url == pairing_b.url {
+        println!("Can't swap the chair of a room with itself.");
+        std::process::exit(1);
+    }
+
+    let empty_panel = || DebateAdjudicator {
+        chair: None,
+        panellists: vec![],
+        trainees: vec![],
+    };
+    let adjs_a = pairing_a.adjudicators.get_or_insert_with(empty_panel);
+    let adjs_b = pairing_b.adjudicators.get_or_insert_with(empty_panel);
+    std::mem::swap(&mut adjs_a.chair, &mut adjs_b.chair);
+
+    patch_adjudicators_in_pairing(&auth, &pairing_a);
+    patch_adjudicators_in_pairing(&auth, &pairing_b);
 }
 
 fn patch_adjudicators_in_pairing(auth: &Auth, pairing_a: &tabbycat_api::types::RoundPairing) {
-    attohttpc::patch(pairing_a.url.clone())
-        .header("Authorization", format!("Token {}", auth.api_key))
-        .json(&json! ({
+    audit_log::patch_json(
+        &pairing_a.url,
+        auth,
+        &json! ({
             "adjudicators": {
                 "chair": pairing_a.adjudicators.as_ref().unwrap().chair.clone(),
                 "panellists": pairing_a.adjudicators.as_ref().unwrap().panellists.clone(),
                 "trainees": pairing_a.adjudicators.as_ref().unwrap().trainees.clone()
             }
-        }))
-        .unwrap()
-        .send()
-        .unwrap();
+        }),
+    );
 }
 
 fn get_adj_ref<'r>(
@@ -212,7 +289,7 @@ enum Role {
     T,
 }
 
-pub async fn alloc(round: &str, to: &str, a: &str, role: &str, auth: Auth) {
+pub async fn alloc(round: &str, to: &str, a: &str, role: &str, force: bool, auth: Auth) {
     let manager = RequestManager::new(&auth.api_key);
 
     let to = match to.parse::<i64>() {
@@ -234,14 +311,12 @@ pub async fn alloc(round: &str, to: &str, a: &str, role: &str, auth: Auth) {
         }
     };
 
-    let (teams, judges, round) = tokio::join! {
+    let (teams, judges, (_, pairings)) = tokio::join! {
         get_teams(&auth, manager.clone()),
         get_judges(&auth, manager.clone()),
-        get_round(round, &auth, manager.clone())
+        get_round_and_pairings(round, &auth, manager.clone()),
     };
 
-    let pairings = pairings_of_round(&auth, &round, manager.clone()).await;
-
     let judge = match kind(a, &teams, &judges) {
         Kind::Judge(adjudicator) => adjudicator,
         Kind::Team(_) => {
@@ -275,6 +350,13 @@ pub async fn alloc(round: &str, to: &str, a: &str, role: &str, auth: Auth) {
                     .trainees
                     .push(judge.url),
             }
+            PanelPolicy::load().enforce(
+                [(
+                    format!("Debate {}", pairing.id),
+                    pairing.adjudicators.as_ref().unwrap(),
+                )],
+                force,
+            );
             patch_adjudicators_in_pairing(&auth, &pairing);
         }
         None => {
@@ -287,12 +369,11 @@ pub async fn alloc(round: &str, to: &str, a: &str, role: &str, auth: Auth) {
 pub async fn remove(round: &str, a: &str, auth: Auth) {
     let manager = RequestManager::new(&auth.api_key);
 
-    let (teams, judges, round) = tokio::join! {
+    let (teams, judges, (_, pairings)) = tokio::join! {
         get_teams(&auth, manager.clone()),
         get_judges(&auth, manager.clone()),
-        get_round(round, &auth, manager.clone()),
+        get_round_and_pairings(round, &auth, manager.clone()),
     };
-    let pairings = pairings_of_round(&auth, &round, manager.clone()).await;
 
     let judge = match kind(a, &teams, &judges) {
         Kind::Judge(adjudicator) => adjudicator,
@@ -326,3 +407,77 @@ pub async fn remove(round: &str, a: &str, auth: Auth) {
 
     patch_adjudicators_in_pairing(&auth, &pairing);
 }
+
+/// Moves a team to a different side of its debate, swapping sides with the
+/// team which was previously on that side (if there is one).
+pub async fn set_side(round: &str, team: &str, side: &str, auth: Auth) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let side = match side.to_lowercase().as_str() {
+        "aff" | "og" | "prop" | "gov" => DebateTeamSideVariant1::Aff,
+        "neg" | "oo" | "opp" => DebateTeamSideVariant1::Neg,
+        "cg" => DebateTeamSideVariant1::Cg,
+        "co" => DebateTeamSideVariant1::Co,
+        _ => {
+            println!("Side should be one of `aff`, `neg`, `og`, `oo`, `cg`, `co`");
+            std::process::exit(1);
+        }
+    };
+    let side = serde_json::to_value(DebateTeamSide::Variant1(side)).unwrap();
+
+    let (teams, judges, (_, pairings)) = tokio::join! {
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_round_and_pairings(round, &auth, manager.clone()),
+    };
+
+    let team = match kind(team, &teams, &judges) {
+        Kind::Team(team) => team,
+        Kind::Judge(_) => {
+            println!("Error: only teams have sides!");
+            std::process::exit(1);
+        }
+    };
+
+    let pairing = pairings
+        .iter()
+        .find(|pairing| pairing.teams.iter().any(|t| t.team == team.url))
+        .unwrap_or_else(|| {
+            println!("Team `{}` is not on the draw", team.long_name);
+            std::process::exit(1);
+        });
+
+    let mut debate_teams = serde_json::to_value(&pairing.teams).unwrap();
+    let debate_teams_list = debate_teams.as_array_mut().unwrap();
+    let old_side = debate_teams_list
+        .iter()
+        .find(|debate_team| debate_team["team"] == team.url)
+        .map(|debate_team| debate_team["side"].clone())
+        .unwrap();
+    for debate_team in debate_teams_list.iter_mut() {
+        if debate_team["team"] == team.url {
+            debate_team["side"] = side.clone();
+        } else if debate_team["side"] == side {
+            debate_team["side"] = old_side.clone();
+        }
+    }
+
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .patch(&pairing.url)
+                .json(&json!({ "teams": debate_teams }))
+                .build()
+                .unwrap()
+        })
+        .await;
+    if !resp.status().is_success() {
+        println!(
+            "Error: failed to update sides: {} {}",
+            resp.status(),
+            resp.text().await.unwrap()
+        );
+        std::process::exit(1);
+    }
+}
@@ -1,21 +1,38 @@
-use std::process::exit;
+use std::{collections::HashSet, process::exit, time::Duration};
+
+use rusqlite::OptionalExtension;
 
 use crate::{
     Auth,
+    anonymise::Anonymiser,
     api_utils::{get_feedback_questions, get_feedbacks, get_judges, get_teams},
     request_manager::RequestManager,
+    tabular::{Column, TableFormat, write_table},
 };
 
-pub async fn export(auth: Auth, format: &str, output: &str) {
+pub async fn export(auth: Auth, format: &str, output: &str, anon: Anonymiser) {
+    if anon.enabled() {
+        tracing::warn!(
+            "Names are being replaced with pseudonyms, however, free-text answers \
+            are exported unchanged and may still contain names."
+        );
+    }
+
     match format {
         "csv" => {
-            export_feedback_csv(auth, output).await;
+            export_feedback_table(auth, output, TableFormat::Csv, anon).await;
+        }
+        "parquet" => {
+            export_feedback_table(auth, output, TableFormat::Parquet, anon).await;
         }
         "sqlite" => {
-            export_feedback_db(auth, output).await;
+            export_feedback_db(auth, output, anon).await;
         }
         _ => {
-            tracing::error!("Invalid format `{}` expected either csv or sqlite", format);
+            tracing::error!(
+                "Invalid format `{}` expected one of csv, parquet or sqlite",
+                format
+            );
             exit(1);
         }
     }
@@ -28,13 +45,33 @@ struct FeedbackData {
     feedback_questions: Vec<tabbycat_api::types::FeedbackQuestion>,
 }
 
+impl FeedbackData {
+    /// Replaces the names and URLs of all judges and teams with pseudonyms.
+    fn anonymise(&mut self, anon: Anonymiser) {
+        for judge in &mut self.judges {
+            judge.name = anon.name("Judge", &judge.url, &judge.name);
+            judge.url = anon.url(&judge.url);
+        }
+        for team in &mut self.teams {
+            team.long_name = anon.name("Team", &team.url, &team.long_name);
+            team.url = anon.url(&team.url);
+        }
+        for feedback in &mut self.feedbacks {
+            feedback.source = anon.url(&feedback.source);
+            feedback.adjudicator = anon.url(&feedback.adjudicator);
+        }
+    }
+}
+
 async fn fetch_feedback_data(auth: &Auth) -> FeedbackData {
     let manager = RequestManager::new(&auth.api_key);
 
-    let feedbacks = get_feedbacks(auth, manager.clone()).await;
-    let judges = get_judges(auth, manager.clone()).await;
-    let teams = get_teams(auth, manager.clone()).await;
-    let feedback_questions = get_feedback_questions(auth, manager.clone()).await;
+    let (feedbacks, judges, teams, feedback_questions) = tokio::join!(
+        get_feedbacks(auth, manager.clone()),
+        get_judges(auth, manager.clone()),
+        get_teams(auth, manager.clone()),
+        get_feedback_questions(auth, manager.clone()),
+    );
 
     FeedbackData {
         feedbacks,
@@ -44,31 +81,210 @@ async fn fetch_feedback_data(auth: &Auth) -> FeedbackData {
     }
 }
 
-pub async fn export_feedback_csv(auth: Auth, output: &str) {
-    let data = fetch_feedback_data(&auth).await;
+/// The CSV column(s) used for the answers to a feedback question. Answers to
+/// multiple-select questions are expanded into one true/false column per
+/// choice.
+struct QuestionColumns<'q> {
+    question: &'q tabbycat_api::types::FeedbackQuestion,
+    /// The question as returned by the API, used to read the answer type and
+    /// the permitted range of values.
+    meta: serde_json::Value,
+    choices: Option<Vec<String>>,
+}
+
+impl<'q> QuestionColumns<'q> {
+    fn new(
+        question: &'q tabbycat_api::types::FeedbackQuestion,
+        feedbacks: &[tabbycat_api::types::Feedback],
+    ) -> Self {
+        let meta = serde_json::to_value(question).unwrap();
+
+        let answers = feedbacks
+            .iter()
+            .flat_map(|feedback| &feedback.answers)
+            .filter(|answer| answer.question == question.url)
+            .collect::<Vec<_>>();
+
+        let is_multiple_select = meta["answer_type"].as_str() == Some("ms")
+            || answers.iter().any(|answer| {
+                matches!(
+                    answer.answer,
+                    tabbycat_api::types::FeedbackAnswerAnswer::Variant3(_)
+                )
+            });
+
+        let choices = is_multiple_select.then(|| {
+            let mut choices = meta["choices"]
+                .as_array()
+                .map(|choices| {
+                    choices
+                        .iter()
+                        .filter_map(|choice| choice.as_str().map(|c| c.to_string()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            for answer in &answers {
+                if let tabbycat_api::types::FeedbackAnswerAnswer::Variant3(items) = &answer.answer {
+                    for item in items {
+                        if !choices.contains(item) {
+                            choices.push(item.clone());
+                        }
+                    }
+                }
+            }
+            choices
+        });
+
+        Self {
+            question,
+            meta,
+            choices,
+        }
+    }
+
+    fn headers(&self) -> Vec<String> {
+        let base = format!("question_{}", self.question.reference.to_string());
+        match &self.choices {
+            Some(choices) => choices
+                .iter()
+                .map(|choice| format!("{base}_{choice}"))
+                .collect(),
+            None => vec![base],
+        }
+    }
+
+    /// The columns (named as in [`QuestionColumns::headers`]) with the type
+    /// of their cells.
+    fn columns(&self) -> Vec<Column> {
+        let headers = self.headers();
+        match (&self.choices, self.meta["answer_type"].as_str()) {
+            (Some(_), _) | (None, Some("bc" | "bs")) => {
+                headers.into_iter().map(Column::bool).collect()
+            }
+            (None, Some("i" | "is" | "f")) => headers.into_iter().map(Column::number).collect(),
+            (None, _) => headers.into_iter().map(Column::text).collect(),
+        }
+    }
+
+    fn cells(&self, answer: Option<&tabbycat_api::types::FeedbackAnswerAnswer>) -> Vec<String> {
+        use tabbycat_api::types::FeedbackAnswerAnswer;
+
+        match (&self.choices, answer) {
+            (Some(choices), None) => vec![String::new(); choices.len()],
+            (Some(choices), Some(answer)) => choices
+                .iter()
+                .map(|choice| match answer {
+                    FeedbackAnswerAnswer::Variant3(items) => items.contains(choice),
+                    FeedbackAnswerAnswer::Variant2(x) => x == choice,
+                    _ => false,
+                })
+                .map(|selected| selected.to_string())
+                .collect(),
+            (None, None) => vec![String::new()],
+            (None, Some(answer)) => vec![match answer {
+                FeedbackAnswerAnswer::Variant0(x) => x.to_string(),
+                FeedbackAnswerAnswer::Variant1(x) => x.to_string(),
+                FeedbackAnswerAnswer::Variant2(x) => x.clone(),
+                FeedbackAnswerAnswer::Variant3(items) => items.join(";"),
+            }],
+        }
+    }
+}
+
+/// The path of the file describing the questions in the feedback CSV file
+/// (e.g. `feedback_questions.csv` for `feedback.csv`).
+fn questions_metadata_path(output: &str) -> std::path::PathBuf {
+    let path = std::path::Path::new(output);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "feedback".to_string());
+    path.with_file_name(format!("{stem}_questions.csv"))
+}
 
-    let mut writer = csv::Writer::from_path(output).unwrap();
+fn write_questions_metadata(columns: &[QuestionColumns], output: &str) {
+    let path = questions_metadata_path(output);
+    let mut writer = csv::Writer::from_path(&path).unwrap();
+
+    let cell = |value: &serde_json::Value| match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    writer
+        .write_record([
+            "column",
+            "reference",
+            "text",
+            "answer_type",
+            "min_value",
+            "max_value",
+        ])
+        .unwrap();
+    for column in columns {
+        for header in column.headers() {
+            writer
+                .write_record([
+                    header,
+                    column.question.reference.to_string(),
+                    column.question.text.to_string(),
+                    cell(&column.meta["answer_type"]),
+                    cell(&column.meta["min_value"]),
+                    cell(&column.meta["max_value"]),
+                ])
+                .unwrap();
+        }
+    }
+
+    writer.flush().unwrap();
+    tracing::info!("Saved question metadata into CSV file {}", path.display());
+}
+
+/// Exports feedback with one row per feedback submission, as either CSV or
+/// Parquet.
+pub async fn export_feedback_table(
+    auth: Auth,
+    output: &str,
+    format: TableFormat,
+    anon: Anonymiser,
+) {
+    let mut data = fetch_feedback_data(&auth).await;
+    // the source kind is derived from the URL, so it has to be determined
+    // before the URLs are anonymised
+    let source_is_team = data
+        .feedbacks
+        .iter()
+        .map(|feedback| feedback.source.contains("/team"))
+        .collect::<Vec<_>>();
+    data.anonymise(anon);
 
     let mut header = vec![
-        "feedback_id".to_string(),
-        "source".to_string(),
-        "source_kind".to_string(),
-        "target".to_string(),
+        Column::number("feedback_id"),
+        Column::text("source"),
+        Column::text("source_kind"),
+        Column::text("target"),
     ];
 
-    for question in data.feedback_questions.iter() {
-        header.push(format!("question_{}", question.reference.to_string()));
+    let columns = data
+        .feedback_questions
+        .iter()
+        .map(|question| QuestionColumns::new(question, &data.feedbacks))
+        .collect::<Vec<_>>();
+
+    for column in &columns {
+        header.extend(column.columns());
     }
 
-    header.push("source_url".to_string());
-    header.push("target_url".to_string());
+    header.push(Column::text("source_url"));
+    header.push(Column::text("target_url"));
 
-    writer.write_record(&header).unwrap();
+    let mut rows = Vec::new();
 
     for (feedback_idx, feedback) in data.feedbacks.iter().enumerate() {
         let mut record = vec![
             feedback_idx.to_string(),
-            if feedback.source.contains("/team") {
+            if source_is_team[feedback_idx] {
                 data.teams
                     .iter()
                     .find(|team| team.url == feedback.source)
@@ -83,7 +299,7 @@ pub async fn export_feedback_csv(auth: Auth, output: &str) {
                     .unwrap()
                     .name
             },
-            if feedback.source.contains("/team") {
+            if source_is_team[feedback_idx] {
                 "team"
             } else {
                 "judge"
@@ -97,38 +313,215 @@ pub async fn export_feedback_csv(auth: Auth, output: &str) {
                 .name,
         ];
 
-        for question in &data.feedback_questions {
-            let qna = feedback.answers.iter().find(|a| a.question == question.url);
+        for column in &columns {
+            let qna = feedback
+                .answers
+                .iter()
+                .find(|a| a.question == column.question.url);
 
-            if let Some(qna) = qna {
-                record.push(match &qna.answer {
-                    tabbycat_api::types::FeedbackAnswerAnswer::Variant0(x) => x.to_string(),
-                    tabbycat_api::types::FeedbackAnswerAnswer::Variant1(x) => x.to_string(),
-                    tabbycat_api::types::FeedbackAnswerAnswer::Variant2(x) => x.clone(),
-                    tabbycat_api::types::FeedbackAnswerAnswer::Variant3(items) => {
-                        format!("\"{}\"", items.join(","))
-                    }
-                });
-            } else {
-                record.push(String::new());
-            }
+            record.extend(column.cells(qna.map(|qna| &qna.answer)));
         }
 
         record.push(feedback.source.clone());
         record.push(feedback.adjudicator.clone());
 
-        writer.write_record(&record).unwrap();
+        rows.push(record);
     }
 
-    writer.flush().unwrap();
-    tracing::info!("Saved all feedback into CSV file {}", output);
+    write_table(output, format, &header, &rows);
+    tracing::info!("Saved all feedback into {:?} file {}", format, output);
+
+    write_questions_metadata(&columns, output);
+}
+
+/// How the answers to a feedback question are summarised in each judge's row
+/// of the pivoted export.
+#[derive(Debug, Clone, PartialEq)]
+enum AnswerSummary {
+    /// The average of numeric answers.
+    Mean,
+    /// The number of times each choice was given (for select, yes/no and
+    /// checkbox questions).
+    Counts(Vec<String>),
+    /// Free-text answers, which are left out.
+    Omitted,
 }
 
-pub async fn export_feedback_db(auth: Auth, output: &str) {
-    let data = fetch_feedback_data(&auth).await;
+/// The choices an answer (as JSON) selects, if it is categorical.
+fn answer_choices(answer: &serde_json::Value) -> Vec<String> {
+    match answer {
+        serde_json::Value::Bool(x) => vec![x.to_string()],
+        serde_json::Value::String(x) => vec![x.clone()],
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                serde_json::Value::String(x) => x.clone(),
+                other => other.to_string(),
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+impl AnswerSummary {
+    /// Decides how to summarise a question from its answer type (see
+    /// Tabbycat's `AdjudicatorFeedbackQuestion`), falling back to the kinds
+    /// of answer which were given.
+    fn new(meta: &serde_json::Value, answers: &[serde_json::Value]) -> AnswerSummary {
+        let categorical = || {
+            let mut choices = meta["choices"]
+                .as_array()
+                .map(|choices| {
+                    choices
+                        .iter()
+                        .filter_map(|choice| choice.as_str().map(|c| c.to_string()))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            for choice in answers.iter().flat_map(answer_choices) {
+                if !choices.contains(&choice) {
+                    choices.push(choice);
+                }
+            }
+            AnswerSummary::Counts(choices)
+        };
+
+        match meta["answer_type"].as_str() {
+            Some("i" | "is" | "f") => AnswerSummary::Mean,
+            Some("bc" | "bs" | "ss" | "ms") => categorical(),
+            Some("t" | "tl") => AnswerSummary::Omitted,
+            _ if answers.iter().all(|answer| answer.is_number()) => AnswerSummary::Mean,
+            _ if answers.iter().any(|answer| answer.is_string()) => AnswerSummary::Omitted,
+            _ => categorical(),
+        }
+    }
+
+    fn headers(&self, base: &str) -> Vec<String> {
+        match self {
+            AnswerSummary::Mean => vec![format!("{base}_mean")],
+            AnswerSummary::Counts(choices) => choices
+                .iter()
+                .map(|choice| format!("{base}_{choice}"))
+                .collect(),
+            AnswerSummary::Omitted => vec![],
+        }
+    }
+
+    /// Summarises the answers given about a single judge.
+    fn cells(&self, answers: &[&serde_json::Value]) -> Vec<String> {
+        match self {
+            AnswerSummary::Mean => {
+                let values = answers
+                    .iter()
+                    .filter_map(|answer| answer.as_f64())
+                    .collect::<Vec<_>>();
+                vec![if values.is_empty() {
+                    String::new()
+                } else {
+                    format!("{:.2}", values.iter().sum::<f64>() / values.len() as f64)
+                }]
+            }
+            AnswerSummary::Counts(choices) => choices
+                .iter()
+                .map(|choice| {
+                    answers
+                        .iter()
+                        .filter(|answer| answer_choices(answer).contains(choice))
+                        .count()
+                        .to_string()
+                })
+                .collect(),
+            AnswerSummary::Omitted => vec![],
+        }
+    }
+}
 
+/// Exports feedback with one row per judge (rather than per submission): the
+/// number of submissions on the judge, the mean of the answers to each
+/// numeric question and the number of times each choice was given to each
+/// categorical question. Free-text questions are left out.
+pub async fn export_feedback_pivot(
+    auth: Auth,
+    output: &str,
+    format: TableFormat,
+    anon: Anonymiser,
+) {
+    let mut data = fetch_feedback_data(&auth).await;
+    data.anonymise(anon);
+
+    let answer_of = |feedback: &tabbycat_api::types::Feedback, question: &str| {
+        feedback
+            .answers
+            .iter()
+            .find(|answer| answer.question == question)
+            .map(|answer| serde_json::to_value(&answer.answer).unwrap())
+    };
+
+    let summaries = data
+        .feedback_questions
+        .iter()
+        .map(|question| {
+            let answers = data
+                .feedbacks
+                .iter()
+                .filter_map(|feedback| answer_of(feedback, &question.url))
+                .collect::<Vec<_>>();
+            let meta = serde_json::to_value(question).unwrap();
+            (question, AnswerSummary::new(&meta, &answers))
+        })
+        .collect::<Vec<_>>();
+
+    let mut header = vec![Column::text("judge"), Column::number("feedback_count")];
+    for (question, summary) in &summaries {
+        // (both the means and the counts of each choice are numbers)
+        header.extend(
+            summary
+                .headers(&format!("question_{}", question.reference.to_string()))
+                .into_iter()
+                .map(Column::number),
+        );
+    }
+    header.push(Column::text("judge_url"));
+
+    let mut rows = Vec::new();
+    for judge in &data.judges {
+        let feedbacks = data
+            .feedbacks
+            .iter()
+            .filter(|feedback| feedback.adjudicator == judge.url)
+            .collect::<Vec<_>>();
+
+        let mut record = vec![judge.name.clone(), feedbacks.len().to_string()];
+        for (question, summary) in &summaries {
+            let answers = feedbacks
+                .iter()
+                .filter_map(|feedback| answer_of(feedback, &question.url))
+                .collect::<Vec<_>>();
+            record.extend(summary.cells(&answers.iter().collect::<Vec<_>>()));
+        }
+        record.push(judge.url.clone());
+
+        rows.push(record);
+    }
+
+    write_table(output, format, &header, &rows);
+    tracing::info!(
+        "Saved feedback on {} judge(s) into {:?} file {}",
+        rows.len(),
+        format,
+        output
+    );
+}
+
+fn open_feedback_db(output: &str) -> rusqlite::Connection {
     let database = rusqlite::Connection::open(output).unwrap();
+    create_feedback_tables(&database);
+    database
+}
 
+/// Creates the tables of the feedback database, if they do not exist, and
+/// brings those created by older versions up to date.
+fn create_feedback_tables(database: &rusqlite::Connection) {
     database
         .execute_batch(
             r#"
@@ -152,6 +545,8 @@ pub async fn export_feedback_db(auth: Auth, output: &str) {
 
         create table if not exists feedbacks (
             id integer not null primary key,
+            -- the ID of this feedback in Tabbycat
+            tabbycat_id integer unique,
             source text not null,
             -- always targets a judge
             target integer not null
@@ -166,10 +561,66 @@ pub async fn export_feedback_db(auth: Auth, output: &str) {
         )
         .unwrap();
 
+    // databases created before the ID of each feedback was recorded (the
+    // column cannot be added as `unique`, so an index is used instead). The
+    // IDs of the existing rows are filled in by `claim_old_feedback` as the
+    // feedback is next fetched.
+    let has_tabbycat_id = database
+        .prepare("select name from pragma_table_info('feedbacks');")
+        .unwrap()
+        .query_map((), |row| row.get::<_, String>(0))
+        .unwrap()
+        .any(|name| name.unwrap() == "tabbycat_id");
+    if !has_tabbycat_id {
+        tracing::info!("Adding the Tabbycat IDs of feedback to an existing database");
+        database
+            .execute_batch(
+                r#"
+            alter table feedbacks add column tabbycat_id integer;
+            create unique index feedbacks_tabbycat_id on feedbacks (tabbycat_id);
+            "#,
+            )
+            .unwrap();
+    }
+}
+
+/// Records `tabbycat_id` as the ID of the earliest feedback from `source` on
+/// `target` which was added before the Tabbycat IDs of feedback were recorded
+/// (if there is one), so that it is not added again. Returns whether there was
+/// such feedback.
+fn claim_old_feedback(
+    database: &rusqlite::Connection,
+    tabbycat_id: i64,
+    source: &str,
+    target: &str,
+) -> bool {
+    database
+        .query_row(
+            "update feedbacks set tabbycat_id = ?1 where id = (
+                select id from feedbacks
+                where tabbycat_id is null and source = ?2 and target = ?3
+                order by id limit 1
+            ) returning id;",
+            (tabbycat_id, source, target),
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .unwrap()
+        .is_some()
+}
+
+/// Inserts all feedback (and the judges, teams and questions it refers to)
+/// which is not yet in the database. Returns the number of new feedback
+/// records.
+fn sync_feedback_db(
+    database: &rusqlite::Connection,
+    data: FeedbackData,
+    seen: &mut HashSet<i64>,
+) -> usize {
     for judge in data.judges {
         database
             .execute(
-                "insert into judges (url, name) values (?, ?);",
+                "insert or ignore into judges (url, name) values (?, ?);",
                 (judge.url, judge.name),
             )
             .unwrap();
@@ -178,7 +629,7 @@ pub async fn export_feedback_db(auth: Auth, output: &str) {
     for team in data.teams {
         database
             .execute(
-                "insert into teams (url, name) values (?, ?);",
+                "insert or ignore into teams (url, name) values (?, ?);",
                 (team.url, team.long_name),
             )
             .unwrap();
@@ -187,17 +638,36 @@ pub async fn export_feedback_db(auth: Auth, output: &str) {
     for question in data.feedback_questions {
         database
             .execute(
-                "insert into questions (url, title) values (?, ?);",
+                "insert or ignore into questions (url, title) values (?, ?);",
                 (question.url, question.text.to_string()),
             )
             .unwrap();
     }
 
-    for feedback in data.feedbacks {
+    let mut feedbacks = data.feedbacks;
+    // so that feedback added before the Tabbycat IDs were recorded is claimed
+    // in the order it was submitted
+    feedbacks.sort_by_key(|feedback| feedback.id);
+
+    let mut added = 0;
+    for feedback in feedbacks {
+        if !seen.insert(feedback.id) {
+            continue;
+        }
+        if claim_old_feedback(
+            database,
+            feedback.id,
+            &feedback.source,
+            &feedback.adjudicator,
+        ) {
+            continue;
+        }
+        added += 1;
+
         let id = database
             .query_one(
-                "insert into feedbacks (source, target) values (?, ?) returning id;",
-                (feedback.source, feedback.adjudicator),
+                "insert into feedbacks (tabbycat_id, source, target) values (?, ?, ?) returning id;",
+                (feedback.id, feedback.source, feedback.adjudicator),
                 |row| row.get::<_, i64>(0),
             )
             .unwrap();
@@ -216,5 +686,137 @@ pub async fn export_feedback_db(auth: Auth, output: &str) {
         }
     }
 
+    added
+}
+
+/// The Tabbycat IDs of the feedback which is already in the database.
+fn seen_feedback_ids(database: &rusqlite::Connection) -> HashSet<i64> {
+    let mut statement = database
+        .prepare("select tabbycat_id from feedbacks where tabbycat_id is not null;")
+        .unwrap();
+    statement
+        .query_map((), |row| row.get::<_, i64>(0))
+        .unwrap()
+        .map(|id| id.unwrap())
+        .collect()
+}
+
+pub async fn export_feedback_db(auth: Auth, output: &str, anon: Anonymiser) {
+    let mut data = fetch_feedback_data(&auth).await;
+    data.anonymise(anon);
+
+    let database = open_feedback_db(output);
+    let mut seen = seen_feedback_ids(&database);
+    sync_feedback_db(&database, data, &mut seen);
+
     tracing::info!("Saved all feedback into database {}", output);
 }
+
+/// Polls Tabbycat for feedback every `interval` seconds, appending new
+/// feedback to the database as it arrives (so that e.g. a live dashboard can
+/// be built on top of the database). Runs until interrupted.
+pub async fn follow_feedback_db(auth: Auth, output: &str, interval: u64, anon: Anonymiser) {
+    let database = open_feedback_db(output);
+    let mut seen = seen_feedback_ids(&database);
+
+    tracing::info!(
+        "Following feedback into database {} ({} records already present)",
+        output,
+        seen.len()
+    );
+
+    loop {
+        let mut data = fetch_feedback_data(&auth).await;
+        data.anonymise(anon);
+
+        let added = sync_feedback_db(&database, data, &mut seen);
+        if added > 0 {
+            tracing::info!("Added {added} new feedback records to {output}");
+        }
+
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_answer_summary() {
+    use serde_json::json;
+
+    let score = AnswerSummary::new(&json!({ "answer_type": "is" }), &[json!(3), json!(4)]);
+    assert_eq!(score, AnswerSummary::Mean);
+    assert_eq!(score.cells(&[&json!(3), &json!(4.5)]), vec!["3.75"]);
+    assert_eq!(score.cells(&[]), vec![""]);
+
+    let agreed = AnswerSummary::new(&json!({ "answer_type": "bs" }), &[json!(true)]);
+    assert_eq!(agreed, AnswerSummary::Counts(vec!["true".to_string()]));
+
+    let strengths = AnswerSummary::new(
+        &json!({ "answer_type": "ms", "choices": ["clear", "fair"] }),
+        &[json!(["fair", "concise"])],
+    );
+    assert_eq!(
+        strengths.headers("question_strengths"),
+        vec![
+            "question_strengths_clear",
+            "question_strengths_fair",
+            "question_strengths_concise"
+        ]
+    );
+    assert_eq!(
+        strengths.cells(&[&json!(["fair", "concise"]), &json!(["fair"])]),
+        vec!["0", "2", "1"]
+    );
+
+    let comments = AnswerSummary::new(&json!({}), &[json!("Great!")]);
+    assert_eq!(comments, AnswerSummary::Omitted);
+}
+
+#[cfg(test)]
+#[test]
+fn test_migrate_feedback_db() {
+    let database = rusqlite::Connection::open_in_memory().unwrap();
+    database
+        .execute_batch(
+            r#"
+        create table feedbacks (
+            id integer not null primary key,
+            source text not null,
+            target integer not null
+        );
+        insert into feedbacks (source, target) values ('team', 'judge-1');
+        insert into feedbacks (source, target) values ('team', 'judge-1');
+        "#,
+        )
+        .unwrap();
+
+    create_feedback_tables(&database);
+    assert!(seen_feedback_ids(&database).is_empty());
+
+    // the existing feedback is matched to the feedback fetched from Tabbycat
+    assert!(claim_old_feedback(&database, 3, "team", "judge-1"));
+    assert!(claim_old_feedback(&database, 4, "team", "judge-1"));
+    assert!(!claim_old_feedback(&database, 5, "team", "judge-1"));
+    assert!(!claim_old_feedback(&database, 6, "judge", "judge-1"));
+    assert_eq!(seen_feedback_ids(&database), HashSet::from([3, 4]));
+
+    database
+        .execute(
+            "insert into feedbacks (tabbycat_id, source, target) values (?, ?, ?);",
+            (7, "judge", 2),
+        )
+        .unwrap();
+    assert_eq!(seen_feedback_ids(&database), HashSet::from([3, 4, 7]));
+    // the IDs are still unique
+    assert!(
+        database
+            .execute(
+                "insert into feedbacks (tabbycat_id, source, target) values (?, ?, ?);",
+                (7, "judge", 3),
+            )
+            .is_err()
+    );
+
+    // opening an up-to-date database again changes nothing
+    create_feedback_tables(&database);
+}
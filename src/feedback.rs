@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, HashSet};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use tracing::info;
+
+use crate::{
+    Auth,
+    api_utils::{
+        get_feedbacks, get_institutions, get_judges, get_preference, get_round_and_pairings,
+        get_teams, get_venues,
+    },
+    contacts::{Contact, dedup_contacts, email, private_url, write_contacts},
+    i18n::{tr, tr_args},
+    request_manager::RequestManager,
+};
+
+/// A piece of feedback which a participant is required to submit for a
+/// debate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Required {
+    /// The team or adjudicator who should submit the feedback.
+    pub source: String,
+    /// The adjudicator the feedback is on, or `None` for team feedback (which
+    /// may be on any adjudicator, as it is on whoever gave the oral
+    /// adjudication).
+    pub target: Option<String>,
+}
+
+/// The feedback required from the participants in a single debate, following
+/// Tabbycat's `feedback_paths` preference: `minimal` (chairs on panellists
+/// and trainees), `with-p-on-c` (and panellists on chairs) or `all-adjs`
+/// (every adjudicator on every other adjudicator). Teams always submit
+/// feedback on the oral adjudication.
+pub fn required_feedback(
+    teams: &[String],
+    chair: Option<&str>,
+    panellists: &[String],
+    trainees: &[String],
+    feedback_paths: &str,
+) -> Vec<Required> {
+    let mut required = teams
+        .iter()
+        .map(|team| Required {
+            source: team.clone(),
+            target: None,
+        })
+        .collect::<Vec<_>>();
+    if chair.is_none() && panellists.is_empty() {
+        return required;
+    }
+
+    let required_between = |source: &str, target: &str| Required {
+        source: source.to_string(),
+        target: Some(target.to_string()),
+    };
+
+    if feedback_paths == "all-adjs" {
+        let adjs = chair
+            .into_iter()
+            .chain(panellists.iter().map(String::as_str))
+            .chain(trainees.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        for &source in &adjs {
+            for &target in adjs.iter().filter(|&&target| target != source) {
+                required.push(required_between(source, target));
+            }
+        }
+        return required;
+    }
+
+    if let Some(chair) = chair {
+        for wing in panellists.iter().chain(trainees) {
+            required.push(required_between(chair, wing.as_str()));
+        }
+        if feedback_paths == "with-p-on-c" {
+            for panellist in panellists {
+                required.push(required_between(panellist.as_str(), chair));
+            }
+        }
+    }
+    required
+}
+
+/// Whether `required` has been met by the feedback in `submitted` (as pairs
+/// of source and target).
+fn is_submitted(required: &Required, submitted: &HashSet<(String, String)>) -> bool {
+    match &required.target {
+        Some(target) => submitted.contains(&(required.source.clone(), target.clone())),
+        None => submitted
+            .iter()
+            .any(|(source, _)| *source == required.source),
+    }
+}
+
+/// Shows which teams and adjudicators have not submitted the feedback they
+/// are required to for `round`, grouped by institution. If `emails` is
+/// provided, the contact details of everyone who is missing feedback are
+/// written to that CSV file (in the same format as `export contacts`) for a
+/// reminder.
+pub async fn missing_feedback(auth: Auth, round: &str, emails: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((round, pairings), feedbacks, teams, judges, institutions, venues, paths) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_feedbacks(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+        get_preference(&auth, "feedback__feedback_paths", manager.clone()),
+    );
+    let feedback_paths = paths.as_str().unwrap_or("minimal");
+
+    let institution_name = |url: Option<&String>| {
+        url.and_then(|url| institutions.iter().find(|inst| &inst.url == url))
+            .map(|inst| inst.name.as_str().to_string())
+            .unwrap_or_default()
+    };
+    let judge_name = |url: &str| {
+        judges
+            .iter()
+            .find(|judge| judge.url == url)
+            .map(|judge| judge.name.clone())
+            .unwrap_or_else(|| url.to_string())
+    };
+
+    // the debate, source and target of each piece of feedback
+    let submissions = feedbacks
+        .iter()
+        .map(|feedback| {
+            (
+                serde_json::to_value(feedback).unwrap()["debate"]
+                    .as_str()
+                    .map(|debate| debate.to_string()),
+                feedback.source.clone(),
+                feedback.adjudicator.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // missing feedback, by institution and then by participant
+    let mut missing: BTreeMap<String, BTreeMap<String, (String, String, Vec<String>)>> =
+        BTreeMap::new();
+    let mut missing_sources = HashSet::new();
+    let mut total = 0;
+    for pairing in &pairings {
+        let submitted = submissions
+            .iter()
+            .filter(|(debate, _, _)| debate.as_deref() == Some(pairing.url.as_str()))
+            .map(|(_, source, target)| (source.clone(), target.clone()))
+            .collect::<HashSet<_>>();
+
+        let pairing_teams = pairing
+            .teams
+            .iter()
+            .map(|team| team.team.clone())
+            .collect::<Vec<_>>();
+        let required = match &pairing.adjudicators {
+            Some(adjs) => required_feedback(
+                &pairing_teams,
+                adjs.chair.as_deref(),
+                &adjs.panellists,
+                &adjs.trainees,
+                feedback_paths,
+            ),
+            None => required_feedback(&pairing_teams, None, &[], &[], feedback_paths),
+        };
+        total += required.len();
+
+        let room = pairing
+            .venue
+            .as_ref()
+            .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+            .map(|venue| venue.name.to_string())
+            .unwrap_or_else(|| format!("debate {}", pairing.id));
+
+        for required in required
+            .iter()
+            .filter(|required| !is_submitted(required, &submitted))
+        {
+            let (name, role, institution) =
+                match teams.iter().find(|team| team.url == required.source) {
+                    Some(team) => (
+                        team.short_name.to_string(),
+                        tr("feedback-role-team"),
+                        institution_name(team.institution.as_ref()),
+                    ),
+                    None => {
+                        let judge = judges.iter().find(|judge| judge.url == required.source);
+                        let is_chair = pairing
+                            .adjudicators
+                            .as_ref()
+                            .and_then(|adjs| adjs.chair.as_ref())
+                            == Some(&required.source);
+                        (
+                            judge_name(&required.source),
+                            tr(if is_chair {
+                                "feedback-role-chair"
+                            } else {
+                                "feedback-role-adjudicator"
+                            }),
+                            institution_name(judge.and_then(|judge| judge.institution.as_ref())),
+                        )
+                    }
+                };
+            let owed = required
+                .target
+                .as_deref()
+                .map(judge_name)
+                .unwrap_or_else(|| tr("feedback-oral"));
+
+            missing_sources.insert(required.source.clone());
+            missing
+                .entry(institution)
+                .or_default()
+                .entry(name)
+                .or_insert_with(|| (role, room.clone(), Vec::new()))
+                .2
+                .push(owed);
+        }
+    }
+
+    let missing_count = missing
+        .values()
+        .flat_map(|participants| participants.values())
+        .map(|(_, _, owed)| owed.len())
+        .sum::<usize>();
+    if missing_count == 0 {
+        println!(
+            "{}",
+            tr_args(
+                "feedback-all-in",
+                [
+                    ("total", total.into()),
+                    ("round", round.name.as_str().into())
+                ]
+            )
+        );
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec![
+            tr("feedback-institution"),
+            tr("feedback-participant"),
+            tr("feedback-role"),
+            tr("room"),
+            tr("feedback-missing-on"),
+        ]);
+    for (institution, participants) in &missing {
+        for (name, (role, room, owed)) in participants {
+            table.add_row(vec![
+                institution.clone(),
+                name.clone(),
+                role.clone(),
+                room.clone(),
+                owed.join("\n"),
+            ]);
+        }
+    }
+    println!("{table}");
+    let percent = 100.0 * (total - missing_count) as f64 / total as f64;
+    println!(
+        "{}",
+        tr_args(
+            "feedback-rate",
+            [
+                ("submitted", (total - missing_count).into()),
+                ("total", total.into()),
+                ("round", round.name.as_str().into()),
+                ("percent", format!("{percent:.0}").into()),
+            ]
+        )
+    );
+
+    if let Some(to) = emails {
+        let mut contacts = Vec::new();
+        for team in teams
+            .iter()
+            .filter(|team| missing_sources.contains(&team.url))
+        {
+            for speaker in &team.speakers {
+                let speaker_json = serde_json::to_value(speaker).unwrap();
+                contacts.push(Contact {
+                    name: speaker.name.to_string(),
+                    role: "speaker".to_string(),
+                    email: email(&speaker_json),
+                    team: team.long_name.clone(),
+                    institution: institution_name(team.institution.as_ref()),
+                    private_url: private_url(&auth, &speaker_json),
+                });
+            }
+        }
+        for judge in judges
+            .iter()
+            .filter(|judge| missing_sources.contains(&judge.url))
+        {
+            let judge_json = serde_json::to_value(judge).unwrap();
+            contacts.push(Contact {
+                name: judge.name.clone(),
+                role: "judge".to_string(),
+                email: email(&judge_json),
+                team: String::new(),
+                institution: institution_name(judge.institution.as_ref()),
+                private_url: private_url(&auth, &judge_json),
+            });
+        }
+
+        let contacts = dedup_contacts(contacts);
+        write_contacts(&to, &contacts);
+        info!(
+            "Wrote the contact details of {} participant(s) to {to}",
+            contacts.len()
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_required_feedback() {
+    let teams = ["og".to_string(), "oo".to_string()];
+    let panel = ["p".to_string()];
+    let trainees = ["t".to_string()];
+
+    let minimal = required_feedback(&teams, Some("c"), &panel, &trainees, "minimal");
+    assert_eq!(minimal.len(), 4);
+    let with_p_on_c = required_feedback(&teams, Some("c"), &panel, &trainees, "with-p-on-c");
+    assert_eq!(with_p_on_c.len(), 5);
+    assert!(with_p_on_c.contains(&Required {
+        source: "p".to_string(),
+        target: Some("c".to_string()),
+    }));
+    let all_adjs = required_feedback(&teams, Some("c"), &panel, &trainees, "all-adjs");
+    assert_eq!(all_adjs.len(), 2 + 6);
+
+    let submitted = HashSet::from([
+        ("og".to_string(), "p".to_string()),
+        ("c".to_string(), "t".to_string()),
+    ]);
+    let missing = minimal
+        .iter()
+        .filter(|required| !is_submitted(required, &submitted))
+        .map(|required| required.source.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(missing, vec!["oo", "c"]);
+}
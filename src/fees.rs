@@ -0,0 +1,354 @@
+//! A local ledger of registration fee payments. Tabbycat does not track fees,
+//! so payments are recorded in a file in the working directory and joined
+//! against the teams and judges registered in Tabbycat to find out who is
+//! registered but has not paid.
+
+use std::{collections::HashMap, process::exit};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde::{Deserialize, Serialize};
+use tabbycat_api::types::{PerTournamentInstitution, Team};
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_institutions, get_judges, get_teams},
+    import::read_rows,
+    institution_aliases::InstitutionAliases,
+    names::names_match,
+    request_manager::RequestManager,
+};
+
+/// The name of the file (in the current working directory) in which the
+/// ledger is stored.
+pub const FEES_FILE: &str = "fees.toml";
+
+/// A single payment, made either by an institution (identified by its code)
+/// or by a team (identified by its API URL, since team names can change).
+/// Payments made by a team which belongs to an institution count towards the
+/// institution.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Payment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub institution: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    pub amount: f64,
+    /// e.g. a bank transfer reference. Payments with a reference which is
+    /// already in the ledger are not imported twice, but payments without a
+    /// reference are always imported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FeesLedger {
+    #[serde(default)]
+    payments: Vec<Payment>,
+}
+
+impl FeesLedger {
+    pub fn load() -> FeesLedger {
+        let text = match std::fs::read_to_string(FEES_FILE) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return FeesLedger::default();
+            }
+            Err(e) => {
+                error!("Could not read {FEES_FILE}: {e}");
+                exit(1)
+            }
+        };
+
+        match toml::from_str(&text) {
+            Ok(ledger) => ledger,
+            Err(e) => {
+                error!("Your {FEES_FILE} file is malformed: {e}");
+                exit(1)
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let text = toml::to_string_pretty(self).unwrap();
+        std::fs::write(FEES_FILE, text).unwrap();
+        info!("Saved {} payments to {FEES_FILE}", self.payments.len());
+    }
+
+    /// Adds a payment, unless one with the same reference has already been
+    /// recorded. Payments without a reference are always added, since two
+    /// payments of the same amount by the same institution can both be real.
+    /// Returns whether the payment was added.
+    pub fn add(&mut self, payment: Payment) -> bool {
+        let duplicate = payment.reference.is_some()
+            && self
+                .payments
+                .iter()
+                .any(|existing| existing.reference == payment.reference);
+        if !duplicate {
+            self.payments.push(payment);
+        }
+        !duplicate
+    }
+
+    /// The total amount paid by each institution and by each team without an
+    /// institution.
+    pub fn paid(&self, teams: &[Team], institutions: &[PerTournamentInstitution]) -> PaidAmounts {
+        let mut paid = PaidAmounts::default();
+        for payment in &self.payments {
+            let team = payment
+                .team
+                .as_ref()
+                .and_then(|url| teams.iter().find(|team| &team.url == url));
+            // a payment for a team which belongs to an institution counts
+            // towards the institution
+            let institution = match (&payment.institution, team) {
+                (Some(code), _) => institutions
+                    .iter()
+                    .find(|inst| names_match(inst.code.as_str(), code)),
+                (None, Some(team)) => institutions
+                    .iter()
+                    .find(|inst| team.institution.as_ref() == Some(&inst.url)),
+                (None, None) => None,
+            };
+            match (institution, team) {
+                (Some(inst), _) => {
+                    *paid.institutions.entry(inst.url.clone()).or_default() += payment.amount
+                }
+                (None, Some(team)) => {
+                    *paid.teams.entry(team.url.clone()).or_default() += payment.amount
+                }
+                (None, None) => warn!(
+                    "The payment of {} in {FEES_FILE} is for an institution or team which \
+                    no longer exists",
+                    payment.amount
+                ),
+            }
+        }
+        paid
+    }
+}
+
+/// The amounts recorded in the ledger, by the URL of the institution or team
+/// which paid them.
+#[derive(Debug, Default)]
+pub struct PaidAmounts {
+    pub institutions: HashMap<String, f64>,
+    /// Payments by teams which do not belong to an institution.
+    pub teams: HashMap<String, f64>,
+}
+
+/// A row of the payments CSV file. Each row should have either an
+/// `institution` (name, code or alias) or a `team`.
+#[derive(Deserialize, Debug)]
+struct PaymentRow {
+    #[serde(default)]
+    institution: Option<String>,
+    #[serde(default)]
+    team: Option<String>,
+    amount: f64,
+    #[serde(default)]
+    reference: Option<String>,
+    #[serde(default)]
+    date: Option<String>,
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+/// Records the payments in a CSV file (with columns `institution` or `team`,
+/// `amount` and optionally `reference` and `date`) in the ledger.
+pub async fn import_payments(auth: Auth, csv: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, institutions) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+    );
+    let aliases = InstitutionAliases::load();
+    let mut ledger = FeesLedger::load();
+
+    let mut added = 0;
+    let mut unreferenced = 0;
+    for row in read_rows::<PaymentRow>(&Some(csv.to_string())) {
+        let (institution, team) = match (non_empty(row.institution), non_empty(row.team)) {
+            (Some(name), _) => match aliases.find(&institutions, &name) {
+                Some(inst) => (Some(inst.code.as_str().to_string()), None),
+                None => {
+                    error!(
+                        "The payment of {} refers to institution {name:?}, which does not exist",
+                        row.amount
+                    );
+                    exit(1);
+                }
+            },
+            (None, Some(name)) => match teams.iter().find(|team| {
+                names_match(&team.short_name, &name) || names_match(&team.long_name, &name)
+            }) {
+                Some(team) => (None, Some(team.url.clone())),
+                None => {
+                    error!(
+                        "The payment of {} refers to team {name:?}, which does not exist",
+                        row.amount
+                    );
+                    exit(1);
+                }
+            },
+            (None, None) => {
+                error!(
+                    "The payment of {} has neither an institution nor a team",
+                    row.amount
+                );
+                exit(1);
+            }
+        };
+
+        let payment = Payment {
+            institution,
+            team,
+            amount: row.amount,
+            reference: non_empty(row.reference),
+            date: non_empty(row.date),
+        };
+        if payment.reference.is_none() {
+            unreferenced += 1;
+        }
+        if ledger.add(payment.clone()) {
+            added += 1;
+        } else if let Some(reference) = payment.reference {
+            warn!("Skipping payment with reference {reference}, which has already been recorded");
+        }
+    }
+
+    info!("Recorded {added} new payments");
+    if unreferenced > 0 {
+        warn!(
+            "{unreferenced} payment(s) have no reference, so are recorded again if {csv} is \
+            imported again (add a `reference` column to avoid this)"
+        );
+    }
+    ledger.save();
+}
+
+/// Shows, for each institution (and each team without an institution), the
+/// number of teams and judges registered, the amount paid and (if the fees
+/// are given) the balance outstanding.
+pub async fn fees_status(
+    auth: Auth,
+    team_fee: Option<f64>,
+    judge_fee: Option<f64>,
+    unpaid_only: bool,
+) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, judges, institutions) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+    );
+    let ledger = FeesLedger::load();
+
+    let paid = ledger.paid(&teams, &institutions);
+
+    let fees_given = team_fee.is_some() || judge_fee.is_some();
+    let due = |teams: usize, judges: usize| {
+        teams as f64 * team_fee.unwrap_or(0.0) + judges as f64 * judge_fee.unwrap_or(0.0)
+    };
+
+    // (name, teams, judges, paid)
+    let mut entries = Vec::new();
+    let mut institutions = institutions.iter().collect::<Vec<_>>();
+    institutions.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+    for inst in institutions {
+        let inst_teams = teams
+            .iter()
+            .filter(|team| team.institution.as_ref() == Some(&inst.url))
+            .count();
+        let inst_judges = judges
+            .iter()
+            .filter(|judge| judge.institution.as_ref() == Some(&inst.url))
+            .count();
+        if inst_teams == 0 && inst_judges == 0 {
+            continue;
+        }
+        entries.push((
+            format!("{} ({})", inst.name.as_str(), inst.code.as_str()),
+            inst_teams,
+            inst_judges,
+            paid.institutions.get(&inst.url).copied().unwrap_or(0.0),
+        ));
+    }
+    for team in teams.iter().filter(|team| team.institution.is_none()) {
+        entries.push((
+            team.short_name.clone(),
+            1,
+            0,
+            paid.teams.get(&team.url).copied().unwrap_or(0.0),
+        ));
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS);
+    let mut header = vec!["registered as", "teams", "judges", "paid"];
+    if fees_given {
+        header.extend(["due", "balance"]);
+    }
+    header.push("status");
+    table.set_header(header);
+
+    let mut unpaid = 0;
+    for (name, teams, judges, paid) in entries {
+        let is_unpaid = if fees_given {
+            paid < due(teams, judges)
+        } else {
+            paid <= 0.0
+        };
+        if is_unpaid {
+            unpaid += 1;
+        } else if unpaid_only {
+            continue;
+        }
+
+        let mut row = vec![
+            name,
+            teams.to_string(),
+            judges.to_string(),
+            format!("{paid:.2}"),
+        ];
+        if fees_given {
+            let due = due(teams, judges);
+            row.extend([format!("{due:.2}"), format!("{:.2}", due - paid)]);
+        }
+        row.push(if is_unpaid { "UNPAID" } else { "paid" }.to_string());
+        table.add_row(row);
+    }
+
+    println!("{table}");
+    println!("{unpaid} registered institution(s) or team(s) have not paid in full.");
+}
+
+#[cfg(test)]
+#[test]
+fn test_ledger_skips_duplicates() {
+    let payment = |reference: Option<&str>| Payment {
+        institution: Some("LSE".to_string()),
+        team: None,
+        amount: 50.0,
+        reference: reference.map(|r| r.to_string()),
+        date: None,
+    };
+
+    let mut ledger = FeesLedger::default();
+    assert!(ledger.add(payment(Some("TX1"))));
+    assert!(!ledger.add(payment(Some("TX1"))));
+    assert!(ledger.add(payment(None)));
+    // without a reference, payments are never duplicates
+    assert!(ledger.add(payment(None)));
+    assert_eq!(ledger.payments.len(), 3);
+}
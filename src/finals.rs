@@ -0,0 +1,232 @@
+use std::{collections::HashMap, fmt::Write};
+
+use serde_json::Value;
+use tabbycat_api::types::{RoundPairing, Team};
+use tracing::{info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{
+        get_ballots, get_break_categories, get_feedbacks, get_judges, get_rounds,
+        get_speaker_categories, get_teams, pairings_of_round,
+    },
+    request_manager::RequestManager,
+    standings::{get_speaker_standings, rank_standings},
+};
+
+/// The URLs of the teams which won a debate, according to its confirmed
+/// ballot. When there is one sheet per adjudicator, a team wins if it won on
+/// a majority of the sheets.
+pub fn debate_winners(ballot: &Value, pairing: &RoundPairing) -> Vec<String> {
+    let sheets = ballot["result"]["sheets"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let mut wins: HashMap<String, usize> = HashMap::new();
+    for sheet in &sheets {
+        for team in sheet["teams"].as_array().into_iter().flatten() {
+            if team["win"].as_bool() != Some(true) {
+                continue;
+            }
+            let url = team["team"]
+                .as_str()
+                .map(|url| url.to_string())
+                .or_else(|| {
+                    pairing
+                        .teams
+                        .iter()
+                        .find(|debate_team| {
+                            serde_json::to_value(&debate_team.side).ok().as_ref()
+                                == Some(&team["side"])
+                        })
+                        .map(|debate_team| debate_team.team.clone())
+                });
+            if let Some(url) = url {
+                *wins.entry(url).or_default() += 1;
+            }
+        }
+    }
+
+    let mut winners = wins
+        .into_iter()
+        .filter(|(_, wins)| *wins * 2 > sheets.len())
+        .map(|(url, _)| url)
+        .collect::<Vec<_>>();
+    winners.sort();
+    winners
+}
+
+fn team_list(urls: &[String], teams: &[Team]) -> String {
+    urls.iter()
+        .filter_map(|url| teams.iter().find(|team| &team.url == url))
+        .map(|team| team.long_name.clone())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Produces a Markdown summary for the closing ceremony: the champions,
+/// finalists and semifinalists of each break category, the top `top`
+/// speakers (overall and in each speaker category) and the `top` judges with
+/// the best average feedback. Written to `out` if given, and otherwise to
+/// standard output.
+pub async fn finals_report(auth: Auth, top: usize, out: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (rounds, teams, judges, break_cats, speaker_cats, standings, feedbacks) = tokio::join!(
+        get_rounds(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_break_categories(&auth, manager.clone()),
+        get_speaker_categories(&auth, manager.clone()),
+        get_speaker_standings(&auth, manager.clone()),
+        get_feedbacks(&auth, manager.clone()),
+    );
+
+    let mut md = String::new();
+    writeln!(md, "# Results").unwrap();
+
+    for category in &break_cats {
+        let mut elims = rounds
+            .iter()
+            .filter(|round| {
+                let round = serde_json::to_value(round).unwrap();
+                round["stage"].as_str() == Some("E")
+                    && round["break_category"].as_str() == Some(category.url.as_str())
+            })
+            .collect::<Vec<_>>();
+        elims.sort_by_key(|round| std::cmp::Reverse(round.seq));
+        if elims.is_empty() {
+            continue;
+        }
+
+        writeln!(md, "\n## {}\n", category.name.as_str()).unwrap();
+
+        // the teams which are already listed (as champions or finalists)
+        let mut listed: Vec<String> = Vec::new();
+        // the final, followed by the semifinal
+        for (i, round) in elims.iter().take(2).enumerate() {
+            let mut winners = Vec::new();
+            let mut losers = Vec::new();
+            for pairing in pairings_of_round(&auth, round, manager.clone()).await {
+                let ballot = get_ballots(&auth, &pairing, manager.clone())
+                    .await
+                    .into_iter()
+                    .find(|ballot| ballot["confirmed"].as_bool() == Some(true));
+                let Some(ballot) = ballot else {
+                    warn!(
+                        "{} has no confirmed ballot for debate {}",
+                        round.name.as_str(),
+                        pairing.id
+                    );
+                    continue;
+                };
+                let debate_winners = debate_winners(&ballot, &pairing);
+                for debate_team in &pairing.teams {
+                    if debate_winners.contains(&debate_team.team) {
+                        winners.push(debate_team.team.clone());
+                    } else {
+                        losers.push(debate_team.team.clone());
+                    }
+                }
+            }
+            losers.retain(|team| !listed.contains(team));
+
+            if i == 0 {
+                writeln!(md, "- **Champions:** {}", team_list(&winners, &teams)).unwrap();
+                writeln!(md, "- **Finalists:** {}", team_list(&losers, &teams)).unwrap();
+                listed.extend(winners);
+            } else {
+                writeln!(md, "- **Semifinalists:** {}", team_list(&losers, &teams)).unwrap();
+            }
+            listed.extend(losers);
+        }
+    }
+
+    let speaker_of = |url: &str| {
+        teams.iter().find_map(|team| {
+            team.speakers
+                .iter()
+                .find(|speaker| speaker.url == url)
+                .map(|speaker| (speaker, team))
+        })
+    };
+    let mut speaker_sections = vec![("Top speakers".to_string(), None)];
+    speaker_sections.extend(speaker_cats.iter().map(|category| {
+        (
+            format!("Top {} speakers", category.name.as_str()),
+            Some(category.url.clone()),
+        )
+    }));
+    for (title, category) in speaker_sections {
+        let standings = standings
+            .iter()
+            .filter(|standing| {
+                let Some((speaker, _)) = standing.speaker.as_deref().and_then(speaker_of) else {
+                    return false;
+                };
+                category
+                    .as_ref()
+                    .map(|category| speaker.categories.contains(category))
+                    .unwrap_or(true)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if standings.is_empty() {
+            continue;
+        }
+
+        writeln!(md, "\n## {title}\n").unwrap();
+        for (standing, (rank, tied)) in standings.iter().zip(rank_standings(&standings)) {
+            if rank > top {
+                break;
+            }
+            let (speaker, team) = speaker_of(standing.speaker.as_deref().unwrap()).unwrap();
+            let total = standing
+                .metrics
+                .first()
+                .and_then(|metric| metric.value)
+                .map(|value| format!(" ({value})"))
+                .unwrap_or_default();
+            writeln!(
+                md,
+                "- **{rank}{}** {} ({}){total}",
+                if tied { "=" } else { "" },
+                speaker.name,
+                team.short_name
+            )
+            .unwrap();
+        }
+    }
+
+    let mut scores: HashMap<&str, Vec<f64>> = HashMap::new();
+    for feedback in &feedbacks {
+        if let Some(score) = serde_json::to_value(feedback).unwrap()["score"].as_f64() {
+            scores.entry(&feedback.adjudicator).or_default().push(score);
+        }
+    }
+    let mut averages = judges
+        .iter()
+        .filter_map(|judge| {
+            let scores = scores.get(judge.url.as_str())?;
+            Some((
+                judge.name.clone(),
+                scores.iter().sum::<f64>() / scores.len() as f64,
+            ))
+        })
+        .collect::<Vec<_>>();
+    averages.sort_by(|a, b| b.1.total_cmp(&a.1));
+    if !averages.is_empty() {
+        writeln!(md, "\n## Best-rated judges\n").unwrap();
+        for (i, (name, average)) in averages.iter().take(top).enumerate() {
+            writeln!(md, "- **{}** {name} ({average:.2})", i + 1).unwrap();
+        }
+    }
+
+    match out {
+        Some(out) => {
+            std::fs::write(&out, md).unwrap();
+            info!("Saved the results summary to {out}");
+        }
+        None => print!("{md}"),
+    }
+}
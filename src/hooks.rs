@@ -0,0 +1,245 @@
+use std::{
+    io::Write,
+    process::{Command as Shell, Stdio, exit},
+};
+
+use chrono::Local;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{error, info, warn};
+
+use crate::{
+    AvailabilityCommand, Command, DrawCommand, JudgesCommand, LogCommand, RoomsCommand,
+    RoundCommand, ScheduleCommand,
+    audit_log::{self, AuditEntry},
+};
+
+/// The name of the file (in the current working directory) from which the
+/// hooks are read.
+pub const HOOKS_FILE: &str = "hooks.toml";
+
+/// Shell commands which are run before and after commands which change data
+/// on Tabbycat, read from a `hooks.toml` file in the working directory (only
+/// when the CLI is run with `--hooks`). For example
+///
+/// ```toml
+/// post_import = "./notify.sh"
+/// post_draw_edit = "cat >> draw_edits.jsonl"
+/// post_change = "./sync.sh"
+/// ```
+///
+/// Each command receives a JSON summary on standard input: the name of the
+/// hook, the command line, the time, and (for `post_` hooks) the requests
+/// which were made, as recorded in the audit log. If a `pre_` hook fails, the
+/// command is not run.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    pub pre_import: Option<String>,
+    pub post_import: Option<String>,
+    pub pre_draw_edit: Option<String>,
+    pub post_draw_edit: Option<String>,
+    pub pre_change: Option<String>,
+    pub post_change: Option<String>,
+}
+
+/// The kinds of command which hooks can be attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// `tabbycat import`.
+    Import,
+    /// Any command which changes the draw or the allocation of a round, or
+    /// releases it.
+    DrawEdit,
+    /// Any other command which changes data on Tabbycat.
+    Change,
+}
+
+impl HookEvent {
+    /// The event which `command` triggers, if any.
+    pub fn of(command: &Command) -> Option<HookEvent> {
+        match command {
+            Command::Import(_) => Some(HookEvent::Import),
+            Command::DrawSwap { .. }
+            | Command::AddJudge { .. }
+            | Command::RemoveJudge { .. }
+            | Command::RestoreAllocs { .. }
+            | Command::Draw(
+                DrawCommand::SetSide { .. }
+                | DrawCommand::SetImportance { .. }
+                | DrawCommand::SwapChairs { .. }
+                | DrawCommand::Flag { .. }
+                | DrawCommand::Unflag { .. }
+                | DrawCommand::Release { .. },
+            )
+            | Command::Round(RoundCommand::Release { .. })
+            | Command::Rooms(RoomsCommand::AllocateCategories { .. }) => Some(HookEvent::DrawEdit),
+            Command::MakeSensibleConflicts
+            | Command::ClearRoomUrls
+            | Command::ComputeBreakEligibility { .. }
+            | Command::Clash { .. }
+            | Command::Log(LogCommand::Revert { .. })
+            | Command::Judges(
+                JudgesCommand::MarkBreaking { .. } | JudgesCommand::SetAdjCore { .. },
+            )
+            | Command::Rooms(
+                RoomsCommand::Prioritize { .. }
+                | RoomsCommand::Generate { .. }
+                | RoomsCommand::Provision { .. }
+                | RoomsCommand::Share { .. },
+            )
+            | Command::Availability(AvailabilityCommand::Set(_))
+            | Command::Schedule(ScheduleCommand::Set { .. }) => Some(HookEvent::Change),
+            Command::Api { method, .. } if !method.eq_ignore_ascii_case("GET") => {
+                Some(HookEvent::Change)
+            }
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::Import => "import",
+            HookEvent::DrawEdit => "draw_edit",
+            HookEvent::Change => "change",
+        }
+    }
+}
+
+impl Hooks {
+    /// Loads `hooks.toml` from the working directory, or returns no hooks if
+    /// the file does not exist or hooks are not `enabled` (with `--hooks`), as
+    /// the file could have been put there by someone else.
+    pub fn load(enabled: bool) -> Hooks {
+        if !enabled {
+            if std::fs::exists(HOOKS_FILE).unwrap_or(false) {
+                warn!("Not running the hooks in {HOOKS_FILE}, as --hooks was not given");
+            }
+            return Hooks::default();
+        }
+
+        let text = match std::fs::read_to_string(HOOKS_FILE) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Hooks::default();
+            }
+            Err(e) => {
+                error!("Could not read {HOOKS_FILE}: {e}");
+                exit(1)
+            }
+        };
+
+        match toml::from_str(&text) {
+            Ok(hooks) => {
+                info!("Using the hooks in {HOOKS_FILE}");
+                hooks
+            }
+            Err(e) => {
+                error!("Your {HOOKS_FILE} file is malformed: {e}");
+                exit(1)
+            }
+        }
+    }
+
+    fn command(&self, pre: bool, event: HookEvent) -> Option<&str> {
+        match (pre, event) {
+            (true, HookEvent::Import) => self.pre_import.as_deref(),
+            (false, HookEvent::Import) => self.post_import.as_deref(),
+            (true, HookEvent::DrawEdit) => self.pre_draw_edit.as_deref(),
+            (false, HookEvent::DrawEdit) => self.post_draw_edit.as_deref(),
+            (true, HookEvent::Change) => self.pre_change.as_deref(),
+            (false, HookEvent::Change) => self.post_change.as_deref(),
+        }
+    }
+
+    /// Whether there is any hook for `event`.
+    pub fn any(&self, event: HookEvent) -> bool {
+        self.command(true, event).is_some() || self.command(false, event).is_some()
+    }
+
+    /// Runs the `pre_` hook for `event` (if there is one), exiting if it fails.
+    pub fn before(&self, event: HookEvent) {
+        let Some(command) = self.command(true, event) else {
+            return;
+        };
+        let hook = format!("pre_{}", event.name());
+        if !run(command, &summary(&hook, None)) {
+            error!("The {hook} hook failed, so nothing has been changed");
+            exit(1);
+        }
+    }
+
+    /// Runs the `post_` hook for `event` (if there is one), with the requests
+    /// which the command made. Failures are reported, but are not fatal (as
+    /// the changes have already been made).
+    pub fn after(&self, event: HookEvent, requests: &[AuditEntry]) {
+        let Some(command) = self.command(false, event) else {
+            return;
+        };
+        let hook = format!("post_{}", event.name());
+        if !run(command, &summary(&hook, Some(requests))) {
+            warn!("The {hook} hook failed");
+        }
+    }
+}
+
+fn summary(hook: &str, requests: Option<&[AuditEntry]>) -> Value {
+    let mut summary = json!({
+        "hook": hook,
+        "command": audit_log::command_line(),
+        "timestamp": Local::now().to_rfc3339(),
+    });
+    if let Some(requests) = requests {
+        summary["requests"] = serde_json::to_value(requests).unwrap();
+    }
+    summary
+}
+
+/// Runs `command` with the shell, writing `input` to its standard input.
+/// Returns whether it succeeded.
+fn run(command: &str, input: &Value) -> bool {
+    let child = Shell::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Could not run hook `{command}`: {e}");
+            return false;
+        }
+    };
+
+    // the hook may not read its input, in which case writing fails
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "{input}");
+    }
+    match child.wait() {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            warn!("Hook `{command}` exited with {status}");
+            false
+        }
+        Err(e) => {
+            warn!("Could not run hook `{command}`: {e}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_hooks() {
+    let hooks: Hooks = toml::from_str(r#"post_draw_edit = "cat > /dev/null""#).unwrap();
+    assert!(hooks.any(HookEvent::DrawEdit));
+    assert!(!hooks.any(HookEvent::Import));
+    assert!(!hooks.any(HookEvent::Change));
+    assert_eq!(hooks.command(true, HookEvent::DrawEdit), None);
+
+    assert!(run(
+        "cat > /dev/null",
+        &summary("post_draw_edit", Some(&[]))
+    ));
+    assert!(!run("exit 3", &summary("pre_import", None)));
+}
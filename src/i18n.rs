@@ -0,0 +1,150 @@
+use std::sync::OnceLock;
+
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue, concurrent::FluentBundle};
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+/// The languages the CLI's messages have been translated into, along with
+/// their translations (see `locales/en.ftl`, which lists every message).
+const LANGUAGES: [(&str, &str); 3] = [
+    ("en", include_str!("../locales/en.ftl")),
+    ("es", include_str!("../locales/es.ftl")),
+    ("fr", include_str!("../locales/fr.ftl")),
+];
+
+struct Localiser {
+    bundle: FluentBundle<FluentResource>,
+    /// English, which is used for any message the chosen language is missing.
+    fallback: FluentBundle<FluentResource>,
+}
+
+static LOCALISER: OnceLock<Localiser> = OnceLock::new();
+
+fn bundle(code: &str) -> FluentBundle<FluentResource> {
+    let (_, source) = LANGUAGES
+        .iter()
+        .find(|(language, _)| *language == code)
+        .expect("unsupported language");
+    let language: LanguageIdentifier = code.parse().unwrap();
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // the Unicode isolation marks which Fluent places around arguments show up
+    // as junk in most terminals
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(source.to_string())
+        .unwrap_or_else(|_| panic!("locales/{code}.ftl is malformed"));
+    bundle
+        .add_resource(resource)
+        .unwrap_or_else(|_| panic!("locales/{code}.ftl defines a message twice"));
+    bundle
+}
+
+impl Localiser {
+    fn new(code: &str) -> Localiser {
+        Localiser {
+            bundle: bundle(code),
+            fallback: bundle("en"),
+        }
+    }
+
+    fn format(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        [&self.bundle, &self.fallback]
+            .into_iter()
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(id)?.value()?;
+                let mut errors = Vec::new();
+                Some(
+                    bundle
+                        .format_pattern(pattern, args, &mut errors)
+                        .to_string(),
+                )
+            })
+            .unwrap_or_else(|| id.to_string())
+    }
+}
+
+/// Chooses the language to show messages in: `requested` (from `--lang`) if
+/// given, otherwise the first of `env` (the values of `TABBYCAT_LANG` and
+/// `LANG`, e.g. `es_ES.UTF-8`) which has been translated, otherwise English.
+fn choose_language(requested: Option<&str>, env: &[Option<String>]) -> &'static str {
+    let supported = |lang: &str| {
+        let code = lang.split(['_', '-', '.']).next().unwrap_or_default();
+        LANGUAGES
+            .iter()
+            .map(|(language, _)| *language)
+            .find(|language| language.eq_ignore_ascii_case(code))
+    };
+
+    if let Some(requested) = requested {
+        return supported(requested).unwrap_or_else(|| {
+            warn!("No translation into {requested} is available, so English will be used");
+            "en"
+        });
+    }
+    env.iter()
+        .flatten()
+        .find_map(|lang| supported(lang))
+        .unwrap_or("en")
+}
+
+/// Sets the language of the CLI's messages (see [`choose_language`]). If this
+/// is not called, messages are shown in English.
+pub fn init(requested: Option<&str>) {
+    let code = choose_language(
+        requested,
+        &[
+            std::env::var("TABBYCAT_LANG").ok(),
+            std::env::var("LANG").ok(),
+        ],
+    );
+    let _ = LOCALISER.set(Localiser::new(code));
+}
+
+fn localiser() -> &'static Localiser {
+    LOCALISER.get_or_init(|| Localiser::new("en"))
+}
+
+/// The message `id` in the chosen language.
+pub fn tr(id: &str) -> String {
+    localiser().format(id, None)
+}
+
+/// The message `id` in the chosen language, filling in its arguments.
+pub fn tr_args<'a>(id: &str, args: impl IntoIterator<Item = (&'a str, FluentValue<'a>)>) -> String {
+    let args = args.into_iter().collect::<FluentArgs>();
+    localiser().format(id, Some(&args))
+}
+
+#[cfg(test)]
+#[test]
+fn test_i18n() {
+    assert_eq!(choose_language(Some("fr"), &[]), "fr");
+    assert_eq!(choose_language(Some("de"), &[]), "en");
+    assert_eq!(
+        choose_language(None, &[None, Some("es_ES.UTF-8".to_string())]),
+        "es"
+    );
+    assert_eq!(choose_language(None, &[Some("C".to_string())]), "en");
+
+    // every translated message should also exist in English
+    let english = Localiser::new("en");
+    for (code, source) in LANGUAGES {
+        for id in source
+            .lines()
+            .filter_map(|line| line.split_once(" = ").map(|(id, _)| id))
+            .filter(|id| !id.starts_with([' ', '#']))
+        {
+            assert!(
+                english.fallback.has_message(id),
+                "{id} (in locales/{code}.ftl) is not in locales/en.ftl"
+            );
+        }
+    }
+
+    let spanish = Localiser::new("es");
+    assert_eq!(spanish.format("room", None), "Sala");
+    let args = [("id", FluentValue::from(12))]
+        .into_iter()
+        .collect::<FluentArgs>();
+    assert_eq!(spanish.format("debate", Some(&args)), "Debate 12");
+    assert_eq!(spanish.format("no-such-message", None), "no-such-message");
+}
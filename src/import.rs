@@ -6,28 +6,56 @@ use std::{
 use tokio::task::JoinSet;
 
 use itertools::Itertools;
+use rand::Rng;
 use serde::{
     Deserialize, Deserializer,
-    de::{self, Unexpected},
+    de::{self, DeserializeOwned, Unexpected},
 };
 use serde_json::json;
-use tabbycat_api::types::{BreakCategory, SpeakerCategory, Team};
-use tracing::{Instrument, Level, Span, debug, error, info, span};
+use tabbycat_api::types::{BreakCategory, DebateAdjudicator, SpeakerCategory, Team};
+use tracing::{Instrument, Level, Span, debug, error, info, span, warn};
 
 use crate::{
     Auth, Import,
-    api_utils::{get_institutions, get_judges, get_rounds, get_teams},
-    merge, open_csv_file,
+    api_utils::{
+        get_institutions, get_judges, get_preference, get_round, get_rounds, get_teams, get_venues,
+        pairing_in_room, pairings_of_round, set_availability,
+    },
+    archive::archive_csvs,
+    availability::is_available,
+    check_stdin_paths,
+    clashes_csv::{self, Severity, SoftClashes, read_clashes},
+    delta_import::{self, DeltaImport},
+    dispatch_req::json_of_resp,
+    import_config::ImportConfig,
+    import_errors::{self, OnError, RowGate},
+    import_plan::{self, ExistingEntities, ImportPlan, ImportStep},
+    institution_aliases::InstitutionAliases,
+    institution_codes, merge,
+    names::names_match,
+    open_csv_file,
+    panel_policy::PanelPolicy,
+    preflight::{
+        PreflightReport, check_emoji, check_speaker_counts, check_team_names, check_url_keys,
+        get_emoji_choices, read_denylist, resolve_name_collisions,
+    },
     request_manager::RequestManager,
+    schedule::round_start,
+    venue_metadata::{VENUE_METADATA_FILE, VenueMetadataStore},
 };
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct InstitutionRow {
     pub region: Option<String>,
-    // TODO: warn when this is >20 characters (Tabbycat currently applies
-    // this restriction) to aid with debugging
+    /// Generated (see [`institution_codes::assign_codes`]) if it is missing or
+    /// longer than Tabbycat allows.
+    #[serde(default)]
     pub short_code: String,
     pub full_name: String,
+    /// Alternative names for the institution, which can be used to refer to
+    /// it in the teams and judges CSV files.
+    #[serde(deserialize_with = "tags_deserialize", default = "Vec::new")]
+    pub aliases: Vec<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -38,6 +66,14 @@ pub struct RoomRow {
     pub barcode: Option<String>,
     pub name: String,
     pub priority: i64,
+    /// The rounds the room can be used for (in the same format as the
+    /// `availability` column of the judges CSV file), with
+    /// `--set-availability`.
+    #[serde(deserialize_with = "tags_deserialize", default = "Vec::new")]
+    pub availability: Vec<String>,
+    /// Not stored on Tabbycat, but in the local venue metadata store (see
+    /// [`VenueMetadataStore`]).
+    pub capacity: Option<u32>,
 }
 
 fn ret_false() -> bool {
@@ -56,7 +92,7 @@ where
         .collect())
 }
 
-fn bool_from_str<'de, D>(deserializer: D) -> Result<bool, D::Error>
+pub fn bool_from_str<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -70,6 +106,18 @@ where
     }
 }
 
+/// Like [`bool_from_str`], but an empty cell is `None` (rather than `false`).
+pub fn optional_bool_from_str<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    bool_from_str(de::value::StringDeserializer::<D::Error>::new(value)).map(Some)
+}
+
 fn not_true() -> bool {
     false
 }
@@ -92,10 +140,68 @@ pub struct TeamRow {
     pub speakers: Vec<Speaker>,
 }
 
+/// Metadata for break categories which are created automatically while
+/// importing teams.
+#[derive(Deserialize, Debug, Clone)]
+pub struct BreakCategoryRow {
+    pub name: String,
+    pub slug: String,
+    pub break_size: i64,
+    #[serde(deserialize_with = "bool_from_str", default = "ret_false")]
+    pub is_general: bool,
+    pub priority: i64,
+    pub limit: Option<i64>,
+}
+
+/// A speaker category which should be created before teams are imported.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpeakerCategoryRow {
+    pub name: String,
+    pub slug: String,
+    pub seq: Option<i64>,
+    #[serde(deserialize_with = "bool_from_str", default = "ret_false")]
+    pub public: bool,
+}
+
+/// The teams debating in a single room. Two-team formats should use the `aff`
+/// and `neg` columns (or `prop`/`opp`), and British Parliamentary should use
+/// `og`, `oo`, `cg` and `co`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DrawRow {
+    /// The name of the room.
+    pub room: String,
+    #[serde(alias = "og", alias = "prop", alias = "gov")]
+    pub aff: Option<String>,
+    #[serde(alias = "oo", alias = "opp")]
+    pub neg: Option<String>,
+    pub cg: Option<String>,
+    pub co: Option<String>,
+}
+
+/// The adjudicators allocated to a single room.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PanelRow {
+    /// Either the name of the room, or the ID of the debate.
+    pub room: String,
+    pub chair: Option<String>,
+    #[serde(deserialize_with = "tags_deserialize", default = "Vec::new")]
+    pub panellists: Vec<String>,
+    #[serde(deserialize_with = "tags_deserialize", default = "Vec::new")]
+    pub trainees: Vec<String>,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Clash {
     pub object_1: String,
     pub object_2: String,
+    /// Tabbycat treats every clash as hard, so soft clashes are only
+    /// recorded locally (see [`SoftClashes`]).
+    #[serde(default)]
+    pub severity: Severity,
+    /// An optional comment, which is not imported (e.g. the reason given by
+    /// `tabbycat clashes infer`).
+    #[serde(default)]
+    pub comment: Option<String>,
 }
 
 fn deserialize_fields_to_vec<'de, D>(deserializer: D) -> Result<Vec<Speaker>, D::Error>
@@ -150,11 +256,13 @@ where
                     phone: map.get("phone").cloned(),
                     anonymous: map
                         .get("anonymous")
-                        .cloned()
-                        .map(|t| t.eq_ignore_ascii_case("true"))
-                        .unwrap_or(false),
+                        .filter(|t| !t.trim().is_empty())
+                        .map(|t| t.trim().eq_ignore_ascii_case("true")),
                     code_name: map.get("code_name").cloned(),
-                    url_key: map.get("url_key").cloned(),
+                    url_key: map
+                        .get("url_key")
+                        .map(|key| key.trim().to_string())
+                        .filter(|key| !key.is_empty()),
                     gender: map.get("gender").map(|gender| {
                         if gender.to_lowercase() == "male" {
                             "M"
@@ -180,7 +288,9 @@ pub struct Speaker {
     pub categories: Vec<String>,
     pub email: Option<String>,
     pub phone: Option<String>,
-    pub anonymous: bool,
+    /// Left empty, this is `None` (so that `--update-existing` does not
+    /// change it).
+    pub anonymous: Option<bool>,
     pub code_name: Option<String>,
     pub url_key: Option<String>,
     // todo: validate correct
@@ -195,28 +305,133 @@ pub struct JudgeRow {
     pub institution: Option<String>,
     #[serde(deserialize_with = "tags_deserialize", default = "Vec::new")]
     pub institution_clashes: Vec<String>,
+    /// The judge's region (defaults to the region of their institution). With
+    /// `--region-conflicts`, judges are clashed with every institution in
+    /// their region.
+    pub region: Option<String>,
     pub email: Option<String>,
-    #[serde(deserialize_with = "bool_from_str", default = "ret_false")]
-    pub is_ca: bool,
-    #[serde(deserialize_with = "bool_from_str", default = "ret_false")]
-    pub is_ia: bool,
+    /// This and the other flags are `None` if left empty (so that
+    /// `--update-existing` does not change them).
+    #[serde(default, deserialize_with = "optional_bool_from_str")]
+    pub is_ca: Option<bool>,
+    #[serde(default, deserialize_with = "optional_bool_from_str")]
+    pub is_ia: Option<bool>,
     pub base_score: Option<f64>,
     #[serde(deserialize_with = "tags_deserialize", default = "Vec::new")]
     pub availability: Vec<String>,
     pub gender: Option<String>,
+    // todo: validate length
+    pub pronoun: Option<String>,
+    pub phone: Option<String>,
+    #[serde(default, deserialize_with = "optional_bool_from_str")]
+    pub anonymous: Option<bool>,
+}
+
+/// Generates a random URL key (in the same format as those generated by
+/// Tabbycat) which is not already in `used`, and adds it to `used`.
+fn generate_url_key(used: &mut HashSet<String>) -> String {
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut rng = rand::rng();
+    loop {
+        let key = (0..8)
+            .map(|_| CHARS[rng.random_range(0..CHARS.len())] as char)
+            .collect::<String>();
+        if used.insert(key.clone()) {
+            return key;
+        }
+    }
+}
+
+/// Reads all the rows of a CSV file (if one was provided) into memory.
+pub fn read_rows<T: DeserializeOwned>(path: &Option<String>) -> Vec<T> {
+    open_csv_file(path.clone(), true)
+        .map(|mut csv| csv.deserialize().map(|row| row.unwrap()).collect())
+        .unwrap_or_default()
 }
 
 pub async fn do_import(auth: Auth, import: Import) {
+    let config = Arc::new(ImportConfig::load());
+    let import = Import {
+        use_institution_prefix: import.use_institution_prefix || config.use_institution_prefix,
+        ..import
+    };
+    // (the files made from the archive are deleted when this is dropped, at
+    // the end of the import)
+    let archive = import.from_archive.as_deref().map(archive_csvs);
+    let import = match &archive {
+        Some(csvs) => Import {
+            institutions_csv: Some(csvs.institutions.clone()),
+            judges_csv: Some(csvs.judges.clone()),
+            rooms: Some(csvs.rooms.clone()),
+            ..import
+        },
+        None => import,
+    };
+
+    check_stdin_paths(&[
+        import.institutions_csv.as_deref(),
+        import.teams_csv.as_deref(),
+        import.judges_csv.as_deref(),
+        import.clashes_csv.as_deref(),
+        import.rooms.as_deref(),
+        import.break_categories_csv.as_deref(),
+        import.speaker_categories_csv.as_deref(),
+        import.panels_csv.as_deref(),
+        import.draw_csv.as_deref(),
+    ]);
+
+    let on_error = OnError::parse(&import.on_error);
+    let mut delta = import.since_state.as_deref().map(DeltaImport::load);
+    let import = match &mut delta {
+        Some(delta) => Import {
+            institutions_csv: delta.filter("institutions", import.institutions_csv.clone(), true),
+            teams_csv: delta.filter("teams", import.teams_csv.clone(), true),
+            judges_csv: delta.filter("judges", import.judges_csv.clone(), true),
+            // the clashes file is rewritten with a header row, since the
+            // rows are read in whichever order its columns are in
+            clashes_csv: import.clashes_csv.clone().map(|path| {
+                let rows = read_clashes(Some(path.clone())).unwrap();
+                let header = csv::StringRecord::from(clashes_csv::HEADER.to_vec());
+                delta.filter_records("clashes", &path, Some(&header), &rows)
+            }),
+            rooms: delta.filter("rooms", import.rooms.clone(), true),
+            break_categories_csv: delta.filter(
+                "break_categories",
+                import.break_categories_csv.clone(),
+                true,
+            ),
+            speaker_categories_csv: delta.filter(
+                "speaker_categories",
+                import.speaker_categories_csv.clone(),
+                true,
+            ),
+            ..import
+        },
+        None => import,
+    };
+    let import = import_plan::check_files(import, on_error, delta.as_mut()).await;
+
     tracing::info!(
         "Running import with these parameters: overwrite={}",
         import.overwrite
     );
 
-    let institutions_csv = open_csv_file(import.institutions_csv.clone(), true);
-    let teams_csv = open_csv_file(import.teams_csv.clone(), true);
-    let judges_csv = open_csv_file(import.judges_csv.clone(), true);
-    let clashes_csv = open_csv_file(import.clashes_csv.clone(), false);
-    let rooms_csv = open_csv_file(import.rooms.clone(), true);
+    let mut institutions_csv = open_csv_file(import.institutions_csv.clone(), true);
+    let mut teams_csv = open_csv_file(import.teams_csv.clone(), true);
+    let mut judges_csv = open_csv_file(import.judges_csv.clone(), true);
+    let mut clashes_csv = read_clashes(import.clashes_csv.clone());
+    let mut rooms_csv = open_csv_file(import.rooms.clone(), true);
+    let mut speaker_categories_csv = open_csv_file(import.speaker_categories_csv.clone(), true);
+    let mut panels_csv = open_csv_file(import.panels_csv.clone(), true);
+
+    let mut draw_csv = open_csv_file(import.draw_csv.clone(), true);
+
+    if (panels_csv.is_some() || draw_csv.is_some()) && import.round.is_none() {
+        error!("Please specify the round the draw/panels are for with `--round`.");
+        delta_import::remove_temp_files();
+        exit(1);
+    }
+    let break_category_rows = Arc::new(read_rows::<BreakCategoryRow>(&import.break_categories_csv));
 
     let api_addr = format!("{}/api/v1", auth.tabbycat_url);
 
@@ -235,7 +450,7 @@ pub async fn do_import(auth: Auth, import: Import) {
                 })
                 .await;
 
-            resp.json().await.unwrap()
+            json_of_resp(resp).await
         };
 
         speaker_categories
@@ -253,7 +468,7 @@ pub async fn do_import(auth: Auth, import: Import) {
             })
             .await;
 
-        let break_categories: Vec<tabbycat_api::types::BreakCategory> = resp.json().await.unwrap();
+        let break_categories: Vec<tabbycat_api::types::BreakCategory> = json_of_resp(resp).await;
 
         break_categories
     };
@@ -277,7 +492,7 @@ pub async fn do_import(auth: Auth, import: Import) {
             panic!("Failed to fetch speakers");
         }
 
-        let speakers: Vec<tabbycat_api::types::Speaker> = resp.json().await.unwrap();
+        let speakers: Vec<tabbycat_api::types::Speaker> = json_of_resp(resp).await;
         speakers
     };
 
@@ -285,7 +500,14 @@ pub async fn do_import(auth: Auth, import: Import) {
 
     let rounds = get_rounds(&auth, request_manager.clone());
 
-    let (speaker_categories, break_categories, mut institutions, mut speakers, mut teams, rounds) = tokio::join!(
+    let (
+        mut speaker_categories,
+        mut break_categories,
+        mut institutions,
+        mut speakers,
+        mut teams,
+        rounds,
+    ) = tokio::join!(
         compute_speaker_categories,
         break_categories,
         institutions,
@@ -306,6 +528,163 @@ pub async fn do_import(auth: Auth, import: Import) {
     }
     let mut judges: Vec<tabbycat_api::types::Adjudicator> = resp.json().unwrap();
 
+    let mut institution_aliases = InstitutionAliases::load();
+
+    let mut plan = ImportPlan::read(&import);
+    let generated_codes = institution_codes::assign_codes(&mut plan.institutions, &institutions);
+    institution_codes::report(&generated_codes, import.institution_codes_csv.as_deref());
+    let venues = if plan.draw.is_empty() && plan.panels.is_empty() {
+        vec![]
+    } else {
+        get_venues(&auth, request_manager.clone()).await
+    };
+
+    let mut preflight = PreflightReport::default();
+
+    // the code of an institution which either exists or is in the
+    // institutions CSV file
+    let institution_code = |name: &str| {
+        institution_aliases
+            .find(&institutions, name)
+            .map(|inst| inst.code.as_str().to_string())
+            .or_else(|| {
+                let name = institution_aliases.resolve(name);
+                plan.institutions
+                    .iter()
+                    .find(|row| {
+                        names_match(&row.full_name, name)
+                            || names_match(&row.short_code, name)
+                            || row.aliases.iter().any(|alias| names_match(alias, name))
+                    })
+                    .map(|row| row.short_code.trim().to_string())
+            })
+    };
+    plan.check_references(
+        &ExistingEntities {
+            teams: &teams,
+            judges: &judges,
+            venues: &venues,
+        },
+        &institution_code,
+        &mut preflight,
+    );
+
+    let renamed_teams = resolve_name_collisions(
+        &mut plan.teams,
+        &institution_code,
+        import.suffix_colliding_names,
+        &mut preflight,
+    );
+    let renamed_teams = Arc::new(
+        renamed_teams
+            .into_iter()
+            .map(|i| {
+                let row = &plan.teams[i];
+                (i, (row.full_name.clone(), row.short_name.clone()))
+            })
+            .collect::<HashMap<_, _>>(),
+    );
+
+    let team_rows = &plan.teams;
+    let emoji_choices = if team_rows.iter().any(|row| row.emoji.is_some()) {
+        get_emoji_choices(&auth, request_manager.clone()).await
+    } else {
+        None
+    };
+    match &emoji_choices {
+        Some(choices) => check_emoji(
+            team_rows,
+            choices,
+            import.strip_invalid_emoji,
+            &mut preflight,
+        ),
+        None if team_rows.iter().any(|row| row.emoji.is_some()) => preflight.warn(
+            "Could not fetch the list of emoji accepted by Tabbycat, so team emoji \
+            have not been validated."
+                .to_string(),
+        ),
+        None => (),
+    }
+
+    let existing_url_keys = speakers
+        .iter()
+        .filter_map(|speaker| {
+            let key = speaker.url_key.as_ref()?;
+            Some((speaker.name.to_string(), key.as_str().to_string()))
+        })
+        .chain(judges.iter().filter_map(|judge| {
+            let key = serde_json::to_value(judge).unwrap()["url_key"]
+                .as_str()?
+                .to_string();
+            Some((judge.name.clone(), key))
+        }))
+        .collect::<Vec<_>>();
+    check_url_keys(team_rows, &existing_url_keys, &mut preflight);
+
+    if !team_rows.is_empty() {
+        let (substantive_speakers, teams_in_debate) = tokio::join!(
+            get_preference(
+                &auth,
+                "debate_rules__substantive_speakers",
+                request_manager.clone()
+            ),
+            get_preference(
+                &auth,
+                "debate_rules__teams_in_debate",
+                request_manager.clone()
+            ),
+        );
+        match substantive_speakers.as_u64() {
+            Some(expected) => check_speaker_counts(
+                team_rows,
+                expected as usize,
+                teams_in_debate.as_u64().unwrap_or(2) as usize,
+                &mut preflight,
+            ),
+            None => preflight.warn(
+                "Could not read the number of speakers per team from Tabbycat, so the \
+                number of speakers in each team has not been checked."
+                    .to_string(),
+            ),
+        }
+    }
+
+    let denylist = import
+        .name_denylist
+        .as_deref()
+        .map(read_denylist)
+        .unwrap_or_default();
+    check_team_names(
+        team_rows,
+        &institution_code,
+        import.use_institution_prefix,
+        &teams
+            .iter()
+            .map(|team| team.short_name.to_string())
+            .collect::<Vec<_>>(),
+        &denylist,
+        &mut preflight,
+    );
+
+    if !preflight.finish() {
+        delta_import::remove_temp_files();
+        exit(1);
+    }
+    plan.log();
+    let emoji_choices = Arc::new(emoji_choices);
+    let used_url_keys = Arc::new(tokio::sync::Mutex::new(
+        existing_url_keys
+            .into_iter()
+            .map(|(_, key)| key)
+            .chain(
+                team_rows
+                    .iter()
+                    .flat_map(|row| &row.speakers)
+                    .filter_map(|speaker| speaker.url_key.clone()),
+            )
+            .collect::<HashSet<_>>(),
+    ));
+
     let exists_some_draw = rounds.iter().any(
         |round| matches!(round.draw_status, Some(t) if t != tabbycat_api::types::DrawStatusEnum::N),
     );
@@ -314,6 +693,7 @@ pub async fn do_import(auth: Auth, import: Import) {
         // todo: is this check robust?
         if exists_some_draw {
             tracing::error!("Not deleting data when the tournament has started!");
+            delta_import::remove_temp_files();
             return;
         }
 
@@ -430,786 +810,1547 @@ pub async fn do_import(auth: Auth, import: Import) {
 
     // todo: confirm if a draw already exists
 
-    let institutions = if let Some(mut institutions_csv) = institutions_csv {
-        let headers = Arc::new(institutions_csv.headers().unwrap().clone());
-        let institutions_span = span!(Level::INFO, "importing institutions");
-        let _institutions_guard = institutions_span.enter();
-
-        let institutions = Arc::new(tokio::sync::Mutex::new(institutions));
-
-        // note: institutions need to be processed sequentially to avoid
-        // running into Tabbycat bugs (!)
-        for institution2import in institutions_csv.records() {
-            let api_addr = api_addr.clone();
-            let headers = headers.clone();
-            let request_manager = request_manager.clone();
-            let institutions = institutions.clone();
-            let institution2import = institution2import.unwrap();
-
-            let institution: InstitutionRow =
-                institution2import.deserialize(Some(&headers)).unwrap();
-
-            if !institutions.lock().await.iter().any(|cmp| {
-                cmp.name.as_str() == institution.full_name
-                    || cmp.code.as_str() == institution.short_code
-            }) {
-                let response = request_manager
-                    .clone()
-                    .send_request(|| {
-                        request_manager
-                            .client
-                            .post(format!("{api_addr}/institutions"))
-                            .json(&serde_json::json!({
-                                "region": institution.region,
-                                "name": institution.full_name,
-                                "code": institution.short_code
-                            }))
-                            .build()
-                            .unwrap()
-                    })
-                    .await;
-                if !response.status().is_success() {
-                    panic!("error: {}", response.text().await.unwrap());
-                }
-                let inst: tabbycat_api::types::PerTournamentInstitution =
-                    response.json().await.unwrap();
-                info!(
-                    "Institution {} added to Tabbycat, id is {}",
-                    inst.name.as_str(),
-                    inst.id
-                );
-                institutions.clone().lock().await.push(inst);
-            } else {
-                info!(
-                    "Institution {} already exists, not inserting",
-                    institution.full_name
-                );
-            }
-        }
+    // the regions given in the institutions CSV file, by short code
+    let mut csv_regions: HashMap<String, String> = HashMap::new();
+
+    // rows which were skipped because they refer to an institution which does
+    // not exist, as (description of the row, institution)
+    let unresolved_institutions = Arc::new(tokio::sync::Mutex::new(Vec::<(String, String)>::new()));
+
+    for step in plan.steps() {
+        match step {
+            ImportStep::Institutions => {
+                institutions = if let Some(mut institutions_csv) = institutions_csv.take() {
+                    let headers = Arc::new(institutions_csv.headers().unwrap().clone());
+                    let institutions_span = span!(Level::INFO, "importing institutions");
+                    let _institutions_guard = institutions_span.enter();
+
+                    let institutions = Arc::new(tokio::sync::Mutex::new(institutions));
+
+                    let mut aliases_changed = false;
+
+                    // note: institutions need to be processed sequentially to avoid
+                    // running into Tabbycat bugs (!)
+                    for institution2import in institutions_csv.records() {
+                        let api_addr = api_addr.clone();
+                        let headers = headers.clone();
+                        let request_manager = request_manager.clone();
+                        let institutions = institutions.clone();
+                        let institution2import = institution2import.unwrap();
+
+                        let mut institution: InstitutionRow =
+                            institution2import.deserialize(Some(&headers)).unwrap();
+                        if let Some(generated) = generated_codes
+                            .iter()
+                            .find(|generated| generated.full_name == institution.full_name)
+                        {
+                            institution.short_code = generated.code.clone();
+                        }
 
-        institutions.clone().lock().await.clone()
-    } else {
-        info!("No institutions were provided to import.");
-        institutions
-    };
+                        if let Some(region) =
+                            institution.region.as_ref().filter(|r| !r.trim().is_empty())
+                        {
+                            csv_regions.insert(institution.short_code.clone(), region.clone());
+                        }
 
-    if let Some(mut rooms_csv) = rooms_csv {
-        let rooms_span = span!(Level::INFO, "importing rooms");
-        let _rooms_guard = rooms_span.enter();
-        let headers = rooms_csv.headers().unwrap().clone();
+                        for alias in &institution.aliases {
+                            institution_aliases.add(alias, &institution.short_code);
+                            aliases_changed = true;
+                        }
 
-        let mut categories = HashMap::new();
+                        if !institutions.lock().await.iter().any(|cmp| {
+                            names_match(cmp.name.as_str(), &institution.full_name)
+                                || names_match(cmp.code.as_str(), &institution.short_code)
+                        }) {
+                            let response = request_manager
+                                .clone()
+                                .send_request(|| {
+                                    request_manager
+                                        .client
+                                        .post(format!("{api_addr}/institutions"))
+                                        .json(&serde_json::json!({
+                                            "region": institution.region,
+                                            "name": institution.full_name,
+                                            "code": institution.short_code
+                                        }))
+                                        .build()
+                                        .unwrap()
+                                })
+                                .await;
+                            if !response.status().is_success() {
+                                panic!("error: {}", response.text().await.unwrap());
+                            }
+                            let inst: tabbycat_api::types::PerTournamentInstitution =
+                                json_of_resp(response).await;
+                            info!(
+                                "Institution {} added to Tabbycat, id is {}",
+                                inst.name.as_str(),
+                                inst.id
+                            );
+                            institutions.clone().lock().await.push(inst);
+                        } else {
+                            info!(
+                                "Institution {} already exists, not inserting",
+                                institution.full_name
+                            );
+                        }
+                    }
 
-        tracing::info!("starting rooms import");
+                    if aliases_changed {
+                        institution_aliases.save();
+                    }
 
-        for room2import in rooms_csv.records() {
-            let single_room_span = span!(Level::INFO, "importing single room");
-            let _single_room_guard = single_room_span.enter();
+                    institutions.clone().lock().await.clone()
+                } else {
+                    info!("No institutions were provided to import.");
+                    institutions
+                };
+            }
+            ImportStep::Rooms => {
+                if let Some(mut rooms_csv) = rooms_csv.take() {
+                    let rooms_span = span!(Level::INFO, "importing rooms");
+                    let _rooms_guard = rooms_span.enter();
+                    let headers = rooms_csv.headers().unwrap().clone();
 
-            tracing::info!("adding room");
+                    let mut categories = HashMap::new();
+                    let mut venue_metadata = VenueMetadataStore::load();
+                    let mut capacities = 0;
 
-            let room2import = room2import.unwrap();
-            let room2import: RoomRow = room2import.deserialize(Some(&headers)).unwrap();
+                    tracing::info!("starting rooms import");
 
-            let res = request_manager
-                .send_request(|| {
-                    request_manager
-                        .client
-                        .post(format!(
-                            "{}/tournaments/{}/venues",
-                            api_addr, auth.tournament_slug
-                        ))
-                        .json(&json!({
-                            "categories": [],
-                            "name": room2import.name,
-                            "priority": room2import.priority
-                        }))
-                        .build()
-                        .unwrap()
-                })
-                .instrument(single_room_span.clone())
-                .await;
+                    for room2import in rooms_csv.records() {
+                        let single_room_span = span!(Level::INFO, "importing single room");
+                        let _single_room_guard = single_room_span.enter();
 
-            let room: tabbycat_api::types::Venue = res
-                .json()
-                .instrument(single_room_span.clone())
-                .await
-                .unwrap();
-
-            for cat in room2import.categories {
-                categories
-                    .entry(cat)
-                    .and_modify(|cat: &mut Vec<_>| {
-                        cat.push(room.url.clone());
-                    })
-                    .or_insert({
-                        let mut v = Vec::new();
-                        v.push(room.url.clone());
-                        v
-                    });
-            }
-        }
+                        tracing::info!("adding room");
 
-        for (key, values) in categories {
-            let res = request_manager
-                .send_request(|| {
-                    request_manager
-                        .client
-                        .post(format!(
-                            "{}/tournaments/{}/venue-categories",
-                            api_addr, auth.tournament_slug
-                        ))
-                        .json(&json!({
-                            "venues": values,
-                            "name": key,
-                            "display_in_venue_name": "P"
-                        }))
-                        .build()
-                        .unwrap()
-                })
-                .instrument(rooms_span.clone())
-                .await;
+                        let room2import = room2import.unwrap();
+                        let room2import: RoomRow = room2import.deserialize(Some(&headers)).unwrap();
 
-            if !res.status().is_success() {
-                error!(
-                    "Failed to create venue category '{}': status = {:?}, body = {}",
-                    key,
-                    res.status(),
-                    res.text()
-                        .await
-                        .unwrap_or_else(|_| "Unable to fetch response body".to_string())
-                );
-                panic!("Failed to create venue category");
-            }
-        }
-    };
+                        let res = request_manager
+                            .send_request(|| {
+                                request_manager
+                                    .client
+                                    .post(format!(
+                                        "{}/tournaments/{}/venues",
+                                        api_addr, auth.tournament_slug
+                                    ))
+                                    .json(&json!({
+                                        "categories": [],
+                                        "name": room2import.name,
+                                        "priority": room2import.priority
+                                    }))
+                                    .build()
+                                    .unwrap()
+                            })
+                            .instrument(single_room_span.clone())
+                            .await;
 
-    let judges = if let Some(mut judges_csv) = judges_csv {
-        let headers = Arc::new(judges_csv.headers().unwrap().clone());
-        let judges_span = span!(Level::INFO, "importing judges");
-        let _judges_guard = judges_span.enter();
-
-        let mut join_set = JoinSet::new();
-
-        let judges = Arc::new(tokio::sync::Mutex::new(judges.clone()));
-        let institutions = Arc::new(institutions.clone());
-        let rounds = Arc::new(rounds);
-
-        for judge2import in judges_csv.records() {
-            let api_addr = api_addr.clone();
-            let headers = headers.clone();
-            let request_manager = request_manager.clone();
-            let judges = judges.clone();
-            let institutions = institutions.clone();
-            let rounds = rounds.clone();
-            let auth = auth.clone();
-            let import = import.clone();
-
-            join_set.spawn(async move {
-                let judge2import = judge2import.unwrap();
-                let judge2import: JudgeRow = judge2import.deserialize(Some(&headers)).unwrap();
-
-                if !judges
-                    .lock()
-                    .await
-                    .iter()
-                    .any(|judge| judge.name == judge2import.name)
-                {
-                    let judge_inst_conflicts = institutions
-                        .iter()
-                        .filter(|inst_from_api| {
-                            judge2import
-                                .institution_clashes
-                                .iter()
-                                .any(|inst_judge_clashes| {
-                                    inst_from_api.name.as_str() == inst_judge_clashes
-                                        || inst_from_api.code.as_str() == inst_judge_clashes
-                                })
-                        })
-                        .map(|inst| inst.url.clone())
-                        .collect::<Vec<_>>();
+                        let room: tabbycat_api::types::Venue = res
+                            .json()
+                            .instrument(single_room_span.clone())
+                            .await
+                            .unwrap();
 
-                    // todo: have a debug mode which logs debug output to a file
+                        if let Some(capacity) = room2import.capacity {
+                            venue_metadata.entry(&room2import.name).capacity = Some(capacity);
+                            capacities += 1;
+                        }
 
-                    let inst_url = institutions
-                        .iter()
-                        .find(|api_inst| {
-                            Some(api_inst.name.as_str().to_string()) == judge2import.institution
-                                || Some(api_inst.code.as_str().to_string())
-                                    == judge2import.institution
-                        })
-                        .map(|inst| inst.url.clone());
-
-                    if judge2import.institution.is_some() {
-                        assert!(
-                            inst_url.is_some(),
-                            "error: {:?} {:?}",
-                            judge2import.institution,
-                            institutions
+                        // rooms without an `availability` column are left as they are
+                        if import.set_availability && !room2import.availability.is_empty() {
+                            import_availability(
+                                &auth,
+                                &format!("room {}", room2import.name),
+                                &room.url,
+                                &room2import.availability,
+                                &rounds,
+                                import.availability_default == "available",
+                                request_manager.clone(),
+                            )
+                            .instrument(single_room_span.clone())
+                            .await;
+                        }
+
+                        for cat in room2import.categories {
+                            categories
+                                .entry(cat)
+                                .and_modify(|cat: &mut Vec<_>| {
+                                    cat.push(room.url.clone());
+                                })
+                                .or_insert({
+                                    let mut v = Vec::new();
+                                    v.push(room.url.clone());
+                                    v
+                                });
+                        }
+                    }
+
+                    if capacities > 0 {
+                        venue_metadata.save();
+                        info!(
+                            "Recorded the capacity of {capacities} room(s) in {VENUE_METADATA_FILE}"
                         );
                     }
 
-                    let mut payload = serde_json::json!({
-                        "name": judge2import.name,
-                        "institution": inst_url,
-                        "institution_conflicts": judge_inst_conflicts,
-                        "email": judge2import.email,
-                        "team_conflicts": [],
-                        "adjudicator_conflicts": [],
-                        "independent": judge2import.is_ia,
-                        "adj_core": judge2import.is_ca
-                    });
+                    for (key, values) in categories {
+                        let res = request_manager
+                            .send_request(|| {
+                                request_manager
+                                    .client
+                                    .post(format!(
+                                        "{}/tournaments/{}/venue-categories",
+                                        api_addr, auth.tournament_slug
+                                    ))
+                                    .json(&json!({
+                                        "venues": values,
+                                        "name": key,
+                                        "display_in_venue_name": "P"
+                                    }))
+                                    .build()
+                                    .unwrap()
+                            })
+                            .instrument(rooms_span.clone())
+                            .await;
 
-                    if let Some(base_score) = judge2import.base_score {
-                        tracing::trace!("base score {base_score}");
-                        merge(&mut payload, &json!({"base_score": base_score}));
+                        if !res.status().is_success() {
+                            error!(
+                                "Failed to create venue category '{}': status = {:?}, body = {}",
+                                key,
+                                res.status(),
+                                res.text()
+                                    .await
+                                    .unwrap_or_else(|_| "Unable to fetch response body".to_string())
+                            );
+                            panic!("Failed to create venue category");
+                        }
                     }
+                };
+            }
+            ImportStep::Judges => {
+                judges = if let Some(mut judges_csv) = judges_csv.take() {
+                    let headers = Arc::new(judges_csv.headers().unwrap().clone());
+                    let institution_aliases = Arc::new(institution_aliases.clone());
+                    let judges_span = span!(Level::INFO, "importing judges");
+                    let _judges_guard = judges_span.enter();
+
+                    let mut join_set = JoinSet::new();
+                    let gate = RowGate::default();
+
+                    let judges = Arc::new(tokio::sync::Mutex::new(judges.clone()));
+                    let regions = Arc::new(institution_regions(&institutions, &csv_regions));
+                    let institutions = Arc::new(institutions.clone());
+                    let rounds = Arc::new(rounds);
+
+                    let rows = judges_csv
+                        .records()
+                        .map(|row| row.unwrap())
+                        .collect::<Vec<_>>();
+                    for (i, judge2import) in rows.iter().cloned().enumerate() {
+                        let api_addr = api_addr.clone();
+                        let headers = headers.clone();
+                        let request_manager = request_manager.clone();
+                        let judges = judges.clone();
+                        let institutions = institutions.clone();
+                        let regions = regions.clone();
+                        let institution_aliases = institution_aliases.clone();
+                        let unresolved_institutions = unresolved_institutions.clone();
+                        let rounds = rounds.clone();
+                        let auth = auth.clone();
+                        let import = import.clone();
+                        let config = config.clone();
+
+                        let task = async move {
+                            let judge2import: JudgeRow =
+                                judge2import.deserialize(Some(&headers)).unwrap();
+                            let availability = if judge2import.availability.is_empty() {
+                                config.availability.clone()
+                            } else {
+                                judge2import.availability.clone()
+                            };
 
-                    if let Some(gender) = judge2import.gender {
-                        tracing::trace!("gender {gender}");
-                        let gender = match gender.to_ascii_lowercase().as_str() {
-                            "male" => "M".to_string(),
-                            "female" => "F".to_string(),
-                            "other" => "O".to_string(),
-                            _ => gender,
-                        };
-                        tracing::trace!("rewritten as {gender}");
-                        merge(&mut payload, &json!({"gender": gender}));
-                    }
+                            if !judges
+                                .lock()
+                                .await
+                                .iter()
+                                .any(|judge| names_match(&judge.name, &judge2import.name))
+                            {
+                                let mut judge_inst_conflicts = judge2import
+                                    .institution_clashes
+                                    .iter()
+                                    .filter_map(|clash| {
+                                        institution_aliases.find(&institutions, clash)
+                                    })
+                                    .map(|inst| inst.url.clone())
+                                    .collect::<Vec<_>>();
+
+                                // todo: have a debug mode which logs debug output to a file
+
+                                let inst_url = judge2import
+                                    .institution
+                                    .as_ref()
+                                    .and_then(|inst| institution_aliases.find(&institutions, inst))
+                                    .map(|inst| inst.url.clone());
+
+                                if let Some(institution) = &judge2import.institution
+                                    && inst_url.is_none()
+                                {
+                                    error!(
+                                        "Judge {} belongs to institution {institution:?}, which \
+                                        does not exist; skipping this judge.",
+                                        judge2import.name
+                                    );
+                                    unresolved_institutions.lock().await.push((
+                                        format!("judge {}", judge2import.name),
+                                        institution.clone(),
+                                    ));
+                                    return Err(format!(
+                                        "the institution {institution:?} does not exist"
+                                    ));
+                                }
 
+                                if import.region_conflicts {
+                                    let region = judge2import.region.clone().or_else(|| {
+                                        let inst_url = inst_url.as_ref()?;
+                                        regions
+                                            .iter()
+                                            .find(|(url, _)| url == inst_url)
+                                            .map(|(_, region)| region.clone())
+                                    });
+                                    match region {
+                                        Some(region) => {
+                                            for url in institutions_in_region(&regions, &region) {
+                                                if !judge_inst_conflicts.contains(&url) {
+                                                    judge_inst_conflicts.push(url);
+                                                }
+                                            }
+                                        }
+                                        None => warn!(
+                                            "Judge {} has no region, so has not been clashed \
+                                            with the institutions in their region",
+                                            judge2import.name
+                                        ),
+                                    }
+                                }
 
-                    tracing::trace!("data for request is: {payload:?}");
+                                let mut payload = serde_json::json!({
+                                    "name": judge2import.name,
+                                    "institution": inst_url,
+                                    "institution_conflicts": judge_inst_conflicts,
+                                    "email": judge2import.email,
+                                    "team_conflicts": [],
+                                    "adjudicator_conflicts": [],
+                                    "independent": judge2import.is_ia.unwrap_or(false),
+                                    "adj_core": judge2import.is_ca.unwrap_or(false),
+                                    "anonymous": judge2import.anonymous.unwrap_or(false)
+                                });
+
+                                if let Some(base_score) =
+                                    judge2import.base_score.or(config.base_score)
+                                {
+                                    tracing::trace!("base score {base_score}");
+                                    merge(&mut payload, &json!({"base_score": base_score}));
+                                }
 
-                    let resp = request_manager
-                        .send_request(|| {
-                            request_manager
-                                .client
-                                .post(format!(
-                                    "{api_addr}/tournaments/{}/adjudicators",
-                                    auth.tournament_slug
-                                ))
-                                .json(&payload)
-                                .build()
-                                .unwrap()
-                        })
-                        .await;
-                    if !resp.status().is_success() {
-                        error!("error");
-                        panic!("error {:?} {}", resp.status(), resp.text().await.unwrap());
-                    }
+                                if let Some(gender) = judge2import.gender {
+                                    tracing::trace!("gender {gender}");
+                                    let gender = normalise_gender(gender);
+                                    tracing::trace!("rewritten as {gender}");
+                                    merge(&mut payload, &json!({"gender": gender}));
+                                }
 
-                    let judge: tabbycat_api::types::Adjudicator = resp.json().await.unwrap();
-                    info!("Created judge {} with id {}", judge.name, judge.id);
-                    judges.lock().await.push(judge.clone());
+                                if let Some(pronoun) = judge2import.pronoun {
+                                    merge(&mut payload, &json!({"pronoun": pronoun}));
+                                }
 
-                    // TODO: there should be a way to opt-out of setting this (or
-                    // at least specify the default)
-                    if import.set_availability {
-                        let norm = judge2import
-                            .availability
-                            .iter()
-                            .map(|availability| availability.to_ascii_lowercase())
-                            .collect::<HashSet<_>>();
-                        for api_round in rounds.iter() {
-                            let (available, method, url) = if norm
-                                .contains(&api_round.abbreviation.to_ascii_lowercase())
-                                || norm.contains(&api_round.name.to_ascii_lowercase())
-                            {
-                                (
-                                    "available",
-                                    "PUT",
-                                    format!(
-                                        "{api_addr}/tournaments/{}/rounds/{}/availabilities",
-                                        auth.tournament_slug, api_round.seq
-                                    ),
-                                )
-                            } else {
-                                (
-                                    "unavailable",
-                                    "POST",
-                                    format!(
-                                        "{api_addr}/tournaments/{}/rounds/{}/availabilities",
-                                        auth.tournament_slug, api_round.seq
-                                    ),
-                                )
-                            };
+                                if let Some(phone) = judge2import.phone {
+                                    merge(&mut payload, &json!({"phone": phone}));
+                                }
 
-                            let resp = request_manager
-                                .send_request(|| {
-                                    let req = if method == "PUT" {
-                                        request_manager.client.put(&url)
-                                    } else {
-                                        request_manager.client.post(&url)
-                                    };
-                                    req.json(&json!([judge.url])).build().unwrap()
-                                })
-                                .await;
+                                tracing::trace!("data for request is: {payload:?}");
+
+                                let resp = request_manager
+                                    .send_request(|| {
+                                        request_manager
+                                            .client
+                                            .post(format!(
+                                                "{api_addr}/tournaments/{}/adjudicators",
+                                                auth.tournament_slug
+                                            ))
+                                            .json(&payload)
+                                            .build()
+                                            .unwrap()
+                                    })
+                                    .await;
+                                if !resp.status().is_success() {
+                                    error!("error");
+                                    panic!(
+                                        "error {:?} {}",
+                                        resp.status(),
+                                        resp.text().await.unwrap()
+                                    );
+                                }
 
-                            if !resp.status().is_success() {
-                                error!(
-                                    "Failed to mark judge {} as {available} for round {}: {} {}",
-                                    judge2import.name,
-                                    api_round.name.as_str(),
-                                    resp.status(),
-                                    resp.text().await.unwrap()
-                                );
-                                panic!("Failed to mark judge as {available}");
+                                let judge: tabbycat_api::types::Adjudicator =
+                                    json_of_resp(resp).await;
+                                info!("Created judge {} with id {}", judge.name, judge.id);
+                                judges.lock().await.push(judge.clone());
+
+                                if import.set_availability {
+                                    import_availability(
+                                        &auth,
+                                        &format!("judge {}", judge.name),
+                                        &judge.url,
+                                        &availability,
+                                        &rounds,
+                                        import.availability_default == "available",
+                                        request_manager.clone(),
+                                    )
+                                    .await;
+                                }
                             } else {
                                 info!(
-                                    "Marked judge {} as {available} for round {}",
-                                    judge2import.name,
-                                    api_round.name.as_str()
+                                    "Judge {} already exists, therefore not creating a record \
+                                    for this judge.",
+                                    judge2import.name
                                 );
+
+                                let judge = judges
+                                    .lock()
+                                    .await
+                                    .iter()
+                                    .find(|judge| names_match(&judge.name, &judge2import.name))
+                                    .cloned()
+                                    .unwrap();
+
+                                if import.update_existing {
+                                    let inst_url = judge2import
+                                        .institution
+                                        .as_ref()
+                                        .and_then(|inst| {
+                                            institution_aliases.find(&institutions, inst)
+                                        })
+                                        .map(|inst| inst.url.clone());
+                                    let fields = json!({
+                                        "institution": inst_url,
+                                        "email": judge2import.email,
+                                        "independent": judge2import.is_ia,
+                                        "adj_core": judge2import.is_ca,
+                                        "anonymous": judge2import.anonymous,
+                                        "base_score": judge2import.base_score.or(config.base_score),
+                                        "gender": judge2import.gender.clone().map(normalise_gender),
+                                        "pronoun": judge2import.pronoun,
+                                        "phone": judge2import.phone,
+                                    });
+                                    update_existing(
+                                        "judge",
+                                        &judge.name,
+                                        &judge.url,
+                                        &serde_json::to_value(&judge).unwrap(),
+                                        &fields,
+                                        request_manager.clone(),
+                                    )
+                                    .await;
+                                }
+
+                                if import.set_availability {
+                                    import_availability(
+                                        &auth,
+                                        &format!("judge {}", judge.name),
+                                        &judge.url,
+                                        &availability,
+                                        &rounds,
+                                        import.availability_default == "available",
+                                        request_manager.clone(),
+                                    )
+                                    .await;
+                                }
                             }
+                            Ok(())
                         }
+                        .instrument(judges_span.clone());
+                        join_set.spawn(import_errors::guard(i, gate.clone(), task));
                     }
-                } else {
-                    info!(
-                        "Judge {} already exists, therefore not creating a record \
-                        for this judge.",
-                        judge2import.name
-                    );
-                }
-            }.instrument(judges_span.clone()));
-        }
-
-        while let Some(result) = join_set.join_next().await {
-            if let Err(err) = result {
-                error!("Error occurred while importing a judge: {:?}", err);
-                panic!("Failed to import judge");
-            }
-        }
 
-        let judges = judges.lock().await.clone();
-        judges
-    } else {
-        info!("No judges were provided to import.");
-        judges
-    };
-
-    let (teams, _, _, _) = if let Some(mut teams_csv) = teams_csv {
-        let headers = Arc::new(teams_csv.headers().unwrap().clone());
-        let teams_span = span!(Level::INFO, "importing teams");
-        let _teams_guard = teams_span.enter();
-
-        let mut join_set = JoinSet::new();
-
-        let teams = Arc::new(tokio::sync::Mutex::new(teams.clone()));
-        let speakers = Arc::new(tokio::sync::Mutex::new(speakers));
-        let break_categories = Arc::new(tokio::sync::Mutex::new(break_categories));
-        let speaker_categories = Arc::new(tokio::sync::Mutex::new(speaker_categories));
-        let institutions = Arc::new(institutions.clone());
-
-        for team2import in teams_csv.records() {
-            let api_addr = api_addr.clone();
-            let headers = headers.clone();
-            let request_manager = request_manager.clone();
-            let teams = teams.clone();
-            let speakers = speakers.clone();
-            let break_categories = break_categories.clone();
-            let speaker_categories = speaker_categories.clone();
-            let institutions = institutions.clone();
-            let auth = auth.clone();
-            let import = import.clone();
-
-            join_set.spawn(async move {
-                let team2import = team2import.unwrap();
-                let team2import: TeamRow = team2import.deserialize(Some(&headers)).unwrap();
-
-                let inst_of_team2_import = institutions.iter().find(|api_inst| {
-                    Some(api_inst.name.as_str().to_lowercase())
-                        == team2import.institution.as_ref().map(|t| t.to_lowercase())
-                        || Some(api_inst.code.as_str().to_lowercase())
-                            == team2import.institution.as_ref().map(|t| t.to_lowercase())
-                });
+                    import_errors::collect(
+                        on_error,
+                        "judges",
+                        Some(headers.as_ref()),
+                        &rows,
+                        join_set,
+                        &gate,
+                        delta.as_mut(),
+                    )
+                    .await;
 
-                let teams_lock = teams.lock().await;
-                let team_url = if let Some(team) = teams_lock.iter().find(|team| {
-                    let (long_prefix, short_prefix) =
-                        if team2import.use_institution_prefix || import.use_institution_prefix {
-                            if let Some(inst) = inst_of_team2_import {
-                                (
-                                    format!("{} ", inst.name.as_str()),
-                                    format!("{} ", inst.code.as_str()),
-                                )
-                            } else {
-                                (String::new(), String::new())
-                            }
-                        } else {
-                            (String::new(), String::new())
-                        };
-
-                    team.long_name == format!("{long_prefix}{}", team2import.full_name.trim())
-                        || Some(format!("{short_prefix}{}", team.short_name.as_str()).as_str())
-                            == team2import.short_name.as_ref().map(|t| t.trim())
-                        || team.code_name.clone().map(|t| t.as_str().to_string())
-                            == team2import.code_name.as_ref().map(|t| t.trim().to_string())
-                }) {
-                    info!(
-                        "Team {} already exists, therefore not creating a record \
-                        for this team.",
-                        team2import.full_name
-                    );
-                    team.url.clone()
+                    let judges = judges.lock().await.clone();
+                    judges
                 } else {
-                    drop(teams_lock);
-                    let inst = inst_of_team2_import.map(|inst| inst.url.clone());
+                    info!("No judges were provided to import.");
+                    judges
+                };
+            }
+            ImportStep::SpeakerCategories => {
+                if let Some(mut speaker_categories_csv) = speaker_categories_csv.take() {
+                    let speaker_categories_span =
+                        span!(Level::INFO, "importing speaker categories");
+                    let _speaker_categories_guard = speaker_categories_span.enter();
+
+                    for row in speaker_categories_csv.deserialize::<SpeakerCategoryRow>() {
+                        let row = row.unwrap();
+
+                        if speaker_categories.iter().any(|api_cat| {
+                            api_cat.slug.as_str().eq_ignore_ascii_case(row.slug.trim())
+                                || api_cat.name.as_str().eq_ignore_ascii_case(row.name.trim())
+                        }) {
+                            info!(
+                                "Speaker category {} already exists, not inserting",
+                                row.name
+                            );
+                            continue;
+                        }
 
-                    if team2import.institution.is_some() {
-                        if inst.is_none() {
-                            error!(
-                                "Team {} belongs to institution {:?}, however, no \
-                                corresponding institution was defined in {}.",
-                                team2import.full_name,
-                                team2import.institution.unwrap(),
-                                import.institutions_csv.as_ref().unwrap()
+                        let seq = row.seq.unwrap_or(speaker_categories.len() as i64 + 1);
+                        let resp = request_manager
+                            .send_request(|| {
+                                request_manager
+                                    .client
+                                    .post(format!(
+                                        "{api_addr}/tournaments/{}/speaker-categories",
+                                        auth.tournament_slug
+                                    ))
+                                    .json(&json!({
+                                        "name": row.name.trim(),
+                                        "slug": row.slug.trim(),
+                                        "seq": seq,
+                                        "public": row.public
+                                    }))
+                                    .build()
+                                    .unwrap()
+                            })
+                            .await;
+                        if !resp.status().is_success() {
+                            panic!(
+                                "error when creating speaker category {}\n{:?} {}",
+                                row.name,
+                                resp.status(),
+                                resp.text().await.unwrap()
                             );
                         }
-                        assert!(inst.is_some());
+                        let category: SpeakerCategory = json_of_resp(resp).await;
+                        info!("Created speaker category {}", category.name.as_str());
+                        speaker_categories.push(category);
                     }
+                }
+            }
+            ImportStep::Teams => {
+                let imported = if let Some(mut teams_csv) = teams_csv.take() {
+                    let headers = Arc::new(teams_csv.headers().unwrap().clone());
+                    let institution_aliases = Arc::new(institution_aliases.clone());
+                    let teams_span = span!(Level::INFO, "importing teams");
+                    let _teams_guard = teams_span.enter();
+
+                    let mut join_set = JoinSet::new();
+                    let gate = RowGate::default();
+
+                    let teams = Arc::new(tokio::sync::Mutex::new(teams.clone()));
+                    let speakers = Arc::new(tokio::sync::Mutex::new(speakers));
+                    let break_categories = Arc::new(tokio::sync::Mutex::new(break_categories));
+                    let speaker_categories = Arc::new(tokio::sync::Mutex::new(speaker_categories));
+                    let institutions = Arc::new(institutions.clone());
+
+                    let rows = teams_csv
+                        .records()
+                        .map(|row| row.unwrap())
+                        .collect::<Vec<_>>();
+                    for (i, team2import) in rows.iter().cloned().enumerate() {
+                        let api_addr = api_addr.clone();
+                        let headers = headers.clone();
+                        let request_manager = request_manager.clone();
+                        let teams = teams.clone();
+                        let speakers = speakers.clone();
+                        let break_categories = break_categories.clone();
+                        let speaker_categories = speaker_categories.clone();
+                        let institutions = institutions.clone();
+                        let institution_aliases = institution_aliases.clone();
+                        let unresolved_institutions = unresolved_institutions.clone();
+                        let auth = auth.clone();
+                        let import = import.clone();
+                        let config = config.clone();
+                        let break_category_rows = break_category_rows.clone();
+                        let emoji_choices = emoji_choices.clone();
+                        let used_url_keys = used_url_keys.clone();
+                        let renamed_teams = renamed_teams.clone();
+
+                        let task = async move {
+                            let mut team2import: TeamRow =
+                                team2import.deserialize(Some(&headers)).unwrap();
+                            if let Some((full_name, short_name)) = renamed_teams.get(&i) {
+                                team2import.full_name = full_name.clone();
+                                team2import.short_name = short_name.clone();
+                            }
 
-                    let break_category_urls = {
-                        let mut break_categories_lock = break_categories.lock().await;
-                        let category_and_optionally_url = team2import
-                            .categories
-                            .iter()
-                            .map(|team2_import_category_name| {
-                                assert!(!team2_import_category_name.is_empty());
-                                (
-                                    team2_import_category_name,
-                                    break_categories_lock
+                            let inst_of_team2_import = team2import
+                                .institution
+                                .as_ref()
+                                .and_then(|inst| institution_aliases.find(&institutions, inst));
+
+                            let emoji = team2import.emoji.clone().filter(|emoji| {
+                                !import.strip_invalid_emoji
+                                    || emoji_choices
+                                        .as_ref()
+                                        .as_ref()
+                                        .map(|choices| {
+                                            choices
+                                                .iter()
+                                                .any(|choice| choice.value == emoji.trim())
+                                        })
+                                        .unwrap_or(true)
+                            });
+
+                            let teams_lock = teams.lock().await;
+                            let team_url = if let Some(team) = teams_lock.iter().find(|team| {
+                                let (long_prefix, short_prefix) = if team2import
+                                    .use_institution_prefix
+                                    || import.use_institution_prefix
+                                {
+                                    if let Some(inst) = inst_of_team2_import {
+                                        (
+                                            format!("{} ", inst.name.as_str()),
+                                            format!("{} ", inst.code.as_str()),
+                                        )
+                                    } else {
+                                        (String::new(), String::new())
+                                    }
+                                } else {
+                                    (String::new(), String::new())
+                                };
+
+                                let short_name =
+                                    format!("{short_prefix}{}", team.short_name.as_str());
+                                names_match(
+                                    &team.long_name,
+                                    &format!("{long_prefix}{}", team2import.full_name),
+                                ) || team2import
+                                    .short_name
+                                    .as_ref()
+                                    .is_some_and(|short| names_match(&short_name, short))
+                                    || match (&team.code_name, &team2import.code_name) {
+                                        (Some(existing), Some(code_name)) => {
+                                            names_match(existing.as_str(), code_name)
+                                        }
+                                        _ => false,
+                                    }
+                            }) {
+                                info!(
+                                    "Team {} already exists, therefore not creating a record \
+                                    for this team.",
+                                    team2import.full_name
+                                );
+                                if import.update_existing {
+                                    let fields = json!({
+                                        "institution": inst_of_team2_import.map(|inst| &inst.url),
+                                        "seed": team2import.seed,
+                                        "emoji": emoji,
+                                        "code_name": team2import.code_name,
+                                    });
+                                    update_existing(
+                                        "team",
+                                        &team.long_name,
+                                        &team.url,
+                                        &serde_json::to_value(team).unwrap(),
+                                        &fields,
+                                        request_manager.clone(),
+                                    )
+                                    .await;
+                                }
+                                team.url.clone()
+                            } else {
+                                drop(teams_lock);
+                                let inst = inst_of_team2_import.map(|inst| inst.url.clone());
+
+                                if let Some(institution) = &team2import.institution
+                                    && inst.is_none()
+                                {
+                                    error!(
+                                        "Team {} belongs to institution {institution:?}, which \
+                                        does not exist; skipping this team.",
+                                        team2import.full_name
+                                    );
+                                    unresolved_institutions.lock().await.push((
+                                        format!("team {}", team2import.full_name),
+                                        institution.clone(),
+                                    ));
+                                    return Err(format!(
+                                        "the institution {institution:?} does not exist"
+                                    ));
+                                }
+
+                                let break_category_urls = {
+                                    let mut break_categories_lock = break_categories.lock().await;
+                                    let category_and_optionally_url = team2import
+                                        .categories
                                         .iter()
-                                        .find(|api_cat| {
-                                            api_cat
-                                                .slug
-                                                .as_str()
-                                                .eq_ignore_ascii_case(team2_import_category_name.trim())
+                                        .map(|team2_import_category_name| {
+                                            assert!(!team2_import_category_name.is_empty());
+                                            let team2_import_category_name =
+                                                config.category(team2_import_category_name);
+                                            (
+                                                team2_import_category_name,
+                                                break_categories_lock
+                                                    .iter()
+                                                    .find(|api_cat| {
+                                                        api_cat.slug.as_str().eq_ignore_ascii_case(
+                                                            team2_import_category_name.trim(),
+                                                        )
+                                                    })
+                                                    .cloned(),
+                                            )
                                         })
-                                        .cloned(),
-                                )
-                            })
-                            .collect::<Vec<_>>();
-
-                        let mut result = Vec::new();
-                        for (name, api_category) in category_and_optionally_url {
-                            match api_category {
-                                Some(t) => result.push(t.url.clone()),
-                                None => {
-                                    let seq = break_categories_lock.len() + 1;
+                                        .collect::<Vec<_>>();
+
+                                    let mut result = Vec::new();
+                                    for (name, api_category) in category_and_optionally_url {
+                                        match api_category {
+                                            Some(t) => result.push(t.url.clone()),
+                                            None => {
+                                                let seq = break_categories_lock.len() + 1;
+                                                let row = break_category_rows.iter().find(|row| {
+                                                    row.slug
+                                                        .trim()
+                                                        .eq_ignore_ascii_case(name.trim())
+                                                        || row
+                                                            .name
+                                                            .trim()
+                                                            .eq_ignore_ascii_case(name.trim())
+                                                });
+                                                let default_break_size =
+                                                    config.break_size.unwrap_or(4);
+                                                let payload = match row {
+                                                    Some(row) => serde_json::json!({
+                                                        "name": row.name,
+                                                        "slug": row.slug,
+                                                        "seq": seq,
+                                                        "break_size": row.break_size,
+                                                        "is_general": row.is_general,
+                                                        "priority": row.priority,
+                                                        "limit": row.limit.unwrap_or(0)
+                                                    }),
+                                                    None => serde_json::json!({
+                                                        "name": name,
+                                                        "slug": name.to_ascii_lowercase(),
+                                                        "seq": seq,
+                                                        "break_size": default_break_size,
+                                                        "is_general": false,
+                                                        "priority": 1
+                                                    }),
+                                                };
+                                                let url = format!(
+                                                    "{api_addr}/tournaments/{}/break-categories",
+                                                    auth.tournament_slug
+                                                );
+                                                let resp = request_manager
+                                                    .send_request(|| {
+                                                        request_manager
+                                                            .client
+                                                            .post(&url)
+                                                            .json(&payload)
+                                                            .build()
+                                                            .unwrap()
+                                                    })
+                                                    .await;
+
+                                                if !resp.status().is_success() {
+                                                    panic!(
+                                                        "error when creating category {name}\n
+                                                        {:?} {}",
+                                                        resp.status(),
+                                                        resp.text().await.unwrap()
+                                                    );
+                                                }
+
+                                                let category: BreakCategory =
+                                                    json_of_resp(resp).await;
+                                                result.push(category.url.clone());
+                                                break_categories_lock.push(category);
+                                            }
+                                        }
+                                    }
+                                    result
+                                };
+
+                                let mut payload = {
+                                    serde_json::json!({
+                                        "institution": inst,
+                                        "reference": team2import.full_name,
+                                        "seed": team2import.seed,
+                                        "emoji": emoji,
+                                        "use_institution_prefix":
+                                            // TODO: document this behaviour
+                                            import.use_institution_prefix
+                                            || team2import.use_institution_prefix,
+                                        "break_categories": break_category_urls,
+                                        // note: we don't add speakers here!
+                                    })
+                                };
+
+                                if let Some(code_name) = team2import.code_name {
+                                    merge(&mut payload, &json!({"code_name": code_name}));
+                                }
+
+                                if let Some(short_name) = team2import.short_name {
+                                    merge(&mut payload, &json!({"short_reference": short_name}));
+                                }
+
+                                let resp = request_manager
+                                    .send_request(|| {
+                                        request_manager
+                                            .client
+                                            .post(format!(
+                                                "{api_addr}/tournaments/{}/teams",
+                                                auth.tournament_slug
+                                            ))
+                                            .json(&payload)
+                                            .build()
+                                            .unwrap()
+                                    })
+                                    .await;
+                                if !resp.status().is_success() {
+                                    panic!(
+                                        "error (team is {}) {:?} {} \n {:#?}",
+                                        team2import.full_name,
+                                        resp.status(),
+                                        resp.text().await.unwrap(),
+                                        teams.lock().await
+                                    );
+                                }
+                                let team: Team = json_of_resp(resp).await;
+                                info!(
+                                    "Created team {} with id {} (institution: {:?})",
+                                    team.long_name, team.id, inst
+                                );
+                                let url = team.url.clone();
+                                teams.lock().await.push(team.clone());
+                                url
+                            };
+
+                            let speaker_categories_url = format!(
+                                "{api_addr}/tournaments/{}/speaker-categories",
+                                auth.tournament_slug
+                            );
+                            let team_span =
+                                span!(Level::INFO, "team", team_name = team2import.full_name);
+                            let _team_guard = team_span.enter();
+                            for speaker2import in team2import.speakers {
+                                let speakers_lock = speakers.lock().await;
+                                let existing_speaker = speakers_lock
+                                    .iter()
+                                    .find(|speaker| {
+                                        names_match(&speaker.name, &speaker2import.name)
+                                            || speaker
+                                                .url_key
+                                                .clone()
+                                                .map(|key| {
+                                                    Some(key.as_str().to_string())
+                                                        == speaker2import.url_key
+                                                })
+                                                .unwrap_or(false)
+                                    })
+                                    .cloned();
+                                if existing_speaker.is_none() {
+                                    drop(speakers_lock);
+                                    let speaker_category_urls = {
+                                        let mut speaker_categories_lock =
+                                            speaker_categories.lock().await;
+                                        let mut ret = Vec::new();
+                                        for speaker2import_cat in speaker2import.categories {
+                                            let speaker2import_cat =
+                                                config.category(&speaker2import_cat);
+                                            let category_from_tabbycat = speaker_categories_lock
+                                                .iter()
+                                                .find(|api_cat| {
+                                                    api_cat
+                                                        .slug
+                                                        .as_str()
+                                                        .to_ascii_lowercase()
+                                                        .trim()
+                                                        == speaker2import_cat.to_ascii_lowercase()
+                                                        || api_cat
+                                                            .name
+                                                            .as_str()
+                                                            .trim()
+                                                            .eq_ignore_ascii_case(
+                                                                speaker2import_cat,
+                                                            )
+                                                })
+                                                .cloned();
+
+                                            match category_from_tabbycat {
+                                                Some(t) => ret.push(t.clone().url),
+                                                None => {
+                                                    let seq = speaker_categories_lock.len() + 1;
+                                                    let resp = request_manager
+                                                        .send_request(|| {
+                                                            request_manager
+                                                                .client
+                                                                .post(&speaker_categories_url)
+                                                                .json(&serde_json::json!({
+                                                                    "name": speaker2import_cat,
+                                                                    "slug": speaker2import_cat,
+                                                                    "seq": seq
+                                                                }))
+                                                                .build()
+                                                                .unwrap()
+                                                        })
+                                                        .await;
+                                                    if !resp.status().is_success() {
+                                                        panic!(
+                                                            "Error: request failed, (note: \
+                                                            response body is {}) \n
+                                                            category: {speaker2import_cat} \n
+                                                            ",
+                                                            resp.text().await.unwrap()
+                                                        )
+                                                    }
+                                                    let category: SpeakerCategory =
+                                                        json_of_resp(resp).await;
+                                                    ret.push(category.url.clone());
+                                                    speaker_categories_lock.push(category);
+                                                }
+                                            }
+                                        }
+                                        ret
+                                    };
+
+                                    let mut payload = json!({
+                                        "name": speaker2import.name,
+                                        "team": team_url,
+                                        "categories": speaker_category_urls,
+                                        "email": speaker2import.email,
+                                        "anonymous": speaker2import.anonymous.unwrap_or(false),
+                                    });
+
+                                    if let Some(code_name) = speaker2import.code_name {
+                                        merge(
+                                            &mut payload,
+                                            &json!({
+                                                "code_name": code_name,
+                                            }),
+                                        );
+                                    }
+
+                                    if let Some(phone) = speaker2import.phone {
+                                        merge(
+                                            &mut payload,
+                                            &json!({
+                                                "phone": phone,
+                                            }),
+                                        )
+                                    }
+
+                                    if let Some(gender) = speaker2import.gender {
+                                        let gender = normalise_gender(gender);
+                                        merge(
+                                            &mut payload,
+                                            &json!({
+                                                "gender": gender,
+                                            }),
+                                        )
+                                    }
+
+                                    if let Some(pronoun) = speaker2import.pronoun {
+                                        merge(
+                                            &mut payload,
+                                            &json!({
+                                                "pronoun": pronoun,
+                                            }),
+                                        )
+                                    }
+
+                                    let url_key = match speaker2import.url_key {
+                                        Some(url_key) => Some(url_key),
+                                        None if import.generate_url_keys => {
+                                            Some(generate_url_key(&mut *used_url_keys.lock().await))
+                                        }
+                                        None => None,
+                                    };
+                                    if let Some(url_key) = url_key {
+                                        merge(
+                                            &mut payload,
+                                            &json!({
+                                                "url_key": url_key,
+                                            }),
+                                        )
+                                    }
+
                                     let resp = request_manager
                                         .send_request(|| {
                                             request_manager
                                                 .client
                                                 .post(format!(
-                                                    "{api_addr}/tournaments/{}/break-categories",
+                                                    "{api_addr}/tournaments/{}/speakers",
                                                     auth.tournament_slug
                                                 ))
-                                                .json(&serde_json::json!({
-                                                    "name": name,
-                                                    "slug": name.to_ascii_lowercase(),
-                                                    "seq": seq,
-                                                    "break_size": 4,
-                                                    "is_general": false,
-                                                    "priority": 1
-                                                }))
+                                                .json(&payload)
                                                 .build()
                                                 .unwrap()
                                         })
                                         .await;
 
+                                    // TODO: we can format the JSON error messages in a more
+                                    // human-friendly way
                                     if !resp.status().is_success() {
                                         panic!(
-                                            "error when creating category {name}\n
-                                            {:?} {}",
+                                            "Error occurred while creating speaker: \n\
+                                            Status: {:?}\nResponse: {}\nSpeaker Name: {}",
                                             resp.status(),
-                                            resp.text().await.unwrap()
+                                            resp.text().await.unwrap(),
+                                            speaker2import.name
                                         );
                                     }
 
-                                    let category: BreakCategory = resp.json().await.unwrap();
-                                    result.push(category.url.clone());
-                                    break_categories_lock.push(category);
+                                    let speaker: tabbycat_api::types::Speaker =
+                                        json_of_resp(resp).await;
+                                    info!(
+                                        "Created speaker {} with id {}",
+                                        speaker.name, speaker.id
+                                    );
+                                    speakers.lock().await.push(speaker.clone());
+                                    let mut teams_lock = teams.lock().await;
+                                    let team = teams_lock
+                                        .iter_mut()
+                                        .find(|team| team.url == speaker.team)
+                                        .unwrap();
+                                    let updated_team_resp = request_manager
+                                        .send_request(|| {
+                                            request_manager
+                                                .client
+                                                .get(team.url.clone())
+                                                .build()
+                                                .unwrap()
+                                        })
+                                        .await;
+                                    *team = json_of_resp(updated_team_resp).await;
+                                } else {
+                                    info!(
+                                        "Speaker {} already exists, therefore not creating a \
+                                        record for this speaker.",
+                                        speaker2import.name
+                                    );
+                                    drop(speakers_lock);
+
+                                    if import.update_existing {
+                                        let speaker = existing_speaker.unwrap();
+                                        let fields = json!({
+                                            "email": speaker2import.email,
+                                            "phone": speaker2import.phone,
+                                            "anonymous": speaker2import.anonymous,
+                                            "code_name": speaker2import.code_name,
+                                            "url_key": speaker2import.url_key,
+                                            "gender": speaker2import.gender.map(normalise_gender),
+                                            "pronoun": speaker2import.pronoun,
+                                        });
+                                        update_existing(
+                                            "speaker",
+                                            &speaker.name,
+                                            &speaker.url,
+                                            &serde_json::to_value(&speaker).unwrap(),
+                                            &fields,
+                                            request_manager.clone(),
+                                        )
+                                        .await;
+                                    }
                                 }
                             }
+                            Ok(())
                         }
-                        result
-                    };
-
-                    let mut payload = {
-                        serde_json::json!({
-                            "institution": inst,
-                            "reference": team2import.full_name,
-                            "seed": team2import.seed,
-                            "emoji": team2import.emoji,
-                            "use_institution_prefix":
-                                // TODO: document this behaviour
-                                import.use_institution_prefix
-                                || team2import.use_institution_prefix,
-                            "break_categories": break_category_urls,
-                            // note: we don't add speakers here!
-                        })
-                    };
-
-                    if let Some(code_name) = team2import.code_name {
-                        merge(&mut payload, &json!({"code_name": code_name}));
+                        .instrument(teams_span.clone());
+                        join_set.spawn(import_errors::guard(i, gate.clone(), task));
                     }
 
-                    if let Some(short_name) = team2import.short_name {
-                        merge(&mut payload, &json!({"short_reference": short_name}));
-                    }
+                    import_errors::collect(
+                        on_error,
+                        "teams",
+                        Some(headers.as_ref()),
+                        &rows,
+                        join_set,
+                        &gate,
+                        delta.as_mut(),
+                    )
+                    .await;
 
-                    let resp = request_manager
-                        .send_request(|| {
-                            request_manager
-                                .client
-                                .post(format!(
-                                    "{api_addr}/tournaments/{}/teams",
-                                    auth.tournament_slug
-                                ))
-                                .json(&payload)
-                                .build()
-                                .unwrap()
-                        })
-                        .await;
-                    if !resp.status().is_success() {
-                        panic!(
-                            "error (team is {}) {:?} {} \n {:#?}",
-                            team2import.full_name,
-                            resp.status(),
-                            resp.text().await.unwrap(),
-                            teams.lock().await
-                        );
-                    }
-                    let team: Team = resp.json().await.unwrap();
-                    info!(
-                        "Created team {} with id {} (institution: {:?})",
-                        team.long_name, team.id, inst
-                    );
-                    let url = team.url.clone();
-                    teams.lock().await.push(team.clone());
-                    url
+                    let teams = teams.lock().await.clone();
+                    let speakers = speakers.lock().await.clone();
+                    let break_categories = break_categories.lock().await.clone();
+                    let speaker_categories = speaker_categories.lock().await.clone();
+                    (teams, speakers, break_categories, speaker_categories)
+                } else {
+                    info!("No teams were provided to import.");
+                    (teams, speakers, break_categories, speaker_categories)
                 };
+                (teams, speakers, break_categories, speaker_categories) = imported;
+            }
+            ImportStep::Draw => {
+                if let Some(mut draw_csv) = draw_csv.take() {
+                    let rows = draw_csv
+                        .deserialize::<DrawRow>()
+                        .map(|row| row.unwrap())
+                        .collect::<Vec<_>>();
+                    import_draw(
+                        &auth,
+                        import.round.as_deref().unwrap(),
+                        &rows,
+                        &teams,
+                        request_manager.clone(),
+                    )
+                    .await;
+                }
+            }
+            ImportStep::Panels => {
+                if let Some(mut panels_csv) = panels_csv.take() {
+                    let rows = panels_csv
+                        .deserialize::<PanelRow>()
+                        .map(|row| row.unwrap())
+                        .collect::<Vec<_>>();
+                    import_panels(
+                        &auth,
+                        import.round.as_deref().unwrap(),
+                        &rows,
+                        &judges,
+                        import.force,
+                        request_manager.clone(),
+                    )
+                    .await;
+                }
+            }
+            ImportStep::Clashes => {
+                if let Some(rows) = clashes_csv.take() {
+                    let institutions = Arc::new(institutions.clone());
+                    let teams1 = Arc::new(tokio::sync::Mutex::new(teams.clone()));
+                    let judges1 = Arc::new(tokio::sync::Mutex::new(judges.clone()));
+
+                    let mut join_set = JoinSet::new();
+                    let gate = RowGate::default();
+
+                    for (i, clash2import) in rows.iter().cloned().enumerate() {
+                        let institutions = institutions.clone();
+                        let teams1 = teams1.clone();
+                        let judges1 = judges1.clone();
+                        let request_manager = request_manager.clone();
+
+                        join_set.spawn(import_errors::guard(i, gate.clone(), async move {
+                            let clash2import: Clash = clash2import.deserialize(None).unwrap();
+
+                            let adding_clash_span = span!(
+                                Level::INFO,
+                                "clash",
+                                a = clash2import.object_1,
+                                b = clash2import.object_2
+                            );
 
-                let team_span = span!(Level::INFO, "team", team_name = team2import.full_name);
-                let _team_guard = team_span.enter();
-                for speaker2import in team2import.speakers {
-                    let speakers_lock = speakers.lock().await;
-                    if !speakers_lock.iter().any(|speaker| {
-                        speaker.name.trim() == speaker2import.name.trim()
-                            || speaker
-                                .url_key
-                                .clone()
-                                .map(|key| Some(key.as_str().to_string()) == speaker2import.url_key)
-                                .unwrap_or(false)
-                    }) {
-                        drop(speakers_lock);
-                        let speaker_category_urls = {
-                            let mut speaker_categories_lock = speaker_categories.lock().await;
-                            let mut ret = Vec::new();
-                            for speaker2import_cat in speaker2import.categories {
-                                let speaker2import_cat = speaker2import_cat.trim();
-                                let category_from_tabbycat = speaker_categories_lock
-                                    .iter()
-                                    .find(|api_cat| {
-                                        api_cat.slug.as_str().to_ascii_lowercase().trim()
-                                            == speaker2import_cat.to_ascii_lowercase()
-                                    })
-                                    .cloned();
+                            add_clash(institutions, teams1, judges1, clash2import, request_manager)
+                                .instrument(adding_clash_span)
+                                .await;
+                            Ok(())
+                        }));
+                    }
 
-                                match category_from_tabbycat {
-                                    Some(t) => ret.push(t.clone().url),
-                                    None => {
-                                        let seq = speaker_categories_lock.len() + 1;
-                                        let resp = request_manager
-                                            .send_request(|| {
-                                                request_manager
-                                                    .client
-                                                    .post(format!(
-                                                        "{api_addr}/tournaments/{}/speaker-categories",
-                                                        auth.tournament_slug
-                                                    ))
-                                                    .json(&serde_json::json!({
-                                                        "name": speaker2import_cat,
-                                                        "slug": speaker2import_cat,
-                                                        "seq": seq
-                                                    }))
-                                                    .build()
-                                                    .unwrap()
-                                            })
-                                            .await;
-                                        if !resp.status().is_success() {
-                                            panic!(
-                                                "Error: request failed, (note: \
-                                                response body is {}) \n
-                                                category: {speaker2import_cat} \n
-                                                ",
-                                                resp.text().await.unwrap()
-                                            )
-                                        }
-                                        let category: SpeakerCategory = resp.json().await.unwrap();
-                                        ret.push(category.url.clone());
-                                        speaker_categories_lock.push(category);
-                                    }
-                                }
-                            }
-                            ret
-                        };
-
-                        let mut payload = json!({
-                            "name": speaker2import.name,
-                            "team": team_url,
-                            "categories": speaker_category_urls,
-                            "email": speaker2import.email,
-                            "anonymous": speaker2import.anonymous,
-                        });
-
-                        if let Some(code_name) = speaker2import.code_name {
-                            merge(
-                                &mut payload,
-                                &json!({
-                                    "code_name": code_name,
-                                }),
-                            );
-                        }
+                    import_errors::collect(
+                        on_error,
+                        "clashes",
+                        None,
+                        &rows,
+                        join_set,
+                        &gate,
+                        delta.as_mut(),
+                    )
+                    .await;
 
-                        if let Some(phone) = speaker2import.phone {
-                            merge(
-                                &mut payload,
-                                &json!({
-                                    "phone": phone,
-                                }),
-                            )
-                        }
+                    SoftClashes::record(
+                        &rows
+                            .iter()
+                            .map(|row| row.deserialize::<Clash>(None).unwrap())
+                            .collect::<Vec<_>>(),
+                    );
+                }
+            }
+        }
+    }
 
-                        if let Some(gender) = speaker2import.gender {
-                            let gender = match gender.to_ascii_lowercase().as_str() {
-                                "male" => "M".to_string(),
-                                "female" => "F".to_string(),
-                                "other" => "O".to_string(),
-                                _ => gender,
-                            };
-                            merge(
-                                &mut payload,
-                                &json!({
-                                    "gender": gender,
-                                }),
-                            )
-                        }
+    let unresolved_institutions = unresolved_institutions.lock().await;
+    if !unresolved_institutions.is_empty() {
+        error!(
+            "{} row(s) were not imported because their institution could not be found:",
+            unresolved_institutions.len()
+        );
+        for (row, institution) in unresolved_institutions.iter() {
+            let suggestion = institution_aliases
+                .suggest(&institutions, institution)
+                .map(|inst| format!(" (did you mean {}?)", inst.name.as_str()))
+                .unwrap_or_default();
+            error!("  {row}: {institution:?}{suggestion}");
+        }
+    }
 
-                        if let Some(pronoun) = speaker2import.pronoun {
-                            merge(
-                                &mut payload,
-                                &json!({
-                                    "pronoun": pronoun,
-                                }),
-                            )
-                        }
+    if let Some(delta) = delta {
+        delta.save();
+    }
+    delta_import::remove_temp_files();
+}
 
-                        let resp = request_manager
-                            .send_request(|| {
-                                request_manager
-                                    .client
-                                    .post(format!(
-                                        "{api_addr}/tournaments/{}/speakers",
-                                        auth.tournament_slug
-                                    ))
-                                    .json(&payload)
-                                    .build()
-                                    .unwrap()
-                            })
-                            .await;
+/// The region of each institution, as (URL, region) pairs. Regions are taken
+/// from Tabbycat or, for institutions which Tabbycat has no region for, the
+/// institutions CSV file (`csv_regions`, by short code).
+fn institution_regions(
+    institutions: &[tabbycat_api::types::PerTournamentInstitution],
+    csv_regions: &HashMap<String, String>,
+) -> Vec<(String, String)> {
+    institutions
+        .iter()
+        .filter_map(|inst| {
+            let region = serde_json::to_value(inst).unwrap()["region"]
+                .as_str()
+                .filter(|region| !region.trim().is_empty())
+                .map(|region| region.to_string())
+                .or_else(|| csv_regions.get(inst.code.as_str()).cloned())?;
+            Some((inst.url.clone(), region))
+        })
+        .collect()
+}
 
-                        // TODO: we can format the JSON error messages in a more
-                        // human-friendly way
-                        if !resp.status().is_success() {
-                            panic!(
-                                "Error occurred while creating speaker: \nStatus: {:?}\nResponse: {}\nSpeaker Name: {}",
-                                resp.status(),
-                                resp.text().await.unwrap(),
-                                speaker2import.name
-                            );
-                        }
+/// The URLs of the institutions in `region`.
+fn institutions_in_region(regions: &[(String, String)], region: &str) -> Vec<String> {
+    regions
+        .iter()
+        .filter(|(_, inst_region)| names_match(inst_region, region))
+        .map(|(url, _)| url.clone())
+        .collect()
+}
 
-                        let speaker: tabbycat_api::types::Speaker = resp.json().await.unwrap();
-                        info!("Created speaker {} with id {}", speaker.name, speaker.id);
-                        speakers.lock().await.push(speaker.clone());
-                        let mut teams_lock = teams.lock().await;
-                        let team = teams_lock
-                            .iter_mut()
-                            .find(|team| team.url == speaker.team)
-                            .unwrap();
-                        let updated_team_resp = request_manager
-                            .send_request(|| {
-                                request_manager
-                                    .client
-                                    .get(team.url.clone())
-                                    .build()
-                                    .unwrap()
-                            })
-                            .await;
-                        *team = updated_team_resp.json().await.unwrap();
-                    } else {
-                        info!(
-                            "Speaker {} already exists, therefore not creating a \
-                            record for this speaker.",
-                            speaker2import.name
-                        );
-                    }
-                }
-            }.instrument(teams_span.clone()));
+/// Converts `male`, `female` and `other` to the codes Tabbycat uses.
+fn normalise_gender(gender: String) -> String {
+    match gender.to_ascii_lowercase().as_str() {
+        "male" => "M".to_string(),
+        "female" => "F".to_string(),
+        "other" => "O".to_string(),
+        _ => gender,
+    }
+}
+
+/// Compares the values in `fields` with those of an existing record, and
+/// PATCHes any which differ (logging the old and new value of each). Fields
+/// which are null (i.e. were left empty in the CSV file) are not changed.
+async fn update_existing(
+    kind: &str,
+    name: &str,
+    url: &str,
+    existing: &serde_json::Value,
+    fields: &serde_json::Value,
+    manager: RequestManager,
+) {
+    let same = |a: &serde_json::Value, b: &serde_json::Value| match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    };
+
+    let mut changes = serde_json::Map::new();
+    for (field, value) in fields.as_object().unwrap() {
+        if value.is_null() || same(&existing[field], value) {
+            continue;
         }
+        info!(
+            "Updating {kind} {name}: {field} {} -> {value}",
+            existing[field]
+        );
+        changes.insert(field.clone(), value.clone());
+    }
 
-        while let Some(result) = join_set.join_next().await {
-            if let Err(err) = result {
-                error!("Error occurred while importing a team: {:?}", err);
-                panic!("Failed to import team");
-            }
+    if changes.is_empty() {
+        return;
+    }
+
+    let resp = manager
+        .send_request(|| manager.client.patch(url).json(&changes).build().unwrap())
+        .await;
+    if !resp.status().is_success() {
+        error!(
+            "Failed to update {kind} {name}: {} {}",
+            resp.status(),
+            resp.text().await.unwrap()
+        );
+        panic!("Failed to update {kind}");
+    }
+}
+
+/// Marks a judge or room (`who`, e.g. `judge Sam Smith`) as available or
+/// unavailable for every round, going by its `availability` column (see
+/// [`is_available`]).
+async fn import_availability(
+    auth: &Auth,
+    who: &str,
+    url: &str,
+    availability: &[String],
+    rounds: &[tabbycat_api::types::Round],
+    default_available: bool,
+    manager: RequestManager,
+) {
+    for api_round in rounds {
+        let is_available = is_available(
+            availability,
+            &[api_round.name.as_str(), api_round.abbreviation.as_str()],
+            round_start(api_round).map(|start| start.time()),
+            default_available,
+        );
+        let status = if is_available {
+            "available"
+        } else {
+            "unavailable"
+        };
+
+        let resp = set_availability(
+            auth,
+            api_round,
+            &[url.to_string()],
+            is_available,
+            manager.clone(),
+        )
+        .await;
+
+        if !resp.status().is_success() {
+            error!(
+                "Failed to mark {who} as {status} for round {}: {} {}",
+                api_round.name.as_str(),
+                resp.status(),
+                resp.text().await.unwrap()
+            );
+            panic!("Failed to mark {who} as {status}");
+        } else {
+            info!(
+                "Marked {who} as {status} for round {}",
+                api_round.name.as_str()
+            );
         }
+    }
+}
 
-        let teams = teams.lock().await.clone();
-        let speakers = speakers.lock().await.clone();
-        let break_categories = break_categories.lock().await.clone();
-        let speaker_categories = speaker_categories.lock().await.clone();
-        (teams, speakers, break_categories, speaker_categories)
-    } else {
-        info!("No teams were provided to import.");
-        (teams, speakers, break_categories, speaker_categories)
+/// Places teams into rooms as listed in the draw CSV file. Debates which
+/// already exist in a room have their teams replaced; otherwise a new debate
+/// is created in that room. Every row is checked before the draw is changed,
+/// so that nothing is changed if a room or team cannot be found.
+async fn import_draw(
+    auth: &Auth,
+    round: &str,
+    rows: &[DrawRow],
+    teams: &[Team],
+    manager: RequestManager,
+) {
+    use tabbycat_api::types::{DebateTeamSide, DebateTeamSideVariant1};
+
+    let draw_span = span!(Level::INFO, "importing draw");
+    let _draw_guard = draw_span.enter();
+
+    let (round, venues) = tokio::join!(
+        get_round(round, auth, manager.clone()),
+        get_venues(auth, manager.clone())
+    );
+    let pairings = pairings_of_round(auth, &round, manager.clone()).await;
+
+    let team_url = |name: &str| {
+        teams
+            .iter()
+            .find(|team| names_match(&team.long_name, name) || names_match(&team.short_name, name))
+            .map(|team| team.url.clone())
     };
 
-    if let Some(mut clashes_csv) = clashes_csv {
-        let institutions = Arc::new(institutions);
-        let teams1 = Arc::new(tokio::sync::Mutex::new(teams));
-        let judges1 = Arc::new(tokio::sync::Mutex::new(judges));
+    let mut problems = Vec::new();
+    let mut debates = Vec::new();
+    for row in rows {
+        let Some(venue) = venues
+            .iter()
+            .find(|venue| names_match(&venue.name, &row.room))
+        else {
+            problems.push(format!("No room named {} exists", row.room));
+            continue;
+        };
 
-        let mut join_set = JoinSet::new();
+        let mut debate_teams = Vec::new();
+        for (name, side) in [
+            (&row.aff, DebateTeamSideVariant1::Aff),
+            (&row.neg, DebateTeamSideVariant1::Neg),
+            (&row.cg, DebateTeamSideVariant1::Cg),
+            (&row.co, DebateTeamSideVariant1::Co),
+        ] {
+            let Some(name) = name.as_deref().filter(|name| !name.trim().is_empty()) else {
+                continue;
+            };
+            match team_url(name) {
+                Some(url) => debate_teams.push(json!({
+                    "team": url,
+                    "side": DebateTeamSide::Variant1(side),
+                })),
+                None => problems.push(format!(
+                    "No team named {name} exists (in room {})",
+                    row.room
+                )),
+            }
+        }
 
-        for clash2import in clashes_csv.records() {
-            let clash2import = clash2import.unwrap();
-            let clash2import: Clash = clash2import.deserialize(None).unwrap();
+        let existing = pairings
+            .iter()
+            .find(|pairing| pairing.venue.as_ref() == Some(&venue.url));
+        debates.push((row, venue, existing, debate_teams));
+    }
+    if !problems.is_empty() {
+        for problem in &problems {
+            error!("{problem}");
+        }
+        error!("The draw has not been changed.");
+        exit(1);
+    }
 
-            let adding_clash_span = span!(
-                Level::INFO,
-                "clash",
-                a = clash2import.object_1,
-                b = clash2import.object_2
+    for (row, venue, existing, debate_teams) in debates {
+        let resp = manager
+            .send_request(|| match existing {
+                Some(pairing) => manager
+                    .client
+                    .patch(&pairing.url)
+                    .json(&json!({ "teams": debate_teams }))
+                    .build()
+                    .unwrap(),
+                None => manager
+                    .client
+                    .post(&round.links.pairing)
+                    .json(&json!({
+                        "venue": venue.url,
+                        "teams": debate_teams,
+                    }))
+                    .build()
+                    .unwrap(),
+            })
+            .await;
+        if !resp.status().is_success() {
+            error!(
+                "Failed to set teams for room {}: {} {}",
+                row.room,
+                resp.status(),
+                resp.text().await.unwrap()
             );
-            let _adding_clash_guard = adding_clash_span.enter();
-
-            join_set.spawn(add_clash(
-                institutions.clone(),
-                teams1.clone(),
-                judges1.clone(),
-                clash2import,
-                request_manager.clone(),
-            ));
+            exit(1);
         }
 
-        while let Some(result) = join_set.join_next().await {
-            if let Err(err) = result {
-                error!("Error occurred while importing a clash: {:?}", err);
-                panic!("Failed to import clash");
+        if existing.is_some() {
+            info!("Updated teams in room {}", row.room);
+        } else {
+            info!("Created debate in room {}", row.room);
+        }
+    }
+}
+
+/// Sets the adjudicators of the rooms listed in the panels CSV file,
+/// overwriting the existing allocation for those rooms. Every row is checked
+/// before any panel is changed, so that nothing is changed if a room or
+/// adjudicator cannot be found.
+async fn import_panels(
+    auth: &Auth,
+    round: &str,
+    rows: &[PanelRow],
+    judges: &[tabbycat_api::types::Adjudicator],
+    force: bool,
+    manager: RequestManager,
+) {
+    let panels_span = span!(Level::INFO, "importing panels");
+    let _panels_guard = panels_span.enter();
+
+    let (round, venues) = tokio::join!(
+        get_round(round, auth, manager.clone()),
+        get_venues(auth, manager.clone())
+    );
+    let pairings = pairings_of_round(auth, &round, manager.clone()).await;
+
+    let judge_url = |name: &str| {
+        judges
+            .iter()
+            .find(|judge| names_match(&judge.name, name))
+            .map(|judge| judge.url.clone())
+    };
+
+    let mut problems = Vec::new();
+    let mut panels = Vec::new();
+    for row in rows {
+        let Some(pairing) = pairing_in_room(&pairings, &venues, &row.room) else {
+            problems.push(format!(
+                "No debate in round {} matches room {}",
+                round.name.as_str(),
+                row.room
+            ));
+            continue;
+        };
+
+        let mut resolve = |name: &str| {
+            let url = judge_url(name);
+            if url.is_none() {
+                problems.push(format!(
+                    "No adjudicator named {name} exists (on the panel in room {})",
+                    row.room
+                ));
             }
+            url
+        };
+        let adjudicators = DebateAdjudicator {
+            chair: row
+                .chair
+                .as_deref()
+                .filter(|chair| !chair.trim().is_empty())
+                .and_then(&mut resolve),
+            panellists: row
+                .panellists
+                .iter()
+                .filter_map(|name| resolve(name))
+                .collect(),
+            trainees: row
+                .trainees
+                .iter()
+                .filter_map(|name| resolve(name))
+                .collect(),
+        };
+        panels.push((row, pairing, adjudicators));
+    }
+    if !problems.is_empty() {
+        for problem in &problems {
+            error!("{problem}");
         }
+        error!("No panels have been changed.");
+        exit(1);
+    }
+
+    PanelPolicy::load().enforce(
+        panels
+            .iter()
+            .map(|(row, _, adjudicators)| (row.room.clone(), adjudicators)),
+        force,
+    );
+
+    for (row, pairing, adjudicators) in panels {
+        let resp = manager
+            .send_request(|| {
+                manager
+                    .client
+                    .patch(&pairing.url)
+                    .json(&json!({ "adjudicators": adjudicators }))
+                    .build()
+                    .unwrap()
+            })
+            .await;
+        if !resp.status().is_success() {
+            error!(
+                "Failed to set panel for room {}: {} {}",
+                row.room,
+                resp.status(),
+                resp.text().await.unwrap()
+            );
+            exit(1);
+        }
+        info!("Set panel for room {}", row.room);
     }
 }
 
@@ -1231,6 +2372,8 @@ pub async fn add_clash_cmd(a: &str, b: &str, auth: &Auth, manager: RequestManage
         Clash {
             object_1: a.into(),
             object_2: b.into(),
+            severity: Severity::Hard,
+            comment: None,
         },
         manager,
     )
@@ -1260,16 +2403,14 @@ async fn add_clash(
         institutions: Arc<Vec<tabbycat_api::types::PerTournamentInstitution>>,
     ) -> Option<ClashKind> {
         for inst in institutions.iter() {
-            if inst.name.as_str().eq_ignore_ascii_case(key)
-                || inst.code.as_str().eq_ignore_ascii_case(key)
-            {
+            if names_match(inst.name.as_str(), key) || names_match(inst.code.as_str(), key) {
                 return Some(ClashKind::Inst(inst.clone()));
             }
         }
 
         let judges_lock = judges.lock().await;
         for judge in judges_lock.iter() {
-            if judge.name.eq_ignore_ascii_case(key) {
+            if names_match(&judge.name, key) {
                 debug!("Resolved {key} as judge {} due to name match.", judge.name);
 
                 return Some(ClashKind::Adj(judge.clone()));
@@ -1278,8 +2419,7 @@ async fn add_clash(
 
         let teams_lock = teams.lock().await;
         for team in teams_lock.iter() {
-            if team.long_name.eq_ignore_ascii_case(key) || team.short_name.eq_ignore_ascii_case(key)
-            {
+            if names_match(&team.long_name, key) || names_match(&team.short_name, key) {
                 debug!(
                     "Resolved {key} as team {} due to name match.",
                     team.long_name
@@ -1290,7 +2430,7 @@ async fn add_clash(
             if team
                 .speakers
                 .iter()
-                .any(|speaker| speaker.name.eq_ignore_ascii_case(key))
+                .any(|speaker| names_match(&speaker.name, key))
             {
                 debug!(
                     "Resolved {key} as team {} as provided key matched \
@@ -1304,10 +2444,7 @@ async fn add_clash(
         None
     }
 
-    if clash2import
-        .object_1
-        .eq_ignore_ascii_case(&clash2import.object_2)
-    {
+    if names_match(&clash2import.object_1, &clash2import.object_2) {
         error!(
             "You have attempted to clash someone against themself: {} and {}",
             clash2import.object_1, clash2import.object_2
@@ -1370,7 +2507,7 @@ async fn add_clash(
                 }
 
                 let adj: tabbycat_api::types::Adjudicator =
-                    resp.json().instrument(Span::current()).await.unwrap();
+                    json_of_resp(resp).instrument(Span::current()).await;
                 let mut judges_lock = judges.lock().await;
                 let judge = judges_lock
                     .iter_mut()
@@ -0,0 +1,78 @@
+use std::{collections::HashMap, process::exit};
+
+use serde::Deserialize;
+use tracing::{error, info};
+
+/// The name of the file (in the current working directory) from which default
+/// import settings are read.
+pub const IMPORT_CONFIG_FILE: &str = "import.toml";
+
+/// Defaults for `tabbycat import`, shared by a tab team via an `import.toml`
+/// file in the working directory. For example
+///
+/// ```toml
+/// use_institution_prefix = true
+/// base_score = 5.0
+/// availability = ["R1", "R2", "R3"]
+/// break_size = 8
+///
+/// [category_aliases]
+/// "English as a second language" = "esl"
+/// ```
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ImportConfig {
+    /// Equivalent to passing `--use-institution-prefix`.
+    #[serde(default)]
+    pub use_institution_prefix: bool,
+    /// Base score for judges which do not have one in the judges CSV file.
+    pub base_score: Option<f64>,
+    /// Rounds which judges are available for, if their `availability` column
+    /// is empty.
+    #[serde(default)]
+    pub availability: Vec<String>,
+    /// Break size used when automatically creating break categories.
+    pub break_size: Option<i64>,
+    /// Maps alternative category names (as they appear in the CSV files) to
+    /// the slug of the category in Tabbycat.
+    #[serde(default)]
+    pub category_aliases: HashMap<String, String>,
+}
+
+impl ImportConfig {
+    /// Loads `import.toml` from the working directory, or returns the default
+    /// configuration if the file does not exist.
+    pub fn load() -> ImportConfig {
+        let text = match std::fs::read_to_string(IMPORT_CONFIG_FILE) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return ImportConfig::default();
+            }
+            Err(e) => {
+                error!("Could not read {IMPORT_CONFIG_FILE}: {e}");
+                exit(1)
+            }
+        };
+
+        match toml::from_str(&text) {
+            Ok(config) => {
+                info!("Using import defaults from {IMPORT_CONFIG_FILE}");
+                config
+            }
+            Err(e) => {
+                error!("Your {IMPORT_CONFIG_FILE} file is malformed: {e}");
+                exit(1)
+            }
+        }
+    }
+
+    /// Resolves a category name from a CSV file using the configured aliases.
+    pub fn category<'a>(&'a self, name: &'a str) -> &'a str {
+        let name = name.trim();
+        self.category_aliases
+            .iter()
+            .find(|(alias, _)| alias.trim().eq_ignore_ascii_case(name))
+            .map(|(_, slug)| slug.as_str())
+            .unwrap_or(name)
+    }
+}
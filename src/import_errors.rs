@@ -0,0 +1,378 @@
+use std::{
+    future::Future,
+    io::Write,
+    process::exit,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use csv::StringRecord;
+use tokio::{
+    sync::Semaphore,
+    task::{JoinError, JoinSet},
+};
+use tracing::{error, info, warn};
+
+use crate::delta_import::{self, DeltaImport};
+
+/// The kinds of CSV file which are imported, in the order they are imported
+/// (break categories are created along with the teams which are in them).
+/// When an import is aborted, the rows of the kinds before the one which
+/// failed are recorded in the checkpoint.
+const IMPORT_ORDER: [&str; 7] = [
+    "institutions",
+    "rooms",
+    "judges",
+    "speaker_categories",
+    "break_categories",
+    "teams",
+    "clashes",
+];
+
+/// How many rows of a CSV file are imported at once.
+const CONCURRENT_ROWS: usize = 16;
+
+/// What the importer does when a row (a judge, team or clash) fails to
+/// import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnError {
+    /// Record the failed row and continue.
+    Skip,
+    /// Stop (once the rows which are already being imported have finished),
+    /// saving a checkpoint which the import can be resumed from.
+    Abort,
+    /// Ask whether to skip the row or abort.
+    Prompt,
+}
+
+impl OnError {
+    pub fn parse(on_error: &str) -> OnError {
+        match on_error.to_ascii_lowercase().as_str() {
+            "skip" => OnError::Skip,
+            "abort" => OnError::Abort,
+            "prompt" => OnError::Prompt,
+            _ => {
+                error!(
+                    "Invalid value `{on_error}` for --on-error (expected skip, abort or prompt)"
+                );
+                exit(1);
+            }
+        }
+    }
+}
+
+/// How the import of a single row went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowOutcome {
+    Imported,
+    Failed(String),
+    /// The row was left out of the import (e.g. because its institution
+    /// could not be found), for the given reason.
+    Skipped(String),
+    /// The import was aborted before the row was started.
+    NotStarted,
+}
+
+/// The outcome of importing a single row, along with its index in the CSV
+/// file.
+pub type RowResult = (usize, RowOutcome);
+
+/// Limits how many rows of a CSV file are imported at once, and stops rows
+/// from being started once the import has been aborted.
+#[derive(Clone)]
+pub struct RowGate {
+    permits: Arc<Semaphore>,
+    aborted: Arc<AtomicBool>,
+}
+
+impl Default for RowGate {
+    fn default() -> RowGate {
+        RowGate {
+            permits: Arc::new(Semaphore::new(CONCURRENT_ROWS)),
+            aborted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl RowGate {
+    fn abort(&self) {
+        self.aborted.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Runs the import of the row at index `row` as a separate task (once `gate`
+/// lets it start), so that if it fails (i.e. panics) the error is returned
+/// along with the row, rather than bringing down the whole import. The task
+/// returns `Err` with the reason if it skipped the row.
+pub fn guard(
+    row: usize,
+    gate: RowGate,
+    task: impl Future<Output = Result<(), String>> + Send + 'static,
+) -> impl Future<Output = RowResult> + Send + 'static {
+    async move {
+        let _permit = gate.permits.acquire_owned().await.unwrap();
+        if gate.aborted.load(Ordering::SeqCst) {
+            return (row, RowOutcome::NotStarted);
+        }
+        match tokio::spawn(task).await {
+            Ok(Ok(())) => (row, RowOutcome::Imported),
+            Ok(Err(reason)) => (row, RowOutcome::Skipped(reason)),
+            Err(err) => (row, RowOutcome::Failed(panic_message(err))),
+        }
+    }
+}
+
+fn panic_message(err: JoinError) -> String {
+    match err.try_into_panic() {
+        Ok(payload) => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|msg| msg.to_string()))
+            .unwrap_or_else(|| "unknown error".to_string()),
+        Err(err) => err.to_string(),
+    }
+}
+
+/// The line of the CSV file which the row at `index` is on.
+fn line_of(index: usize, headers: Option<&StringRecord>) -> usize {
+    index + if headers.is_some() { 2 } else { 1 }
+}
+
+/// Asks whether to skip a row which failed, or abort the import. Returns
+/// `true` to abort (which is also the answer if standard input is closed).
+/// Standard input is read on a blocking thread, so that the rows which are
+/// already being imported carry on in the meantime.
+async fn ask_abort(kind: &str, line: usize, message: &str) -> bool {
+    let (kind, message) = (kind.to_string(), message.to_string());
+    tokio::task::spawn_blocking(move || prompt_abort(&kind, line, &message))
+        .await
+        .unwrap()
+}
+
+fn prompt_abort(kind: &str, line: usize, message: &str) -> bool {
+    loop {
+        eprint!(
+            "Line {line} of the {kind} CSV file failed to import: {message}\n\
+            Skip it and continue, or abort the import? [s/a] "
+        );
+        std::io::stderr().flush().unwrap();
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            return true;
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "s" | "skip" => return false,
+            "a" | "abort" => return true,
+            _ => continue,
+        }
+    }
+}
+
+/// Writes the rows which failed to import to `failed-<kind>.csv`, so that
+/// they can be corrected and imported again.
+fn write_failed_rows(kind: &str, headers: Option<&StringRecord>, rows: &[&StringRecord]) {
+    let path = format!("failed-{kind}.csv");
+    let mut writer = csv::Writer::from_path(&path).unwrap();
+    if let Some(headers) = headers {
+        writer.write_record(headers).unwrap();
+    }
+    for row in rows {
+        writer.write_record(*row).unwrap();
+    }
+    writer.flush().unwrap();
+    warn!(
+        "{} {kind} row(s) were not imported; they have been written to {path}",
+        rows.len()
+    );
+}
+
+/// Handles the rows of the `kind` CSV file which could not be read (by index,
+/// along with the error), according to `on_error`, before anything has been
+/// imported. The rows are written to `failed-<kind>.csv`, and if the import is
+/// aborted the process exits.
+pub async fn reject_rows(
+    on_error: OnError,
+    kind: &str,
+    headers: Option<&StringRecord>,
+    rows: &[StringRecord],
+    malformed: &[(usize, String)],
+) {
+    let mut abort = false;
+    for (index, message) in malformed {
+        let line = line_of(*index, headers);
+        error!("Line {line} of the {kind} CSV file could not be read: {message}");
+        abort |= match on_error {
+            OnError::Skip => false,
+            OnError::Abort => true,
+            OnError::Prompt if abort => true,
+            OnError::Prompt => ask_abort(kind, line, message).await,
+        };
+    }
+
+    let failed_rows = malformed
+        .iter()
+        .map(|(index, _)| &rows[*index])
+        .collect::<Vec<_>>();
+    write_failed_rows(kind, headers, &failed_rows);
+
+    if abort {
+        error!(
+            "The import was aborted before anything was imported. Once the rows which could not \
+            be read have been fixed, run the import again."
+        );
+        delta_import::remove_temp_files();
+        exit(1);
+    }
+    info!(
+        "Continuing the import without {} {kind} row(s)",
+        malformed.len()
+    );
+}
+
+/// Waits for every row of the `kind` CSV file in `join_set` to be imported,
+/// and handles those which failed according to `on_error`. Failed rows (and
+/// those which were skipped or not started) are removed from `delta`, so that
+/// they are imported again next time. Skipped rows are written to
+/// `failed-<kind>.csv` along with the failed rows, but do not stop the import. If the import is aborted, no more rows are
+/// started through `gate`, a checkpoint is saved to the state file (if the
+/// import was run with `--since-state`) and the process exits.
+pub async fn collect(
+    on_error: OnError,
+    kind: &str,
+    headers: Option<&StringRecord>,
+    rows: &[StringRecord],
+    mut join_set: JoinSet<RowResult>,
+    gate: &RowGate,
+    mut delta: Option<&mut DeltaImport>,
+) {
+    let mut failed = Vec::new();
+    let mut skipped = Vec::new();
+    let mut not_started = 0;
+    let mut abort = false;
+    while let Some(result) = join_set.join_next().await {
+        let (index, outcome) = result.unwrap();
+        let message = match outcome {
+            RowOutcome::Imported => continue,
+            RowOutcome::Failed(message) => message,
+            RowOutcome::Skipped(reason) => {
+                let line = line_of(index, headers);
+                warn!("Line {line} of the {kind} CSV file was skipped: {reason}");
+                skipped.push(index);
+                if let Some(delta) = delta.as_deref_mut() {
+                    delta.forget(kind, &rows[index]);
+                }
+                continue;
+            }
+            RowOutcome::NotStarted => {
+                not_started += 1;
+                if let Some(delta) = delta.as_deref_mut() {
+                    delta.forget(kind, &rows[index]);
+                }
+                continue;
+            }
+        };
+
+        let line = line_of(index, headers);
+        error!("Line {line} of the {kind} CSV file failed to import: {message}");
+        failed.push(index);
+        if let Some(delta) = delta.as_deref_mut() {
+            delta.forget(kind, &rows[index]);
+        }
+
+        abort |= match on_error {
+            OnError::Skip => false,
+            OnError::Abort => true,
+            OnError::Prompt if abort => true,
+            OnError::Prompt => ask_abort(kind, line, &message).await,
+        };
+        if abort {
+            gate.abort();
+        }
+    }
+
+    if failed.is_empty() && skipped.is_empty() {
+        return;
+    }
+    let mut written = failed.iter().chain(&skipped).copied().collect::<Vec<_>>();
+    written.sort();
+    let failed_rows = written
+        .iter()
+        .map(|index| &rows[*index])
+        .collect::<Vec<_>>();
+    write_failed_rows(kind, headers, &failed_rows);
+    if failed.is_empty() {
+        return;
+    }
+
+    if abort {
+        if not_started > 0 {
+            warn!("{not_started} {kind} row(s) were not imported because the import was aborted");
+        }
+        let Some(delta) = delta else {
+            error!(
+                "The import was aborted after the {kind}. Run imports with `--since-state` to \
+                record the rows which were imported, so that an aborted import can be resumed."
+            );
+            delta_import::remove_temp_files();
+            exit(1);
+        };
+        let completed = IMPORT_ORDER
+            .iter()
+            .take_while(|completed| **completed != kind)
+            .chain([&kind])
+            .copied()
+            .collect::<Vec<_>>();
+        delta.checkpoint(&completed);
+        let path = delta.path();
+        error!(
+            "The import was aborted after the {kind} (the rows which were imported have been \
+            recorded). Once the failed rows have been fixed, run the same import with \
+            `--since-state {path}` to resume it."
+        );
+        delta_import::remove_temp_files();
+        exit(1);
+    }
+
+    info!(
+        "Continuing the import without {} {kind} row(s)",
+        failed.len()
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_guard() {
+    let gate = RowGate::default();
+    let mut join_set = JoinSet::new();
+    join_set.spawn(guard(0, gate.clone(), async { Ok(()) }));
+    join_set.spawn(guard(1, gate.clone(), async { panic!("bad row") }));
+    join_set.spawn(guard(2, gate.clone(), async {
+        Err("no institution".to_string())
+    }));
+
+    let mut results = Vec::new();
+    while let Some(result) = join_set.join_next().await {
+        results.push(result.unwrap());
+    }
+    results.sort_by_key(|(row, _)| *row);
+    assert_eq!(
+        results,
+        vec![
+            (0, RowOutcome::Imported),
+            (1, RowOutcome::Failed("bad row".to_string())),
+            (2, RowOutcome::Skipped("no institution".to_string()))
+        ]
+    );
+
+    // once the import has been aborted, no more rows are started
+    gate.abort();
+    assert_eq!(
+        guard(3, gate.clone(), async { panic!("should not run") }).await,
+        (3, RowOutcome::NotStarted)
+    );
+    assert_eq!(line_of(1, Some(&StringRecord::new())), 3);
+    assert_eq!(line_of(1, None), 2);
+}
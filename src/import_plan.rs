@@ -0,0 +1,473 @@
+//! The planning phase of `tabbycat import`. All the CSV files are read up
+//! front, and every reference from one file to another (e.g. from a team to
+//! its institution, or from a clash to a judge) is checked against the
+//! entities which already exist in Tabbycat and those which the import will
+//! create. Dangling references are reported before anything is created, and
+//! the entities are then created in the order given by [`ImportPlan::steps`],
+//! so that everything a row refers to exists before the row is imported.
+
+use std::{collections::HashSet, process::exit};
+
+use csv::StringRecord;
+use serde::de::DeserializeOwned;
+use tabbycat_api::types::{Adjudicator, Team, Venue};
+use tracing::{error, info};
+
+use crate::{
+    Import,
+    clashes_csv::{self, read_clashes},
+    delta_import::{DeltaImport, write_temp_csv},
+    import::{
+        BreakCategoryRow, Clash, DrawRow, InstitutionRow, JudgeRow, PanelRow, RoomRow,
+        SpeakerCategoryRow, TeamRow, read_rows,
+    },
+    import_errors::{self, OnError},
+    names::normalise,
+    open_csv_file,
+    preflight::PreflightReport,
+};
+
+/// The entities which already exist in Tabbycat.
+pub struct ExistingEntities<'a> {
+    pub teams: &'a [Team],
+    pub judges: &'a [Adjudicator],
+    pub venues: &'a [Venue],
+}
+
+/// A set of names, compared after normalisation (see [`normalise`]), so that
+/// e.g. differences in case or whitespace are ignored.
+#[derive(Default)]
+struct Names(HashSet<String>);
+
+impl Names {
+    fn add(&mut self, name: &str) {
+        self.0.insert(normalise(name));
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.0.contains(&normalise(name))
+    }
+}
+
+/// A step of the import, which creates one kind of entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportStep {
+    Institutions,
+    Rooms,
+    Judges,
+    SpeakerCategories,
+    Teams,
+    Draw,
+    Panels,
+    Clashes,
+}
+
+impl ImportStep {
+    const ALL: [ImportStep; 8] = [
+        ImportStep::Institutions,
+        ImportStep::Rooms,
+        ImportStep::Judges,
+        ImportStep::SpeakerCategories,
+        ImportStep::Teams,
+        ImportStep::Draw,
+        ImportStep::Panels,
+        ImportStep::Clashes,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ImportStep::Institutions => "institutions",
+            ImportStep::Rooms => "rooms",
+            ImportStep::Judges => "judges",
+            ImportStep::SpeakerCategories => "speaker categories",
+            ImportStep::Teams => "teams",
+            ImportStep::Draw => "debates (draw)",
+            ImportStep::Panels => "panels",
+            ImportStep::Clashes => "clashes",
+        }
+    }
+
+    /// The steps which create the entities that the rows of this step refer
+    /// to.
+    fn depends_on(self) -> &'static [ImportStep] {
+        match self {
+            ImportStep::Institutions | ImportStep::Rooms | ImportStep::SpeakerCategories => &[],
+            ImportStep::Judges => &[ImportStep::Institutions],
+            ImportStep::Teams => &[ImportStep::Institutions, ImportStep::SpeakerCategories],
+            ImportStep::Draw => &[ImportStep::Rooms, ImportStep::Teams],
+            ImportStep::Panels => &[ImportStep::Rooms, ImportStep::Judges, ImportStep::Draw],
+            ImportStep::Clashes => &[
+                ImportStep::Institutions,
+                ImportStep::Judges,
+                ImportStep::Teams,
+            ],
+        }
+    }
+}
+
+/// The contents of every CSV file passed to `tabbycat import`.
+pub struct ImportPlan {
+    pub institutions: Vec<InstitutionRow>,
+    pub rooms: Vec<RoomRow>,
+    pub judges: Vec<JudgeRow>,
+    pub speaker_categories: Vec<SpeakerCategoryRow>,
+    pub teams: Vec<TeamRow>,
+    pub draw: Vec<DrawRow>,
+    pub panels: Vec<PanelRow>,
+    pub clashes: Vec<Clash>,
+}
+
+/// Reads the header and rows of the `kind` CSV file at `path` (the clashes
+/// file has no header), exiting if the file itself cannot be read.
+fn read_records(kind: &str, path: &str) -> (Option<StringRecord>, Vec<StringRecord>) {
+    if kind == "clashes" {
+        return (
+            None,
+            read_clashes(Some(path.to_string())).unwrap_or_default(),
+        );
+    }
+
+    let mut reader = open_csv_file(Some(path.to_string()), true).unwrap();
+    let header = match reader.headers() {
+        Ok(header) => header.clone(),
+        Err(e) => {
+            error!("Could not read {path}: {e}");
+            exit(1);
+        }
+    };
+    match reader.records().collect::<Result<Vec<_>, _>>() {
+        Ok(rows) => (Some(header), rows),
+        Err(e) => {
+            error!("Could not read {path}: {e}");
+            exit(1);
+        }
+    }
+}
+
+/// Checks that every row of the `kind` CSV file at `path` can be read as a
+/// `T`. Rows which cannot are handled according to `on_error` (and removed
+/// from `delta`, so that they are imported once they have been fixed), and the
+/// path of a copy of the file without them is returned.
+async fn check_rows<T: DeserializeOwned>(
+    kind: &str,
+    path: Option<String>,
+    on_error: OnError,
+    delta: Option<&mut DeltaImport>,
+) -> Option<String> {
+    let path = path?;
+    let (header, rows) = read_records(kind, &path);
+    let malformed = rows
+        .iter()
+        .enumerate()
+        .filter_map(|(index, row)| {
+            row.deserialize::<T>(header.as_ref())
+                .err()
+                .map(|e| (index, e.to_string()))
+        })
+        .collect::<Vec<_>>();
+    if malformed.is_empty() {
+        return Some(path);
+    }
+
+    import_errors::reject_rows(on_error, kind, header.as_ref(), &rows, &malformed).await;
+    if let Some(delta) = delta {
+        for (index, _) in &malformed {
+            delta.forget(kind, &rows[*index]);
+        }
+    }
+    let kept = rows
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !malformed.iter().any(|(malformed, _)| malformed == index))
+        .map(|(_, row)| row);
+    // the clashes file is written with a header row (as with `--since-state`),
+    // since the rows are read in whichever order its columns are in
+    let header = header.unwrap_or_else(|| StringRecord::from(clashes_csv::HEADER.to_vec()));
+    Some(write_temp_csv(kind, Some(&header), kept))
+}
+
+/// Checks that every row of the CSV files passed to `tabbycat import` can be
+/// read, before anything is imported or planned. Rows which cannot are handled
+/// according to `on_error` like rows which fail to import, and (unless the
+/// import is aborted) the returned [`Import`] reads copies of the files
+/// without them.
+pub async fn check_files(
+    import: Import,
+    on_error: OnError,
+    mut delta: Option<&mut DeltaImport>,
+) -> Import {
+    Import {
+        institutions_csv: check_rows::<InstitutionRow>(
+            "institutions",
+            import.institutions_csv.clone(),
+            on_error,
+            delta.as_deref_mut(),
+        )
+        .await,
+        rooms: check_rows::<RoomRow>(
+            "rooms",
+            import.rooms.clone(),
+            on_error,
+            delta.as_deref_mut(),
+        )
+        .await,
+        judges_csv: check_rows::<JudgeRow>(
+            "judges",
+            import.judges_csv.clone(),
+            on_error,
+            delta.as_deref_mut(),
+        )
+        .await,
+        speaker_categories_csv: check_rows::<SpeakerCategoryRow>(
+            "speaker_categories",
+            import.speaker_categories_csv.clone(),
+            on_error,
+            delta.as_deref_mut(),
+        )
+        .await,
+        break_categories_csv: check_rows::<BreakCategoryRow>(
+            "break_categories",
+            import.break_categories_csv.clone(),
+            on_error,
+            delta.as_deref_mut(),
+        )
+        .await,
+        teams_csv: check_rows::<TeamRow>(
+            "teams",
+            import.teams_csv.clone(),
+            on_error,
+            delta.as_deref_mut(),
+        )
+        .await,
+        draw_csv: check_rows::<DrawRow>("draw", import.draw_csv.clone(), on_error, None).await,
+        panels_csv: check_rows::<PanelRow>("panels", import.panels_csv.clone(), on_error, None)
+            .await,
+        clashes_csv: check_rows::<Clash>(
+            "clashes",
+            import.clashes_csv.clone(),
+            on_error,
+            delta.as_deref_mut(),
+        )
+        .await,
+        ..import
+    }
+}
+
+impl ImportPlan {
+    /// Reads every CSV file, which must have been checked with
+    /// [`check_files`] first (so every row can be read).
+    pub fn read(import: &Import) -> ImportPlan {
+        ImportPlan {
+            institutions: read_rows(&import.institutions_csv),
+            rooms: read_rows(&import.rooms),
+            judges: read_rows(&import.judges_csv),
+            speaker_categories: read_rows(&import.speaker_categories_csv),
+            teams: read_rows(&import.teams_csv),
+            draw: read_rows(&import.draw_csv),
+            panels: read_rows(&import.panels_csv),
+            clashes: read_clashes(import.clashes_csv.clone())
+                .unwrap_or_default()
+                .iter()
+                .map(|row| {
+                    row.deserialize(None)
+                        .expect("the clashes have been checked")
+                })
+                .collect(),
+        }
+    }
+
+    /// The steps of the import, in the order in which they are carried out:
+    /// each step comes after every step it depends on.
+    pub fn steps(&self) -> Vec<ImportStep> {
+        let mut steps = Vec::with_capacity(ImportStep::ALL.len());
+        while steps.len() < ImportStep::ALL.len() {
+            let next = ImportStep::ALL
+                .into_iter()
+                .find(|step| {
+                    !steps.contains(step) && step.depends_on().iter().all(|dep| steps.contains(dep))
+                })
+                .expect("the import steps should not depend on each other cyclically");
+            steps.push(next);
+        }
+        steps
+    }
+
+    /// The number of rows imported by `step`.
+    fn rows(&self, step: ImportStep) -> usize {
+        match step {
+            ImportStep::Institutions => self.institutions.len(),
+            ImportStep::Rooms => self.rooms.len(),
+            ImportStep::Judges => self.judges.len(),
+            ImportStep::SpeakerCategories => self.speaker_categories.len(),
+            ImportStep::Teams => self.teams.len(),
+            ImportStep::Draw => self.draw.len(),
+            ImportStep::Panels => self.panels.len(),
+            ImportStep::Clashes => self.clashes.len(),
+        }
+    }
+
+    /// Logs the steps of the import which have rows to import, in the order
+    /// in which they are carried out.
+    pub fn log(&self) {
+        let steps = self.steps().into_iter().filter(|step| self.rows(*step) > 0);
+        for (i, step) in steps.enumerate() {
+            info!("Step {}: import {} {}", i + 1, self.rows(step), step.name());
+        }
+    }
+
+    /// Reports every reference in the CSV files which does not refer to an
+    /// existing entity, or one which will be created by this import.
+    ///
+    /// `institution_code` gives the code of an institution which either exists
+    /// or is in the institutions CSV file. Rows which refer to any other
+    /// institution are only warned about, since they are skipped during the
+    /// import (and reported again at the end).
+    pub fn check_references(
+        &self,
+        existing: &ExistingEntities,
+        institution_code: &impl Fn(&str) -> Option<String>,
+        report: &mut PreflightReport,
+    ) {
+        let institution_exists = |name: &str| institution_code(name).is_some();
+
+        let mut rooms = Names::default();
+        for venue in existing.venues {
+            rooms.add(venue.name.as_str());
+        }
+        for row in &self.rooms {
+            rooms.add(&row.name);
+        }
+
+        let mut judges = Names::default();
+        for judge in existing.judges {
+            judges.add(&judge.name);
+        }
+        for row in &self.judges {
+            judges.add(&row.name);
+        }
+
+        let mut teams = Names::default();
+        let mut speakers = Names::default();
+        for team in existing.teams {
+            teams.add(&team.long_name);
+            teams.add(&team.short_name);
+            for speaker in &team.speakers {
+                speakers.add(&speaker.name);
+            }
+        }
+        for row in &self.teams {
+            teams.add(&row.full_name);
+            if let Some(short_name) = &row.short_name {
+                teams.add(short_name);
+            }
+            // teams may also be referred to by their name with the
+            // institution prefix applied
+            if let Some(code) = row.institution.as_deref().and_then(institution_code) {
+                teams.add(&format!("{code} {}", row.full_name.trim()));
+                if let Some(short_name) = &row.short_name {
+                    teams.add(&format!("{code} {}", short_name.trim()));
+                }
+            }
+            for speaker in &row.speakers {
+                speakers.add(&speaker.name);
+            }
+        }
+
+        for row in &self.judges {
+            for inst in row.institution.iter().chain(&row.institution_clashes) {
+                if !institution_exists(inst) {
+                    report.warn(format!(
+                        "Judge {} refers to institution {inst:?}, which does not exist \
+                        and is not in the institutions CSV file, so will not be imported",
+                        row.name
+                    ));
+                }
+            }
+        }
+
+        for row in &self.teams {
+            if let Some(inst) = &row.institution
+                && !institution_exists(inst)
+            {
+                report.warn(format!(
+                    "Team {} refers to institution {inst:?}, which does not exist and \
+                    is not in the institutions CSV file, so will not be imported",
+                    row.full_name
+                ));
+            }
+        }
+
+        for row in &self.draw {
+            if !rooms.contains(&row.room) {
+                report.error(format!(
+                    "The draw refers to room {:?}, which does not exist",
+                    row.room
+                ));
+            }
+            for team in [&row.aff, &row.neg, &row.cg, &row.co].into_iter().flatten() {
+                if !teams.contains(team) {
+                    report.error(format!(
+                        "The draw (room {}) refers to team {team:?}, which does not exist",
+                        row.room
+                    ));
+                }
+            }
+        }
+
+        for row in &self.panels {
+            // panels can also refer to debates by their ID
+            if row.room.trim().parse::<i64>().is_err() && !rooms.contains(&row.room) {
+                report.error(format!(
+                    "The panels refer to room {:?}, which does not exist",
+                    row.room
+                ));
+            }
+            for judge in row.chair.iter().chain(&row.panellists).chain(&row.trainees) {
+                if !judges.contains(judge) {
+                    report.error(format!(
+                        "The panel for room {} refers to judge {judge:?}, which does not exist",
+                        row.room
+                    ));
+                }
+            }
+        }
+
+        for clash in &self.clashes {
+            for object in [&clash.object_1, &clash.object_2] {
+                if !(institution_exists(object)
+                    || judges.contains(object)
+                    || teams.contains(object)
+                    || speakers.contains(object))
+                {
+                    report.error(format!(
+                        "The clash between {:?} and {:?} refers to {object:?}, which is not \
+                        an institution, judge, team or speaker",
+                        clash.object_1, clash.object_2
+                    ));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_steps_are_in_dependency_order() {
+    let plan = ImportPlan {
+        institutions: vec![],
+        rooms: vec![],
+        judges: vec![],
+        speaker_categories: vec![],
+        teams: vec![],
+        draw: vec![],
+        panels: vec![],
+        clashes: vec![],
+    };
+    let steps = plan.steps();
+    assert_eq!(steps.len(), ImportStep::ALL.len());
+    for (i, step) in steps.iter().enumerate() {
+        for dep in step.depends_on() {
+            assert!(steps[..i].contains(dep), "{step:?} runs before {dep:?}");
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::process::exit;
+
+use serde_json::json;
+use tabbycat_api::types::RoundPairing;
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_round_and_pairings, get_rounds, get_teams, get_venues, pairing_in_room},
+    break_live::{is_prelim, live_teams, remaining_prelims},
+    request_manager::RequestManager,
+};
+
+/// The importance given to debates containing a team which can still break
+/// when importances are computed automatically.
+const LIVE_IMPORTANCE: i64 = 1;
+/// The importance given to debates in which no team can break.
+const DEAD_IMPORTANCE: i64 = -1;
+
+async fn patch_importance(pairing: &RoundPairing, importance: i64, manager: RequestManager) {
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .patch(&pairing.url)
+                .json(&json!({ "importance": importance }))
+                .build()
+                .unwrap()
+        })
+        .await;
+    if !resp.status().is_success() {
+        error!(
+            "Failed to set the importance of debate {}: {} {}",
+            pairing.id,
+            resp.status(),
+            resp.text().await.unwrap()
+        );
+        exit(1);
+    }
+}
+
+/// Sets the importance (between -2 and 2) of the debate in `room` (either the
+/// name of the room, or the ID of the debate).
+pub async fn set_importance(auth: Auth, round: &str, room: &str, importance: i64) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    if !(-2..=2).contains(&importance) {
+        error!("The importance should be between -2 and 2 (not {importance}).");
+        exit(1);
+    }
+
+    let ((round, pairings), venues) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_venues(&auth, manager.clone())
+    );
+
+    let pairing = pairing_in_room(&pairings, &venues, room).unwrap_or_else(|| {
+        error!(
+            "No debate in round {} matches room {room}",
+            round.name.as_str()
+        );
+        exit(1);
+    });
+
+    patch_importance(pairing, importance, manager).await;
+    info!("Set the importance of {room} to {importance}");
+}
+
+/// Sets the importance of every debate in the round based on whether the
+/// teams in it can still break. Debates with a live team (see
+/// [`crate::break_live::Liveness`]) are given a high importance and all other
+/// debates a low importance. `break_size` overrides the size of the general
+/// break category.
+pub async fn set_importance_automatically(auth: Auth, round: &str, break_size: Option<usize>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((round, pairings), rounds, teams) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_rounds(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+    );
+
+    if !is_prelim(&round) {
+        warn!("{} is not a preliminary round.", round.name.as_str());
+    }
+    let remaining = remaining_prelims(&rounds, Some(&round));
+    let live_teams = live_teams(&auth, &teams, remaining, break_size, manager.clone()).await;
+
+    info!("{remaining} preliminary round(s) remain");
+
+    for pairing in &pairings {
+        let live = pairing
+            .teams
+            .iter()
+            .any(|team| live_teams.contains(&team.team));
+        let importance = if live {
+            LIVE_IMPORTANCE
+        } else {
+            DEAD_IMPORTANCE
+        };
+        patch_importance(pairing, importance, manager.clone()).await;
+        info!(
+            "Debate {} is {} (importance {importance})",
+            pairing.id,
+            if live { "live" } else { "dead" }
+        );
+    }
+}
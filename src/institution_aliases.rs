@@ -0,0 +1,152 @@
+use std::{collections::BTreeMap, process::exit};
+
+use serde::{Deserialize, Serialize};
+use tabbycat_api::types::PerTournamentInstitution;
+use tracing::{error, info};
+
+use crate::names::{names_match, normalise};
+
+/// The name of the file (in the current working directory) in which
+/// institution aliases are stored between imports.
+pub const INSTITUTION_ALIASES_FILE: &str = "institution_aliases.toml";
+
+/// Alternative names for institutions (e.g. "LSE" for "London School of
+/// Economics"), which are read from the `aliases` column of the institutions
+/// CSV file. These are not stored in Tabbycat, so they are saved locally in
+/// order that later imports of teams and judges can use them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct InstitutionAliases {
+    /// Maps each alias to the code of the institution it refers to.
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+}
+
+impl InstitutionAliases {
+    /// Loads the aliases from the working directory (if any have been saved).
+    pub fn load() -> InstitutionAliases {
+        let text = match std::fs::read_to_string(INSTITUTION_ALIASES_FILE) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return InstitutionAliases::default();
+            }
+            Err(e) => {
+                error!("Could not read {INSTITUTION_ALIASES_FILE}: {e}");
+                exit(1)
+            }
+        };
+
+        match toml::from_str(&text) {
+            Ok(aliases) => aliases,
+            Err(e) => {
+                error!("Your {INSTITUTION_ALIASES_FILE} file is malformed: {e}");
+                exit(1)
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let text = toml::to_string_pretty(self).unwrap();
+        std::fs::write(INSTITUTION_ALIASES_FILE, text).unwrap();
+        info!(
+            "Saved {} institution aliases to {INSTITUTION_ALIASES_FILE}",
+            self.aliases.len()
+        );
+    }
+
+    pub fn add(&mut self, alias: &str, code: &str) {
+        let alias = alias.trim();
+        if !alias.is_empty() {
+            self.aliases.insert(alias.to_string(), code.to_string());
+        }
+    }
+
+    /// Returns the institution code an alias refers to, or `name` unchanged
+    /// if it is not an alias.
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        let name = name.trim();
+        self.aliases
+            .iter()
+            .find(|(alias, _)| names_match(alias, name))
+            .map(|(_, code)| code.as_str())
+            .unwrap_or(name)
+    }
+
+    /// Finds the institution with the given name, code or alias (compared
+    /// using [`names_match`]).
+    pub fn find<'a>(
+        &self,
+        institutions: &'a [PerTournamentInstitution],
+        name: &str,
+    ) -> Option<&'a PerTournamentInstitution> {
+        let name = self.resolve(name);
+        institutions.iter().find(|inst| {
+            names_match(inst.name.as_str(), name) || names_match(inst.code.as_str(), name)
+        })
+    }
+
+    /// Suggests the institution whose name, code or alias is most similar to
+    /// `name` (for use in error messages).
+    pub fn suggest<'a>(
+        &self,
+        institutions: &'a [PerTournamentInstitution],
+        name: &str,
+    ) -> Option<&'a PerTournamentInstitution> {
+        let name = normalise(name);
+        let candidates = institutions
+            .iter()
+            .flat_map(|inst| {
+                [inst.name.as_str(), inst.code.as_str()]
+                    .into_iter()
+                    .map(move |candidate| (candidate.to_string(), inst))
+            })
+            .chain(self.aliases.iter().filter_map(|(alias, code)| {
+                institutions
+                    .iter()
+                    .find(|inst| names_match(inst.code.as_str(), code))
+                    .map(|inst| (alias.clone(), inst))
+            }));
+
+        candidates
+            .map(|(candidate, inst)| (edit_distance(&name, &normalise(&candidate)), inst))
+            .filter(|(distance, _)| *distance <= name.chars().count().max(3) / 3)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, inst)| inst)
+    }
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(row[j + 1])
+            };
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("oxford", "oxford"), 0);
+}
+
+#[cfg(test)]
+#[test]
+fn test_resolve_alias() {
+    let mut aliases = InstitutionAliases::default();
+    aliases.add(" LSE ", "LSE-UK");
+    assert_eq!(aliases.resolve("lse"), "LSE-UK");
+    assert_eq!(aliases.resolve("Oxford"), "Oxford");
+}
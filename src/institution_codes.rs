@@ -0,0 +1,217 @@
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use tabbycat_api::types::PerTournamentInstitution;
+use tracing::info;
+
+use crate::{import::InstitutionRow, names::names_match};
+
+/// The longest institution code Tabbycat accepts.
+pub const MAX_CODE_LENGTH: usize = 20;
+
+/// Words which are left out of the initials of an institution's name.
+const MINOR_WORDS: &[&str] = &[
+    "of", "the", "and", "for", "at", "in", "on", "de", "del", "des", "di", "du", "da", "la", "le",
+    "los", "las", "el", "der", "die", "das", "und", "et", "y", "e",
+];
+
+/// A code generated for an institution in the institutions CSV file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedCode {
+    pub full_name: String,
+    /// The `short_code` given in the CSV file (if any), which was too long.
+    pub original: Option<String>,
+    pub code: String,
+}
+
+/// The first `n` characters of `s`, without trailing whitespace.
+fn truncate(s: &str, n: usize) -> String {
+    s.chars().take(n).collect::<String>().trim_end().to_string()
+}
+
+/// Abbreviates the name of an institution: the initials of its words (e.g.
+/// `LSE` for "London School of Economics"), or for names made up of a single
+/// word, the name itself (truncated to [`MAX_CODE_LENGTH`] characters).
+fn abbreviate(full_name: &str) -> String {
+    let words = full_name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let major = words
+        .iter()
+        .filter(|word| {
+            !MINOR_WORDS
+                .iter()
+                .any(|minor| word.eq_ignore_ascii_case(minor))
+        })
+        .collect::<Vec<_>>();
+
+    match major.as_slice() {
+        [] => truncate(full_name.trim(), MAX_CODE_LENGTH),
+        [word] => truncate(word, MAX_CODE_LENGTH),
+        words => truncate(
+            &words
+                .iter()
+                .filter_map(|word| word.chars().next())
+                .flat_map(char::to_uppercase)
+                .collect::<String>(),
+            MAX_CODE_LENGTH,
+        ),
+    }
+}
+
+/// Chooses a code for `full_name` which is not in `used`: its abbreviation,
+/// or failing that its name truncated to [`MAX_CODE_LENGTH`] characters, or
+/// failing that its abbreviation followed by a number.
+fn unique_code(full_name: &str, used: &[String]) -> String {
+    let is_free = |code: &str| !code.is_empty() && !used.iter().any(|u| names_match(u, code));
+
+    let abbreviation = abbreviate(full_name);
+    let truncated = truncate(full_name.trim(), MAX_CODE_LENGTH);
+    if is_free(&abbreviation) {
+        return abbreviation;
+    }
+    if is_free(&truncated) {
+        return truncated;
+    }
+    (2..)
+        .map(|n| {
+            let suffix = format!(" {n}");
+            let base = truncate(&abbreviation, MAX_CODE_LENGTH - suffix.len());
+            format!("{base}{suffix}")
+        })
+        .find(|code| is_free(code))
+        .unwrap()
+}
+
+/// Gives every row of the institutions CSV file which has no `short_code`
+/// (or one longer than Tabbycat accepts) a code: that of the institution with
+/// the same name if it already exists, and otherwise a newly generated one
+/// which no other institution uses. Returns the codes which were generated.
+pub fn assign_codes(
+    rows: &mut [InstitutionRow],
+    existing: &[PerTournamentInstitution],
+) -> Vec<GeneratedCode> {
+    let mut used = existing
+        .iter()
+        .map(|inst| inst.code.as_str().to_string())
+        .chain(
+            rows.iter()
+                .map(|row| row.short_code.trim().to_string())
+                .filter(|code| !code.is_empty() && code.chars().count() <= MAX_CODE_LENGTH),
+        )
+        .collect::<Vec<_>>();
+
+    let mut generated = Vec::new();
+    for row in rows.iter_mut() {
+        let code = row.short_code.trim();
+        if !code.is_empty() && code.chars().count() <= MAX_CODE_LENGTH {
+            continue;
+        }
+        let original = (!code.is_empty()).then(|| code.to_string());
+
+        if let Some(inst) = existing
+            .iter()
+            .find(|inst| names_match(&inst.name, &row.full_name))
+        {
+            row.short_code = inst.code.as_str().to_string();
+            continue;
+        }
+
+        let code = unique_code(&row.full_name, &used);
+        used.push(code.clone());
+        row.short_code = code.clone();
+        generated.push(GeneratedCode {
+            full_name: row.full_name.clone(),
+            original,
+            code,
+        });
+    }
+    generated
+}
+
+/// Shows the codes which were generated, and (if `path` is given) writes them
+/// to a CSV file so that registrants can be told their institution's code.
+pub fn report(generated: &[GeneratedCode], path: Option<&str>) {
+    if generated.is_empty() {
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["Institution", "Code in CSV file", "Generated code"]);
+    for code in generated {
+        table.add_row(vec![
+            code.full_name.clone(),
+            code.original.clone().unwrap_or_default(),
+            code.code.clone(),
+        ]);
+    }
+    info!(
+        "Generated codes for {} institution(s) without a valid short code",
+        generated.len()
+    );
+    println!("{table}");
+
+    if let Some(path) = path {
+        let mut writer = csv::Writer::from_path(path).unwrap();
+        writer
+            .write_record(["full_name", "original_short_code", "short_code"])
+            .unwrap();
+        for code in generated {
+            writer
+                .write_record([
+                    code.full_name.as_str(),
+                    code.original.as_deref().unwrap_or_default(),
+                    code.code.as_str(),
+                ])
+                .unwrap();
+        }
+        writer.flush().unwrap();
+        info!("Saved the generated institution codes into CSV file {path}");
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_institution_codes() {
+    assert_eq!(abbreviate("London School of Economics"), "LSE");
+    assert_eq!(abbreviate("University of Cape Town"), "UCT");
+    assert_eq!(abbreviate("Universidad de los Andes"), "UA");
+    assert_eq!(abbreviate("Harvard"), "Harvard");
+    assert_eq!(abbreviate("Technische Universität München"), "TUM");
+    assert_eq!(
+        abbreviate("Llanfairpwllgwyngyllgogerychwyrndrobwll"),
+        "Llanfairpwllgwyngyll"
+    );
+
+    let used = vec!["LSE".to_string()];
+    assert_eq!(
+        unique_code("London School of Economics", &used),
+        "London School of Eco"
+    );
+    let used = vec!["lse".to_string(), "London School of Eco".to_string()];
+    assert_eq!(unique_code("London School of Economics", &used), "LSE 2");
+
+    let row = |full_name: &str, short_code: &str| InstitutionRow {
+        region: None,
+        short_code: short_code.to_string(),
+        full_name: full_name.to_string(),
+        aliases: Vec::new(),
+    };
+    let mut rows = vec![
+        row("University of Sydney", ""),
+        row("Sydney University", "US"),
+        row("Monash University", "Monash University Clayton Campus"),
+    ];
+    let generated = assign_codes(&mut rows, &[]);
+    assert_eq!(rows[0].short_code, "University of Sydney");
+    assert_eq!(rows[1].short_code, "US");
+    assert_eq!(rows[2].short_code, "MU");
+    assert_eq!(generated.len(), 2);
+    assert_eq!(
+        generated[1].original.as_deref(),
+        Some("Monash University Clayton Campus")
+    );
+}
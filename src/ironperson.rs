@@ -0,0 +1,301 @@
+use std::{collections::BTreeMap, process::exit};
+
+use comfy_table::{Cell, Color, ContentArrangement, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tabbycat_api::types::DrawStatusEnum;
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_ballots, get_round, get_rounds, get_teams, pairings_of_round},
+    names::names_match,
+    request_manager::RequestManager,
+};
+
+/// The file (in the current working directory) in which iron-person speeches
+/// are recorded.
+pub const LEDGER_FILE: &str = "ironperson.json";
+
+/// An iron-person speech: a speaker who gave two substantive speeches for
+/// their team in a round (because a teammate was missing).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IronSpeech {
+    /// The abbreviation of the round.
+    pub round: String,
+    pub team: String,
+    pub speaker: String,
+    pub team_url: String,
+    pub speaker_url: String,
+}
+
+/// Reads the iron-person speeches recorded with `ironperson mark` (none, if
+/// the ledger does not exist yet).
+fn load_ledger() -> Vec<IronSpeech> {
+    let text = match std::fs::read_to_string(LEDGER_FILE) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            error!("Could not read {LEDGER_FILE}: {e}");
+            exit(1)
+        }
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        error!("Your {LEDGER_FILE} file is malformed: {e}");
+        exit(1)
+    })
+}
+
+fn save_ledger(ledger: &[IronSpeech]) {
+    if let Err(e) = std::fs::write(LEDGER_FILE, serde_json::to_string_pretty(ledger).unwrap()) {
+        error!("Could not write {LEDGER_FILE}: {e}");
+        exit(1);
+    }
+}
+
+/// Records that `speaker` gave an iron-person speech for `team` in `round`.
+/// A team can only have one iron-person speech per round, so this replaces
+/// any speech already recorded for the team in that round.
+pub async fn mark(auth: Auth, round: &str, team: &str, speaker: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (round, teams) = tokio::join!(
+        get_round(round, &auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+    );
+
+    let Some(team) = teams
+        .iter()
+        .find(|t| names_match(&t.short_name, team) || names_match(&t.long_name, team))
+    else {
+        error!("No team is called {team}");
+        exit(1);
+    };
+    let Some(speaker) = team.speakers.iter().find(|s| names_match(&s.name, speaker)) else {
+        error!("{} has no speaker called {speaker}", team.short_name);
+        exit(1);
+    };
+
+    let speech = IronSpeech {
+        round: round.abbreviation.as_str().to_string(),
+        team: team.short_name.clone(),
+        speaker: speaker.name.to_string(),
+        team_url: team.url.clone(),
+        speaker_url: speaker.url.clone(),
+    };
+
+    let mut ledger = load_ledger();
+    if let Some(existing) = ledger
+        .iter_mut()
+        .find(|s| s.round == speech.round && s.team_url == speech.team_url)
+    {
+        warn!(
+            "Replacing the iron-person speech already recorded for {} in {} (by {})",
+            speech.team, speech.round, existing.speaker
+        );
+        *existing = speech;
+    } else {
+        ledger.push(speech);
+    }
+    save_ledger(&ledger);
+    info!(
+        "Recorded an iron-person speech by {} for {} in {}",
+        speaker.name.as_str(),
+        team.short_name,
+        round.name.as_str()
+    );
+}
+
+/// Finds the iron-person speeches on a sheet of a ballot, as (team URL,
+/// speaker URL) pairs. Tabbycat marks the second speech given by an
+/// iron-person as a "ghost" speech, which is not counted in the speaker tab.
+fn ghost_speeches(
+    sheet: &Value,
+    team_on_side: impl Fn(&str) -> Option<String>,
+) -> Vec<(String, String)> {
+    let mut speeches = Vec::new();
+    for team in sheet["teams"].as_array().into_iter().flatten() {
+        let Some(team_url) = team["team"]
+            .as_str()
+            .map(|url| url.to_string())
+            .or_else(|| team["side"].as_str().and_then(&team_on_side))
+        else {
+            continue;
+        };
+        for speech in team["speeches"].as_array().into_iter().flatten() {
+            if speech["ghost"].as_bool() != Some(true) {
+                continue;
+            }
+            if let Some(speaker) = speech["speaker"].as_str() {
+                speeches.push((team_url.clone(), speaker.to_string()));
+            }
+        }
+    }
+    speeches
+}
+
+/// Combines the speeches recorded in the ledger with those found on the
+/// ballots (preferring the ledger where both have a speech for a team in a
+/// round), grouped by team URL.
+fn by_team(ledger: &[IronSpeech], ballots: &[IronSpeech]) -> BTreeMap<String, Vec<IronSpeech>> {
+    let mut teams: BTreeMap<String, Vec<IronSpeech>> = BTreeMap::new();
+    for speech in ledger.iter().chain(ballots) {
+        let speeches = teams.entry(speech.team_url.clone()).or_default();
+        if !speeches.iter().any(|s| s.round == speech.round) {
+            speeches.push(speech.clone());
+        }
+    }
+    teams
+}
+
+/// Shows the iron-person speeches given by each team, both those recorded with
+/// `ironperson mark` and those marked on confirmed ballots. Teams with more
+/// than `max` iron-person speeches are highlighted.
+pub async fn report(auth: Auth, max: Option<usize>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (mut rounds, teams) = tokio::join!(
+        get_rounds(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+    );
+    rounds.sort_by_key(|round| round.seq);
+
+    let speaker_name = |url: &str| {
+        teams
+            .iter()
+            .flat_map(|team| &team.speakers)
+            .find(|speaker| speaker.url == url)
+            .map(|speaker| speaker.name.to_string())
+            .unwrap_or_else(|| url.to_string())
+    };
+
+    let mut from_ballots = Vec::new();
+    for round in rounds
+        .iter()
+        .filter(|round| matches!(round.draw_status, Some(DrawStatusEnum::R)))
+    {
+        for pairing in pairings_of_round(&auth, round, manager.clone()).await {
+            let team_on_side = |side: &str| {
+                pairing
+                    .teams
+                    .iter()
+                    .find(|team| serde_json::to_value(&team.side).unwrap().as_str() == Some(side))
+                    .map(|team| team.team.clone())
+            };
+            for ballot in get_ballots(&auth, &pairing, manager.clone()).await {
+                if ballot["confirmed"].as_bool() != Some(true) {
+                    continue;
+                }
+                // every sheet of a ballot has the same speakers
+                let Some(sheet) = ballot["result"]["sheets"].get(0) else {
+                    continue;
+                };
+                for (team_url, speaker_url) in ghost_speeches(sheet, team_on_side) {
+                    from_ballots.push(IronSpeech {
+                        round: round.abbreviation.as_str().to_string(),
+                        team: teams
+                            .iter()
+                            .find(|team| team.url == team_url)
+                            .map(|team| team.short_name.clone())
+                            .unwrap_or_else(|| team_url.clone()),
+                        speaker: speaker_name(&speaker_url),
+                        team_url,
+                        speaker_url,
+                    });
+                }
+            }
+        }
+    }
+
+    let by_team = by_team(&load_ledger(), &from_ballots);
+    if by_team.is_empty() {
+        println!("No iron-person speeches have been recorded");
+        return;
+    }
+
+    let round_position = |abbreviation: &str| {
+        rounds
+            .iter()
+            .position(|round| round.abbreviation.as_str() == abbreviation)
+            .unwrap_or(usize::MAX)
+    };
+    let mut rows = by_team.into_values().collect::<Vec<_>>();
+    rows.sort_by_key(|speeches| std::cmp::Reverse(speeches.len()));
+
+    let mut table = Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Team", "Iron-person speeches", "Rounds"]);
+    let mut over_cap = 0;
+    for mut speeches in rows {
+        speeches.sort_by_key(|speech| round_position(&speech.round));
+        let count = Cell::new(speeches.len());
+        let count = if max.is_some_and(|max| speeches.len() > max) {
+            over_cap += 1;
+            count.fg(Color::Red)
+        } else {
+            count
+        };
+        table.add_row(vec![
+            Cell::new(&speeches[0].team),
+            count,
+            Cell::new(
+                speeches
+                    .iter()
+                    .map(|speech| format!("{} ({})", speech.round, speech.speaker))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        ]);
+    }
+    println!("{table}");
+
+    if let Some(max) = max {
+        if over_cap > 0 {
+            warn!("{over_cap} team(s) have given more than {max} iron-person speech(es)");
+        } else {
+            info!("No team has given more than {max} iron-person speech(es)");
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_iron_speeches() {
+    let sheet = serde_json::json!({
+        "teams": [
+            {
+                "side": "aff",
+                "speeches": [
+                    { "speaker": "a1" },
+                    { "speaker": "a1", "ghost": true },
+                ]
+            },
+            {
+                "side": "neg",
+                "team": "neg-team",
+                "speeches": [
+                    { "speaker": "n1" },
+                    { "speaker": "n2", "ghost": false },
+                ]
+            }
+        ]
+    });
+    let speeches = ghost_speeches(&sheet, |side| Some(format!("{side}-team")));
+    assert_eq!(speeches, vec![("aff-team".to_string(), "a1".to_string())]);
+
+    let speech = |round: &str, speaker: &str| IronSpeech {
+        round: round.to_string(),
+        team: "A".to_string(),
+        speaker: speaker.to_string(),
+        team_url: "a".to_string(),
+        speaker_url: speaker.to_string(),
+    };
+    let teams = by_team(
+        &[speech("R1", "Alex")],
+        &[speech("R1", "Sam"), speech("R2", "Sam")],
+    );
+    assert_eq!(teams["a"], vec![speech("R1", "Alex"), speech("R2", "Sam")]);
+}
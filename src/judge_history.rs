@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use tracing::{info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_feedbacks, get_judges, get_rounds, pairings_of_round},
+    breaking_judges::{feedback_scores, mean},
+    names::{names_match, normalise},
+    request_manager::RequestManager,
+};
+
+/// What a single judge did at a single tournament.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TournamentRecord {
+    pub tournament: String,
+    pub name: String,
+    pub chaired: usize,
+    pub panelled: usize,
+    pub trainee: usize,
+    pub feedback: Vec<f64>,
+}
+
+impl TournamentRecord {
+    fn rounds(&self) -> usize {
+        self.chaired + self.panelled + self.trainee
+    }
+}
+
+/// A judge's records at every tournament, merged by name (ignoring case and
+/// surrounding whitespace, as judges do not share an ID across tournaments).
+#[derive(Debug, Clone, Default)]
+pub struct JudgeHistory {
+    pub name: String,
+    pub records: Vec<TournamentRecord>,
+}
+
+impl JudgeHistory {
+    fn total(&self) -> TournamentRecord {
+        TournamentRecord {
+            tournament: format!("{} tournament(s)", self.records.len()),
+            name: self.name.clone(),
+            chaired: self.records.iter().map(|r| r.chaired).sum(),
+            panelled: self.records.iter().map(|r| r.panelled).sum(),
+            trainee: self.records.iter().map(|r| r.trainee).sum(),
+            feedback: self
+                .records
+                .iter()
+                .flat_map(|r| r.feedback.iter().copied())
+                .collect(),
+        }
+    }
+}
+
+/// Collects the record of every judge at the tournament `auth` refers to.
+async fn tournament_records(auth: &Auth) -> Vec<TournamentRecord> {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (judges, rounds, feedbacks) = tokio::join!(
+        get_judges(auth, manager.clone()),
+        get_rounds(auth, manager.clone()),
+        get_feedbacks(auth, manager.clone()),
+    );
+
+    let mut records = judges
+        .iter()
+        .map(|judge| {
+            (
+                judge.url.clone(),
+                TournamentRecord {
+                    tournament: auth.tournament_slug.clone(),
+                    name: judge.name.clone(),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    for round in &rounds {
+        for pairing in pairings_of_round(auth, round, manager.clone()).await {
+            let Some(adjs) = pairing.adjudicators else {
+                continue;
+            };
+            if let Some(record) = adjs.chair.and_then(|chair| records.get_mut(&chair)) {
+                record.chaired += 1;
+            }
+            for panellist in &adjs.panellists {
+                if let Some(record) = records.get_mut(panellist) {
+                    record.panelled += 1;
+                }
+            }
+            for trainee in &adjs.trainees {
+                if let Some(record) = records.get_mut(trainee) {
+                    record.trainee += 1;
+                }
+            }
+        }
+    }
+
+    for (url, scores) in feedback_scores(&feedbacks) {
+        if let Some(record) = records.get_mut(&url) {
+            record.feedback = scores;
+        }
+    }
+
+    records.into_values().collect()
+}
+
+/// Merges the records from several tournaments by judge name.
+pub fn merge_records(records: Vec<TournamentRecord>) -> Vec<JudgeHistory> {
+    let mut histories: BTreeMap<String, JudgeHistory> = BTreeMap::new();
+    for record in records {
+        let history = histories
+            .entry(normalise(&record.name))
+            .or_insert_with(|| JudgeHistory {
+                name: record.name.trim().to_string(),
+                records: vec![],
+            });
+        history.records.push(record);
+    }
+    histories.into_values().collect()
+}
+
+fn average(scores: &[f64]) -> String {
+    mean(scores)
+        .map(|mean| format!("{mean:.2}"))
+        .unwrap_or_default()
+}
+
+fn record_row(first_column: String, record: &TournamentRecord) -> Vec<String> {
+    let rounds = record.rounds();
+    vec![
+        first_column,
+        rounds.to_string(),
+        record.chaired.to_string(),
+        record.panelled.to_string(),
+        record.trainee.to_string(),
+        if rounds > 0 {
+            format!("{:.0}%", 100.0 * record.chaired as f64 / rounds as f64)
+        } else {
+            String::new()
+        },
+        record.feedback.len().to_string(),
+        average(&record.feedback),
+    ]
+}
+
+/// Summarises the judging record of every judge across the tournaments in
+/// `profiles` (see `tabbycat set --profile`). If `judge` is given, a
+/// per-tournament breakdown for that judge is shown instead.
+pub async fn judge_history(profiles: Vec<Auth>, judge: Option<String>) {
+    let mut records = Vec::new();
+    for auth in &profiles {
+        let tournament_records = tournament_records(auth).await;
+        info!(
+            "Fetched {} judges from {}",
+            tournament_records.len(),
+            auth.tournament_slug
+        );
+        records.extend(tournament_records);
+    }
+    let histories = merge_records(records);
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS);
+    let stats = [
+        "rounds",
+        "chaired",
+        "panellist",
+        "trainee",
+        "chair %",
+        "feedback",
+        "avg. score",
+    ];
+
+    match judge {
+        Some(name) => {
+            let Some(history) = histories
+                .iter()
+                .find(|history| names_match(&history.name, &name))
+            else {
+                warn!("No judge named {name} judged at any of these tournaments.");
+                return;
+            };
+
+            let mut header = vec!["tournament"];
+            header.extend(stats);
+            table.set_header(header);
+            for record in &history.records {
+                table.add_row(record_row(record.tournament.clone(), record));
+            }
+            let total = history.total();
+            table.add_row(record_row(total.tournament.clone(), &total));
+
+            println!("{}", history.name);
+        }
+        None => {
+            let mut header = vec!["judge", "tournaments"];
+            header.extend(stats);
+            table.set_header(header);
+            for history in &histories {
+                let mut row = record_row(history.name.clone(), &history.total());
+                row.insert(1, history.records.len().to_string());
+                table.add_row(row);
+            }
+        }
+    }
+
+    println!("{table}");
+}
+
+#[cfg(test)]
+#[test]
+fn test_merge_records() {
+    let record = |tournament: &str, name: &str, chaired: usize| TournamentRecord {
+        tournament: tournament.to_string(),
+        name: name.to_string(),
+        chaired,
+        ..Default::default()
+    };
+
+    let histories = merge_records(vec![
+        record("a", "Jane Doe", 3),
+        record("a", "John Smith", 1),
+        record("b", "jane doe ", 4),
+    ]);
+
+    assert_eq!(histories.len(), 2);
+    assert_eq!(histories[0].name, "Jane Doe");
+    assert_eq!(histories[0].records.len(), 2);
+    assert_eq!(histories[0].total().chaired, 7);
+}
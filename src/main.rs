@@ -1,18 +1,77 @@
+pub mod adj_core;
+pub mod anonymise;
 pub mod api_utils;
+pub mod archive;
+pub mod audit_log;
+pub mod availability;
+pub mod ballot_compare;
+pub mod ballot_lint;
+pub mod ballots;
+pub mod bot;
 pub mod break_eligibility;
+pub mod break_live;
+pub mod breaking_judges;
+pub mod cache;
+pub mod clash_graph;
+pub mod clash_inference;
+pub mod clashes_csv;
 pub mod clear_rooms;
+pub mod conflicts;
+pub mod contacts;
+pub mod debate_flags;
+pub mod defaults;
+pub mod delta_import;
 pub mod dispatch_req;
+pub mod diversity;
+pub mod draw_image;
 pub mod edit_draw;
 pub mod export;
+pub mod feedback;
+pub mod fees;
+pub mod finals;
+pub mod hooks;
+pub mod i18n;
 pub mod import;
+pub mod import_config;
+pub mod import_errors;
+pub mod import_plan;
+pub mod importance;
+pub mod institution_aliases;
+pub mod institution_codes;
+pub mod ironperson;
+pub mod judge_history;
+pub mod meetings;
+pub mod motions;
+pub mod names;
+pub mod panel_diversity;
+pub mod panel_policy;
+pub mod preflight;
+pub mod raw_api;
+pub mod release;
+pub mod report;
 pub mod request_manager;
+pub mod rooms;
 pub mod save_panels;
+pub mod schedule;
 pub mod sensible;
+pub mod shared_rooms;
+pub mod signage;
+pub mod snapshot_diff;
+pub mod standings;
+pub mod suggest_moves;
+pub mod tabular;
+pub mod terminal;
+pub mod venue_metadata;
 pub mod view_draw;
+pub mod watch;
 
-use std::process::exit;
+use std::{
+    io::{Cursor, Read},
+    process::exit,
+    sync::OnceLock,
+};
 
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use csv::Trim;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -20,18 +79,45 @@ use tracing::{error, info};
 use url::Url;
 
 use crate::{
+    anonymise::Anonymiser,
     break_eligibility::do_compute_break_eligibility,
     clear_rooms::do_clear_room_urls,
+    hooks::{HookEvent, Hooks},
     import::do_import,
     request_manager::RequestManager,
     save_panels::{restore_panels, save_panels},
     sensible::do_make_sensible_conflicts,
-    view_draw::view_draw,
+    tabular::TableFormat,
+    terminal::TableOutput,
+    view_draw::{DrawFilter, view_draw},
 };
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// The language to show messages in (`en`, `es` or `fr`). Defaults to the
+    /// language set by the `TABBYCAT_LANG` or `LANG` environment variables.
+    #[arg(long, global = true)]
+    lang: Option<String>,
+    /// Treat names which only differ in their accents (e.g. "Müller" and
+    /// "Muller") as the same when matching participants, institutions and
+    /// rooms by name.
+    #[arg(long, global = true)]
+    #[clap(default_value_t = false)]
+    ignore_accents: bool,
+    /// Don't contact Tabbycat: answer every request with the response cached
+    /// the last time it was made (read-only commands only). For read-only
+    /// commands, this also happens automatically if Tabbycat cannot be
+    /// reached.
+    #[arg(long, global = true)]
+    #[clap(default_value_t = false)]
+    offline: bool,
+    /// Run the commands in `hooks.toml` (in the working directory) before and
+    /// after commands which change data on Tabbycat. Hooks are never run
+    /// without this flag.
+    #[arg(long, global = true)]
+    #[clap(default_value_t = false)]
+    hooks: bool,
     #[clap(subcommand)]
     command: Command,
 }
@@ -40,7 +126,12 @@ struct Args {
 pub enum Command {
     /// Set the current tournament. After running this, you will be prompted for
     /// the Tabbycat instance's URL, the tournament slug and an API key.
-    Set,
+    Set {
+        /// Save the details as a named profile (instead of as the current
+        /// tournament), for commands which work across several tournaments.
+        #[arg(long)]
+        profile: Option<String>,
+    },
     /// Import teams from a spreadsheet (CSV file).
     Import(Import),
     /// Create missing conflicts that Tabbycat often doesn't add.
@@ -58,6 +149,16 @@ pub enum Command {
     ///   in this category.
     ComputeBreakEligibility {
         format: String,
+        /// Path of a CSV file (with `team`, `category` and `eligible`
+        /// columns) of decisions which override the preset for individual
+        /// teams.
+        #[arg(long)]
+        overrides: Option<String>,
+        /// Path of a CSV file (with `speaker`, `status` and
+        /// `certifying_body` columns) of certified language statuses, which
+        /// are used for ESL/EFL eligibility instead of speaker categories.
+        #[arg(long)]
+        certifications: Option<String>,
     },
     SaveAllocs {
         to: String,
@@ -66,15 +167,47 @@ pub enum Command {
     RestoreAllocs {
         to: String,
         round: String,
+        /// Restore the panels even if they break the panel policy (in
+        /// `panels.toml`) or include judges who are not marked as available.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        force: bool,
     },
     ViewDraw {
         round: String,
+        /// Only show the debate in this room (the name of the room, or the ID
+        /// of the debate).
+        #[arg(long)]
+        room: Option<String>,
+        /// Only show debates with a judge whose name contains this.
+        #[arg(long)]
+        judge: Option<String>,
+        /// Only show debates with a team whose name contains this.
+        #[arg(long)]
+        team: Option<String>,
+        /// Also show the bracket, importance and flags of each debate shown.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        detail: bool,
+        /// Draw the table this many characters wide (by default, it is fitted
+        /// to the terminal).
+        #[arg(long)]
+        width: Option<u16>,
+        /// Show the draw through `$PAGER` (or `less -R`).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        pager: bool,
     },
     /// Swap two entities (either two teams, or two judges) on the draw.
     DrawSwap {
         round: String,
         a: String,
         b: String,
+        /// Exchange the sides of two teams in the same debate (rather than
+        /// moving them between debates).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        swap_sides: bool,
     },
     /// Add a judge to the draw for a given round.
     AddJudge {
@@ -82,26 +215,914 @@ pub enum Command {
         judge: String,
         room_id: String,
         role: String,
+        /// Add the judge even if the panel would then break the panel policy
+        /// (in `panels.toml`).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        force: bool,
     },
     RemoveJudge {
         round: String,
         judge: String,
     },
+    /// Edit the draw.
+    #[clap(subcommand)]
+    Draw(DrawCommand),
+    /// Improve the judge allocation.
+    #[clap(subcommand)]
+    Allocate(AllocateCommand),
+    /// Start a round.
+    #[clap(subcommand)]
+    Round(RoundCommand),
     Clash {
         a: String,
         b: String,
     },
+    /// Inspect the clashes (conflicts) between judges, teams and institutions.
+    #[clap(subcommand)]
+    Clashes(ClashesCommand),
     /// Exports data from Tabbycat. Currently this is primarily oriented
     /// towards extracting feedback in a format suitable for subsequent
     /// analysis.
     ExportFeedback {
-        /// One of `csv`, `sqlite`
+        /// One of `csv`, `parquet`, `sqlite`
         format: String,
         /// Location to write the data to. Warning: overwrites existing files!
         output: String,
+        /// Replace judge and team names with pseudonyms (which only match those in
+        /// other exports if `TABBYCAT_ANONYMISE_KEY` is set to the same key).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        anonymise: bool,
+    },
+    /// Exports data (e.g. the speaker tab) from Tabbycat.
+    #[clap(subcommand)]
+    Export(ExportCommand),
+    /// Save a static copy of the tournament (draws, results, standings,
+    /// motions and the break), which outlives the Tabbycat site. The data the
+    /// pages are made from is saved alongside them (in `data/`), and can be
+    /// used with `tabbycat import --from-archive`. Participants' contact
+    /// details and private URLs are left out of the data unless
+    /// `--include-private` is given.
+    Archive {
+        /// Directory to write the HTML pages to (created if it does not
+        /// exist). Warning: overwrites existing files!
+        #[arg(long)]
+        html: String,
+        /// Also save the email addresses, phone numbers, genders, pronouns
+        /// and private URL keys of judges and speakers in `data/` (so that
+        /// they are imported with `--from-archive`). Don't publish an archive
+        /// made with this!
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        include_private: bool,
+    },
+    /// View the team standings (or, with `--replies`, the reply speaker
+    /// standings).
+    ViewStandings {
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        replies: bool,
+        /// Show which metric separates each team from the one above it.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        explain: bool,
+    },
+    /// Summaries of the break.
+    #[clap(subcommand)]
+    Break(BreakCommand),
+    /// Track the submission of feedback.
+    #[clap(subcommand)]
+    Feedback(FeedbackCommand),
+    /// Track iron-person speeches (speakers who speak twice for their team
+    /// because a teammate is missing).
+    #[clap(subcommand)]
+    Ironperson(IronpersonCommand),
+    /// Manage rooms (venues).
+    #[clap(subcommand)]
+    Rooms(RoomsCommand),
+    #[clap(subcommand)]
+    Motions(MotionsCommand),
+    /// Summaries used to run the tournament (e.g. at registration).
+    #[clap(subcommand)]
+    Report(ReportCommand),
+    /// Check that the tournament follows its rules.
+    #[clap(subcommand)]
+    Check(CheckCommand),
+    /// Track registration fee payments (in a local ledger, `fees.toml`).
+    #[clap(subcommand)]
+    Fees(FeesCommand),
+    /// View and manage the judges: their records across tournaments, which of
+    /// them are breaking, and the adjudication core.
+    #[clap(subcommand)]
+    Judges(JudgesCommand),
+    /// Check the ballots submitted for a round.
+    #[clap(subcommand)]
+    Ballots(BallotsCommand),
+    /// Compare the data stored in Tabbycat with records kept elsewhere.
+    #[clap(subcommand)]
+    Verify(VerifyCommand),
+    /// Show what changed (room by room) between two draws saved by
+    /// `save-allocs`, or between a saved draw and the live draw.
+    #[clap(subcommand)]
+    Diff(DiffCommand),
+    /// Watch for new ballots and feedback, posting a notification to a
+    /// (Slack or Discord compatible) webhook whenever something is submitted.
+    Watch {
+        /// URL of the webhook.
+        #[arg(long)]
+        webhook: String,
+        /// Watch for ballots in this round (if not provided, only feedback is
+        /// watched).
+        #[arg(long)]
+        round: Option<String>,
+        /// Flag feedback with a score below this value.
+        #[arg(long)]
+        low_score: Option<f64>,
+        /// How often (in seconds) to check for new submissions.
+        #[arg(long)]
+        #[clap(default_value_t = 30)]
+        interval: u64,
+    },
+    /// Run a chat bot for the tab room, which carries out commands posted in
+    /// a Slack or Discord channel (e.g. `!draw R3` or `!swap R3 "Team A"
+    /// "Team B"`) and replies with the output. Post `!help` for a list of
+    /// commands.
+    Bot {
+        /// Token of the Slack app (`xoxb-...`), which needs the
+        /// `channels:history` and `chat:write` scopes.
+        #[arg(long, required_unless_present = "discord_token")]
+        slack_token: Option<String>,
+        /// Token of the Discord bot, which needs the message content intent.
+        #[arg(long, conflicts_with = "slack_token")]
+        discord_token: Option<String>,
+        /// The ID of the channel to listen in.
+        #[arg(long)]
+        channel: String,
+        /// A user (by their Slack or Discord user ID) who may run commands
+        /// which change the draw. Can be given more than once.
+        #[arg(long)]
+        allow: Vec<String>,
+        /// How often (in seconds) to check for new commands.
+        #[arg(long)]
+        #[clap(default_value_t = 5)]
+        interval: u64,
+    },
+    /// Send a request to the Tabbycat API (with the stored credentials), and
+    /// print the response. For example `tabbycat api GET
+    /// /tournaments/{slug}/teams`.
+    Api {
+        #[arg(value_parser = ["GET", "POST", "PATCH", "PUT", "DELETE"], ignore_case = true)]
+        method: String,
+        /// The endpoint, relative to the API root (`{slug}` is replaced with
+        /// the slug of the tournament).
+        path: String,
+        /// A file containing the JSON body of the request (or `-` to read it
+        /// from standard input).
+        #[arg(long)]
+        body: Option<String>,
+    },
+    /// Manage adjudicator availability.
+    #[clap(subcommand)]
+    Availability(AvailabilityCommand),
+    /// Manage the start times of rounds.
+    #[clap(subcommand)]
+    Schedule(ScheduleCommand),
+    /// Inspect the log of every change this CLI has made to Tabbycat.
+    #[clap(subcommand)]
+    Log(LogCommand),
+    /// Check that the tournament is ready for round 1 (teams have the right
+    /// number of speakers, judges have institutions, there are enough rooms,
+    /// etc).
+    Preflight {
+        /// The number of speakers each team should have (defaults to the
+        /// number most teams have).
+        #[arg(long)]
+        speakers: Option<usize>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum ExportCommand {
+    /// Export feedback. Exactly one of `--csv`, `--parquet` or `--sqlite`
+    /// should be provided.
+    Feedback {
+        #[arg(long)]
+        csv: Option<String>,
+        #[arg(long)]
+        parquet: Option<String>,
+        #[arg(long)]
+        sqlite: Option<String>,
+        /// Keep running, and append new feedback to the SQLite database as it
+        /// is submitted.
+        #[arg(long, requires = "sqlite")]
+        #[clap(default_value_t = false)]
+        follow: bool,
+        /// How often (in seconds) to check for new feedback with `--follow`.
+        #[arg(long)]
+        #[clap(default_value_t = 30)]
+        interval: u64,
+        /// Replace judge and team names with pseudonyms (which only match those in
+        /// other exports if `TABBYCAT_ANONYMISE_KEY` is set to the same key).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        anonymise: bool,
+        /// Export one row per judge (with the mean of each numeric answer and
+        /// the number of times each choice was given) instead of one row per
+        /// feedback submission. Only `judge` is supported.
+        #[arg(long, value_parser = ["judge"], conflicts_with = "sqlite")]
+        pivot: Option<String>,
+    },
+    /// Export the speaker tab, optionally restricted to a single speaker
+    /// category (e.g. ESL). Ranks are recomputed within the category, and
+    /// tied ranks are marked with `=`.
+    Speakers {
+        /// Slug or name of the speaker category.
+        #[arg(long)]
+        category: Option<String>,
+        /// One of `csv`, `parquet`
+        #[arg(long)]
+        #[clap(default_value = "csv")]
+        format: String,
+        /// Location to write the CSV file to. Warning: overwrites existing
+        /// files!
+        #[arg(long)]
+        to: String,
+        /// Replace speaker and team names with pseudonyms (which only match those in
+        /// other exports if `TABBYCAT_ANONYMISE_KEY` is set to the same key).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        anonymise: bool,
+    },
+    /// Export the speaker tab in "wide" format, with a column for each
+    /// speaker's score in every preliminary round (from the confirmed
+    /// ballots), so that ties and dropped rounds can be checked.
+    SpeakerRounds {
+        /// One of `csv`, `parquet`
+        #[arg(long)]
+        #[clap(default_value = "csv")]
+        format: String,
+        /// Location to write the CSV file to. Warning: overwrites existing
+        /// files!
+        #[arg(long)]
+        to: String,
+        /// Replace speaker and team names with pseudonyms (which only match those in
+        /// other exports if `TABBYCAT_ANONYMISE_KEY` is set to the same key).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        anonymise: bool,
+    },
+    /// Export the results of every confirmed ballot, with one row per speech
+    /// (round, room, team, side, speaker, position and score, along with the
+    /// team's total, points and margin).
+    Results {
+        /// Write one row per debate (with a group of columns for each side)
+        /// instead of one row per speech.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        by_debate: bool,
+        /// One of `csv`, `parquet`
+        #[arg(long)]
+        #[clap(default_value = "csv")]
+        format: String,
+        /// Location to write the CSV file to. Warning: overwrites existing
+        /// files!
+        #[arg(long)]
+        to: String,
+        /// Replace speaker and team names with pseudonyms (which only match those in
+        /// other exports if `TABBYCAT_ANONYMISE_KEY` is set to the same key).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        anonymise: bool,
+    },
+    /// Export the speakers and judges with their email addresses, team,
+    /// institution and private URL (e.g. for a mail merge). Participants with
+    /// missing, malformed or duplicate email addresses are reported.
+    Contacts {
+        /// Location to write the CSV file to. Warning: overwrites existing
+        /// files!
+        #[arg(long)]
+        to: String,
+    },
+    /// Export a judge's allocations (round, room, role, teams and the rest of
+    /// the panel) as an iCalendar file. Only rounds with a start time (see
+    /// `tabbycat schedule set`) are included.
+    JudgeSchedule {
+        /// The name (or ID) of the judge.
+        judge: String,
+        /// Location to write the file to. Warning: overwrites existing files!
+        #[arg(long)]
+        ics: String,
+        /// How long each round lasts, in minutes.
+        #[arg(long)]
+        #[clap(default_value_t = 90)]
+        duration: i64,
+    },
+    /// Export the team standings (or, with `--replies`, the reply speaker
+    /// standings).
+    Standings {
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        replies: bool,
+        /// Add a column showing which metric separates each team from the
+        /// one above it.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        explain: bool,
+        /// One of `csv`, `parquet`
+        #[arg(long)]
+        #[clap(default_value = "csv")]
+        format: String,
+        /// Location to write the CSV file to. Warning: overwrites existing
+        /// files!
+        #[arg(long)]
+        to: String,
+        /// Replace speaker and team names with pseudonyms (which only match those in
+        /// other exports if `TABBYCAT_ANONYMISE_KEY` is set to the same key).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        anonymise: bool,
+    },
+    /// Render a signage file for each room in a round (containing the room
+    /// name, teams, panel and, once released, the motion) from a Tera
+    /// template.
+    Signage {
+        round: String,
+        /// Path to the template (e.g. `template.html`). The generated files use
+        /// the same extension as the template.
+        #[arg(long)]
+        template: String,
+        /// Directory to write the signage files to. Warning: overwrites
+        /// existing files!
+        #[arg(long)]
+        out: String,
+    },
+    /// Generate a PDF of pre-filled paper ballots (one per debate) for a
+    /// round.
+    Ballots {
+        round: String,
+        /// Location to write the PDF to. Warning: overwrites existing files!
+        #[arg(long)]
+        pdf: String,
+        /// Tera template used to produce the text of each ballot (see
+        /// `export signage` for the available variables). A simple default
+        /// ballot is used if this is not provided.
+        #[arg(long)]
+        template: Option<String>,
+    },
+    /// Render the released draw for a round as a PNG image (e.g. for posting
+    /// on social media).
+    DrawImage {
+        round: String,
+        /// Location to write the image to. Warning: overwrites existing files!
+        #[arg(long)]
+        png: String,
+        /// TOML file setting the title, logo, colours and font of the image.
+        #[arg(long)]
+        theme: Option<String>,
     },
 }
 
+#[derive(Debug, Subcommand, Clone)]
+pub enum DrawCommand {
+    /// Move a team to a different side of its debate (the team currently on
+    /// that side takes the team's old side).
+    SetSide {
+        round: String,
+        team: String,
+        /// One of `aff`, `neg` (two-team formats) or `og`, `oo`, `cg`, `co`
+        /// (British Parliamentary).
+        side: String,
+    },
+    /// Set the importance of a debate (used when allocating judges), or with
+    /// `--auto`, of every debate in the round according to whether the teams
+    /// in it can still break.
+    SetImportance {
+        round: String,
+        /// The name of the room (or the ID of the debate).
+        #[arg(required_unless_present = "auto")]
+        room: Option<String>,
+        /// An integer between -2 (very low) and 2 (very high).
+        #[arg(required_unless_present = "auto", allow_negative_numbers = true)]
+        value: Option<i64>,
+        #[arg(long, conflicts_with_all = ["room", "value"])]
+        #[clap(default_value_t = false)]
+        auto: bool,
+        /// Number of teams breaking (defaults to the break size of the open
+        /// category). Only used with `--auto`.
+        #[arg(long, requires = "auto")]
+        break_size: Option<usize>,
+    },
+    /// Exchange the chairs of two debates, leaving the panellists and
+    /// trainees in place.
+    SwapChairs {
+        round: String,
+        /// The name of the room (or the ID of the debate).
+        room_a: String,
+        /// The name of the room (or the ID of the debate).
+        room_b: String,
+    },
+    /// Flag a debate (e.g. "needs runner"). Flags are kept locally (in
+    /// `debate-flags.json`), and shown in `view-draw` and exported signage.
+    Flag {
+        round: String,
+        /// The name of the room (or the ID of the debate).
+        room: String,
+        flag: String,
+    },
+    /// Remove a flag set with `draw flag`.
+    Unflag {
+        round: String,
+        /// The name of the room (or the ID of the debate).
+        room: String,
+        flag: String,
+    },
+    /// Release the (confirmed) draw for a round, optionally waiting until a
+    /// given time.
+    Release {
+        round: String,
+        /// Wait until this time before releasing the draw, showing a
+        /// countdown: either a time today (`13:30`), a day and a time (`Sat
+        /// 13:30`) or a date and a time (`2025-10-18 13:30`).
+        #[arg(long)]
+        at: Option<String>,
+        /// Also release the round's motions.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        motions: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum RoundCommand {
+    /// Release the draw and motions of a round in one step (both, unless only
+    /// one of `--draw` or `--motions` is given), and announce the release.
+    Release {
+        round: String,
+        /// Release the (confirmed) draw.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        draw: bool,
+        /// Release the motions.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        motions: bool,
+        /// Announce that debates start this many minutes after the release,
+        /// and count down to the start.
+        #[arg(long)]
+        start_timer: Option<u64>,
+        /// URL of a (Slack or Discord compatible) webhook to post the
+        /// announcement, with the motions and info slides, to.
+        #[arg(long)]
+        webhook: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum AllocateCommand {
+    /// Suggest swaps of judges which strengthen the panels of debates with
+    /// live teams (teams which may or may not break), taking judges from
+    /// debates in which no team is live. The swaps are printed as
+    /// `tabbycat draw-swap` commands, which can be run with e.g.
+    /// `tabbycat allocate suggest-moves R5 | sh`.
+    SuggestMoves {
+        round: String,
+        /// The most swaps to suggest.
+        #[arg(long)]
+        #[clap(default_value_t = 10)]
+        max_moves: usize,
+        /// Number of teams breaking in the open category (defaults to its
+        /// break size).
+        #[arg(long)]
+        break_size: Option<usize>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum ClashesCommand {
+    /// Export a graph of judges, teams and institutions and the conflicts
+    /// between them (for viewing in e.g. Graphviz or Gephi).
+    ExportGraph {
+        /// Either `dot` (Graphviz) or `graphml` (Gephi, yEd, etc).
+        #[arg(long, value_parser = ["dot", "graphml"])]
+        #[clap(default_value = "dot")]
+        format: String,
+        /// File to write the graph to (defaults to standard output).
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Propose clashes which look likely but have not been recorded, and
+    /// write them to a CSV file (in the format read by `import --clashes`)
+    /// for review.
+    Infer {
+        /// Judges who share a rare surname with another judge or a speaker.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        by_surname: bool,
+        /// Judges who share an email domain (other than those of common email
+        /// providers) with another judge or a speaker.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        by_email_domain: bool,
+        /// An archive of a previous tournament (made by `tabbycat archive`);
+        /// judges who were at the same institution as another judge or a
+        /// speaker in it are proposed.
+        #[arg(long)]
+        prior: Option<String>,
+        /// Surnames and email domains shared by more than this many people
+        /// are too common to suggest a conflict.
+        #[arg(long)]
+        #[clap(default_value_t = 3)]
+        max_group: usize,
+        /// Location to write the proposed clashes to. Warning: overwrites
+        /// existing files!
+        #[arg(long)]
+        out: String,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum MotionsCommand {
+    /// List the motions for each round.
+    View {
+        /// Only show the motions for this round.
+        #[arg(long)]
+        round: Option<String>,
+        /// Show how balanced each motion was (using the confirmed ballots).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        stats: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum ReportCommand {
+    /// Show the number of teams, speakers and judges registered by each
+    /// institution, and whether each institution meets the n-1 judge rule.
+    Institutions {
+        /// Show how much each institution has paid, from the ledger kept by
+        /// `tabbycat fees`; institutions which have not paid are flagged.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        payments: bool,
+    },
+    /// Summarise the results for the closing ceremony (champions, finalists,
+    /// top speakers and best-rated judges) as Markdown.
+    Finals {
+        /// The number of speakers (in each category) and judges to list.
+        #[arg(long)]
+        #[clap(default_value_t = 10)]
+        top: usize,
+        /// File to write the summary to (defaults to standard output).
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Summarise the genders and pronouns of speakers, judges, chairs and
+    /// breaking participants (where they have been recorded). Only aggregate
+    /// counts are shown.
+    Diversity,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum CheckCommand {
+    /// List the institutions which do not bring enough judges for their
+    /// number of teams.
+    N1 {
+        /// The number of judges required for `n` teams: `n-1`, `n`, or a
+        /// number of judges per team (e.g. `1` or `2/3`, rounded up).
+        #[arg(long)]
+        #[clap(default_value = "n-1")]
+        ratio: String,
+    },
+    /// Flag the debates in a round whose voting adjudicators are all of the
+    /// same gender, or which have no experienced chair.
+    PanelsDiversity {
+        round: String,
+        /// The lowest base score an experienced chair can have (defaults to
+        /// the median base score of all adjudicators).
+        #[arg(long)]
+        min_chair_score: Option<f64>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum FeesCommand {
+    /// Record the payments in a CSV file with the columns `institution` (or
+    /// `team`, for teams without an institution), `amount` and optionally
+    /// `reference` and `date`. Payments whose reference has already been
+    /// recorded (or, without a reference, which are identical to one already
+    /// recorded) are skipped.
+    Import { csv: String },
+    /// Show who is registered and how much they have paid.
+    Status {
+        /// The fee for each team (used to compute the balance due).
+        #[arg(long)]
+        team_fee: Option<f64>,
+        /// The fee for each judge (used to compute the balance due).
+        #[arg(long)]
+        judge_fee: Option<f64>,
+        /// Only show those who have not paid in full.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        unpaid: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum JudgesCommand {
+    /// Summarise each judge's record (rounds chaired, feedback, etc) across
+    /// several tournaments.
+    History {
+        /// The profiles (saved with `tabbycat set --profile`) of the
+        /// tournaments to include, separated by commas.
+        #[arg(long, value_delimiter = ',', required = true)]
+        profiles: Vec<String>,
+        /// Show a per-tournament breakdown for this judge.
+        #[arg(long)]
+        judge: Option<String>,
+    },
+    /// Mark judges as breaking (eligible to judge outrounds).
+    MarkBreaking {
+        /// Path of a CSV file (with a `name` column) listing the breaking
+        /// judges.
+        #[arg(long, required_unless_present = "from_feedback_threshold")]
+        csv: Option<String>,
+        /// Mark the judges whose average feedback score is at least this
+        /// value as breaking.
+        #[arg(long, conflicts_with = "csv")]
+        from_feedback_threshold: Option<f64>,
+        /// Mark every other judge as not breaking.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        reset: bool,
+    },
+    /// List the judges who are currently marked as breaking.
+    Breaking,
+    /// Add judges to (or remove them from) the adjudication core, and set
+    /// whether they are independent adjudicators.
+    SetAdjCore {
+        /// The names (or IDs) of the judges, separated by commas.
+        #[arg(long, value_delimiter = ',', required_unless_present = "csv")]
+        names: Vec<String>,
+        /// Path of a CSV file with a `name` column, and optionally `is_ca`
+        /// and `is_ia` columns (as in the judges CSV file). Judges are added
+        /// to the adj core unless `is_ca` is false, and their independent
+        /// flag is only changed if `is_ia` is given.
+        #[arg(long, conflicts_with = "names")]
+        csv: Option<String>,
+        /// Remove the judges in `--names` from the adj core instead.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        unset: bool,
+        /// Also mark the judges in `--names` as independent adjudicators (or
+        /// not, with `--unset`).
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        independent: bool,
+        /// Remove every judge who is not listed from the adj core.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        reset: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum ScheduleCommand {
+    /// Set the start time of a round.
+    Set {
+        round: String,
+        /// Either a time today (`10:00`), a day and a time (`Sat 10:00`) or a
+        /// date and a time (`2025-10-18 10:00`).
+        #[arg(long)]
+        start: String,
+    },
+    /// Show the start time of each round.
+    Show,
+    /// Export the start times of the rounds as an iCalendar file, which
+    /// calendar applications can subscribe to.
+    Ics {
+        /// Location to write the file to. Warning: overwrites existing files!
+        #[arg(long)]
+        to: String,
+        /// How long each round lasts, in minutes.
+        #[arg(long)]
+        #[clap(default_value_t = 90)]
+        duration: i64,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum LogCommand {
+    /// Show the requests which modified data on Tabbycat (most recent last).
+    Show {
+        /// Only show requests made since this time: either relative (e.g.
+        /// `30m`, `2h`, `1d`), a date (`2025-10-16`) or a date and time
+        /// (`2025-10-16 13:30`).
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Undo a change recorded in the log (by the ID shown by `log show`),
+    /// restoring the previous state of the pairing, team, judge, etc.
+    Revert { id: usize },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum BreakCommand {
+    /// Show which teams are mathematically safe, live or dead in each break
+    /// category, based on the current standings and the preliminary rounds
+    /// which remain.
+    Live {
+        /// Only show this break category (by name or slug).
+        #[arg(long)]
+        category: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum FeedbackCommand {
+    /// Show which teams and adjudicators have not submitted the feedback they
+    /// are required to for a round, grouped by institution.
+    Missing {
+        round: String,
+        /// Write the contact details (email address and private URL) of
+        /// everyone who is missing feedback to this CSV file, for a reminder.
+        #[arg(long)]
+        emails: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum IronpersonCommand {
+    /// Record that a speaker gave an iron-person speech for their team in a
+    /// round. The speeches are recorded in `ironperson.json` in the current
+    /// directory.
+    Mark {
+        round: String,
+        /// The team's short or long name.
+        team: String,
+        /// The name of the speaker who spoke twice.
+        speaker: String,
+    },
+    /// Show the iron-person speeches each team has given, both those recorded
+    /// with `ironperson mark` and those marked on confirmed ballots.
+    Report {
+        /// Highlight teams which have given more than this many iron-person
+        /// speeches (e.g. the cap set by the tournament's rules).
+        #[arg(long)]
+        max: Option<usize>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum BallotsCommand {
+    /// Find debates whose unconfirmed ballots (e.g. from double ballot entry)
+    /// disagree, and show the differing speeches side-by-side.
+    Compare { round: String },
+    /// Flag implausible scores: scores outside the tournament's range,
+    /// speakers who speak twice, excessive margins and low-point wins.
+    Lint { round: String },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum VerifyCommand {
+    /// List the differences, room by room, between the results of the
+    /// confirmed ballots and a CSV file in the format written by `export
+    /// results` (e.g. typed up from the paper ballots). Speakers are matched
+    /// by name, so the file must not be anonymised.
+    Results {
+        #[arg(long)]
+        against: String,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum DiffCommand {
+    /// Compare two saved draws (`a` is treated as the earlier one).
+    Snapshots { a: String, b: String },
+    /// Compare a saved draw with the current draw of the same round.
+    Live {
+        snapshot: String,
+        /// The round to compare with, if not the round the draw was saved
+        /// from.
+        #[arg(long)]
+        round: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum RoomsCommand {
+    /// Assign descending priorities to all rooms.
+    Prioritize {
+        /// One of `name-order` (rooms are ordered by name) or `csv` (rooms are
+        /// ordered as they are listed in the `name` column of `--csv`).
+        #[arg(long, value_parser = ["name-order", "csv"])]
+        by: String,
+        #[arg(long)]
+        csv: Option<String>,
+    },
+    /// Move debates so that participants with room constraints (e.g. who
+    /// need an accessible room) are in a room of the required category.
+    AllocateCategories {
+        #[arg(long)]
+        round: String,
+    },
+    /// Flag debates in rooms too small for the people expected in them. Room
+    /// capacities are taken from the `capacity` column of the rooms CSV file
+    /// (which is stored locally, as Tabbycat does not record it).
+    CheckCapacity {
+        round: String,
+        /// The number of spectators expected in each room (e.g. for
+        /// out-rounds), in addition to the speakers and adjudicators.
+        #[arg(long)]
+        #[clap(default_value_t = 0)]
+        audience: usize,
+    },
+    /// Create numbered rooms (e.g. breakout rooms for an online tournament)
+    /// without needing a rooms CSV file. Rooms which already exist are
+    /// skipped.
+    Generate {
+        /// The number of rooms to create.
+        #[arg(long)]
+        count: usize,
+        /// The name of each room, in which `{n}` is replaced by its number.
+        #[arg(long)]
+        #[clap(default_value = "Room {n}")]
+        pattern: String,
+        /// The priority given to every room.
+        #[arg(long)]
+        #[clap(default_value_t = 100)]
+        priority: i64,
+        /// The number of the first room.
+        #[arg(long)]
+        #[clap(default_value_t = 1)]
+        start: usize,
+    },
+    /// Create an online meeting for each room with Zoom or Google Meet, and
+    /// set the room's link (its external URL) to the meeting's join link.
+    Provision {
+        /// One of `zoom`, `google-meet`
+        #[arg(long)]
+        provider: String,
+        /// An OAuth access token for the provider's API (for Zoom, from a
+        /// Server-to-Server OAuth app with the `meeting:write` scope).
+        #[arg(long)]
+        api_key: String,
+        /// Replace the links of rooms which already have one.
+        #[arg(long)]
+        #[clap(default_value_t = false)]
+        overwrite: bool,
+    },
+    /// Share rooms with another tournament in the same building (on the same
+    /// Tabbycat site): rooms used by a debate in either tournament are marked
+    /// as unavailable in the rounds of the other tournament which overlap
+    /// with it. Run this again after each draw is generated or changed.
+    Share {
+        /// The slug of the other tournament.
+        #[arg(long)]
+        with: String,
+        /// A CSV file listing the shared rooms, with a `name` column (and an
+        /// `other_name` column for rooms named differently in the other
+        /// tournament).
+        #[arg(long)]
+        csv: String,
+        /// How long each round lasts, in minutes (rounds overlap if they start
+        /// less than this far apart).
+        #[arg(long)]
+        #[clap(default_value_t = 90)]
+        duration: i64,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+pub enum AvailabilityCommand {
+    /// Mark a group of adjudicators as available (or unavailable) for a round.
+    Set(SetAvailability),
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct SetAvailability {
+    #[arg(long)]
+    round: String,
+    /// Select all adjudicators from this institution (full name or code).
+    #[arg(long)]
+    institution: Option<String>,
+    /// Select all adjudicators who are members of the adjudication core.
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    adj_core: bool,
+    /// Select all independent adjudicators.
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    independent: bool,
+    /// Mark the selected adjudicators as unavailable (by default they are
+    /// marked as available).
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    unavailable: bool,
+}
+
+/// Any of the CSV files can be read from standard input by passing `-` as its
+/// path (e.g. `curl ... | tabbycat import --teams-csv -`).
 #[derive(Debug, Parser, Clone)]
 pub struct Import {
     /// Path of the CSV file containing the institutions.
@@ -122,6 +1143,42 @@ pub struct Import {
     #[arg(long, alias = "rooms_csv")]
     rooms: Option<String>,
 
+    #[arg(long, conflicts_with_all = ["institutions_csv", "judges_csv", "rooms"])]
+    /// Recreate the institutions, judges and rooms of a previous tournament
+    /// from the directory written by `tabbycat archive` (e.g. for an annual
+    /// event which reuses last year's pool of judges).
+    from_archive: Option<String>,
+
+    #[arg(long, alias = "break-categories")]
+    /// Path of a CSV file describing the break categories which should be
+    /// created if they are referenced in the teams CSV file but do not yet
+    /// exist (columns: `name`, `slug`, `break_size`, `is_general`, `priority`
+    /// and optionally `limit`).
+    break_categories_csv: Option<String>,
+
+    #[arg(long, alias = "speaker-categories")]
+    /// Path of a CSV file containing speaker categories, which are created
+    /// before any teams are imported (columns: `name`, `slug`, and optionally
+    /// `seq` and `public`).
+    speaker_categories_csv: Option<String>,
+
+    #[arg(long, alias = "panels")]
+    /// Path of a CSV file containing adjudicator allocations for `--round`
+    /// (columns: `room`, which is the room name or debate ID, `chair`,
+    /// `panellists` and `trainees`). Existing allocations for the listed rooms
+    /// are overwritten.
+    panels_csv: Option<String>,
+
+    #[arg(long, alias = "draw")]
+    /// Path of a CSV file containing the draw for `--round` (columns: `room`,
+    /// and then either `aff`/`neg` or `og`/`oo`/`cg`/`co` containing team
+    /// names). Debates are created if they do not already exist.
+    draw_csv: Option<String>,
+
+    #[arg(long)]
+    /// The round which `--panels` and `--draw` apply to.
+    round: Option<String>,
+
     #[arg(long)]
     /// Whether teams should use be prefixed with the name of their institution
     /// by default.
@@ -129,6 +1186,9 @@ pub struct Import {
     /// Note: if you specify a value in the `use_institutional_prefix` column
     /// (if this column is supplied) of the teams CSV file, those values will
     /// take precedence over this flag.
+    ///
+    /// Defaults for this (and several other settings) can also be provided in
+    /// an `import.toml` file in the current directory.
     #[clap(default_value_t = false)]
     use_institution_prefix: bool,
     /// Whether existing data should be overwitten. This is UNSAFE if you have
@@ -138,9 +1198,77 @@ pub struct Import {
     #[arg(long)]
     #[clap(default_value_t = false)]
     overwrite: bool,
+    /// Set the availability of judges (including those which already exist)
+    /// and rooms from the `availability` column of the judges and rooms CSV
+    /// files.
     #[arg(long)]
     #[clap(default_value_t = false)]
     set_availability: bool,
+    /// Update the fields of judges, teams and speakers which already exist
+    /// (instead of skipping them) if they differ from the CSV files. Empty
+    /// cells leave the existing value unchanged.
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    update_existing: bool,
+    /// Whether judges and rooms are available for rounds which are not listed
+    /// in their `availability` column (one of `available` or `unavailable`).
+    #[arg(long, value_parser = ["available", "unavailable"])]
+    #[clap(default_value = "unavailable")]
+    availability_default: String,
+    /// Drop team emoji which Tabbycat does not accept (instead of refusing to
+    /// import the teams).
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    strip_invalid_emoji: bool,
+    /// Give speakers who don't have a `url_key` in the teams CSV file a
+    /// randomly generated one.
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    generate_url_keys: bool,
+    /// Only import the rows of the CSV files which are new or have changed
+    /// since the last import which used this state file (which is created if
+    /// it does not exist). Existing teams, speakers and judges whose rows have
+    /// changed are only updated if `--update-existing` is also passed. The
+    /// `--panels` and `--draw` files are always imported in full.
+    #[arg(long)]
+    since_state: Option<String>,
+    /// Import the panels in `--panels` even if they break the panel policy
+    /// (in `panels.toml`).
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    force: bool,
+    /// Path of a file of words and phrases (one per line) which team names
+    /// may not contain. Teams whose full, short or code names contain one of
+    /// them are not imported.
+    #[arg(long)]
+    name_denylist: Option<String>,
+    /// Rename teams which have the same name as another team from the same
+    /// institution (e.g. a second team called `A` becomes `B`), instead of
+    /// refusing to import them.
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    suffix_colliding_names: bool,
+    /// What to do when a judge, team or clash fails to import: `skip` it and
+    /// continue, `abort` the import once the rows which are already being
+    /// imported have finished (saving a checkpoint to the `--since-state`
+    /// file, if given, which the import can be resumed from) or `prompt` for
+    /// which to do. Rows of any CSV file which cannot be read are handled
+    /// the same way, before anything is imported. Failed rows are written to
+    /// `failed-<kind>.csv`.
+    #[arg(long, value_parser = ["skip", "abort", "prompt"])]
+    #[clap(default_value = "abort")]
+    on_error: String,
+    /// Clash each new judge with every institution in their region (from the
+    /// `region` column of the judges CSV file, or otherwise the region of
+    /// their institution), as some circuits require.
+    #[arg(long)]
+    #[clap(default_value_t = false)]
+    region_conflicts: bool,
+    /// Path of a CSV file to write the codes generated for institutions which
+    /// have no `short_code` (or one longer than 20 characters) to, so that
+    /// registrants can be told them.
+    #[arg(long)]
+    institution_codes_csv: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -150,18 +1278,81 @@ pub struct Auth {
     api_key: String,
 }
 
+/// The file in which the credentials for the current tournament (or the given
+/// profile) are stored.
+fn credentials_path(profile: Option<&str>) -> std::path::PathBuf {
+    if let Some(profile) = profile.filter(|profile| !is_valid_profile(profile)) {
+        error!(
+            "Invalid profile name {profile:?}: profile names cannot be empty, or contain `/`, `\\` \
+            or `..`."
+        );
+        exit(1);
+    }
+
+    let home_dir = dirs::home_dir().expect("Could not determine home directory");
+    match profile {
+        Some(profile) => home_dir.join(".tabbycat_profiles").join(profile),
+        None => home_dir.join(".tabbycat"),
+    }
+}
+
+/// Whether `profile` can be used as the name of a file in the profiles
+/// directory (so cannot point outside it).
+fn is_valid_profile(profile: &str) -> bool {
+    !profile.trim().is_empty()
+        && !profile.contains(['/', '\\'])
+        && !profile.contains("..")
+        && std::path::Path::new(profile).is_relative()
+}
+
+/// Whether `command` only reads from Tabbycat (so can use the cache of
+/// responses, see [`cache`]).
+fn is_read_only(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::ViewDraw { .. }
+            | Command::ViewStandings { .. }
+            | Command::Preflight { .. }
+            | Command::Schedule(ScheduleCommand::Show | ScheduleCommand::Ics { .. })
+            | Command::Report(_)
+            | Command::Check(_)
+            | Command::Break(BreakCommand::Live { .. })
+            | Command::Feedback(FeedbackCommand::Missing { .. })
+            | Command::Motions(MotionsCommand::View { .. })
+            | Command::Diff(_)
+            | Command::Ballots(_)
+            | Command::Verify(_)
+            | Command::Ironperson(IronpersonCommand::Report { .. })
+            | Command::Rooms(RoomsCommand::CheckCapacity { .. })
+            | Command::Export(
+                ExportCommand::Signage { .. }
+                    | ExportCommand::Ballots { .. }
+                    | ExportCommand::DrawImage { .. }
+                    | ExportCommand::JudgeSchedule { .. }
+            )
+    )
+}
+
 fn load_credentials() -> Auth {
-    use dirs;
+    load_profile(None)
+}
+
+/// Loads the credentials saved by `tabbycat set` (or `tabbycat set --profile
+/// <profile>`).
+fn load_profile(profile: Option<&str>) -> Auth {
     use std::fs;
     use toml;
 
-    let home_dir = dirs::home_dir().expect("Could not determine home directory");
-    let auth_path = home_dir.join(".tabbycat");
+    let auth_path = credentials_path(profile);
+    let set_command = match profile {
+        Some(profile) => format!("tabbycat set --profile {profile}"),
+        None => "tabbycat set".to_string(),
+    };
 
     let auth_toml = match fs::read_to_string(&auth_path) {
         Ok(t) => t,
         Err(_) => {
-            error!("Please run `tabbycat set` and provide your tournament's details first.");
+            error!("Please run `{set_command}` and provide your tournament's details first.");
             exit(1)
         }
     };
@@ -170,7 +1361,8 @@ fn load_credentials() -> Auth {
         Ok(t) => t,
         Err(_) => {
             error!(
-                "Your ~/.tabbycat file is malformed (you may need to run `tabbycat set` again to fix this)."
+                "Your {} file is malformed (you may need to run `{set_command}` again to fix this).",
+                auth_path.display()
             );
             exit(1)
         }
@@ -196,10 +1388,35 @@ async fn main() {
         .with_ansi(true)
         .init();
 
-    let args = Args::parse();
+    let command = match defaults::load() {
+        Some(table) => defaults::apply(Args::command(), &table, "tabbycat"),
+        None => Args::command(),
+    };
+    let args = Args::from_arg_matches(&command.get_matches()).unwrap_or_else(|e| e.exit());
+    i18n::init(args.lang.as_deref());
+    names::set_ignore_accents(args.ignore_accents);
+    if is_read_only(&args.command) {
+        cache::enable();
+    } else if args.offline {
+        error!("Only read-only commands (e.g. `view-draw`) can be run with --offline.");
+        exit(1);
+    }
+    cache::set_offline(args.offline);
+
+    // hooks, along with the ID of the last entry in the audit log (so that the
+    // requests made by this command can be given to the `post_` hook)
+    let hooks = HookEvent::of(&args.command).and_then(|event| {
+        let hooks = Hooks::load(args.hooks);
+        if !hooks.any(event) {
+            return None;
+        }
+        hooks.before(event);
+        let last = audit_log::read_entries().last().map(|(id, _)| *id);
+        Some((hooks, event, last.unwrap_or(0)))
+    });
 
     match args.command {
-        Command::Set => {
+        Command::Set { profile } => {
             use rpassword::read_password;
             use std::io::{self, Write};
 
@@ -236,11 +1453,13 @@ async fn main() {
                 api_key,
             };
 
-            let home_dir = dirs::home_dir().expect("Could not determine home directory");
-            let auth_path = home_dir.join(".tabbycat");
+            let auth_path = credentials_path(profile.as_deref());
+            if let Some(dir) = auth_path.parent() {
+                std::fs::create_dir_all(dir).expect("Failed to create the profiles directory");
+            }
 
             let auth_json = toml::to_string_pretty(&auth).expect("Failed to serialize Auth");
-            std::fs::write(&auth_path, auth_json).expect("Failed to write Auth to ~/.tabbycat");
+            std::fs::write(&auth_path, auth_json).expect("Failed to write Auth");
 
             info!("Tabbycat credentials saved to {}", auth_path.display());
         }
@@ -256,61 +1475,555 @@ async fn main() {
             let auth = load_credentials();
             do_clear_room_urls(auth);
         }
-        Command::ComputeBreakEligibility { format } => {
+        Command::ComputeBreakEligibility {
+            format,
+            overrides,
+            certifications,
+        } => {
             let auth = load_credentials();
-            do_compute_break_eligibility(auth, format);
+            do_compute_break_eligibility(auth, format, overrides, certifications);
         }
         Command::SaveAllocs { to, round } => {
             let auth = load_credentials();
             save_panels(&round, &to, auth).await;
         }
-        Command::RestoreAllocs { to, round } => {
+        Command::RestoreAllocs { to, round, force } => {
             let auth = load_credentials();
-            restore_panels(&round, &to, auth).await;
+            restore_panels(&round, &to, force, auth).await;
         }
-        Command::ViewDraw { round } => {
+        Command::ViewDraw {
+            round,
+            room,
+            judge,
+            team,
+            detail,
+            width,
+            pager,
+        } => {
             let auth = load_credentials();
 
-            view_draw(&round, auth).await;
+            let filter = DrawFilter { room, judge, team };
+            let output = TableOutput { width, pager };
+            view_draw(&round, auth, filter, detail, output).await;
         }
-        Command::DrawSwap { round, a, b } => {
+        Command::DrawSwap {
+            round,
+            a,
+            b,
+            swap_sides,
+        } => {
             let auth = load_credentials();
 
-            edit_draw::swap(&round, &a, &b, auth).await;
+            edit_draw::swap(&round, &a, &b, swap_sides, auth).await;
         }
         Command::AddJudge {
             round,
             room_id,
             judge,
             role,
+            force,
         } => {
             let auth = load_credentials();
 
-            edit_draw::alloc(&round, &room_id, &judge, &role, auth).await;
+            edit_draw::alloc(&round, &room_id, &judge, &role, force, auth).await;
         }
         Command::RemoveJudge { round, judge } => {
             let auth = load_credentials();
 
             edit_draw::remove(&round, &judge, auth).await;
         }
+        Command::Draw(DrawCommand::SetSide { round, team, side }) => {
+            let auth = load_credentials();
+
+            edit_draw::set_side(&round, &team, &side, auth).await;
+        }
+        Command::Draw(DrawCommand::SetImportance {
+            round,
+            room,
+            value,
+            auto,
+            break_size,
+        }) => {
+            let auth = load_credentials();
+
+            match (room, value) {
+                (Some(room), Some(value)) if !auto => {
+                    importance::set_importance(auth, &round, &room, value).await
+                }
+                _ => importance::set_importance_automatically(auth, &round, break_size).await,
+            }
+        }
+        Command::Draw(DrawCommand::SwapChairs {
+            round,
+            room_a,
+            room_b,
+        }) => {
+            let auth = load_credentials();
+
+            edit_draw::swap_chairs(&round, &room_a, &room_b, auth).await;
+        }
+        Command::Draw(DrawCommand::Flag { round, room, flag }) => {
+            let auth = load_credentials();
+            debate_flags::flag(auth, &round, &room, &flag, false).await;
+        }
+        Command::Draw(DrawCommand::Unflag { round, room, flag }) => {
+            let auth = load_credentials();
+            debate_flags::flag(auth, &round, &room, &flag, true).await;
+        }
+        Command::Draw(DrawCommand::Release { round, at, motions }) => {
+            let auth = load_credentials();
+
+            release::release_draw(auth, &round, at, motions).await;
+        }
         Command::Clash { a, b } => {
             let auth = load_credentials();
             import::add_clash_cmd(&a, &b, &auth, RequestManager::new(&auth.api_key)).await
         }
-        Command::ExportFeedback { output, format } => {
+        Command::Clashes(ClashesCommand::ExportGraph { format, out }) => {
+            let auth = load_credentials();
+            clash_graph::export_clash_graph(auth, &format, out).await;
+        }
+        Command::Clashes(ClashesCommand::Infer {
+            by_surname,
+            by_email_domain,
+            prior,
+            max_group,
+            out,
+        }) => {
+            let auth = load_credentials();
+            clash_inference::infer_clashes(
+                auth,
+                by_surname,
+                by_email_domain,
+                prior,
+                max_group,
+                &out,
+            )
+            .await;
+        }
+        Command::ExportFeedback {
+            output,
+            format,
+            anonymise,
+        } => {
+            let auth = load_credentials();
+            export::export(auth, &format, &output, Anonymiser::new(anonymise)).await;
+        }
+        Command::Export(ExportCommand::Feedback {
+            csv,
+            parquet,
+            sqlite,
+            follow,
+            interval,
+            anonymise,
+            pivot,
+        }) => {
             let auth = load_credentials();
-            export::export(auth, &format, &output).await;
+            let anon = Anonymiser::new(anonymise);
+            match (csv, parquet, sqlite) {
+                (Some(csv), None, None) if pivot.is_some() => {
+                    export::export_feedback_pivot(auth, &csv, TableFormat::Csv, anon).await;
+                }
+                (None, Some(parquet), None) if pivot.is_some() => {
+                    export::export_feedback_pivot(auth, &parquet, TableFormat::Parquet, anon).await;
+                }
+                (Some(csv), None, None) => {
+                    export::export(auth, "csv", &csv, anon).await;
+                }
+                (None, Some(parquet), None) => {
+                    export::export(auth, "parquet", &parquet, anon).await;
+                }
+                (None, None, Some(sqlite)) if follow => {
+                    export::follow_feedback_db(auth, &sqlite, interval, anon).await;
+                }
+                (None, None, Some(sqlite)) => {
+                    export::export(auth, "sqlite", &sqlite, anon).await;
+                }
+                _ => {
+                    error!("Please provide exactly one of --csv, --parquet or --sqlite.");
+                    exit(1);
+                }
+            }
+        }
+        Command::Export(ExportCommand::Speakers {
+            category,
+            format,
+            to,
+            anonymise,
+        }) => {
+            let auth = load_credentials();
+            standings::export_speakers(
+                auth,
+                category,
+                &to,
+                TableFormat::parse(&format),
+                Anonymiser::new(anonymise),
+            )
+            .await;
+        }
+        Command::Export(ExportCommand::SpeakerRounds {
+            format,
+            to,
+            anonymise,
+        }) => {
+            let auth = load_credentials();
+            standings::export_speaker_rounds(
+                auth,
+                &to,
+                TableFormat::parse(&format),
+                Anonymiser::new(anonymise),
+            )
+            .await;
+        }
+        Command::Export(ExportCommand::Results {
+            by_debate,
+            format,
+            to,
+            anonymise,
+        }) => {
+            let auth = load_credentials();
+            results::export_results(
+                auth,
+                &to,
+                by_debate,
+                TableFormat::parse(&format),
+                Anonymiser::new(anonymise),
+            )
+            .await;
+        }
+        Command::Export(ExportCommand::Contacts { to }) => {
+            let auth = load_credentials();
+            contacts::export_contacts(auth, &to).await;
+        }
+        Command::Export(ExportCommand::JudgeSchedule {
+            judge,
+            ics,
+            duration,
+        }) => {
+            let auth = load_credentials();
+            schedule::export_judge_schedule(auth, &judge, &ics, duration).await;
+        }
+        Command::Archive {
+            html,
+            include_private,
+        } => {
+            let auth = load_credentials();
+            archive::archive_html(auth, &html, include_private).await;
+        }
+        Command::Export(ExportCommand::Standings {
+            replies,
+            explain,
+            format,
+            to,
+            anonymise,
+        }) => {
+            let auth = load_credentials();
+            standings::export_standings(
+                auth,
+                replies,
+                explain,
+                &to,
+                TableFormat::parse(&format),
+                Anonymiser::new(anonymise),
+            )
+            .await;
+        }
+        Command::Export(ExportCommand::Signage {
+            round,
+            template,
+            out,
+        }) => {
+            let auth = load_credentials();
+            signage::export_signage(auth, &round, &template, &out).await;
+        }
+        Command::Export(ExportCommand::Ballots {
+            round,
+            pdf,
+            template,
+        }) => {
+            let auth = load_credentials();
+            ballots::export_ballots(auth, &round, &pdf, template).await;
+        }
+        Command::Export(ExportCommand::DrawImage { round, png, theme }) => {
+            let auth = load_credentials();
+            draw_image::export_draw_image(auth, &round, &png, theme).await;
+        }
+        Command::ViewStandings { replies, explain } => {
+            let auth = load_credentials();
+            standings::view_standings(auth, replies, explain).await;
+        }
+        Command::Motions(MotionsCommand::View { round, stats }) => {
+            let auth = load_credentials();
+            motions::view_motions(auth, round, stats).await;
+        }
+        Command::Report(ReportCommand::Institutions { payments }) => {
+            let auth = load_credentials();
+            report::institutions_report(auth, payments).await;
+        }
+        Command::Report(ReportCommand::Finals { top, out }) => {
+            let auth = load_credentials();
+            finals::finals_report(auth, top, out).await;
+        }
+        Command::Report(ReportCommand::Diversity) => {
+            let auth = load_credentials();
+            diversity::diversity_report(auth).await;
+        }
+        Command::Check(CheckCommand::N1 { ratio }) => {
+            let rule = report::JudgeRule::parse(&ratio).unwrap_or_else(|e| {
+                error!("{e}");
+                exit(1);
+            });
+            let auth = load_credentials();
+            report::check_judge_rule(auth, rule, &ratio).await;
+        }
+        Command::Check(CheckCommand::PanelsDiversity {
+            round,
+            min_chair_score,
+        }) => {
+            let auth = load_credentials();
+            panel_diversity::check_panels_diversity(auth, &round, min_chair_score).await;
+        }
+        Command::Fees(FeesCommand::Import { csv }) => {
+            let auth = load_credentials();
+            fees::import_payments(auth, &csv).await;
+        }
+        Command::Fees(FeesCommand::Status {
+            team_fee,
+            judge_fee,
+            unpaid,
+        }) => {
+            let auth = load_credentials();
+            fees::fees_status(auth, team_fee, judge_fee, unpaid).await;
+        }
+        Command::Judges(JudgesCommand::History { profiles, judge }) => {
+            let profiles = profiles
+                .iter()
+                .map(|profile| load_profile(Some(profile.trim())))
+                .collect();
+            judge_history::judge_history(profiles, judge).await;
+        }
+        Command::Judges(JudgesCommand::MarkBreaking {
+            csv,
+            from_feedback_threshold,
+            reset,
+        }) => {
+            let auth = load_credentials();
+            breaking_judges::mark_breaking(auth, csv, from_feedback_threshold, reset).await;
+        }
+        Command::Judges(JudgesCommand::Breaking) => {
+            let auth = load_credentials();
+            breaking_judges::list_breaking(auth).await;
+        }
+        Command::Judges(JudgesCommand::SetAdjCore {
+            names,
+            csv,
+            unset,
+            independent,
+            reset,
+        }) => {
+            let auth = load_credentials();
+            adj_core::set_adj_core(auth, names, csv, unset, independent, reset).await;
+        }
+        Command::Schedule(ScheduleCommand::Set { round, start }) => {
+            let auth = load_credentials();
+            schedule::set_start(auth, &round, &start).await;
+        }
+        Command::Schedule(ScheduleCommand::Show) => {
+            let auth = load_credentials();
+            schedule::show_schedule(auth).await;
+        }
+        Command::Schedule(ScheduleCommand::Ics { to, duration }) => {
+            let auth = load_credentials();
+            schedule::export_ics(auth, &to, duration).await;
+        }
+        Command::Log(LogCommand::Show { since }) => {
+            audit_log::show_log(since);
+        }
+        Command::Log(LogCommand::Revert { id }) => {
+            let auth = load_credentials();
+            audit_log::revert(auth, id).await;
+        }
+        Command::Break(BreakCommand::Live { category }) => {
+            let auth = load_credentials();
+            break_live::break_live(auth, category).await;
+        }
+        Command::Feedback(FeedbackCommand::Missing { round, emails }) => {
+            let auth = load_credentials();
+            feedback::missing_feedback(auth, &round, emails).await;
+        }
+        Command::Ironperson(IronpersonCommand::Mark {
+            round,
+            team,
+            speaker,
+        }) => {
+            let auth = load_credentials();
+            ironperson::mark(auth, &round, &team, &speaker).await;
+        }
+        Command::Ironperson(IronpersonCommand::Report { max }) => {
+            let auth = load_credentials();
+            ironperson::report(auth, max).await;
+        }
+        Command::Ballots(BallotsCommand::Compare { round }) => {
+            let auth = load_credentials();
+            ballot_compare::compare_ballots(auth, &round).await;
+        }
+        Command::Ballots(BallotsCommand::Lint { round }) => {
+            let auth = load_credentials();
+            ballot_lint::lint_ballots(auth, &round).await;
+        }
+        Command::Verify(VerifyCommand::Results { against }) => {
+            let auth = load_credentials();
+            results::verify_results(auth, &against).await;
+        }
+        Command::Diff(DiffCommand::Snapshots { a, b }) => {
+            snapshot_diff::diff_snapshots(&a, &b);
+        }
+        Command::Diff(DiffCommand::Live { snapshot, round }) => {
+            let auth = load_credentials();
+            snapshot_diff::diff_live(auth, &snapshot, round).await;
+        }
+        Command::Rooms(RoomsCommand::Prioritize { by, csv }) => {
+            let auth = load_credentials();
+            rooms::prioritize(auth, &by, csv).await;
+        }
+        Command::Rooms(RoomsCommand::AllocateCategories { round }) => {
+            let auth = load_credentials();
+            rooms::allocate_categories(auth, &round).await;
+        }
+        Command::Rooms(RoomsCommand::CheckCapacity { round, audience }) => {
+            let auth = load_credentials();
+            rooms::check_capacity(auth, &round, audience).await;
+        }
+        Command::Rooms(RoomsCommand::Generate {
+            count,
+            pattern,
+            priority,
+            start,
+        }) => {
+            let auth = load_credentials();
+            rooms::generate(auth, count, &pattern, priority, start).await;
+        }
+        Command::Rooms(RoomsCommand::Provision {
+            provider,
+            api_key,
+            overwrite,
+        }) => {
+            let auth = load_credentials();
+            meetings::provision(auth, &provider, &api_key, overwrite).await;
+        }
+        Command::Round(RoundCommand::Release {
+            round,
+            draw,
+            motions,
+            start_timer,
+            webhook,
+        }) => {
+            let auth = load_credentials();
+            release::release_round(auth, &round, draw, motions, start_timer, webhook).await;
+        }
+        Command::Allocate(AllocateCommand::SuggestMoves {
+            round,
+            max_moves,
+            break_size,
+        }) => {
+            let auth = load_credentials();
+            suggest_moves::suggest_moves(auth, &round, max_moves, break_size).await;
+        }
+        Command::Rooms(RoomsCommand::Share {
+            with,
+            csv,
+            duration,
+        }) => {
+            let auth = load_credentials();
+            shared_rooms::share(auth, &with, &csv, duration).await;
+        }
+        Command::Watch {
+            webhook,
+            round,
+            low_score,
+            interval,
+        } => {
+            let auth = load_credentials();
+            watch::watch(auth, &webhook, round, low_score, interval).await;
+        }
+        Command::Api { method, path, body } => {
+            let auth = load_credentials();
+            raw_api::api_request(auth, &method, &path, body).await;
+        }
+        Command::Bot {
+            slack_token,
+            discord_token,
+            channel,
+            allow,
+            interval,
+        } => {
+            let platform = match (slack_token, discord_token) {
+                (Some(token), _) => bot::Platform::Slack { token },
+                (None, Some(token)) => bot::Platform::Discord { token },
+                (None, None) => unreachable!(),
+            };
+            bot::run_bot(platform, &channel, allow, interval).await;
+        }
+        Command::Availability(AvailabilityCommand::Set(args)) => {
+            let auth = load_credentials();
+            availability::do_set_availability(auth, args).await;
+        }
+        Command::Preflight { speakers } => {
+            let auth = load_credentials();
+            preflight::tournament_preflight(auth, speakers).await;
         }
     }
+
+    if let Some((hooks, event, last)) = hooks {
+        let requests = audit_log::read_entries()
+            .into_iter()
+            .filter(|(id, _)| *id > last)
+            .map(|(_, entry)| entry)
+            .collect::<Vec<_>>();
+        hooks.after(event, &requests);
+    }
+}
+
+/// Standard input, which is read in full the first time a CSV file is read
+/// from it (so that it can be read more than once, e.g. when an import is
+/// checked before it is carried out).
+static STDIN: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Exits with an error if more than one of the CSV files given to a command is
+/// `-`, since standard input can only be read as one of them (the others
+/// would silently get the same rows).
+fn check_stdin_paths(paths: &[Option<&str>]) {
+    if paths.iter().filter(|path| **path == Some("-")).count() > 1 {
+        error!("Only one of the CSV files can be read from standard input (`-`).");
+        exit(1);
+    }
+}
+
+/// Opens a CSV file, or standard input if the path is `-`.
+fn open_csv_file(file_path: Option<String>, headers: bool) -> Option<csv::Reader<Box<dyn Read>>> {
+    let mut builder = csv::ReaderBuilder::new();
+    builder.has_headers(headers).trim(Trim::All);
+    open_csv_file_with(file_path, &builder)
 }
 
-fn open_csv_file(file_path: Option<String>, headers: bool) -> Option<csv::Reader<std::fs::File>> {
+/// Opens the CSV file at `file_path` (or standard input, if it is `-`) with
+/// the options set in `builder`.
+fn open_csv_file_with(
+    file_path: Option<String>,
+    builder: &csv::ReaderBuilder,
+) -> Option<csv::Reader<Box<dyn Read>>> {
     file_path.map(|path| {
-        let file = std::fs::File::open(path).unwrap();
-        csv::ReaderBuilder::new()
-            .has_headers(headers)
-            .trim(Trim::All)
-            .from_reader(file)
+        let input: Box<dyn Read> = if path == "-" {
+            let stdin = STDIN.get_or_init(|| {
+                let mut stdin = Vec::new();
+                std::io::stdin().read_to_end(&mut stdin).unwrap();
+                stdin
+            });
+            Box::new(Cursor::new(stdin.as_slice()))
+        } else {
+            Box::new(std::fs::File::open(path).unwrap())
+        };
+        builder.from_reader(input)
     })
 }
 
@@ -0,0 +1,175 @@
+use std::process::exit;
+
+use serde_json::{Value, json};
+use tracing::{Level, error, info, span, warn};
+
+use crate::{Auth, api_utils::get_venues, request_manager::RequestManager, rooms::natural_cmp};
+
+/// A video-conferencing service which can create a meeting for each room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Provider {
+    Zoom,
+    GoogleMeet,
+}
+
+impl Provider {
+    fn parse(provider: &str) -> Provider {
+        match provider.to_ascii_lowercase().as_str() {
+            "zoom" => Provider::Zoom,
+            "google-meet" | "meet" => Provider::GoogleMeet,
+            _ => {
+                error!("Invalid provider `{provider}`, expected either zoom or google-meet");
+                exit(1);
+            }
+        }
+    }
+
+    /// The endpoint which creates a meeting.
+    fn endpoint(self) -> &'static str {
+        match self {
+            Provider::Zoom => "https://api.zoom.us/v2/users/me/meetings",
+            Provider::GoogleMeet => "https://meet.googleapis.com/v2/spaces",
+        }
+    }
+
+    /// The body of the request which creates the meeting for a room. Zoom
+    /// meetings are created as recurring meetings with no fixed time (so that
+    /// the same link works for every round), which participants can join
+    /// before the host.
+    fn body(self, topic: &str) -> Value {
+        match self {
+            Provider::Zoom => json!({
+                "topic": topic,
+                "type": 3,
+                "settings": {
+                    "join_before_host": true,
+                    "waiting_room": false,
+                }
+            }),
+            Provider::GoogleMeet => json!({
+                "config": { "accessType": "OPEN" }
+            }),
+        }
+    }
+
+    /// The link participants use to join the meeting, from the provider's
+    /// response to the request which created it.
+    fn join_url(self, meeting: &Value) -> Option<String> {
+        let url = match self {
+            Provider::Zoom => &meeting["join_url"],
+            Provider::GoogleMeet => &meeting["meetingUri"],
+        };
+        url.as_str().map(|url| url.to_string())
+    }
+}
+
+/// Creates a meeting with `provider` for each room and stores its join link
+/// as the room's external URL (which Tabbycat shows next to the room on the
+/// draw). `token` is an OAuth access token for the provider's API. Rooms which
+/// already have an external URL are skipped unless `overwrite` is set.
+pub async fn provision(auth: Auth, provider: &str, token: &str, overwrite: bool) {
+    let provider = Provider::parse(provider);
+    let manager = RequestManager::new(&auth.api_key);
+
+    let mut venues = get_venues(&auth, manager.clone()).await;
+    venues.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+
+    let span = span!(Level::INFO, "provision_rooms");
+    let _guard = span.enter();
+
+    // note: this deliberately doesn't use `RequestManager`, which would attach
+    // the Tabbycat API key to the request
+    let client = reqwest::Client::new();
+
+    let mut created = 0;
+    for venue in &venues {
+        let name = venue.name.as_str();
+        let current = serde_json::to_value(venue).unwrap()["external_url"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if !current.is_empty() && !overwrite {
+            info!("Room {name} already has a link ({current})");
+            continue;
+        }
+
+        let topic = format!("{} – {name}", auth.tournament_slug);
+        let meeting = match client
+            .post(provider.endpoint())
+            .bearer_auth(token)
+            .json(&provider.body(&topic))
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => resp.json::<Value>().await.ok(),
+            Ok(resp) => {
+                error!(
+                    "Failed to create a meeting for room {name}: {} {}",
+                    resp.status(),
+                    resp.text().await.unwrap_or_default()
+                );
+                exit(1);
+            }
+            Err(e) => {
+                error!("Failed to create a meeting for room {name}: {e}");
+                exit(1);
+            }
+        };
+        let Some(join_url) = meeting.as_ref().and_then(|m| provider.join_url(m)) else {
+            error!("The meeting created for room {name} has no join link");
+            exit(1);
+        };
+
+        let resp = manager
+            .send_request(|| {
+                manager
+                    .client
+                    .patch(&venue.url)
+                    .json(&json!({ "external_url": join_url }))
+                    .build()
+                    .unwrap()
+            })
+            .await;
+        if !resp.status().is_success() {
+            // the meeting exists now, so make sure its link isn't lost
+            warn!("The link for room {name} is {join_url}");
+            error!(
+                "Failed to set the link of room {name}: {} {}",
+                resp.status(),
+                resp.text().await.unwrap()
+            );
+            exit(1);
+        }
+        created += 1;
+        info!("Set the link of room {name} to {join_url}");
+    }
+
+    let skipped = venues.len() - created;
+    if skipped > 0 {
+        info!(
+            "Skipped {skipped} room(s) which already have a link (use --overwrite to replace them)"
+        );
+    }
+    info!("Created {created} meeting(s)");
+}
+
+#[cfg(test)]
+#[test]
+fn test_providers() {
+    assert_eq!(Provider::parse("Zoom"), Provider::Zoom);
+    assert_eq!(Provider::parse("google-meet"), Provider::GoogleMeet);
+
+    assert_eq!(Provider::Zoom.body("R1")["topic"], "R1");
+    assert_eq!(
+        Provider::Zoom.join_url(&json!({ "id": 1, "join_url": "https://zoom.us/j/1" })),
+        Some("https://zoom.us/j/1".to_string())
+    );
+    assert_eq!(
+        Provider::GoogleMeet.join_url(&json!({
+            "name": "spaces/abc",
+            "meetingUri": "https://meet.google.com/abc-defg-hij"
+        })),
+        Some("https://meet.google.com/abc-defg-hij".to_string())
+    );
+    assert_eq!(Provider::GoogleMeet.join_url(&json!({})), None);
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+
+use crate::{
+    Auth,
+    api_utils::{get_ballots, get_round, get_rounds, pairings_of_round},
+    request_manager::RequestManager,
+};
+
+/// How teams on one side fared when debating a motion.
+#[derive(Default, Debug, Clone, Copy)]
+struct SideRecord {
+    debates: u32,
+    wins: u32,
+    points: f64,
+}
+
+/// Lists the motions of each round (or only `round`, if provided). With
+/// `stats`, the results of confirmed ballots are used to show how balanced
+/// each motion was: the number of wins for each side in two-team formats, and
+/// the average points of each position in British Parliamentary.
+pub async fn view_motions(auth: Auth, round: Option<String>, stats: bool) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let rounds = match round {
+        Some(round) => vec![get_round(&round, &auth, manager.clone()).await],
+        None => {
+            let mut rounds = get_rounds(&auth, manager.clone()).await;
+            rounds.sort_by_key(|round| round.seq);
+            rounds
+        }
+    };
+
+    let mut header = vec!["Round".to_string(), "Motion".to_string()];
+    let mut rows = Vec::new();
+    // the sides which appear in any ballot, in the order they should be shown
+    let mut sides: Vec<String> = Vec::new();
+
+    for round in &rounds {
+        let round_json = serde_json::to_value(round).unwrap();
+        let Some(motions) = round_json["motions"].as_array() else {
+            continue;
+        };
+
+        let mut records: BTreeMap<String, BTreeMap<String, SideRecord>> = BTreeMap::new();
+        if stats {
+            for pairing in pairings_of_round(&auth, round, manager.clone()).await {
+                for ballot in get_ballots(&auth, &pairing, manager.clone()).await {
+                    if ballot["confirmed"].as_bool() != Some(true) {
+                        continue;
+                    }
+                    let motion = ballot["motion"].as_str().unwrap_or_default().to_string();
+                    let Some(teams) = ballot["result"]["sheets"][0]["teams"].as_array() else {
+                        continue;
+                    };
+                    for team in teams {
+                        let Some(side) = team["side"].as_str() else {
+                            continue;
+                        };
+                        if !sides.iter().any(|s| s == side) {
+                            sides.push(side.to_string());
+                        }
+                        let record = records
+                            .entry(motion.clone())
+                            .or_default()
+                            .entry(side.to_string())
+                            .or_default();
+                        record.debates += 1;
+                        record.wins += team["win"].as_bool().unwrap_or(false) as u32;
+                        record.points += team["points"].as_f64().unwrap_or(0.0);
+                    }
+                }
+            }
+        }
+
+        for motion in motions {
+            let url = motion["url"].as_str().unwrap_or_default();
+            let row = vec![
+                round.abbreviation.as_str().to_string(),
+                motion["text"].as_str().unwrap_or_default().to_string(),
+            ];
+            rows.push((row, records.get(url).cloned().unwrap_or_default()));
+        }
+    }
+
+    if stats {
+        header.push("Debates".to_string());
+        // in British Parliamentary, every team wins some points, so the
+        // average is more informative than the number of wins
+        let bp = sides.len() > 2;
+        for side in &sides {
+            header.push(if bp {
+                format!("{} avg points", side.to_uppercase())
+            } else {
+                format!("{} wins", side.to_uppercase())
+            });
+        }
+        for (row, records) in rows.iter_mut() {
+            let debates = records.values().map(|r| r.debates).max().unwrap_or(0);
+            row.push(debates.to_string());
+            for side in &sides {
+                let record = records.get(side).copied().unwrap_or_default();
+                row.push(if record.debates == 0 {
+                    String::new()
+                } else if bp {
+                    format!("{:.2}", record.points / record.debates as f64)
+                } else {
+                    record.wins.to_string()
+                });
+            }
+        }
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(header);
+    for (row, _) in rows {
+        table.add_row(row);
+    }
+    println!("{table}");
+}
@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+static IGNORE_ACCENTS: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether names which only differ in their accents (e.g. "Müller" and
+/// "Muller") are treated as the same name (set by `--ignore-accents`).
+pub fn set_ignore_accents(ignore: bool) {
+    IGNORE_ACCENTS.store(ignore, Ordering::Relaxed);
+}
+
+fn normalise_with(name: &str, ignore_accents: bool) -> String {
+    // NFKC first, so that compatibility characters (e.g. full-width letters)
+    // are folded into the characters they stand for
+    let folded = caseless::default_case_fold_str(&name.nfkc().collect::<String>());
+    let normalised = if ignore_accents {
+        folded
+            .nfd()
+            .filter(|c| !is_combining_mark(*c))
+            .nfc()
+            .collect::<String>()
+    } else {
+        // case folding can produce text which is no longer normalised
+        folded.nfkc().collect::<String>()
+    };
+    normalised.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Normalises the name of a participant, institution or room so that it can
+/// be compared with other names: it is converted to Unicode normalisation form
+/// NFKC, case folded, and has its whitespace collapsed (and, if enabled with
+/// [`set_ignore_accents`], its accents removed).
+pub fn normalise(name: &str) -> String {
+    normalise_with(name, IGNORE_ACCENTS.load(Ordering::Relaxed))
+}
+
+/// Whether `a` and `b` are the same name (see [`normalise`]).
+pub fn names_match(a: &str, b: &str) -> bool {
+    normalise(a) == normalise(b)
+}
+
+/// Whether `name` contains `query` (see [`normalise`]).
+pub fn name_contains(name: &str, query: &str) -> bool {
+    normalise(name).contains(&normalise(query))
+}
+
+/// A key to sort names alphabetically by. Accents are always ignored, so that
+/// (for example) "Émile" sorts next to "Emile" rather than after "Zoë".
+pub fn sort_key(name: &str) -> String {
+    normalise_with(name, true)
+}
+
+#[cfg(test)]
+#[test]
+fn test_normalise() {
+    assert_eq!(normalise_with("  Alex   SMITH ", false), "alex smith");
+    assert_eq!(normalise_with("Ｍüller", false), "müller");
+    assert_eq!(
+        normalise_with("STRASSE", false),
+        normalise_with("Straße", false)
+    );
+    // "ü" written as a single character and as "u" plus a combining diaeresis
+    assert_eq!(
+        normalise_with("M\u{fc}ller", false),
+        normalise_with("Mu\u{308}ller", false)
+    );
+    assert_ne!(
+        normalise_with("Müller", false),
+        normalise_with("Muller", false)
+    );
+    assert_eq!(
+        normalise_with("Müller", true),
+        normalise_with("Muller", true)
+    );
+    assert_eq!(normalise_with("Łódź", true), "łodz");
+
+    let mut names = vec!["Zoë", "Émile", "Emma", "anna"];
+    names.sort_by_key(|name| sort_key(name));
+    assert_eq!(names, vec!["anna", "Émile", "Emma", "Zoë"]);
+}
@@ -0,0 +1,210 @@
+use std::process::exit;
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use tracing::{error, info};
+
+use crate::{
+    Auth,
+    api_utils::{get_judges, get_round_and_pairings, get_venues},
+    diversity::{NOT_GIVEN, gender},
+    request_manager::RequestManager,
+};
+
+/// A voting member of a panel, as far as the checks are concerned.
+#[derive(Debug, Clone, PartialEq)]
+struct PanelJudge {
+    name: String,
+    gender: &'static str,
+    base_score: Option<f64>,
+}
+
+/// The median of `scores` (or `None` if there are none).
+fn median(mut scores: Vec<f64>) -> Option<f64> {
+    if scores.is_empty() {
+        return None;
+    }
+    scores.sort_by(f64::total_cmp);
+    let mid = scores.len() / 2;
+    Some(if scores.len() % 2 == 0 {
+        (scores[mid - 1] + scores[mid]) / 2.0
+    } else {
+        scores[mid]
+    })
+}
+
+/// The problems with a panel: a missing or inexperienced chair (whose base
+/// score is below `min_chair_score`), or voting adjudicators who are all of
+/// the same gender. Panels where anyone's gender is unknown are not flagged
+/// for gender.
+fn problems(
+    chair: Option<&PanelJudge>,
+    panellists: &[PanelJudge],
+    min_chair_score: f64,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    match chair {
+        None => problems.push("no chair".to_string()),
+        Some(chair) => match chair.base_score {
+            Some(score) if score >= min_chair_score => {}
+            Some(score) => problems.push(format!(
+                "chair {} has a base score of {score}, below {min_chair_score}",
+                chair.name
+            )),
+            None => problems.push(format!("chair {} has no base score", chair.name)),
+        },
+    }
+
+    let voting = chair.into_iter().chain(panellists).collect::<Vec<_>>();
+    if voting.len() >= 2 && voting.iter().all(|judge| judge.gender != NOT_GIVEN) {
+        let first = voting[0].gender;
+        if voting.iter().all(|judge| judge.gender == first) {
+            problems.push(format!("all-{first} panel"));
+        }
+    }
+    problems
+}
+
+/// Flags the debates in `round` whose panels are all of one gender, or have no
+/// experienced chair (one whose base score is at least `min_chair_score`,
+/// which defaults to the median base score of all adjudicators).
+pub async fn check_panels_diversity(auth: Auth, round: &str, min_chair_score: Option<f64>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((round, pairings), judges, venues) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+    );
+
+    let judges = judges
+        .iter()
+        .map(|judge| {
+            let json = serde_json::to_value(judge).unwrap();
+            (
+                judge.url.clone(),
+                PanelJudge {
+                    name: judge.name.clone(),
+                    gender: gender(&json),
+                    base_score: json["base_score"].as_f64(),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    let min_chair_score = min_chair_score
+        .or_else(|| {
+            median(
+                judges
+                    .iter()
+                    .filter_map(|(_, judge)| judge.base_score)
+                    .collect(),
+            )
+        })
+        .unwrap_or(0.0);
+    info!("Chairs should have a base score of at least {min_chair_score}");
+
+    let judge = |url: &String| {
+        judges
+            .iter()
+            .find(|(judge_url, _)| judge_url == url)
+            .map(|(_, judge)| judge.clone())
+    };
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["Room", "Panel", "Problems"]);
+    let mut flagged = 0;
+    for pairing in &pairings {
+        let chair = pairing
+            .adjudicators
+            .as_ref()
+            .and_then(|adjs| adjs.chair.as_ref())
+            .and_then(judge);
+        let panellists = pairing
+            .adjudicators
+            .iter()
+            .flat_map(|adjs| &adjs.panellists)
+            .filter_map(judge)
+            .collect::<Vec<_>>();
+
+        let problems = problems(chair.as_ref(), &panellists, min_chair_score);
+        if problems.is_empty() {
+            continue;
+        }
+        flagged += 1;
+
+        let room = pairing
+            .venue
+            .as_ref()
+            .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+            .map(|venue| venue.name.as_str().to_string())
+            .unwrap_or_else(|| format!("Debate {}", pairing.id));
+        let panel = chair
+            .iter()
+            .map(|chair| (chair, " (c)"))
+            .chain(panellists.iter().map(|panellist| (panellist, "")))
+            .map(|(judge, role)| {
+                format!(
+                    "{}{role}: {}, {}",
+                    judge.name,
+                    judge.gender,
+                    judge
+                        .base_score
+                        .map(|score| score.to_string())
+                        .unwrap_or_else(|| "no score".to_string())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        table.add_row(vec![room, panel, problems.join("\n")]);
+    }
+
+    if flagged == 0 {
+        println!(
+            "No panels in {} have diversity or chair problems",
+            round.name.as_str()
+        );
+        return;
+    }
+    println!("{table}");
+    error!(
+        "{flagged} of {} panel(s) in {} have problems",
+        pairings.len(),
+        round.name.as_str()
+    );
+    exit(1);
+}
+
+#[cfg(test)]
+#[test]
+fn test_panel_problems() {
+    let judge = |name: &str, gender: &'static str, base_score: Option<f64>| PanelJudge {
+        name: name.to_string(),
+        gender,
+        base_score,
+    };
+
+    assert_eq!(median(vec![3.0, 1.0, 2.0]), Some(2.0));
+    assert_eq!(median(vec![1.0, 4.0]), Some(2.5));
+    assert_eq!(median(vec![]), None);
+
+    let chair = judge("Alex", "female", Some(4.0));
+    let wings = [judge("Sam", "male", Some(3.0))];
+    assert!(problems(Some(&chair), &wings, 3.5).is_empty());
+    assert_eq!(
+        problems(Some(&chair), &[judge("Kim", "female", None)], 3.5),
+        vec!["all-female panel"]
+    );
+    // unknown genders are not flagged
+    assert!(problems(Some(&chair), &[judge("Kim", NOT_GIVEN, None)], 3.5).is_empty());
+    assert_eq!(
+        problems(Some(&judge("Jo", "male", Some(2.0))), &wings, 3.5),
+        vec![
+            "chair Jo has a base score of 2, below 3.5",
+            "all-male panel"
+        ]
+    );
+    assert_eq!(problems(None, &[], 3.5), vec!["no chair"]);
+}
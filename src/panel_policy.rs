@@ -0,0 +1,149 @@
+use std::process::exit;
+
+use serde::Deserialize;
+use tabbycat_api::types::DebateAdjudicator;
+use tracing::{error, info, warn};
+
+/// The name of the file (in the current working directory) from which the
+/// panel-size policy is read.
+pub const PANEL_POLICY_FILE: &str = "panels.toml";
+
+/// Rules which every panel should follow, read from a `panels.toml` file in
+/// the working directory. For example
+///
+/// ```toml
+/// odd_panels = true
+/// max_trainees = 1
+/// max_panel_size = 5
+/// ```
+///
+/// The size of a panel is the number of voting adjudicators (i.e. the chair
+/// and the panellists, but not the trainees).
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PanelPolicy {
+    /// Every panel must have an odd number of voting adjudicators.
+    #[serde(default)]
+    pub odd_panels: bool,
+    /// The largest number of trainees in a single room.
+    pub max_trainees: Option<usize>,
+    pub min_panel_size: Option<usize>,
+    pub max_panel_size: Option<usize>,
+}
+
+impl PanelPolicy {
+    /// Loads `panels.toml` from the working directory, or returns a policy
+    /// which allows every panel if the file does not exist.
+    pub fn load() -> PanelPolicy {
+        let text = match std::fs::read_to_string(PANEL_POLICY_FILE) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return PanelPolicy::default();
+            }
+            Err(e) => {
+                error!("Could not read {PANEL_POLICY_FILE}: {e}");
+                exit(1)
+            }
+        };
+
+        match toml::from_str(&text) {
+            Ok(policy) => {
+                info!("Using the panel policy from {PANEL_POLICY_FILE}");
+                policy
+            }
+            Err(e) => {
+                error!("Your {PANEL_POLICY_FILE} file is malformed: {e}");
+                exit(1)
+            }
+        }
+    }
+
+    /// The ways in which `panel` breaks this policy. Empty panels (i.e. rooms
+    /// which have not been allocated yet) are always allowed.
+    pub fn violations(&self, panel: &DebateAdjudicator) -> Vec<String> {
+        let size = panel.chair.iter().count() + panel.panellists.len();
+        if size == 0 && panel.trainees.is_empty() {
+            return vec![];
+        }
+
+        let mut problems = Vec::new();
+        if self.odd_panels && size % 2 == 0 {
+            problems.push(format!("the panel has an even number of judges ({size})"));
+        }
+        if let Some(min) = self.min_panel_size.filter(|min| size < *min) {
+            problems.push(format!("the panel has {size} judge(s) (minimum {min})"));
+        }
+        if let Some(max) = self.max_panel_size.filter(|max| size > *max) {
+            problems.push(format!("the panel has {size} judge(s) (maximum {max})"));
+        }
+        if let Some(max) = self.max_trainees.filter(|max| panel.trainees.len() > *max) {
+            problems.push(format!(
+                "the room has {} trainee(s) (maximum {max})",
+                panel.trainees.len()
+            ));
+        }
+        problems
+    }
+
+    /// Checks each `(room, panel)` against this policy, before any of them
+    /// are saved. Violations are reported as warnings if `force` is set, and
+    /// otherwise cause the command to exit without making any changes.
+    pub fn enforce<'a>(
+        &self,
+        panels: impl IntoIterator<Item = (String, &'a DebateAdjudicator)>,
+        force: bool,
+    ) {
+        let mut violated = false;
+        for (room, panel) in panels {
+            for problem in self.violations(panel) {
+                violated = true;
+                warn!("{room}: {problem}");
+            }
+        }
+
+        if violated && !force {
+            error!(
+                "Refusing to make changes which break the panel policy in {PANEL_POLICY_FILE} (pass --force to override)"
+            );
+            exit(1)
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_violations() {
+    let policy = PanelPolicy {
+        odd_panels: true,
+        max_trainees: Some(1),
+        min_panel_size: None,
+        max_panel_size: Some(3),
+    };
+    let panel = |panellists: usize, trainees: usize| DebateAdjudicator {
+        chair: Some("chair".to_string()),
+        panellists: vec!["panellist".to_string(); panellists],
+        trainees: vec!["trainee".to_string(); trainees],
+    };
+
+    assert!(policy.violations(&panel(2, 1)).is_empty());
+    assert_eq!(
+        policy.violations(&panel(1, 2)),
+        vec![
+            "the panel has an even number of judges (2)".to_string(),
+            "the room has 2 trainee(s) (maximum 1)".to_string(),
+        ]
+    );
+    assert_eq!(
+        policy.violations(&panel(4, 0)),
+        vec!["the panel has 5 judge(s) (maximum 3)".to_string()]
+    );
+    assert!(
+        policy
+            .violations(&DebateAdjudicator {
+                chair: None,
+                panellists: vec![],
+                trainees: vec![],
+            })
+            .is_empty()
+    );
+}
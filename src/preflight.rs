@@ -0,0 +1,723 @@
+//! Checks which are run over the CSV files before `tabbycat import` sends
+//! anything to Tabbycat, so that problems in the spreadsheets are reported up
+//! front instead of as an opaque API error half-way through the import, and
+//! the tournament-wide checks run by `tabbycat preflight` before round 1.
+
+use std::{
+    collections::{HashMap, HashSet},
+    process::exit,
+};
+
+use itertools::Itertools;
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use crate::{
+    Auth,
+    api_utils::{
+        get_availabilities, get_break_categories, get_judges, get_preference, get_rounds,
+        get_teams, get_venues,
+    },
+    import::TeamRow,
+    names::{names_match, normalise},
+    request_manager::RequestManager,
+};
+
+/// Problems found during preflight.
+#[derive(Default, Debug)]
+pub struct PreflightReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl PreflightReport {
+    pub fn error(&mut self, message: String) {
+        self.errors.push(message);
+    }
+
+    pub fn warn(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+
+    /// Logs all problems, returning `true` if the import can go ahead.
+    pub fn finish(&self) -> bool {
+        for warning in &self.warnings {
+            warn!("{warning}");
+        }
+        for e in &self.errors {
+            error!("{e}");
+        }
+        if !self.errors.is_empty() {
+            error!(
+                "Preflight found {} problem(s) in the CSV files; nothing has been imported.",
+                self.errors.len()
+            );
+        }
+        self.errors.is_empty()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Choice {
+    pub value: String,
+    pub display_name: String,
+}
+
+/// Fetches the emoji which Tabbycat accepts for teams (from the metadata
+/// returned by an OPTIONS request to the teams endpoint). Returns `None` if
+/// the server doesn't expose this information.
+pub async fn get_emoji_choices(auth: &Auth, manager: RequestManager) -> Option<Vec<Choice>> {
+    let url = format!(
+        "{}/api/v1/tournaments/{}/teams",
+        auth.tabbycat_url, auth.tournament_slug
+    );
+    let resp = manager
+        .send_request(|| {
+            manager
+                .client
+                .request(reqwest::Method::OPTIONS, &url)
+                .build()
+                .unwrap()
+        })
+        .await;
+
+    let metadata: serde_json::Value = resp.json().await.ok()?;
+    serde_json::from_value(metadata["actions"]["POST"]["emoji"]["choices"].clone()).ok()
+}
+
+/// Removes variation selectors, joiners and skin tone modifiers, which are
+/// frequently added (or lost) when emoji are copied between spreadsheets.
+fn strip_emoji_modifiers(emoji: &str) -> String {
+    emoji
+        .chars()
+        .filter(|c| !matches!(*c as u32, 0xFE0E | 0xFE0F | 0x200D | 0x1F3FB..=0x1F3FF))
+        .collect()
+}
+
+/// Suggests the closest valid emoji to `emoji`, either by comparing emoji
+/// without modifiers or (if a name such as "smile" was entered) by name.
+pub fn nearest_emoji<'c>(emoji: &str, choices: &'c [Choice]) -> Option<&'c Choice> {
+    let stripped = strip_emoji_modifiers(emoji.trim());
+    choices
+        .iter()
+        .find(|choice| strip_emoji_modifiers(&choice.value) == stripped)
+        .or_else(|| {
+            let name = emoji
+                .trim()
+                .trim_matches(':')
+                .replace(['_', '-'], " ")
+                .to_lowercase();
+            if name.is_empty() {
+                return None;
+            }
+            choices
+                .iter()
+                .find(|choice| choice.display_name.to_lowercase().contains(&name))
+        })
+}
+
+/// Checks that every emoji in the teams CSV file is one which Tabbycat
+/// accepts.
+pub fn check_emoji(
+    rows: &[TeamRow],
+    choices: &[Choice],
+    strip: bool,
+    report: &mut PreflightReport,
+) {
+    for row in rows {
+        let Some(emoji) = row.emoji.as_ref().filter(|e| !e.trim().is_empty()) else {
+            continue;
+        };
+        if choices.iter().any(|choice| choice.value == emoji.trim()) {
+            continue;
+        }
+
+        let suggestion = nearest_emoji(emoji, choices)
+            .map(|choice| format!(" (did you mean {}?)", choice.value))
+            .unwrap_or_default();
+        let message = format!(
+            "Team {} has emoji {emoji} which Tabbycat does not accept{suggestion}",
+            row.full_name
+        );
+        if strip {
+            report.warn(format!("{message}; it will not be imported."));
+        } else {
+            report.error(format!("{message}. Pass --strip-invalid-emoji to drop it."));
+        }
+    }
+}
+
+/// Checks that the URL keys of speakers in the teams CSV file are unique, both
+/// within the file and across the participants (given as `(name, url_key)`)
+/// which already exist in the tournament.
+pub fn check_url_keys(
+    rows: &[TeamRow],
+    existing: &[(String, String)],
+    report: &mut PreflightReport,
+) {
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for speaker in rows.iter().flat_map(|row| &row.speakers) {
+        let Some(key) = speaker.url_key.as_deref() else {
+            continue;
+        };
+
+        if let Some(other) = seen.insert(key, &speaker.name) {
+            report.error(format!(
+                "Speakers {other} and {} both have URL key {key}",
+                speaker.name
+            ));
+        }
+
+        // a speaker with the same name is the same person (and will not be
+        // imported again)
+        if let Some((name, _)) = existing
+            .iter()
+            .find(|(name, existing_key)| existing_key == key && name.trim() != speaker.name.trim())
+        {
+            report.error(format!(
+                "Speaker {} has URL key {key}, which is already used by {name}",
+                speaker.name
+            ));
+        }
+    }
+}
+
+/// Checks that each team in the teams CSV file has `substantive_speakers`
+/// speakers (as set in the tournament's debate rules). In British
+/// Parliamentary (where `teams_in_debate` is 4), a team with one or four
+/// speakers usually means that the `speakerN_` columns of the spreadsheet have
+/// shifted, so this is pointed out.
+pub fn check_speaker_counts(
+    rows: &[TeamRow],
+    substantive_speakers: usize,
+    teams_in_debate: usize,
+    report: &mut PreflightReport,
+) {
+    for row in rows {
+        let count = row.speakers.len();
+        if count == substantive_speakers {
+            continue;
+        }
+
+        let mut message = format!(
+            "Team {} has {count} speaker(s), but the tournament expects \
+            {substantive_speakers}",
+            row.full_name.trim()
+        );
+        if teams_in_debate == 4 && matches!(count, 1 | 4) {
+            message.push_str(
+                " (this usually means the speakerN_ columns of the spreadsheet are misaligned)",
+            );
+        }
+        report.warn(message);
+    }
+}
+
+/// The longest `reference` (full name) Tabbycat accepts for a team.
+pub const MAX_TEAM_NAME: usize = 150;
+/// The longest `short_reference` Tabbycat accepts for a team (longer full
+/// names are truncated to this length if no short name is given).
+pub const MAX_TEAM_SHORT_NAME: usize = 35;
+/// The longest code name Tabbycat accepts for a team.
+pub const MAX_TEAM_CODE_NAME: usize = 150;
+
+/// Reads a denylist of words and phrases (one per line; blank lines and lines
+/// starting with `#` are ignored) which team names may not contain.
+pub fn read_denylist(path: &str) -> Vec<String> {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        error!("Could not read the denylist {path}: {e}");
+        exit(1)
+    });
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Lowercases `name` and replaces punctuation with spaces, so that words can
+/// be matched on their boundaries.
+fn words(name: &str) -> String {
+    let words = name
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .join(" ");
+    format!(" {words} ")
+}
+
+/// Returns the first entry in `denylist` which occurs in `name` as a whole
+/// word (or phrase).
+pub fn denied_word<'d>(name: &str, denylist: &'d [String]) -> Option<&'d String> {
+    let name = words(name);
+    denylist
+        .iter()
+        .find(|denied| !denied.trim().is_empty() && name.contains(&words(denied)))
+}
+
+/// Checks the names of the teams in the teams CSV file: that they fit within
+/// Tabbycat's length limits, that their short names (after the institution
+/// prefix is applied) do not collide with each other or with existing teams,
+/// and that no name contains a word from the denylist.
+///
+/// `institution_code` returns the code of a team's institution (for the
+/// prefix), and `existing` is the short names of the existing teams.
+pub fn check_team_names(
+    rows: &[TeamRow],
+    institution_code: impl Fn(&str) -> Option<String>,
+    use_institution_prefix: bool,
+    existing: &[String],
+    denylist: &[String],
+    report: &mut PreflightReport,
+) {
+    let mut seen_short: HashMap<String, &str> = HashMap::new();
+    let mut seen_code: HashMap<String, &str> = HashMap::new();
+
+    for row in rows {
+        let full_name = row.full_name.trim();
+        let short_name = row
+            .short_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty());
+        let code_name = row
+            .code_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty());
+
+        for (kind, name, limit) in [
+            ("name", Some(full_name), MAX_TEAM_NAME),
+            ("short name", short_name, MAX_TEAM_SHORT_NAME),
+            ("code name", code_name, MAX_TEAM_CODE_NAME),
+        ] {
+            let Some(name) = name else {
+                continue;
+            };
+            let length = name.chars().count();
+            if length > limit {
+                report.error(format!(
+                    "Team {full_name} has a {kind} of {length} characters, but Tabbycat only \
+                    allows {limit}"
+                ));
+            }
+            if let Some(word) = denied_word(name, denylist) {
+                report.error(format!(
+                    "Team {full_name} has the {kind} {name:?}, which contains {word:?} (from \
+                    the denylist)"
+                ));
+            }
+        }
+
+        // Tabbycat truncates the full name if there is no short name
+        let mut effective = short_name
+            .map(str::to_string)
+            .unwrap_or_else(|| full_name.chars().take(MAX_TEAM_SHORT_NAME).collect());
+        let prefix = row
+            .institution
+            .as_deref()
+            .filter(|_| use_institution_prefix || row.use_institution_prefix)
+            .and_then(&institution_code);
+        if let Some(code) = prefix {
+            effective = format!("{code} {effective}");
+        }
+
+        if let Some(other) = seen_short.insert(normalise(&effective), full_name) {
+            report.error(format!(
+                "Teams {other} and {full_name} would both have the short name {effective:?}"
+            ));
+        } else if existing.iter().any(|name| names_match(name, &effective)) {
+            report.warn(format!(
+                "Team {full_name} would have the short name {effective:?}, which an existing \
+                team already has (it will be treated as the same team)"
+            ));
+        }
+
+        if let Some(code_name) = code_name
+            && let Some(other) = seen_code.insert(normalise(code_name), full_name)
+        {
+            report.error(format!(
+                "Teams {other} and {full_name} both have the code name {code_name:?}"
+            ));
+        }
+    }
+}
+
+/// The next free name for a team called `name`: if it ends in a single
+/// capital letter (e.g. `A` or `Oxford A`) the letter is advanced, otherwise a
+/// letter is appended (`Oxford` becomes `Oxford B`).
+fn next_name(name: &str, taken: &HashSet<String>) -> String {
+    let name = name.trim();
+    let (base, first) = match name.rsplit_once(' ').unwrap_or(("", name)) {
+        (base, letter) if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_uppercase()) => {
+            (base.to_string(), letter.as_bytes()[0] + 1)
+        }
+        _ => (name.to_string(), b'B'),
+    };
+
+    (first..=b'Z')
+        .map(|letter| format!("{base} {}", letter as char).trim().to_string())
+        .find(|candidate| !taken.contains(&normalise(candidate)))
+        .unwrap_or_else(|| format!("{name} {}", taken.len() + 1))
+}
+
+/// Finds teams from the same institution with the same name, which Tabbycat
+/// will not accept (and which would be indistinguishable once the institution
+/// prefix is applied). If `suffix` is set the later teams are renamed (e.g. a
+/// second team called `A` becomes `B`) and the indices of the renamed rows are
+/// returned; otherwise the offending rows are reported as errors.
+pub fn resolve_name_collisions(
+    rows: &mut [TeamRow],
+    institution_code: impl Fn(&str) -> Option<String>,
+    suffix: bool,
+    report: &mut PreflightReport,
+) -> Vec<usize> {
+    let institution = |row: &TeamRow| {
+        row.institution
+            .as_deref()
+            .map(|name| institution_code(name).unwrap_or_else(|| name.trim().to_string()))
+            .map(|code| normalise(&code))
+    };
+
+    let mut renamed = Vec::new();
+    for i in 0..rows.len() {
+        let Some(inst) = institution(&rows[i]) else {
+            continue;
+        };
+        let Some(first) = (0..i).find(|&j| {
+            institution(&rows[j]).as_ref() == Some(&inst)
+                && names_match(&rows[j].full_name, &rows[i].full_name)
+        }) else {
+            continue;
+        };
+
+        // the CSV file has a header, so the first team is on line 2
+        if !suffix {
+            report.error(format!(
+                "Teams {} (rows {} and {} of the teams CSV file) are from the same institution \
+                and have the same name. Rename one of them, or pass --suffix-colliding-names.",
+                rows[i].full_name.trim(),
+                first + 2,
+                i + 2
+            ));
+            continue;
+        }
+
+        let same_institution = rows
+            .iter()
+            .filter(|row| institution(row).as_ref() == Some(&inst))
+            .collect::<Vec<_>>();
+        let full_names = same_institution
+            .iter()
+            .map(|row| normalise(&row.full_name))
+            .collect();
+        let short_names = same_institution
+            .iter()
+            .filter_map(|row| row.short_name.as_deref())
+            .map(normalise)
+            .collect();
+
+        let full_name = next_name(&rows[i].full_name, &full_names);
+        let short_name = rows[i]
+            .short_name
+            .as_deref()
+            .map(|name| next_name(name, &short_names));
+        report.warn(format!(
+            "Renamed team {} (row {}) to {full_name}, as row {} has the same name and \
+            institution",
+            rows[i].full_name.trim(),
+            i + 2,
+            first + 2
+        ));
+        rows[i].full_name = full_name;
+        rows[i].short_name = short_name;
+        renamed.push(i);
+    }
+    renamed
+}
+
+/// One item of the `tabbycat preflight` checklist.
+struct Check {
+    description: String,
+    problems: Vec<String>,
+}
+
+impl Check {
+    fn new(description: impl Into<String>) -> Check {
+        Check {
+            description: description.into(),
+            problems: vec![],
+        }
+    }
+
+    fn fail(&mut self, problem: String) {
+        self.problems.push(problem);
+    }
+}
+
+/// Checks that the tournament is ready for round 1, printing a checklist.
+/// `speakers` is the number of speakers each team should have (if not given,
+/// the most common number of speakers per team is used).
+pub async fn tournament_preflight(auth: Auth, speakers: Option<usize>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, judges, venues, break_categories, rounds, teams_in_debate) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+        get_break_categories(&auth, manager.clone()),
+        get_rounds(&auth, manager.clone()),
+        get_preference(&auth, "debate_rules__teams_in_debate", manager.clone()),
+    );
+    let teams_in_debate = teams_in_debate.as_u64().unwrap_or(2) as usize;
+
+    let mut checks = Vec::new();
+
+    let expected_speakers = speakers.unwrap_or_else(|| {
+        teams
+            .iter()
+            .map(|team| team.speakers.len())
+            .counts()
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(speakers, _)| speakers)
+            .unwrap_or(0)
+    });
+    let mut check = Check::new(format!("Every team has {expected_speakers} speakers"));
+    for team in &teams {
+        if team.speakers.len() != expected_speakers {
+            check.fail(format!(
+                "{} has {} speakers",
+                team.long_name,
+                team.speakers.len()
+            ));
+        }
+    }
+    checks.push(check);
+
+    let mut check = Check::new("Every judge has an institution or is an independent adjudicator");
+    for judge in &judges {
+        if judge.institution.is_none() && judge.independent != Some(true) {
+            check.fail(format!(
+                "{} has no institution and is not marked as independent",
+                judge.name
+            ));
+        }
+    }
+    checks.push(check);
+
+    let mut check = Check::new("Every break category has eligible teams");
+    for category in &break_categories {
+        let eligible = teams
+            .iter()
+            .filter(|team| team.break_categories.contains(&category.url))
+            .count();
+        if eligible == 0 {
+            check.fail(format!(
+                "No teams are eligible for {}",
+                category.name.as_str()
+            ));
+        }
+    }
+    checks.push(check);
+
+    let debates = teams.len().div_ceil(teams_in_debate.max(1));
+    let mut check = Check::new(format!(
+        "There are enough rooms for {debates} debates ({} teams)",
+        teams.len()
+    ));
+    if venues.len() < debates {
+        check.fail(format!("There are only {} rooms", venues.len()));
+    }
+    checks.push(check);
+
+    let mut check = Check::new("Availability has been set for round 1");
+    match rounds.iter().min_by_key(|round| round.seq) {
+        Some(round) => {
+            let available = get_availabilities(&auth, round, manager.clone()).await;
+            let available_judges = judges
+                .iter()
+                .filter(|judge| available.contains(&judge.url))
+                .count();
+            let available_teams = teams
+                .iter()
+                .filter(|team| available.contains(&team.url))
+                .count();
+            if available_judges == 0 {
+                check.fail(format!(
+                    "No judges are marked as available for {}",
+                    round.name.as_str()
+                ));
+            }
+            if available_teams == 0 {
+                check.fail(format!(
+                    "No teams are marked as available for {}",
+                    round.name.as_str()
+                ));
+            }
+        }
+        None => check.fail("The tournament has no rounds".to_string()),
+    }
+    checks.push(check);
+
+    for check in &checks {
+        let status = if check.problems.is_empty() {
+            "PASS"
+        } else {
+            "FAIL"
+        };
+        println!("[{status}] {}", check.description);
+        for problem in &check.problems {
+            println!("       - {problem}");
+        }
+    }
+
+    if checks.iter().any(|check| !check.problems.is_empty()) {
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_nearest_emoji() {
+    let choices = vec![
+        Choice {
+            value: "👍".to_string(),
+            display_name: "Thumbs Up".to_string(),
+        },
+        Choice {
+            value: "😄".to_string(),
+            display_name: "Smiling Face With Open Mouth".to_string(),
+        },
+    ];
+
+    assert_eq!(nearest_emoji("👍🏽", &choices).unwrap().value, "👍");
+    assert_eq!(nearest_emoji(":thumbs_up:", &choices).unwrap().value, "👍");
+    assert!(nearest_emoji("🦀", &choices).is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn test_check_team_names() {
+    let row = |full_name: &str, short_name: Option<&str>, institution: &str| TeamRow {
+        full_name: full_name.to_string(),
+        short_name: short_name.map(str::to_string),
+        categories: vec![],
+        code_name: None,
+        institution: Some(institution.to_string()),
+        seed: None,
+        emoji: None,
+        use_institution_prefix: false,
+        speakers: vec![],
+    };
+    let institution_code = |name: &str| Some(name.chars().take(3).collect::<String>());
+
+    let mut report = PreflightReport::default();
+    check_team_names(
+        &[
+            row("Alpha", Some("A"), "Oxford"),
+            row("Bravo", Some("A"), "Cambridge"),
+            row("Charlie", Some("A"), "Cambridge Union"),
+            row("Darn It", None, "Durham"),
+        ],
+        institution_code,
+        true,
+        &["Dur Darn It".to_string()],
+        &["darn".to_string()],
+        &mut report,
+    );
+    // only the short names which are the same after prefixing collide
+    assert!(report.errors.iter().all(|e| !e.contains("Alpha")));
+    assert_eq!(report.errors.len(), 2);
+    assert!(report.errors[0].contains("Bravo and Charlie"));
+    assert!(report.errors[1].contains("\"darn\""));
+    assert_eq!(report.warnings.len(), 1);
+
+    assert_eq!(
+        denied_word("Hell's Angels", &["hell".to_string()]).unwrap(),
+        "hell"
+    );
+    assert_eq!(denied_word("Hello World", &["hell".to_string()]), None);
+}
+
+#[cfg(test)]
+#[test]
+fn test_resolve_name_collisions() {
+    let row = |full_name: &str, institution: &str| TeamRow {
+        full_name: full_name.to_string(),
+        short_name: None,
+        categories: vec![],
+        code_name: None,
+        institution: Some(institution.to_string()),
+        seed: None,
+        emoji: None,
+        use_institution_prefix: true,
+        speakers: vec![],
+    };
+    let mut rows = vec![
+        row("A", "Oxford"),
+        row("A", "Cambridge"),
+        row("A", "Oxford"),
+        row("B", "Oxford"),
+        row("Blue", "Oxford"),
+        row("blue", "Oxford"),
+    ];
+
+    let mut report = PreflightReport::default();
+    resolve_name_collisions(&mut rows.clone(), |_| None, false, &mut report);
+    assert_eq!(report.errors.len(), 2);
+    assert!(report.errors[0].contains("rows 2 and 4"));
+
+    let mut report = PreflightReport::default();
+    let renamed = resolve_name_collisions(&mut rows, |_| None, true, &mut report);
+    assert_eq!(renamed, vec![2, 5]);
+    assert_eq!(rows[2].full_name, "C");
+    assert_eq!(rows[5].full_name, "blue B");
+    assert!(report.errors.is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn test_check_speaker_counts() {
+    let speaker = |name: &str| crate::import::Speaker {
+        name: name.to_string(),
+        categories: vec![],
+        email: None,
+        phone: None,
+        anonymous: None,
+        code_name: None,
+        url_key: None,
+        gender: None,
+        pronoun: None,
+    };
+    let row = |full_name: &str, speakers: &[&str]| TeamRow {
+        full_name: full_name.to_string(),
+        short_name: None,
+        categories: vec![],
+        code_name: None,
+        institution: None,
+        seed: None,
+        emoji: None,
+        use_institution_prefix: false,
+        speakers: speakers.iter().map(|name| speaker(name)).collect(),
+    };
+    let rows = vec![
+        row("A", &["Jo", "Sam"]),
+        row("B", &["Kim"]),
+        row("C", &["Pat", "Alex", "Lee", "Ash"]),
+        row("D", &["Ola", "Ana", "Eve"]),
+    ];
+
+    let mut report = PreflightReport::default();
+    check_speaker_counts(&rows, 2, 4, &mut report);
+    assert!(report.errors.is_empty());
+    assert_eq!(report.warnings.len(), 3);
+    assert!(report.warnings[0].contains("misaligned"));
+    assert!(report.warnings[1].contains("misaligned"));
+    assert!(!report.warnings[2].contains("misaligned"));
+
+    let mut report = PreflightReport::default();
+    check_speaker_counts(&rows, 3, 2, &mut report);
+    assert_eq!(report.warnings.len(), 3);
+    assert!(report.warnings.iter().all(|w| !w.contains("misaligned")));
+}
@@ -0,0 +1,157 @@
+use std::{io::Read, process::exit};
+
+use reqwest::Method;
+use serde_json::Value;
+use tracing::error;
+use url::Url;
+
+use crate::{Auth, request_manager::RequestManager};
+
+/// Whether `url` is on the same origin (scheme, host and port) as the
+/// configured Tabbycat site, and so may be sent the API key.
+fn on_instance(auth: &Auth, url: &str) -> bool {
+    match (Url::parse(url), Url::parse(&auth.tabbycat_url)) {
+        (Ok(url), Ok(instance)) => url.origin() == instance.origin(),
+        _ => false,
+    }
+}
+
+/// The URL of an API endpoint, given either as a path relative to the API
+/// root (e.g. `/tournaments/{slug}/teams`, where `{slug}` is replaced with the
+/// slug of the tournament) or as a full URL on the Tabbycat site.
+fn endpoint_url(auth: &Auth, path: &str) -> Result<String, String> {
+    let path = path.trim().replace("{slug}", &auth.tournament_slug);
+    if path.starts_with("http://") || path.starts_with("https://") {
+        // the API key must not be sent anywhere else
+        if !on_instance(auth, &path) {
+            return Err(format!("{path} is not on {}", auth.tabbycat_url));
+        }
+        return Ok(path);
+    }
+
+    let path = path.trim_start_matches('/');
+    if path.starts_with("api/") {
+        Ok(format!("{}/{path}", auth.tabbycat_url))
+    } else {
+        Ok(format!("{}/api/v1/{path}", auth.tabbycat_url))
+    }
+}
+
+/// Reads the JSON body of a request from `path` (or standard input, if it is
+/// `-`).
+fn read_body(path: &str) -> Value {
+    let text = if path == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).unwrap();
+        text
+    } else {
+        std::fs::read_to_string(path).unwrap_or_else(|e| {
+            error!("Could not read {path}: {e}");
+            exit(1)
+        })
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        error!("{path} is not valid JSON: {e}");
+        exit(1)
+    })
+}
+
+/// Sends a request to an API endpoint which the CLI does not otherwise wrap
+/// (with the stored credentials, and retrying if rate limited), and prints
+/// the response. For `GET` requests, paginated responses (with `results` and
+/// `next` fields) are followed to the end, and the results of every page are
+/// printed together.
+pub async fn api_request(auth: Auth, method: &str, path: &str, body: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let method = Method::from_bytes(method.to_ascii_uppercase().as_bytes()).unwrap();
+    let url = endpoint_url(&auth, path).unwrap_or_else(|e| {
+        error!("{e}");
+        exit(1)
+    });
+    let body = body.as_deref().map(read_body);
+    if body.is_some() && method == Method::GET {
+        error!("GET requests cannot have a body.");
+        exit(1);
+    }
+
+    let mut next = Some(url);
+    let mut results: Option<Vec<Value>> = None;
+    while let Some(url) = next.take() {
+        let resp = manager
+            .send_request_unchecked(|| {
+                let req = manager.client.request(method.clone(), &url);
+                match &body {
+                    Some(body) => req.json(body),
+                    None => req,
+                }
+                .build()
+                .unwrap()
+            })
+            .await;
+
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        if !status.is_success() {
+            error!("Tabbycat responded with {status}");
+            println!("{text}");
+            exit(1);
+        }
+        let Ok(json) = serde_json::from_str::<Value>(&text) else {
+            // e.g. `204 No Content`
+            print!("{text}");
+            return;
+        };
+
+        match (&json["results"], &json["next"]) {
+            (Value::Array(page), next_page) if method == Method::GET => {
+                results.get_or_insert_with(Vec::new).extend(page.clone());
+                next = next_page.as_str().map(|next| next.to_string());
+                if let Some(next) = next.as_deref().filter(|next| !on_instance(&auth, next)) {
+                    error!(
+                        "Not following the next page at {next}, which is not on {}",
+                        auth.tabbycat_url
+                    );
+                    exit(1);
+                }
+            }
+            _ => {
+                println!("{}", serde_json::to_string_pretty(&json).unwrap());
+                return;
+            }
+        }
+    }
+
+    let results = Value::Array(results.unwrap_or_default());
+    println!("{}", serde_json::to_string_pretty(&results).unwrap());
+}
+
+#[cfg(test)]
+#[test]
+fn test_endpoint_url() {
+    let auth = Auth {
+        tabbycat_url: "https://example.com".to_string(),
+        tournament_slug: "wudc".to_string(),
+        api_key: String::new(),
+    };
+
+    assert_eq!(
+        endpoint_url(&auth, "/tournaments/{slug}/teams").unwrap(),
+        "https://example.com/api/v1/tournaments/wudc/teams"
+    );
+    assert_eq!(
+        endpoint_url(&auth, "api/v1/tournaments").unwrap(),
+        "https://example.com/api/v1/tournaments"
+    );
+    assert_eq!(
+        endpoint_url(
+            &auth,
+            "https://example.com/api/v1/tournaments/wudc/rounds/1"
+        )
+        .unwrap(),
+        "https://example.com/api/v1/tournaments/wudc/rounds/1"
+    );
+    assert!(endpoint_url(&auth, "https://example.org/api/v1/tournaments").is_err());
+    assert!(endpoint_url(&auth, "https://example.com.evil.org/api/v1/tournaments").is_err());
+    assert!(endpoint_url(&auth, "http://example.com/api/v1/tournaments").is_err());
+}
@@ -0,0 +1,292 @@
+use std::{io::Write, process::exit, time::Duration};
+
+use chrono::{Local, NaiveTime};
+use serde_json::{Value, json};
+use tabbycat_api::types::{DrawStatusEnum, Round};
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth, api_utils::get_round, request_manager::RequestManager, schedule::parse_start,
+    watch::notify,
+};
+
+/// Parses a time of day such as `13:30` (or `13:30:15`).
+pub fn parse_time(at: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(at.trim(), "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(at.trim(), "%H:%M:%S"))
+        .ok()
+}
+
+fn format_countdown(secs: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+/// The number of whole seconds (rounded up) from now until `deadline`.
+fn seconds_until(deadline: Instant, now: Instant) -> u64 {
+    let remaining = deadline.saturating_duration_since(now);
+    remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+}
+
+/// Shows a countdown (after `label`) to `deadline`, returning once it is
+/// reached. The time shown is always worked out from the deadline, so it does
+/// not drift however long printing takes.
+async fn countdown(label: &str, deadline: Instant) {
+    loop {
+        let secs = seconds_until(deadline, Instant::now());
+        if secs == 0 {
+            break;
+        }
+        print!("\r{label} {} ", format_countdown(secs));
+        std::io::stdout().flush().unwrap();
+        tokio::time::sleep_until(deadline - Duration::from_secs(secs - 1)).await;
+    }
+    println!();
+}
+
+/// Checks that the draw for the round has been confirmed (Tabbycat will only
+/// release confirmed draws). Returns false if it has already been released.
+async fn check_releasable(auth: &Auth, round: &str, manager: RequestManager) -> bool {
+    let round = get_round(round, auth, manager).await;
+    match round.draw_status {
+        Some(DrawStatusEnum::R) => {
+            info!(
+                "The draw for {} has already been released",
+                round.name.as_str()
+            );
+            false
+        }
+        Some(DrawStatusEnum::C) => true,
+        _ => {
+            error!(
+                "The draw for {} has not been confirmed yet, so it cannot be released",
+                round.name.as_str()
+            );
+            exit(1);
+        }
+    }
+}
+
+/// Releases the draw for `round` (and, if `motions` is set, its motions). If
+/// `at` is given (in any of the formats [`parse_start`] accepts), waits until
+/// then (showing a countdown) first.
+pub async fn release_draw(auth: Auth, round: &str, at: Option<String>, motions: bool) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    if let Some(at) = at {
+        let now = Local::now();
+        let Some(release_at) = parse_start(&at, now.date_naive())
+            .and_then(|release_at| release_at.and_local_timezone(Local).earliest())
+        else {
+            error!("Could not understand the time {at:?} (expected e.g. `13:30` or `Sat 13:30`)");
+            exit(1);
+        };
+        let Ok(wait) = (release_at - now).to_std() else {
+            error!("{at} has already passed");
+            exit(1);
+        };
+        let deadline = Instant::now() + wait;
+
+        if !check_releasable(&auth, round, manager.clone()).await && !motions {
+            return;
+        }
+
+        info!("Waiting until {at} to release {round}");
+        countdown("Releasing in", deadline).await;
+    }
+
+    // the draw may have been released (or unconfirmed) while waiting, in
+    // which case only the motions (if any) are released
+    let draw = check_releasable(&auth, round, manager.clone()).await;
+    if !draw && !motions {
+        return;
+    }
+
+    let round = get_round(round, &auth, manager.clone()).await;
+    let mut body = json!({});
+    if draw {
+        body["draw_status"] = json!("R");
+    }
+    if motions {
+        body["motions_released"] = json!(true);
+    }
+    patch_round(&round, &body, manager).await;
+
+    info!(
+        "Released the {} for {}",
+        match (draw, motions) {
+            (true, true) => "draw and motions",
+            (true, false) => "draw",
+            _ => "motions",
+        },
+        round.name.as_str()
+    );
+}
+
+/// Updates `round` with the fields in `body`.
+async fn patch_round(round: &Round, body: &Value, manager: RequestManager) {
+    let url = serde_json::to_value(round).unwrap()["url"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let resp = manager
+        .send_request(|| manager.client.patch(&url).json(body).build().unwrap())
+        .await;
+    if !resp.status().is_success() {
+        error!(
+            "Failed to release {}: {} {}",
+            round.name.as_str(),
+            resp.status(),
+            resp.text().await.unwrap()
+        );
+        exit(1);
+    }
+}
+
+/// The announcement posted when a round is released: what was released, the
+/// motions (with their info slides) if they were, and when debates start.
+fn announcement(
+    round: &str,
+    draw: bool,
+    motions: Option<&[Value]>,
+    start: Option<NaiveTime>,
+) -> String {
+    let released = match (draw, motions.is_some()) {
+        (true, true) => "The draw and motions have",
+        (true, false) => "The draw has",
+        _ => "The motions have",
+    };
+    let mut lines = vec![format!("*{round}*: {released} been released.")];
+
+    for motion in motions.into_iter().flatten() {
+        let info_slide = motion["info_slide"].as_str().unwrap_or_default().trim();
+        if !info_slide.is_empty() {
+            lines.push(String::new());
+            lines.push(format!("Info slide: {info_slide}"));
+        }
+        lines.push(format!(
+            "Motion: {}",
+            motion["text"].as_str().unwrap_or_default().trim()
+        ));
+    }
+
+    if let Some(start) = start {
+        lines.push(String::new());
+        lines.push(format!("Debates start at {}.", start.format("%H:%M")));
+    }
+    lines.join("\n")
+}
+
+/// Releases the draw (if `draw` is set) and the motions (if `motions` is set)
+/// of `round` together; if neither is set, both are released. If a `webhook`
+/// is given, an announcement (including the motions and their info slides,
+/// if they were released) is posted to it. With `start_timer`, debates are
+/// announced to start that many minutes later, and a countdown to the start
+/// is shown (with a second notification when it ends).
+pub async fn release_round(
+    auth: Auth,
+    round: &str,
+    draw: bool,
+    motions: bool,
+    start_timer: Option<u64>,
+    webhook: Option<String>,
+) {
+    let manager = RequestManager::new(&auth.api_key);
+    let (draw, motions) = if draw || motions {
+        (draw, motions)
+    } else {
+        (true, true)
+    };
+
+    let draw = draw && check_releasable(&auth, round, manager.clone()).await;
+    let round = get_round(round, &auth, manager.clone()).await;
+    let round_json = serde_json::to_value(&round).unwrap();
+    let round_motions = round_json["motions"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    if motions && round_motions.is_empty() {
+        warn!("{} has no motions to release", round.name.as_str());
+    }
+
+    let mut body = json!({});
+    if draw {
+        body["draw_status"] = json!("R");
+    }
+    if motions {
+        body["motions_released"] = json!(true);
+    }
+    if body.as_object().unwrap().is_empty() {
+        return;
+    }
+    patch_round(&round, &body, manager).await;
+    info!(
+        "Released the {} for {}",
+        match (draw, motions) {
+            (true, true) => "draw and motions",
+            (true, false) => "draw",
+            _ => "motions",
+        },
+        round.name.as_str()
+    );
+
+    let deadline = start_timer.map(|minutes| Instant::now() + Duration::from_secs(minutes * 60));
+    let start = start_timer
+        .map(|minutes| (Local::now() + chrono::Duration::minutes(minutes as i64)).time());
+    if let Some(webhook) = &webhook {
+        let message = announcement(
+            round.name.as_str(),
+            draw,
+            motions.then_some(round_motions.as_slice()),
+            start,
+        );
+        notify(webhook, &message).await;
+    }
+
+    if let Some(deadline) = deadline {
+        countdown("Debates start in", deadline).await;
+        info!("Preparation time for {} is over", round.name.as_str());
+        if let Some(webhook) = &webhook {
+            let message = format!(
+                "*{}*: Preparation time is over; debates are starting.",
+                round.name.as_str()
+            );
+            notify(webhook, &message).await;
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_time() {
+    assert_eq!(parse_time("13:30"), NaiveTime::from_hms_opt(13, 30, 0));
+    assert_eq!(parse_time(" 09:05:10 "), NaiveTime::from_hms_opt(9, 5, 10));
+    assert_eq!(parse_time("1:30pm"), None);
+    assert_eq!(format_countdown(3725), "01:02:05");
+
+    let now = Instant::now();
+    assert_eq!(seconds_until(now + Duration::from_millis(2500), now), 3);
+    assert_eq!(seconds_until(now + Duration::from_secs(2), now), 2);
+    assert_eq!(seconds_until(now, now + Duration::from_secs(1)), 0);
+
+    let motions = [json!({
+        "text": "This House would ban zoos",
+        "info_slide": "",
+    })];
+    assert_eq!(
+        announcement("Round 3", true, Some(&motions), parse_time("14:30")),
+        "*Round 3*: The draw and motions have been released.\n\
+        Motion: This House would ban zoos\n\
+        \n\
+        Debates start at 14:30."
+    );
+    assert_eq!(
+        announcement("Round 3", true, None, None),
+        "*Round 3*: The draw has been released."
+    );
+}
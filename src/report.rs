@@ -0,0 +1,269 @@
+use std::{collections::HashMap, process::exit};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use tabbycat_api::types::{Adjudicator, PerTournamentInstitution, Team};
+use tracing::error;
+
+use crate::{
+    Auth,
+    api_utils::{get_institutions, get_judges, get_teams},
+    fees::FeesLedger,
+    request_manager::RequestManager,
+};
+
+/// A rule for the number of judges an institution must bring for a given
+/// number of teams: `ceil(teams * numerator / denominator) - minus`. This is
+/// written as e.g. `n-1`, `n`, `2/3` or `1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JudgeRule {
+    numerator: usize,
+    denominator: usize,
+    minus: usize,
+}
+
+impl Default for JudgeRule {
+    /// The n-1 rule.
+    fn default() -> JudgeRule {
+        JudgeRule {
+            numerator: 1,
+            denominator: 1,
+            minus: 1,
+        }
+    }
+}
+
+impl JudgeRule {
+    pub fn parse(rule: &str) -> Result<JudgeRule, String> {
+        let rule = rule.trim().to_lowercase();
+        let invalid = || {
+            format!(
+                "invalid rule `{rule}` (expected e.g. `n-1`, `n`, `2/3` or `1` judges per team)"
+            )
+        };
+
+        if let Some(rest) = rule.strip_prefix('n') {
+            let minus = match rest.trim() {
+                "" => 0,
+                rest => rest
+                    .strip_prefix('-')
+                    .and_then(|minus| minus.trim().parse().ok())
+                    .ok_or_else(invalid)?,
+            };
+            return Ok(JudgeRule {
+                numerator: 1,
+                denominator: 1,
+                minus,
+            });
+        }
+
+        let (numerator, denominator) = match rule.split_once('/') {
+            Some((numerator, denominator)) => (numerator.trim(), denominator.trim()),
+            None => (rule.as_str(), "1"),
+        };
+        let numerator = numerator.parse().map_err(|_| invalid())?;
+        let denominator = denominator
+            .parse()
+            .ok()
+            .filter(|d| *d > 0)
+            .ok_or_else(invalid)?;
+        Ok(JudgeRule {
+            numerator,
+            denominator,
+            minus: 0,
+        })
+    }
+
+    /// The number of judges required of an institution with `teams` teams.
+    pub fn required(&self, teams: usize) -> usize {
+        (teams * self.numerator)
+            .div_ceil(self.denominator)
+            .saturating_sub(self.minus)
+    }
+}
+
+/// The number of teams, speakers and judges registered by an institution.
+struct InstitutionCounts<'a> {
+    institution: &'a PerTournamentInstitution,
+    teams: usize,
+    speakers: usize,
+    judges: usize,
+}
+
+impl InstitutionCounts<'_> {
+    fn label(&self) -> String {
+        format!(
+            "{} ({})",
+            self.institution.name.as_str(),
+            self.institution.code.as_str()
+        )
+    }
+}
+
+/// Counts the participants of each institution (ordered by name), omitting
+/// institutions with neither teams nor judges.
+fn institution_counts<'a>(
+    institutions: &'a [PerTournamentInstitution],
+    teams: &[Team],
+    judges: &[Adjudicator],
+) -> Vec<InstitutionCounts<'a>> {
+    let mut counts = institutions
+        .iter()
+        .map(|inst| {
+            let inst_teams = teams
+                .iter()
+                .filter(|team| team.institution.as_ref() == Some(&inst.url))
+                .collect::<Vec<_>>();
+            InstitutionCounts {
+                institution: inst,
+                teams: inst_teams.len(),
+                speakers: inst_teams.iter().map(|team| team.speakers.len()).sum(),
+                judges: judges
+                    .iter()
+                    .filter(|judge| judge.institution.as_ref() == Some(&inst.url))
+                    .count(),
+            }
+        })
+        .filter(|counts| counts.teams > 0 || counts.judges > 0)
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| a.institution.name.as_str().cmp(b.institution.name.as_str()));
+    counts
+}
+
+/// Prints a table summarising, for each institution, the number of teams,
+/// speakers and judges it has registered, whether it meets the n-1 judge
+/// rule and (with `payments`) how much it has paid, going by the fees ledger
+/// (see [`crate::fees`]).
+pub async fn institutions_report(auth: Auth, payments: bool) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, judges, institutions) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+    );
+
+    let paid = if payments {
+        FeesLedger::load().paid(&teams, &institutions).institutions
+    } else {
+        HashMap::new()
+    };
+
+    let counts = institution_counts(&institutions, &teams, &judges);
+    if counts.is_empty() {
+        println!("No institutions have any teams or judges registered.");
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS);
+    let mut header = vec!["institution", "teams", "speakers", "judges", "n-1"];
+    if payments {
+        header.push("payment");
+    }
+    table.set_header(header);
+
+    let rule = JudgeRule::default();
+    let mut short = 0;
+    for inst in &counts {
+        let required = rule.required(inst.teams);
+        let compliance = if inst.judges >= required {
+            "ok".to_string()
+        } else {
+            short += 1;
+            format!("short by {}", required - inst.judges)
+        };
+
+        let mut row = vec![
+            inst.label(),
+            inst.teams.to_string(),
+            inst.speakers.to_string(),
+            inst.judges.to_string(),
+            compliance,
+        ];
+        if payments {
+            row.push(match paid.get(&inst.institution.url) {
+                Some(amount) if *amount > 0.0 => format!("paid {amount:.2}"),
+                _ => "OUTSTANDING".to_string(),
+            });
+        }
+        table.add_row(row);
+    }
+
+    println!("{table}");
+    if short > 0 {
+        println!("{short} institution(s) do not meet the n-1 judge rule.");
+    }
+}
+
+/// Lists the institutions which do not bring as many judges as `rule`
+/// requires for their number of teams, exiting with an error if there are
+/// any.
+pub async fn check_judge_rule(auth: Auth, rule: JudgeRule, rule_name: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, judges, institutions) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+    );
+
+    let violations = institution_counts(&institutions, &teams, &judges)
+        .into_iter()
+        .filter(|inst| inst.judges < rule.required(inst.teams))
+        .collect::<Vec<_>>();
+
+    if violations.is_empty() {
+        println!("Every institution meets the {rule_name} judge rule.");
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec![
+            "institution",
+            "teams",
+            "judges",
+            "required",
+            "missing",
+        ]);
+    for inst in &violations {
+        let required = rule.required(inst.teams);
+        table.add_row(vec![
+            inst.label(),
+            inst.teams.to_string(),
+            inst.judges.to_string(),
+            required.to_string(),
+            (required - inst.judges).to_string(),
+        ]);
+    }
+
+    println!("{table}");
+    error!(
+        "{} institution(s) do not meet the {rule_name} judge rule.",
+        violations.len()
+    );
+    exit(1);
+}
+
+#[cfg(test)]
+#[test]
+fn test_judge_rule() {
+    let rule = |s: &str| JudgeRule::parse(s).unwrap();
+
+    assert_eq!(rule("n-1"), JudgeRule::default());
+    assert_eq!(rule("n-1").required(0), 0);
+    assert_eq!(rule("n-1").required(1), 0);
+    assert_eq!(rule("n-1").required(4), 3);
+    assert_eq!(rule("n").required(4), 4);
+    assert_eq!(rule("1").required(4), 4);
+    assert_eq!(rule("2/3").required(3), 2);
+    assert_eq!(rule("2/3").required(4), 3);
+    assert!(JudgeRule::parse("n+1").is_err());
+    assert!(JudgeRule::parse("1/0").is_err());
+}
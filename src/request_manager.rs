@@ -1,9 +1,58 @@
 use std::{
-    sync::{Arc, atomic::AtomicU64},
+    fmt,
+    process::exit,
+    sync::{Arc, OnceLock, atomic::AtomicU64},
     time::Duration,
 };
 
 use reqwest::StatusCode;
+use tracing::{debug, error};
+
+use crate::{audit_log, cache};
+
+/// The version of Tabbycat running on a site (as reported by the API root).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TabbycatVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl TabbycatVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses versions such as `2.9.1` (or `2.9`, `v2.9.1-dev`).
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().trim_start_matches('v');
+        let mut parts = version
+            .split(|c: char| !c.is_ascii_digit())
+            .take_while(|part| !part.is_empty())
+            .map(|part| part.parse::<u32>());
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for TabbycatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The first release of Tabbycat whose API has ballot endpoints.
+pub const BALLOTS_API: TabbycatVersion = TabbycatVersion::new(2, 7, 0);
+/// The first release of Tabbycat whose API has availability endpoints.
+pub const AVAILABILITIES_API: TabbycatVersion = TabbycatVersion::new(2, 7, 0);
+/// The first release of Tabbycat whose API exposes tournament preferences.
+pub const PREFERENCES_API: TabbycatVersion = TabbycatVersion::new(2, 8, 0);
 
 /// Manages a set of HTTP requests.
 #[derive(Clone)]
@@ -11,6 +60,9 @@ pub struct RequestManager {
     pub client: reqwest::Client,
     authorization: String,
     backoff_secs: std::sync::Arc<AtomicU64>,
+    /// The version of Tabbycat the requests are sent to (fetched the first
+    /// time it is needed; `None` if the site does not report it).
+    version: Arc<OnceLock<Option<TabbycatVersion>>>,
 }
 
 impl RequestManager {
@@ -23,13 +75,174 @@ impl RequestManager {
             client,
             authorization: format!("Token {}", authorization),
             backoff_secs: Arc::new(AtomicU64::new(0)),
+            version: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Returns the version of Tabbycat running at `tabbycat_url`, which is
+    /// read from the root of the API the first time this is called.
+    pub async fn tabbycat_version(&self, tabbycat_url: &str) -> Option<TabbycatVersion> {
+        if let Some(version) = self.version.get() {
+            return *version;
         }
+        if cache::is_offline() {
+            return None;
+        }
+
+        let mut version = None;
+        for root in ["api/v1/", "api/"] {
+            let resp = self
+                .client
+                .get(format!("{tabbycat_url}/{root}"))
+                .header("Authorization", &self.authorization)
+                .send()
+                .await;
+            let Ok(resp) = resp else {
+                continue;
+            };
+            let Ok(json) = resp.json::<serde_json::Value>().await else {
+                continue;
+            };
+            version = json["version"].as_str().and_then(TabbycatVersion::parse);
+            if version.is_some() {
+                break;
+            }
+        }
+
+        match version {
+            Some(version) => debug!("Detected Tabbycat {version}"),
+            None => debug!("Could not detect the version of Tabbycat; assuming it is recent"),
+        }
+        *self.version.get_or_init(|| version)
+    }
+
+    /// Exits with an error if the site at `tabbycat_url` runs a version of
+    /// Tabbycat older than `required`, which `feature` needs. If the version
+    /// cannot be detected, it is assumed to be recent enough.
+    pub async fn require_version(
+        &self,
+        tabbycat_url: &str,
+        required: TabbycatVersion,
+        feature: &str,
+    ) {
+        if let Some(version) = self.tabbycat_version(tabbycat_url).await
+            && version < required
+        {
+            error!(
+                "{feature} requires Tabbycat ≥ {required}, but this site runs Tabbycat {version}."
+            );
+            exit(1);
+        }
+    }
+
+    /// For `PATCH` requests, fetches the current values of the fields which
+    /// are about to be changed (so that the change can be reverted using the
+    /// audit log).
+    async fn pre_image(&self, req: &reqwest::Request) -> Option<serde_json::Value> {
+        if req.method() != reqwest::Method::PATCH {
+            return None;
+        }
+        let payload = serde_json::from_slice(req.body()?.as_bytes()?).ok()?;
+        let res = self
+            .execute(|| self.client.get(req.url().clone()).build().unwrap(), None)
+            .await;
+        if !res.status().is_success() {
+            return None;
+        }
+        let current = res.json().await.ok()?;
+        audit_log::pre_image(&current, &payload)
     }
 
     pub async fn send_request(
         &self,
         get_request: impl Fn() -> reqwest::Request,
     ) -> reqwest::Response {
+        let res = self.send_request_unchecked(&get_request).await;
+        if res.status().is_success() {
+            return res;
+        }
+
+        let req = (get_request)();
+        tracing::error!(
+            "{} \n {} \n {} \n {:?}",
+            req.url(),
+            res.status(),
+            res.text().await.unwrap(),
+            req.body()
+                .map(|body| String::from_utf8_lossy(body.as_bytes().unwrap()))
+        );
+        // todo: log specific problems with the request
+        panic!("Encountered unexpected request failure.")
+    }
+
+    /// Like [`RequestManager::send_request`], but returns unsuccessful
+    /// responses (other than `429 Too Many Requests`, which is retried)
+    /// instead of stopping.
+    pub async fn send_request_unchecked(
+        &self,
+        get_request: impl Fn() -> reqwest::Request,
+    ) -> reqwest::Response {
+        let first = (get_request)();
+        if cache::is_offline() {
+            let url = first.url().to_string();
+            if first.method() != reqwest::Method::GET {
+                error!("Tabbycat cannot be changed while offline (remove --offline to do this).");
+                exit(1);
+            }
+            return cache::cached(&self.authorization, &url).unwrap_or_else(|| {
+                error!("OFFLINE: {url} has not been fetched before, so there is no cached copy.");
+                exit(1)
+            });
+        }
+
+        let pre_image = self.pre_image(&first).await;
+        self.execute(get_request, pre_image).await
+    }
+
+    /// Like [`RequestManager::send_request`], but returns an error instead of
+    /// stopping if Tabbycat cannot be reached or responds with an error, for
+    /// commands which poll Tabbycat (and so can try again later).
+    pub async fn try_send_request(
+        &self,
+        get_request: impl Fn() -> reqwest::Request,
+    ) -> Result<reqwest::Response, String> {
+        let res = if cache::is_offline() {
+            self.send_request_unchecked(&get_request).await
+        } else {
+            let pre_image = self.pre_image(&(get_request)()).await;
+            self.try_execute(&get_request, pre_image).await?
+        };
+        if !res.status().is_success() {
+            return Err(format!("Tabbycat responded with {}", res.status()));
+        }
+        Ok(res)
+    }
+
+    /// Like [`RequestManager::try_execute`], but exits if Tabbycat cannot be
+    /// reached.
+    async fn execute(
+        &self,
+        get_request: impl Fn() -> reqwest::Request,
+        pre_image: Option<serde_json::Value>,
+    ) -> reqwest::Response {
+        match self.try_execute(get_request, pre_image).await {
+            Ok(res) => res,
+            Err(e) => {
+                error!("{e}");
+                exit(1);
+            }
+        }
+    }
+
+    /// Sends the request, waiting out the current backoff first and retrying
+    /// (with increasing backoff) while Tabbycat responds with `429 Too Many
+    /// Requests`. Mutating requests are recorded in the audit log, along with
+    /// `pre_image`. Returns an error if Tabbycat cannot be reached.
+    async fn try_execute(
+        &self,
+        get_request: impl Fn() -> reqwest::Request,
+        pre_image: Option<serde_json::Value>,
+    ) -> Result<reqwest::Response, String> {
         let mut timeout = None;
 
         let secs = self.backoff_secs.load(std::sync::atomic::Ordering::SeqCst);
@@ -37,6 +250,10 @@ impl RequestManager {
             tokio::time::sleep(Duration::from_secs(secs)).await;
         }
 
+        let first = (get_request)();
+        let url = first.url().to_string();
+        let is_get = first.method() == reqwest::Method::GET;
+
         loop {
             let mut req = (get_request)();
             req.headers_mut().insert(
@@ -44,7 +261,37 @@ impl RequestManager {
                 reqwest::header::HeaderValue::from_str(&self.authorization)
                     .expect("Invalid authorization header"),
             );
-            let res = self.client.execute(req.try_clone().unwrap()).await.unwrap();
+            let res = match self.client.execute(req.try_clone().unwrap()).await {
+                Ok(res) => res,
+                // fall back to the last copy of the response if Tabbycat can't
+                // be reached (only read-only commands use the cache), and
+                // answer the rest of the command's requests from the cache too
+                Err(e) if is_get && cache::is_enabled() => {
+                    match cache::cached(&self.authorization, &url) {
+                        Some(res) => {
+                            cache::set_offline(true);
+                            return Ok(res);
+                        }
+                        None => {
+                            return Err(format!(
+                                "Could not reach Tabbycat ({e}), and {url} has not been cached."
+                            ));
+                        }
+                    }
+                }
+                Err(e) => return Err(format!("Could not reach Tabbycat: {e}")),
+            };
+
+            if req.method() != reqwest::Method::GET && res.status() != StatusCode::TOO_MANY_REQUESTS
+            {
+                audit_log::record(
+                    req.method().as_str(),
+                    req.url().as_str(),
+                    req.body().and_then(|body| body.as_bytes()),
+                    res.status().as_u16(),
+                    pre_image.clone(),
+                );
+            }
 
             if res.status().is_success() {
                 let current_backoff = self.backoff_secs.load(std::sync::atomic::Ordering::SeqCst);
@@ -60,7 +307,10 @@ impl RequestManager {
                     std::sync::atomic::Ordering::SeqCst,
                 );
 
-                return res;
+                if is_get && cache::is_enabled() {
+                    return Ok(cache::store(&self.authorization, &url, res).await);
+                }
+                return Ok(res);
             }
 
             if matches!(res.status(), StatusCode::TOO_MANY_REQUESTS) {
@@ -74,17 +324,23 @@ impl RequestManager {
                 timeout = Some(wait * 2.0);
                 tokio::time::sleep(Duration::from_secs_f32(wait)).await;
             } else {
-                tracing::error!(
-                    "{} \n {} \n {} \n {:?}",
-                    req.url(),
-                    res.status(),
-                    res.text().await.unwrap(),
-                    req.body()
-                        .map(|body| String::from_utf8_lossy(body.as_bytes().unwrap()))
-                );
-                // todo: log specific problems with the request
-                panic!("Encountered unexpected request failure.")
+                return Ok(res);
             }
         }
     }
 }
+
+#[cfg(test)]
+#[test]
+fn test_parse_version() {
+    assert_eq!(
+        TabbycatVersion::parse("2.9.1"),
+        Some(TabbycatVersion::new(2, 9, 1))
+    );
+    assert_eq!(
+        TabbycatVersion::parse("v2.10-dev"),
+        Some(TabbycatVersion::new(2, 10, 0))
+    );
+    assert_eq!(TabbycatVersion::parse("unknown"), None);
+    assert!(TabbycatVersion::new(2, 10, 0) > TabbycatVersion::new(2, 9, 3));
+}
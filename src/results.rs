@@ -0,0 +1,655 @@
+use std::process::exit;
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde::Deserialize;
+use serde_json::Value;
+use tabbycat_api::types::DrawStatusEnum;
+use tracing::{error, info};
+
+use crate::{
+    Auth,
+    anonymise::Anonymiser,
+    api_utils::{
+        get_ballots, get_preference, get_rounds, get_teams, get_venues, pairings_of_round,
+    },
+    names::names_match,
+    open_csv_file,
+    request_manager::RequestManager,
+    tabular::{Column, TableFormat, write_table},
+};
+
+/// A single speech, with its score averaged across the sheets of a ballot.
+#[derive(Debug, Clone, PartialEq)]
+struct Speech {
+    speaker: String,
+    /// The speaking position (starting from 1), or `reply`.
+    position: String,
+    score: Option<f64>,
+    ghost: bool,
+}
+
+/// The result of a single team in a debate.
+#[derive(Debug, Clone, PartialEq)]
+struct TeamResult {
+    side: String,
+    team: Option<String>,
+    score: Option<f64>,
+    points: Option<f64>,
+    speeches: Vec<Speech>,
+}
+
+fn mean(values: impl IntoIterator<Item = f64>) -> Option<f64> {
+    let values = values.into_iter().collect::<Vec<_>>();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+/// Combines the sheets of a confirmed ballot into the result of each team.
+/// Scores are the mean across the sheets (a ballot from a voting panel has
+/// one sheet per adjudicator). A team's points are taken from the ballot if
+/// Tabbycat provides them, and otherwise are 1 if the team won on a majority
+/// of the sheets and 0 if not.
+fn team_results(sheets: &[Value], substantive_speakers: usize) -> Vec<TeamResult> {
+    let Some(first) = sheets.first() else {
+        return Vec::new();
+    };
+    let team_on = |sheet: &Value, side: &str| {
+        sheet["teams"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|team| team["side"].as_str() == Some(side))
+            .cloned()
+            .unwrap_or(Value::Null)
+    };
+
+    let mut results = Vec::new();
+    for team in first["teams"].as_array().into_iter().flatten() {
+        let side = team["side"].as_str().unwrap_or_default().to_string();
+        let on_sheets = sheets
+            .iter()
+            .map(|sheet| team_on(sheet, &side))
+            .collect::<Vec<_>>();
+
+        let speeches = team["speeches"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, speech)| Speech {
+                speaker: speech["speaker"].as_str().unwrap_or_default().to_string(),
+                position: if i < substantive_speakers {
+                    (i + 1).to_string()
+                } else {
+                    "reply".to_string()
+                },
+                score: mean(
+                    on_sheets
+                        .iter()
+                        .filter_map(|team| team["speeches"].get(i)?["score"].as_f64()),
+                ),
+                ghost: speech["ghost"].as_bool() == Some(true),
+            })
+            .collect::<Vec<_>>();
+
+        let score = mean(on_sheets.iter().filter_map(|team| {
+            team["score"].as_f64().or_else(|| {
+                let speeches = team["speeches"].as_array()?;
+                speeches
+                    .iter()
+                    .map(|speech| speech["score"].as_f64())
+                    .sum::<Option<f64>>()
+            })
+        }));
+        let points = team["points"].as_f64().or_else(|| {
+            let wins = on_sheets
+                .iter()
+                .filter(|team| team["win"].as_bool() == Some(true))
+                .count();
+            if on_sheets.iter().all(|team| team["win"].is_null()) {
+                None
+            } else if wins * 2 > sheets.len() {
+                Some(1.0)
+            } else {
+                Some(0.0)
+            }
+        });
+
+        results.push(TeamResult {
+            side,
+            team: team["team"].as_str().map(|url| url.to_string()),
+            score,
+            points,
+            speeches,
+        });
+    }
+    results
+}
+
+/// The margin of each team: its score minus that of its opponent (only
+/// defined in two-team formats).
+fn margins(results: &[TeamResult]) -> Vec<Option<f64>> {
+    match results {
+        [a, b] => vec![
+            a.score.zip(b.score).map(|(a, b)| a - b),
+            b.score.zip(a.score).map(|(b, a)| b - a),
+        ],
+        _ => vec![None; results.len()],
+    }
+}
+
+fn cell(value: Option<f64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// The result of a debate, from its confirmed ballot.
+#[derive(Debug, Clone)]
+struct Debate {
+    /// The abbreviation of the round (e.g. `R1`).
+    round: String,
+    round_name: String,
+    room: String,
+    results: Vec<TeamResult>,
+}
+
+/// Fetches the results of every debate with a confirmed ballot in the rounds
+/// which have been released, in round order.
+async fn get_debates(auth: &Auth, manager: RequestManager) -> Vec<Debate> {
+    let (mut rounds, venues, substantive) = tokio::join!(
+        get_rounds(auth, manager.clone()),
+        get_venues(auth, manager.clone()),
+        get_preference(auth, "debate_rules__substantive_speakers", manager.clone()),
+    );
+    rounds.sort_by_key(|round| round.seq);
+    let substantive_speakers = substantive
+        .as_u64()
+        .or_else(|| substantive.as_str().and_then(|n| n.parse().ok()))
+        .map(|n| n as usize)
+        .unwrap_or(usize::MAX);
+
+    let mut debates = Vec::new();
+    for round in rounds
+        .iter()
+        .filter(|round| matches!(round.draw_status, Some(DrawStatusEnum::R)))
+    {
+        for pairing in pairings_of_round(auth, round, manager.clone()).await {
+            let room = pairing
+                .venue
+                .as_ref()
+                .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+                .map(|venue| venue.name.as_str().to_string())
+                .unwrap_or_else(|| format!("Debate {}", pairing.id));
+            let team_on_side = |side: &str| {
+                pairing
+                    .teams
+                    .iter()
+                    .find(|team| serde_json::to_value(&team.side).unwrap().as_str() == Some(side))
+                    .map(|team| team.team.clone())
+            };
+
+            for ballot in get_ballots(auth, &pairing, manager.clone()).await {
+                if ballot["confirmed"].as_bool() != Some(true) {
+                    continue;
+                }
+                let sheets = ballot["result"]["sheets"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                let mut results = team_results(&sheets, substantive_speakers);
+                for result in &mut results {
+                    if result.team.is_none() {
+                        result.team = team_on_side(&result.side);
+                    }
+                }
+                debates.push(Debate {
+                    round: round.abbreviation.as_str().to_string(),
+                    round_name: round.name.as_str().to_string(),
+                    room: room.clone(),
+                    results,
+                });
+            }
+        }
+    }
+    debates
+}
+
+/// Exports the results of every confirmed ballot in the rounds which have
+/// been released, with one row per speech (or, with `by_debate`, one row per
+/// debate with a group of columns for each side).
+pub async fn export_results(
+    auth: Auth,
+    to: &str,
+    by_debate: bool,
+    format: TableFormat,
+    anon: Anonymiser,
+) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (teams, debates) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_debates(&auth, manager.clone()),
+    );
+
+    let team_name = |url: &str| {
+        teams
+            .iter()
+            .find(|team| team.url == url)
+            .map(|team| anon.name("Team", &team.url, &team.short_name))
+            .unwrap_or_default()
+    };
+    let speaker_name = |url: &str| {
+        teams
+            .iter()
+            .flat_map(|team| &team.speakers)
+            .find(|speaker| speaker.url == url)
+            .map(|speaker| anon.name("Speaker", &speaker.url, &speaker.name))
+            .unwrap_or_else(|| anon.url(url))
+    };
+
+    let (header, rows) = if by_debate {
+        // the sides, in the order they first appear
+        let mut sides: Vec<String> = Vec::new();
+        for result in debates.iter().flat_map(|debate| &debate.results) {
+            if !sides.contains(&result.side) {
+                sides.push(result.side.clone());
+            }
+        }
+
+        let mut header = vec![Column::text("round"), Column::text("room")];
+        for side in &sides {
+            header.extend([
+                Column::text(format!("{side}_team")),
+                Column::number(format!("{side}_score")),
+                Column::number(format!("{side}_points")),
+                Column::number(format!("{side}_margin")),
+            ]);
+        }
+        let rows = debates
+            .iter()
+            .map(|debate| {
+                let results = &debate.results;
+                let margins = margins(results);
+                let mut row = vec![debate.round.clone(), debate.room.clone()];
+                for side in &sides {
+                    match results.iter().position(|result| &result.side == side) {
+                        Some(i) => row.extend([
+                            results[i]
+                                .team
+                                .as_deref()
+                                .map(team_name)
+                                .unwrap_or_default(),
+                            cell(results[i].score),
+                            cell(results[i].points),
+                            cell(margins[i]),
+                        ]),
+                        None => row.extend(vec![String::new(); 4]),
+                    }
+                }
+                row
+            })
+            .collect::<Vec<_>>();
+        (header, rows)
+    } else {
+        let header = vec![
+            Column::text("round"),
+            Column::text("room"),
+            Column::text("team"),
+            Column::text("side"),
+            Column::text("speaker"),
+            Column::text("position"),
+            Column::number("score"),
+            Column::bool("ghost"),
+            Column::number("team_score"),
+            Column::number("team_points"),
+            Column::number("margin"),
+        ];
+        let mut rows = Vec::new();
+        for debate in &debates {
+            let results = &debate.results;
+            for (result, margin) in results.iter().zip(margins(results)) {
+                for speech in &result.speeches {
+                    rows.push(vec![
+                        debate.round.clone(),
+                        debate.room.clone(),
+                        result.team.as_deref().map(team_name).unwrap_or_default(),
+                        result.side.clone(),
+                        speaker_name(&speech.speaker),
+                        speech.position.clone(),
+                        cell(speech.score),
+                        speech.ghost.to_string(),
+                        cell(result.score),
+                        cell(result.points),
+                        cell(margin),
+                    ]);
+                }
+            }
+        }
+        (header, rows)
+    };
+
+    write_table(to, format, &header, &rows);
+    info!(
+        "Saved the results of {} debates ({} rows) into {:?} file {}",
+        debates.len(),
+        rows.len(),
+        format,
+        to
+    );
+}
+
+/// A row of a results CSV file, in the format written by `export results`
+/// (other columns are ignored).
+#[derive(Deserialize, Debug, Clone)]
+struct ResultRow {
+    round: String,
+    room: String,
+    side: String,
+    speaker: String,
+    position: String,
+    score: Option<f64>,
+    #[serde(default)]
+    team_points: Option<f64>,
+}
+
+fn scores_differ(a: Option<f64>, b: Option<f64>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => (a - b).abs() > 0.005,
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+/// The differences between the results of a debate stored in Tabbycat and
+/// the `rows` of the results CSV file for the same debate.
+fn discrepancies(
+    results: &[TeamResult],
+    rows: &[&ResultRow],
+    speaker_name: impl Fn(&str) -> String,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let show = |score: Option<f64>| {
+        score
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "no score".to_string())
+    };
+
+    for result in results {
+        let side = &result.side;
+        let on_side = rows
+            .iter()
+            .filter(|row| names_match(&row.side, side))
+            .collect::<Vec<_>>();
+        if on_side.is_empty() {
+            problems.push(format!("{side}: not in the CSV file"));
+            continue;
+        }
+
+        if let Some(points) = on_side
+            .iter()
+            .find_map(|row| row.team_points)
+            .filter(|&points| scores_differ(result.points, Some(points)))
+        {
+            problems.push(format!(
+                "{side}: Tabbycat has {} points, the CSV file has {points}",
+                show(result.points)
+            ));
+        }
+
+        for speech in &result.speeches {
+            let name = speaker_name(&speech.speaker);
+            let Some(row) = on_side
+                .iter()
+                .find(|row| names_match(&row.position, &speech.position))
+            else {
+                problems.push(format!(
+                    "{side} {}: {name} ({}) is not in the CSV file",
+                    speech.position,
+                    show(speech.score)
+                ));
+                continue;
+            };
+            if !names_match(&row.speaker, &name) {
+                problems.push(format!(
+                    "{side} {}: Tabbycat has {name}, the CSV file has {}",
+                    speech.position, row.speaker
+                ));
+            }
+            if scores_differ(speech.score, row.score) {
+                problems.push(format!(
+                    "{side} {}: Tabbycat gives {name} {}, the CSV file has {}",
+                    speech.position,
+                    show(speech.score),
+                    show(row.score)
+                ));
+            }
+        }
+        for row in &on_side {
+            if !result
+                .speeches
+                .iter()
+                .any(|speech| names_match(&row.position, &speech.position))
+            {
+                problems.push(format!(
+                    "{side} {}: {} ({}) is not in Tabbycat",
+                    row.position,
+                    row.speaker,
+                    show(row.score)
+                ));
+            }
+        }
+    }
+
+    for row in rows {
+        if !results
+            .iter()
+            .any(|result| names_match(&row.side, &result.side))
+        {
+            problems.push(format!(
+                "{} {}: {} ({}) is on a side which is not in Tabbycat",
+                row.side,
+                row.position,
+                row.speaker,
+                show(row.score)
+            ));
+        }
+    }
+    problems
+}
+
+/// Compares the results stored in Tabbycat with a CSV file (in the format
+/// written by `export results`, e.g. typed up from the paper ballots) and
+/// lists the differences in each room.
+pub async fn verify_results(auth: Auth, against: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let rows = open_csv_file(Some(against.to_string()), true)
+        .unwrap()
+        .deserialize::<ResultRow>()
+        .map(|row| {
+            row.unwrap_or_else(|e| {
+                error!("{against} is malformed: {e}");
+                exit(1)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let (teams, debates) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_debates(&auth, manager.clone()),
+    );
+    let speaker_name = |url: &str| {
+        teams
+            .iter()
+            .flat_map(|team| &team.speakers)
+            .find(|speaker| speaker.url == url)
+            .map(|speaker| speaker.name.to_string())
+            .unwrap_or_else(|| url.to_string())
+    };
+    let same_debate = |debate: &Debate, row: &ResultRow| {
+        (names_match(&row.round, &debate.round) || names_match(&row.round, &debate.round_name))
+            && names_match(&row.room, &debate.room)
+    };
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["Round", "Room", "Discrepancies"]);
+    let mut flagged = 0;
+    for debate in &debates {
+        let rows = rows
+            .iter()
+            .filter(|row| same_debate(debate, row))
+            .collect::<Vec<_>>();
+        let problems = if rows.is_empty() {
+            vec![format!("not in {against}")]
+        } else {
+            discrepancies(&debate.results, &rows, speaker_name)
+        };
+        if problems.is_empty() {
+            continue;
+        }
+        flagged += 1;
+        table.add_row(vec![
+            debate.round.clone(),
+            debate.room.clone(),
+            problems.join("\n"),
+        ]);
+    }
+
+    // debates in the file for which Tabbycat has no confirmed ballot
+    let mut missing: Vec<(&str, &str)> = Vec::new();
+    for row in &rows {
+        if debates.iter().any(|debate| same_debate(debate, row)) {
+            continue;
+        }
+        if !missing.contains(&(row.round.as_str(), row.room.as_str())) {
+            missing.push((&row.round, &row.room));
+        }
+    }
+    for (round, room) in &missing {
+        flagged += 1;
+        table.add_row(vec![
+            round.to_string(),
+            room.to_string(),
+            "no confirmed ballot in Tabbycat".to_string(),
+        ]);
+    }
+
+    if flagged == 0 {
+        println!(
+            "The results of all {} debates match {against}",
+            debates.len()
+        );
+        return;
+    }
+    println!("{table}");
+    error!("Found discrepancies in {flagged} debate(s)");
+    exit(1);
+}
+
+#[cfg(test)]
+#[test]
+fn test_team_results() {
+    let sheet = |aff: [f64; 3], aff_wins: bool| {
+        serde_json::json!({
+            "teams": [
+                {
+                    "side": "aff",
+                    "win": aff_wins,
+                    "speeches": [
+                        { "speaker": "a1", "score": aff[0] },
+                        { "speaker": "a2", "score": aff[1] },
+                        { "speaker": "a1", "score": aff[2] },
+                    ]
+                },
+                {
+                    "side": "neg",
+                    "win": !aff_wins,
+                    "speeches": [
+                        { "speaker": "n1", "score": 74.0 },
+                        { "speaker": "n1", "score": 74.0, "ghost": true },
+                        { "speaker": "n1", "score": 37.0 },
+                    ]
+                }
+            ]
+        })
+    };
+
+    let results = team_results(
+        &[
+            sheet([75.0, 75.0, 38.0], true),
+            sheet([74.0, 75.0, 37.0], true),
+            sheet([73.0, 74.0, 37.0], false),
+        ],
+        2,
+    );
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].side, "aff");
+    assert_eq!(results[0].points, Some(1.0));
+    assert_eq!(results[1].points, Some(0.0));
+    assert_eq!(results[0].score, Some(186.0));
+    assert_eq!(results[1].score, Some(185.0));
+    assert_eq!(
+        results[0].speeches[0],
+        Speech {
+            speaker: "a1".to_string(),
+            position: "1".to_string(),
+            score: Some(74.0),
+            ghost: false,
+        }
+    );
+    assert_eq!(results[1].speeches[2].position, "reply");
+    assert!(results[1].speeches[1].ghost);
+    assert_eq!(margins(&results), vec![Some(1.0), Some(-1.0)]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_discrepancies() {
+    let speech = |speaker: &str, position: &str, score: f64| Speech {
+        speaker: speaker.to_string(),
+        position: position.to_string(),
+        score: Some(score),
+        ghost: false,
+    };
+    let results = vec![TeamResult {
+        side: "aff".to_string(),
+        team: None,
+        score: Some(150.0),
+        points: Some(1.0),
+        speeches: vec![speech("a1", "1", 75.0), speech("a2", "2", 75.0)],
+    }];
+    let row = |speaker: &str, position: &str, score: f64| ResultRow {
+        round: "R1".to_string(),
+        room: "Room 1".to_string(),
+        side: "aff".to_string(),
+        speaker: speaker.to_string(),
+        position: position.to_string(),
+        score: Some(score),
+        team_points: Some(1.0),
+    };
+    let speaker_name = |url: &str| format!("Speaker {url}");
+
+    let rows = [row("speaker A1", "1", 75.0), row("Speaker A2", "2", 75.0)];
+    assert!(discrepancies(&results, &rows.iter().collect::<Vec<_>>(), speaker_name).is_empty());
+
+    let rows = [row("Speaker A1", "1", 76.0), row("Speaker B", "2", 75.0)];
+    assert_eq!(
+        discrepancies(&results, &rows.iter().collect::<Vec<_>>(), speaker_name),
+        vec![
+            "aff 1: Tabbycat gives Speaker a1 75, the CSV file has 76",
+            "aff 2: Tabbycat has Speaker a2, the CSV file has Speaker B",
+        ]
+    );
+
+    let rows = [row("Speaker a1", "1", 75.0)];
+    assert_eq!(
+        discrepancies(&results, &rows.iter().collect::<Vec<_>>(), speaker_name),
+        vec!["aff 2: Speaker a2 (75) is not in the CSV file"]
+    );
+}
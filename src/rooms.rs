@@ -0,0 +1,715 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    process::exit,
+};
+
+use comfy_table::{Cell, Color, Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{Level, error, info, span, warn};
+
+use crate::{
+    Auth,
+    api_utils::{
+        get_availabilities, get_institutions, get_judges, get_round_and_pairings, get_teams,
+        get_venues,
+    },
+    dispatch_req::json_of_resp,
+    names::{names_match, sort_key},
+    open_csv_file,
+    request_manager::RequestManager,
+    venue_metadata::{VENUE_METADATA_FILE, VenueMetadataStore},
+};
+
+#[derive(Deserialize, Debug, Clone)]
+struct RoomOrderRow {
+    name: String,
+}
+
+/// Compares two room names, treating runs of digits as numbers (so that
+/// "Room 2" comes before "Room 10") and ignoring case and accents.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn chunks(s: &str) -> Vec<(bool, String)> {
+        let mut chunks: Vec<(bool, String)> = Vec::new();
+        for c in sort_key(s).chars() {
+            match chunks.last_mut() {
+                Some((digit, chunk)) if *digit == c.is_ascii_digit() => chunk.push(c),
+                _ => chunks.push((c.is_ascii_digit(), c.to_string())),
+            }
+        }
+        chunks
+    }
+
+    for (x, y) in chunks(a).iter().zip(chunks(b).iter()) {
+        let ord = match (x, y) {
+            ((true, x), (true, y)) => x
+                .trim_start_matches('0')
+                .len()
+                .cmp(&y.trim_start_matches('0').len())
+                .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0'))),
+            ((_, x), (_, y)) => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// The priorities of `count` rooms, in descending order, all of which are
+/// higher than the priorities of the rooms which are not being ordered
+/// (`unlisted`), so that the order of every room is unambiguous.
+fn descending_priorities(count: usize, unlisted: impl Iterator<Item = i64>) -> Vec<i64> {
+    let base = unlisted.max().unwrap_or(0).max(0);
+    (1..=count as i64).rev().map(|n| base + n).collect()
+}
+
+/// Assigns descending priorities to venues, either in the (natural) order of
+/// their names, or in the order they are listed in a CSV file (with a `name`
+/// column). The first venue receives the highest priority. Venues which are
+/// not listed in the CSV file keep their priorities, and the listed venues
+/// are placed above all of them.
+pub async fn prioritize(auth: Auth, by: &str, csv: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let venues = get_venues(&auth, manager.clone()).await;
+
+    let ordered = match by {
+        "name-order" => {
+            let mut venues = venues.clone();
+            venues.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+            venues
+        }
+        "csv" => {
+            let Some(mut csv) = open_csv_file(csv, true) else {
+                error!("Please provide the order of the rooms with `--csv`.");
+                exit(1);
+            };
+            let mut ordered = Vec::new();
+            for row in csv.deserialize::<RoomOrderRow>() {
+                let row = row.unwrap();
+                match venues
+                    .iter()
+                    .find(|venue| names_match(&venue.name, &row.name))
+                {
+                    Some(venue) => ordered.push(venue.clone()),
+                    None => {
+                        error!("No room named {} exists", row.name);
+                        exit(1);
+                    }
+                }
+            }
+            for venue in &venues {
+                if !ordered.iter().any(|v| v.url == venue.url) {
+                    warn!(
+                        "Room {} is not listed in the CSV file, so its priority was not changed \
+                        (the listed rooms are placed above it)",
+                        venue.name.as_str()
+                    );
+                }
+            }
+            ordered
+        }
+        _ => {
+            error!("Invalid ordering `{by}`, expected either name-order or csv");
+            exit(1);
+        }
+    };
+
+    let span = span!(Level::INFO, "prioritize_rooms");
+    let _guard = span.enter();
+
+    let unlisted = venues
+        .iter()
+        .filter(|venue| !ordered.iter().any(|v| v.url == venue.url))
+        .filter_map(|venue| serde_json::to_value(venue).unwrap()["priority"].as_i64());
+    let priorities = descending_priorities(ordered.len(), unlisted);
+    for (venue, priority) in ordered.iter().zip(priorities) {
+        let resp = manager
+            .send_request(|| {
+                manager
+                    .client
+                    .patch(&venue.url)
+                    .json(&json!({ "priority": priority }))
+                    .build()
+                    .unwrap()
+            })
+            .await;
+        if !resp.status().is_success() {
+            error!(
+                "Failed to set priority of room {}: {} {}",
+                venue.name.as_str(),
+                resp.status(),
+                resp.text().await.unwrap()
+            );
+            exit(1);
+        }
+        info!("Set priority of room {} to {priority}", venue.name.as_str());
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct VenueCategory {
+    url: String,
+    name: String,
+    #[serde(default)]
+    venues: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct VenueConstraint {
+    category: String,
+    #[serde(default)]
+    priority: i64,
+}
+
+/// A participant in a debate who needs a room in a particular category.
+#[derive(Debug, Clone, PartialEq)]
+struct Constraint {
+    /// Who the constraint belongs to (for error messages).
+    who: String,
+    /// The URL of the venue category.
+    category: String,
+    priority: i64,
+}
+
+/// The venue constraints of a team, adjudicator or institution (which are
+/// not part of the types in `tabbycat_api`).
+fn venue_constraints(participant: &impl serde::Serialize) -> Vec<VenueConstraint> {
+    serde_json::from_value(serde_json::to_value(participant).unwrap()["venue_constraints"].clone())
+        .unwrap_or_default()
+}
+
+/// Assigns a room to each debate (given as its current room and the
+/// constraints of its participants) from `rooms`, which are in order of
+/// preference. Debates with the highest priority constraints choose first,
+/// and debates keep their current room where possible. Returns the new room
+/// of each debate, along with the constraints which could not be satisfied
+/// (and the index of their debate).
+fn allocate(
+    debates: &[(Option<String>, Vec<Constraint>)],
+    rooms: &[String],
+    categories: &HashMap<String, HashSet<String>>,
+) -> (Vec<Option<String>>, Vec<(usize, Constraint)>) {
+    let in_category = |room: &str, category: &str| {
+        categories
+            .get(category)
+            .is_some_and(|rooms| rooms.contains(room))
+    };
+
+    let mut assigned: Vec<Option<String>> = vec![None; debates.len()];
+    let mut used: HashSet<String> = HashSet::new();
+
+    let mut order = (0..debates.len())
+        .filter(|&i| !debates[i].1.is_empty())
+        .collect::<Vec<_>>();
+    order.sort_by_key(|&i| {
+        std::cmp::Reverse(debates[i].1.iter().map(|c| c.priority).max().unwrap_or(0))
+    });
+    for i in order {
+        let (current, constraints) = &debates[i];
+        let mut constraints = constraints.iter().collect::<Vec<_>>();
+        constraints.sort_by_key(|constraint| std::cmp::Reverse(constraint.priority));
+
+        // the room which satisfies the most important constraint it can
+        let room = constraints.iter().find_map(|constraint| {
+            let suitable =
+                |room: &&String| !used.contains(*room) && in_category(room, &constraint.category);
+            current
+                .iter()
+                .find(suitable)
+                .or_else(|| rooms.iter().find(suitable))
+                .cloned()
+        });
+        if let Some(room) = room {
+            used.insert(room.clone());
+            assigned[i] = Some(room);
+        }
+    }
+
+    // then every other debate keeps its room if it is still free...
+    for (i, (current, _)) in debates.iter().enumerate() {
+        if assigned[i].is_some() {
+            continue;
+        }
+        if let Some(room) = current.as_ref().filter(|room| !used.contains(*room)) {
+            used.insert(room.clone());
+            assigned[i] = Some(room.clone());
+        }
+    }
+    // ...or is given the best remaining room
+    for slot in assigned.iter_mut().filter(|slot| slot.is_none()) {
+        if let Some(room) = rooms.iter().find(|room| !used.contains(*room)) {
+            used.insert(room.clone());
+            *slot = Some(room.clone());
+        }
+    }
+
+    let unsatisfied = debates
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (_, constraints))| {
+            let room = assigned[i].clone();
+            constraints
+                .iter()
+                .filter(move |constraint| {
+                    !room
+                        .as_ref()
+                        .is_some_and(|room| in_category(room, &constraint.category))
+                })
+                .map(move |constraint| (i, constraint.clone()))
+        })
+        .collect();
+
+    (assigned, unsatisfied)
+}
+
+/// Moves the debates in `round` so that every team, adjudicator and
+/// institution with a venue constraint (e.g. needing an accessible room)
+/// debates in a room of the required category, reporting the constraints
+/// which cannot be satisfied.
+pub async fn allocate_categories(auth: Auth, round: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let categories_resp = manager.send_request(|| {
+        manager
+            .client
+            .get(format!(
+                "{}/api/v1/tournaments/{}/venue-categories",
+                auth.tabbycat_url, auth.tournament_slug
+            ))
+            .build()
+            .unwrap()
+    });
+    let ((round, pairings), venues, teams, judges, institutions, categories_resp) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_institutions(&auth, manager.clone()),
+        categories_resp,
+    );
+    let venue_categories: Vec<VenueCategory> = json_of_resp(categories_resp).await;
+    let available = get_availabilities(&auth, &round, manager.clone()).await;
+
+    let category_rooms = venue_categories
+        .iter()
+        .map(|cat| (cat.url.clone(), cat.venues.iter().cloned().collect()))
+        .collect::<HashMap<_, _>>();
+    let category_name = |url: &str| {
+        venue_categories
+            .iter()
+            .find(|cat| cat.url == url)
+            .map(|cat| cat.name.clone())
+            .unwrap_or_else(|| url.to_string())
+    };
+    let room_name = |url: Option<&String>| {
+        url.and_then(|url| venues.iter().find(|venue| &venue.url == url))
+            .map(|venue| venue.name.to_string())
+            .unwrap_or_else(|| "(no room)".to_string())
+    };
+
+    let institution_constraints = |url: Option<&String>, who: &str| {
+        url.and_then(|url| institutions.iter().find(|inst| &inst.url == url))
+            .map(|inst| {
+                venue_constraints(inst)
+                    .into_iter()
+                    .map(|constraint| Constraint {
+                        who: format!("{who} (via {})", inst.name.as_str()),
+                        category: constraint.category,
+                        priority: constraint.priority,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    let debates = pairings
+        .iter()
+        .map(|pairing| {
+            let mut constraints = Vec::new();
+            for debate_team in &pairing.teams {
+                let Some(team) = teams.iter().find(|team| team.url == debate_team.team) else {
+                    continue;
+                };
+                constraints.extend(venue_constraints(team).into_iter().map(|constraint| {
+                    Constraint {
+                        who: team.short_name.to_string(),
+                        category: constraint.category,
+                        priority: constraint.priority,
+                    }
+                }));
+                constraints.extend(institution_constraints(
+                    team.institution.as_ref(),
+                    &team.short_name,
+                ));
+            }
+            for adj in pairing.adjudicators.iter().flat_map(|panel| {
+                panel
+                    .chair
+                    .iter()
+                    .chain(&panel.panellists)
+                    .chain(&panel.trainees)
+            }) {
+                let Some(judge) = judges.iter().find(|judge| &judge.url == adj) else {
+                    continue;
+                };
+                constraints.extend(venue_constraints(judge).into_iter().map(|constraint| {
+                    Constraint {
+                        who: judge.name.clone(),
+                        category: constraint.category,
+                        priority: constraint.priority,
+                    }
+                }));
+                constraints.extend(institution_constraints(
+                    judge.institution.as_ref(),
+                    &judge.name,
+                ));
+            }
+            (pairing.venue.clone(), constraints)
+        })
+        .collect::<Vec<_>>();
+
+    if debates
+        .iter()
+        .all(|(_, constraints)| constraints.is_empty())
+    {
+        info!(
+            "No participants in {} have room constraints",
+            round.name.as_str()
+        );
+        return;
+    }
+
+    // the rooms which are available (or already in use) for this round, best
+    // first
+    let mut rooms = venues
+        .iter()
+        .filter(|venue| {
+            available.is_empty()
+                || available.contains(&venue.url)
+                || pairings
+                    .iter()
+                    .any(|pairing| pairing.venue.as_ref() == Some(&venue.url))
+        })
+        .collect::<Vec<_>>();
+    rooms.sort_by_key(|venue| {
+        std::cmp::Reverse(serde_json::to_value(venue).unwrap()["priority"].as_i64())
+    });
+    let rooms = rooms
+        .into_iter()
+        .map(|venue| venue.url.clone())
+        .collect::<Vec<_>>();
+
+    let (assigned, unsatisfied) = allocate(&debates, &rooms, &category_rooms);
+
+    let span = span!(Level::INFO, "allocate_categories");
+    let _guard = span.enter();
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec!["debate", "from", "to"]);
+    let mut moved = 0;
+    for (pairing, room) in pairings.iter().zip(&assigned) {
+        if room.is_none() || *room == pairing.venue {
+            continue;
+        }
+
+        let resp = manager
+            .send_request(|| {
+                manager
+                    .client
+                    .patch(&pairing.url)
+                    .json(&json!({ "venue": room }))
+                    .build()
+                    .unwrap()
+            })
+            .await;
+        if !resp.status().is_success() {
+            error!(
+                "Failed to move debate {}: {} {}",
+                pairing.id,
+                resp.status(),
+                resp.text().await.unwrap()
+            );
+            exit(1);
+        }
+        moved += 1;
+        table.add_row(vec![
+            pairing.id.to_string(),
+            room_name(pairing.venue.as_ref()),
+            room_name(room.as_ref()),
+        ]);
+    }
+
+    if moved > 0 {
+        println!("{table}");
+    } else {
+        info!("No debates needed to be moved");
+    }
+
+    for (i, constraint) in &unsatisfied {
+        error!(
+            "{} needs a room in {} (priority {}), but no suitable room was free; they are in {}",
+            constraint.who,
+            category_name(&constraint.category),
+            constraint.priority,
+            room_name(assigned[*i].as_ref())
+        );
+    }
+    if assigned.iter().any(Option::is_none) {
+        warn!("There were not enough rooms for every debate");
+    }
+    if !unsatisfied.is_empty() {
+        exit(1);
+    }
+}
+
+/// Whether a room is big enough for a debate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fit {
+    Fits,
+    TooSmall,
+    /// No capacity has been recorded for the room.
+    Unknown,
+}
+
+fn fit(capacity: Option<u32>, expected: usize) -> Fit {
+    match capacity {
+        Some(capacity) if capacity as usize >= expected => Fit::Fits,
+        Some(_) => Fit::TooSmall,
+        None => Fit::Unknown,
+    }
+}
+
+/// Flags the debates in `round` which are in rooms too small for the people
+/// expected in them: the speakers, the adjudicators and `audience` others.
+/// The capacity of each room is read from the local venue metadata store
+/// (filled in from the `capacity` column of the rooms CSV file on import).
+pub async fn check_capacity(auth: Auth, round: &str, audience: usize) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((round, pairings), venues, teams) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+    );
+    let metadata = VenueMetadataStore::load();
+
+    let mut debates = pairings
+        .iter()
+        .map(|pairing| {
+            let room = pairing
+                .venue
+                .as_ref()
+                .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+                .map(|venue| venue.name.to_string());
+            let speakers = pairing
+                .teams
+                .iter()
+                .filter_map(|debate_team| teams.iter().find(|team| team.url == debate_team.team))
+                .map(|team| team.speakers.len())
+                .sum::<usize>();
+            let adjudicators = pairing
+                .adjudicators
+                .as_ref()
+                .map(|adjs| adjs.chair.iter().count() + adjs.panellists.len() + adjs.trainees.len())
+                .unwrap_or(0);
+            let capacity = room
+                .as_deref()
+                .and_then(|room| metadata.get(room))
+                .and_then(|room| room.capacity);
+            let room = room.unwrap_or_else(|| format!("Debate {}", pairing.id));
+            (room, capacity, speakers + adjudicators + audience)
+        })
+        .collect::<Vec<_>>();
+    debates.sort_by(|(a, ..), (b, ..)| natural_cmp(a, b));
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["Room", "Capacity", "Expected", "Status"]);
+    let mut too_small = 0;
+    let mut unknown = 0;
+    for (room, capacity, expected) in &debates {
+        let status = match fit(*capacity, *expected) {
+            Fit::Fits => Cell::new("ok").fg(Color::Green),
+            Fit::TooSmall => {
+                too_small += 1;
+                Cell::new("too small").fg(Color::Red)
+            }
+            Fit::Unknown => {
+                unknown += 1;
+                Cell::new("unknown").fg(Color::Yellow)
+            }
+        };
+        table.add_row(vec![
+            Cell::new(room),
+            Cell::new(capacity.map(|c| c.to_string()).unwrap_or_default()),
+            Cell::new(expected),
+            status,
+        ]);
+    }
+    println!("{table}");
+
+    if unknown > 0 {
+        warn!(
+            "{unknown} room(s) have no capacity recorded in {VENUE_METADATA_FILE} (add a \
+            `capacity` column to the rooms CSV file and import it)"
+        );
+    }
+    if too_small > 0 {
+        error!(
+            "{too_small} debate(s) in {} are in rooms which are too small",
+            round.name.as_str()
+        );
+        exit(1);
+    }
+    info!(
+        "Every room in {} with a known capacity is big enough",
+        round.name.as_str()
+    );
+}
+
+/// The names produced by substituting the numbers `start`, `start + 1`, ...
+/// (`count` of them) for `{n}` in `pattern`.
+fn numbered_names(pattern: &str, count: usize, start: usize) -> Vec<String> {
+    (start..start + count)
+        .map(|n| pattern.replace("{n}", &n.to_string()))
+        .collect()
+}
+
+/// Creates `count` rooms named according to `pattern` (e.g. `Room {n}`), all
+/// with the same priority. Rooms which already exist are left alone, so this
+/// can safely be run again (e.g. with a larger `count`).
+pub async fn generate(auth: Auth, count: usize, pattern: &str, priority: i64, start: usize) {
+    if !pattern.contains("{n}") {
+        error!(
+            "The pattern `{pattern}` should contain `{{n}}`, which is replaced by the room number"
+        );
+        exit(1);
+    }
+
+    let manager = RequestManager::new(&auth.api_key);
+    let venues = get_venues(&auth, manager.clone()).await;
+
+    let span = span!(Level::INFO, "generate_rooms");
+    let _guard = span.enter();
+
+    let mut created = 0;
+    for name in numbered_names(pattern, count, start) {
+        if venues.iter().any(|venue| names_match(&venue.name, &name)) {
+            info!("Room {name} already exists");
+            continue;
+        }
+        let resp = manager
+            .send_request(|| {
+                manager
+                    .client
+                    .post(format!(
+                        "{}/api/v1/tournaments/{}/venues",
+                        auth.tabbycat_url, auth.tournament_slug
+                    ))
+                    .json(&json!({
+                        "categories": [],
+                        "name": name,
+                        "priority": priority
+                    }))
+                    .build()
+                    .unwrap()
+            })
+            .await;
+        if !resp.status().is_success() {
+            error!(
+                "Failed to create room {name}: {} {}",
+                resp.status(),
+                resp.text().await.unwrap()
+            );
+            exit(1);
+        }
+        created += 1;
+        info!("Created room {name}");
+    }
+    info!("Created {created} room(s)");
+}
+
+#[cfg(test)]
+#[test]
+fn test_natural_cmp() {
+    let mut names = vec!["Room 10", "Room 2", "Hall", "room 1"];
+    names.sort_by(|a, b| natural_cmp(a, b));
+    assert_eq!(names, vec!["Hall", "room 1", "Room 2", "Room 10"]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_descending_priorities() {
+    assert_eq!(descending_priorities(3, [].into_iter()), vec![3, 2, 1]);
+    // above every room which is not being ordered
+    assert_eq!(
+        descending_priorities(2, [5, 40, 1].into_iter()),
+        vec![42, 41]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_numbered_names() {
+    assert_eq!(
+        numbered_names("Breakout {n}", 3, 1),
+        vec!["Breakout 1", "Breakout 2", "Breakout 3"]
+    );
+    assert_eq!(
+        numbered_names("Zoom {n} ({n})", 1, 10),
+        vec!["Zoom 10 (10)"]
+    );
+    assert!(numbered_names("Room {n}", 0, 1).is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn test_allocate() {
+    let constraint = |who: &str, category: &str, priority: i64| Constraint {
+        who: who.to_string(),
+        category: category.to_string(),
+        priority,
+    };
+    let rooms = ["r1", "r2", "r3"].map(str::to_string);
+    let categories = HashMap::from([("accessible".to_string(), HashSet::from(["r3".to_string()]))]);
+
+    let debates = vec![
+        (Some("r3".to_string()), vec![]),
+        (
+            Some("r1".to_string()),
+            vec![constraint("Oxford A", "accessible", 10)],
+        ),
+        (
+            Some("r2".to_string()),
+            vec![constraint("Alex", "accessible", 5)],
+        ),
+    ];
+    let (assigned, unsatisfied) = allocate(&debates, &rooms, &categories);
+    assert_eq!(
+        assigned,
+        vec![
+            Some("r1".to_string()),
+            Some("r3".to_string()),
+            Some("r2".to_string())
+        ]
+    );
+    assert_eq!(unsatisfied, vec![(2, constraint("Alex", "accessible", 5))]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_fit() {
+    assert_eq!(fit(Some(10), 10), Fit::Fits);
+    assert_eq!(fit(Some(9), 10), Fit::TooSmall);
+    assert_eq!(fit(None, 10), Fit::Unknown);
+}
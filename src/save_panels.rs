@@ -1,23 +1,114 @@
-use std::{fs::File, io::BufReader};
+use std::{collections::HashMap, fs::File, io::BufReader, process::exit};
 
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
 use itertools::Itertools;
-use tabbycat_api::types::RoundPairing;
-use tracing::info;
+use serde_json::{Value, json};
+use tabbycat_api::types::{DebateAdjudicator, RoundPairing};
+use tracing::{error, info, warn};
 
 use crate::{
     Auth,
-    api_utils::{get_round, pairings_of_round},
+    api_utils::{
+        get_availabilities, get_judges, get_round, get_teams, get_venues, pairings_of_round,
+    },
+    dispatch_req::json_of_resp,
+    panel_policy::PanelPolicy,
     request_manager::RequestManager,
 };
 
+/// The key under which the names of the room, teams and adjudicators of each
+/// debate are stored in a saved draw. They are only there to make the file
+/// readable (and are ignored when the panels are restored).
+const NAMES_KEY: &str = "_names";
+
+/// The order in which debates are saved (and restored): by room rank, then by
+/// ID (so that debates without a room rank are still in a stable order).
+pub fn pairing_order(pairing: &RoundPairing) -> impl Ord + use<> {
+    (pairing.room_rank.unwrap_or(i32::MAX), pairing.id)
+}
+
+/// Serializes `pairing`, adding the names of its room, teams and adjudicators
+/// under [`NAMES_KEY`].
+fn annotate(
+    pairing: &RoundPairing,
+    room_name: impl Fn(&String) -> Option<String>,
+    team_name: impl Fn(&String) -> Option<String>,
+    judge_name: impl Fn(&String) -> Option<String>,
+) -> Value {
+    let mut json = serde_json::to_value(pairing).unwrap();
+    let teams = pairing
+        .teams
+        .iter()
+        .map(|debate_team| {
+            json!({
+                "side": serde_json::to_value(&debate_team.side).unwrap(),
+                "team": team_name(&debate_team.team),
+            })
+        })
+        .collect::<Vec<_>>();
+    let adjudicators = pairing.adjudicators.as_ref();
+    json[NAMES_KEY] = json!({
+        "room": pairing.venue.as_ref().and_then(&room_name),
+        "teams": teams,
+        "chair": adjudicators
+            .and_then(|adjs| adjs.chair.as_ref())
+            .and_then(&judge_name),
+        "panellists": adjudicators
+            .iter()
+            .flat_map(|adjs| &adjs.panellists)
+            .map(&judge_name)
+            .collect::<Vec<_>>(),
+        "trainees": adjudicators
+            .iter()
+            .flat_map(|adjs| &adjs.trainees)
+            .map(&judge_name)
+            .collect::<Vec<_>>(),
+    });
+    json
+}
+
+/// Saves the draw of `round` to `to`, so that its panels can be restored
+/// later. The debates are saved in a fixed order and pretty-printed, together
+/// with the names of everyone in them, so that successive snapshots can be
+/// reviewed (and diffed) in Git.
 pub async fn save_panels(round: &str, to: &str, auth: Auth) {
     let manager = RequestManager::new(&auth.api_key);
 
     let round = get_round(round, &auth, manager.clone()).await;
 
-    let pairings = pairings_of_round(&auth, &round, manager).await;
+    let (pairings, teams, judges, venues) = tokio::join!(
+        pairings_of_round(&auth, &round, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+    );
 
-    std::fs::write(to, serde_json::to_string(&pairings).unwrap()).unwrap();
+    let room_name = |url: &String| {
+        venues
+            .iter()
+            .find(|venue| &venue.url == url)
+            .map(|venue| venue.name.as_str().to_string())
+    };
+    let team_name = |url: &String| {
+        teams
+            .iter()
+            .find(|team| &team.url == url)
+            .map(|team| team.short_name.to_string())
+    };
+    let judge_name = |url: &String| {
+        judges
+            .iter()
+            .find(|judge| &judge.url == url)
+            .map(|judge| judge.name.clone())
+    };
+
+    let pairings = pairings
+        .iter()
+        .sorted_by_key(|pairing| pairing_order(pairing))
+        .map(|pairing| annotate(pairing, room_name, team_name, judge_name))
+        .collect::<Vec<_>>();
+
+    std::fs::write(to, serde_json::to_string_pretty(&pairings).unwrap() + "\n").unwrap();
 
     info!("Successfully wrote current draw to `{}`.", to)
 }
@@ -86,13 +177,146 @@ fn test_deserialize() {
     ).unwrap();
 }
 
-pub async fn restore_panels(round: &str, to: &str, auth: Auth) {
+/// The teams of a debate (by URL), in a fixed order.
+fn team_set(pairing: &RoundPairing) -> Vec<&String> {
+    pairing
+        .teams
+        .iter()
+        .map(|debate_team| &debate_team.team)
+        .sorted()
+        .collect()
+}
+
+/// Matches each saved debate to a debate in the live draw: the debate with
+/// the same ID if there is one (i.e. the draw has not been regenerated), and
+/// otherwise the debate between the same teams. Returns the pairs of indices
+/// `(saved, live)` which were matched, and the indices of the saved debates
+/// which could not be.
+pub fn match_rooms(
+    saved: &[RoundPairing],
+    live: &[RoundPairing],
+) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let mut taken = vec![false; live.len()];
+    let mut matched = Vec::new();
+    let mut unmatched = Vec::new();
+
+    let by_id = saved
+        .iter()
+        .map(|old| live.iter().position(|new| new.id == old.id))
+        .collect::<Vec<_>>();
+    for j in by_id.iter().flatten() {
+        taken[*j] = true;
+    }
+
+    for (i, old) in saved.iter().enumerate() {
+        if let Some(j) = by_id[i] {
+            matched.push((i, j));
+            continue;
+        }
+        let teams = team_set(old);
+        let by_teams = (0..live.len())
+            .find(|&j| !taken[j] && !teams.is_empty() && team_set(&live[j]) == teams);
+        match by_teams {
+            Some(j) => {
+                taken[j] = true;
+                matched.push((i, j));
+            }
+            None => unmatched.push(i),
+        }
+    }
+    (matched, unmatched)
+}
+
+/// The adjudicators of a panel, with their roles (`c` for the chair, `p` for
+/// panellists and `t` for trainees).
+pub fn panel_of(panel: Option<&DebateAdjudicator>) -> impl Iterator<Item = (&String, char)> {
+    panel.into_iter().flat_map(|adjs| {
+        adjs.chair
+            .iter()
+            .map(|chair| (chair, 'c'))
+            .chain(adjs.panellists.iter().map(|panellist| (panellist, 'p')))
+            .chain(adjs.trainees.iter().map(|trainee| (trainee, 't')))
+    })
+}
+
+/// One adjudicator of a panel per line, with the chair and trainees marked.
+fn describe_panel(
+    panel: Option<&DebateAdjudicator>,
+    judge_name: impl Fn(&String) -> String,
+) -> String {
+    panel_of(panel)
+        .map(|(url, role)| match role {
+            'c' => format!("{} (c)", judge_name(url)),
+            't' => format!("{} (t)", judge_name(url)),
+            _ => judge_name(url),
+        })
+        .join("\n")
+}
+
+/// Reads a draw saved by `save_panels`, returning its debates (in the order
+/// they are saved in) and the names (of rooms, teams and adjudicators, by URL)
+/// stored alongside them.
+pub fn read_snapshot(path: &str) -> (Vec<RoundPairing>, HashMap<String, String>) {
+    let file = File::open(path).unwrap_or_else(|e| {
+        error!("Could not open {path}: {e}");
+        exit(1)
+    });
+    let snapshot: Vec<Value> = serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|e| {
+        error!("{path} is not a draw saved by `tabbycat save-allocs`: {e}");
+        exit(1)
+    });
+
+    let mut names = HashMap::new();
+    let mut pairings = Vec::new();
+    for mut json in snapshot {
+        let saved_names = json
+            .as_object_mut()
+            .and_then(|pairing| pairing.remove(NAMES_KEY))
+            .unwrap_or_default();
+        let pairing = serde_json::from_value::<RoundPairing>(json).unwrap_or_else(|e| {
+            error!("{path} is not a draw saved by `tabbycat save-allocs`: {e}");
+            exit(1)
+        });
+
+        let mut name = |url: Option<&String>, name: &Value| {
+            if let (Some(url), Some(name)) = (url, name.as_str()) {
+                names.insert(url.clone(), name.to_string());
+            }
+        };
+        name(pairing.venue.as_ref(), &saved_names["room"]);
+        for (i, debate_team) in pairing.teams.iter().enumerate() {
+            name(Some(&debate_team.team), &saved_names["teams"][i]["team"]);
+        }
+        if let Some(adjs) = &pairing.adjudicators {
+            name(adjs.chair.as_ref(), &saved_names["chair"]);
+            for (i, panellist) in adjs.panellists.iter().enumerate() {
+                name(Some(panellist), &saved_names["panellists"][i]);
+            }
+            for (i, trainee) in adjs.trainees.iter().enumerate() {
+                name(Some(trainee), &saved_names["trainees"][i]);
+            }
+        }
+        pairings.push(pairing);
+    }
+
+    pairings.sort_by_key(pairing_order);
+    (pairings, names)
+}
+
+/// Restores the panels saved (by `save_panels`) in `to` onto the current draw
+/// of `round`. Debates are matched by ID or, failing that, by their teams;
+/// saved debates which cannot be matched are reported (and their judges
+/// should be re-allocated by hand), rather than assigned to a guessed room.
+///
+/// Only the adjudicators of each debate are changed. Nothing is changed if a
+/// saved judge no longer exists, or (unless `force` is set) is not available
+/// for the round. The panels which were changed are printed before and after.
+pub async fn restore_panels(round: &str, to: &str, force: bool, auth: Auth) {
     let manager = RequestManager::new(&auth.api_key);
 
     let round = get_round(round, &auth, manager.clone()).await;
 
-    let old_draw: Vec<tabbycat_api::types::RoundPairing> =
-        serde_json::from_reader(BufReader::new(File::open(to).unwrap())).unwrap();
+    let (old_draw, _) = read_snapshot(to);
 
     let live_pairings = manager
         .send_request(|| {
@@ -102,37 +326,232 @@ pub async fn restore_panels(round: &str, to: &str, auth: Auth) {
         .await;
 
     let mut live_pairings: Vec<tabbycat_api::types::RoundPairing> =
-        live_pairings.json().await.unwrap();
-    live_pairings.sort_by_key(|k| k.room_rank.unwrap_or(i32::MAX));
+        json_of_resp(live_pairings).await;
+    live_pairings.sort_by_key(pairing_order);
 
-    for (i, room) in old_draw
-        .iter()
-        .sorted_by_key(|r| r.room_rank.unwrap_or(i32::MAX))
-        .enumerate()
-        // If the number of rooms decreases, the panel which was previously
-        // judging the lowest-ranked teams will be dropped (these judges should
-        // then be re-allocated).
-        .take(live_pairings.len())
-    {
-        let corresponding_room = &live_pairings[i];
+    let (teams, judges, venues, available) = tokio::join!(
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+        get_availabilities(&auth, &round, manager.clone()),
+    );
+    let team_names = |pairing: &RoundPairing| {
+        pairing
+            .teams
+            .iter()
+            .map(|debate_team| {
+                teams
+                    .iter()
+                    .find(|team| team.url == debate_team.team)
+                    .map(|team| team.short_name.to_string())
+                    .unwrap_or_else(|| debate_team.team.clone())
+            })
+            .join(" vs ")
+    };
+    let judge_name = |url: &String| {
+        judges
+            .iter()
+            .find(|judge| &judge.url == url)
+            .map(|judge| judge.name.clone())
+            .unwrap_or_else(|| url.clone())
+    };
+
+    let (matched, unmatched) = match_rooms(&old_draw, &live_pairings);
+    for i in &unmatched {
+        warn!(
+            "Could not match saved debate {} ({}) to a debate in the current draw; its panel \
+            will not be restored",
+            old_draw[*i].id,
+            team_names(&old_draw[*i])
+        );
+    }
+    for (j, pairing) in live_pairings.iter().enumerate() {
+        if !matched.iter().any(|(_, live)| *live == j) {
+            warn!(
+                "Debate {} ({}) was not in the saved draw; its panel will be left as it is",
+                pairing.id,
+                team_names(pairing)
+            );
+        }
+    }
+
+    // check that everyone on the saved panels can still judge this round
+    let mut missing = Vec::new();
+    let mut unavailable = Vec::new();
+    for (i, _) in &matched {
+        for url in panel_of(old_draw[*i].adjudicators.as_ref()).map(|(url, _)| url) {
+            if !judges.iter().any(|judge| &judge.url == url) {
+                missing.push(url.clone());
+            } else if !available.contains(url) {
+                unavailable.push(judge_name(url));
+            }
+        }
+    }
+    if !missing.is_empty() {
+        for url in &missing {
+            error!("Adjudicator {url} is on a saved panel, but no longer exists");
+        }
+        error!("Nothing has been restored.");
+        exit(1);
+    }
+    if !unavailable.is_empty() {
+        for name in &unavailable {
+            if force {
+                warn!(
+                    "{name} is not marked as available for {}",
+                    round.name.as_str()
+                );
+            } else {
+                error!(
+                    "{name} is not marked as available for {}",
+                    round.name.as_str()
+                );
+            }
+        }
+        if !force {
+            error!("Nothing has been restored (pass --force to restore the panels anyway).");
+            exit(1);
+        }
+    }
+
+    PanelPolicy::load().enforce(
+        matched.iter().filter_map(|(i, j)| {
+            Some((
+                format!("Debate {}", live_pairings[*j].id),
+                old_draw[*i].adjudicators.as_ref()?,
+            ))
+        }),
+        force,
+    );
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["Room", "Teams", "Before", "After"]);
+    let matched_count = matched.len();
+    let mut changed = 0;
+    for (i, j) in matched {
+        let saved = &old_draw[i];
+        let live = &live_pairings[j];
+        let before = describe_panel(live.adjudicators.as_ref(), judge_name);
+        let after = describe_panel(saved.adjudicators.as_ref(), judge_name);
+        if before == after {
+            continue;
+        }
 
         let res = manager
             .send_request(|| {
                 manager
                     .client
-                    .post(&corresponding_room.url)
-                    .json(&RoundPairing {
-                        adjudicators: room.adjudicators.clone(),
-                        ..corresponding_room.clone()
-                    })
+                    .patch(&live.url)
+                    .json(&json!({ "adjudicators": saved.adjudicators }))
                     .build()
                     .unwrap()
             })
             .await;
         if !res.status().is_success() {
-            panic!("{}", res.text().await.unwrap())
+            error!(
+                "Failed to restore the panel of debate {}: {} {}",
+                live.id,
+                res.status(),
+                res.text().await.unwrap()
+            );
+            exit(1);
         }
+        changed += 1;
+
+        let room = live
+            .venue
+            .as_ref()
+            .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+            .map(|venue| venue.name.as_str().to_string())
+            .unwrap_or_else(|| format!("Debate {}", live.id));
+        table.add_row(vec![room, team_names(live), before, after]);
+    }
+
+    if changed > 0 {
+        println!("{table}");
     }
+    info!(
+        "Restored {changed} panel(s) ({} were already as saved)",
+        matched_count - changed
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn test_annotate() {
+    let pairing = serde_json::from_value::<RoundPairing>(json!({
+        "id": 2,
+        "url": "https://example.com/api/v1/tournaments/t/rounds/1/pairings/2",
+        "venue": "https://example.com/api/v1/tournaments/t/venues/1",
+        "teams": [
+            { "team": "https://example.com/api/v1/tournaments/t/teams/1", "side": "aff", "flags": [] },
+            { "team": "https://example.com/api/v1/tournaments/t/teams/2", "side": "neg", "flags": [] }
+        ],
+        "adjudicators": {
+            "chair": "https://example.com/api/v1/tournaments/t/adjudicators/1",
+            "panellists": [],
+            "trainees": ["https://example.com/api/v1/tournaments/t/adjudicators/2"]
+        },
+        "barcode": null,
+        "_links": {
+            "ballots": "https://example.com/api/v1/tournaments/t/rounds/1/pairings/2/ballots"
+        },
+        "sides_confirmed": true
+    }))
+    .unwrap();
+
+    let name = |url: &String| url.rsplit('/').next().map(|id| format!("#{id}"));
+    let json = annotate(&pairing, name, name, |_| None);
+    assert_eq!(
+        json[NAMES_KEY],
+        json!({
+            "room": "#1",
+            "teams": [
+                { "side": "aff", "team": "#1" },
+                { "side": "neg", "team": "#2" }
+            ],
+            "chair": null,
+            "panellists": [],
+            "trainees": [null]
+        })
+    );
+
+    // the names are ignored when the draw is read back
+    let mut json = json;
+    json.as_object_mut().unwrap().remove(NAMES_KEY);
+    assert_eq!(
+        serde_json::from_value::<RoundPairing>(json).unwrap().id,
+        pairing.id
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_match_rooms() {
+    let pairing = |id: i64, teams: [&str; 2]| {
+        serde_json::from_value::<RoundPairing>(json!({
+            "id": id,
+            "url": format!("https://example.com/pairings/{id}"),
+            "venue": null,
+            "teams": teams.map(|team| json!({ "team": team, "side": "aff", "flags": [] })),
+            "adjudicators": null,
+            "barcode": null,
+            "_links": { "ballots": format!("https://example.com/pairings/{id}/ballots") },
+            "sides_confirmed": true
+        }))
+        .unwrap()
+    };
+
+    // the same draw
+    let saved = vec![pairing(1, ["a", "b"]), pairing(2, ["c", "d"])];
+    let live = vec![pairing(2, ["c", "d"]), pairing(1, ["a", "b"])];
+    assert_eq!(match_rooms(&saved, &live), (vec![(0, 1), (1, 0)], vec![]));
 
-    info!("Restored previous panels.")
+    // a regenerated draw, where one debate has changed
+    let live = vec![pairing(3, ["b", "a"]), pairing(4, ["c", "e"])];
+    assert_eq!(match_rooms(&saved, &live), (vec![(0, 0)], vec![1]));
 }
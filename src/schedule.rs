@@ -0,0 +1,389 @@
+use std::process::exit;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, Utc, Weekday};
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde_json::json;
+use tabbycat_api::types::{DebateAdjudicator, Round};
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_judges, get_round, get_rounds, get_teams, get_venues, pairings_of_round},
+    names::names_match,
+    release::parse_time,
+    request_manager::RequestManager,
+};
+
+/// Parses the start time of a round: either a time today (`10:00`), a day
+/// of the week and a time (`Sat 10:00`, which is the next Saturday, or today
+/// if today is a Saturday) or a date and time (`2025-10-18 10:00`).
+pub fn parse_start(start: &str, today: NaiveDate) -> Option<NaiveDateTime> {
+    let start = start.trim();
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(start, "%Y-%m-%d %H:%M") {
+        return Some(datetime);
+    }
+
+    let (date, time) = match start.split_once(char::is_whitespace) {
+        Some((day, time)) => {
+            let day = day.parse::<Weekday>().ok()?;
+            let offset = (7 + day.num_days_from_monday() as i64
+                - today.weekday().num_days_from_monday() as i64)
+                % 7;
+            (today + Duration::days(offset), time)
+        }
+        None => (today, start),
+    };
+    Some(date.and_time(parse_time(time)?))
+}
+
+/// When a round starts (according to Tabbycat), if this has been set.
+pub fn round_start(round: &Round) -> Option<DateTime<Local>> {
+    serde_json::to_value(round).unwrap()["starts_at"]
+        .as_str()
+        .and_then(|starts_at| DateTime::parse_from_rfc3339(starts_at).ok())
+        .map(|starts_at| starts_at.with_timezone(&Local))
+}
+
+/// Sets the start time of `round` (see [`parse_start`] for the formats which
+/// are accepted).
+pub async fn set_start(auth: Auth, round: &str, start: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let Some(starts_at) = parse_start(start, Local::now().date_naive())
+        .and_then(|start| start.and_local_timezone(Local).earliest())
+    else {
+        error!("Could not understand the start time {start:?} (expected e.g. `Sat 10:00`)");
+        exit(1);
+    };
+
+    let round = get_round(round, &auth, manager.clone()).await;
+    let url = serde_json::to_value(&round).unwrap()["url"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let resp = manager
+        .send_request_unchecked(|| {
+            manager
+                .client
+                .patch(&url)
+                .json(&json!({ "starts_at": starts_at.to_rfc3339() }))
+                .build()
+                .unwrap()
+        })
+        .await;
+    if !resp.status().is_success() {
+        error!(
+            "Failed to set the start time of {}: {} {}",
+            round.name.as_str(),
+            resp.status(),
+            resp.text().await.unwrap()
+        );
+        exit(1);
+    }
+
+    info!(
+        "{} starts at {}",
+        round.name.as_str(),
+        starts_at.format("%a %d %b %H:%M")
+    );
+}
+
+/// Prints the start time of each round.
+pub async fn show_schedule(auth: Auth) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let mut rounds = get_rounds(&auth, manager).await;
+    rounds.sort_by_key(|round| round.seq);
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec!["round", "starts at"]);
+    for round in &rounds {
+        table.add_row(vec![
+            round.name.as_str().to_string(),
+            round_start(round)
+                .map(|start| start.format("%a %d %b %H:%M").to_string())
+                .unwrap_or_else(|| "not set".to_string()),
+        ]);
+    }
+
+    println!("{table}");
+}
+
+/// A single event in an iCalendar file.
+#[derive(Debug, Clone)]
+pub struct IcsEvent {
+    /// Must stay the same when the calendar is regenerated, so that calendar
+    /// applications update the event rather than adding another.
+    pub uid: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub summary: String,
+    pub description: String,
+}
+
+fn ics_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line so that no line is longer than 75 octets (as
+/// required by RFC 5545).
+fn ics_fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+/// Renders the events as an iCalendar file.
+pub fn to_ics(name: &str, events: &[IcsEvent], now: DateTime<Utc>) -> String {
+    let format = |time: DateTime<Utc>| time.format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//tabbycat-cli//EN".to_string(),
+        format!("X-WR-CALNAME:{}", ics_escape(name)),
+    ];
+    for event in events {
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", event.uid),
+            format!("DTSTAMP:{}", format(now)),
+            format!("DTSTART:{}", format(event.start)),
+            format!("DTEND:{}", format(event.end)),
+            format!("SUMMARY:{}", ics_escape(&event.summary)),
+            format!("DESCRIPTION:{}", ics_escape(&event.description)),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| ics_fold(line) + "\r\n").collect()
+}
+
+/// A UID for an iCalendar event which is unique to this tournament.
+fn ics_uid(auth: &Auth, event: &str) -> String {
+    format!(
+        "{}-{event}@{}",
+        auth.tournament_slug,
+        auth.tabbycat_url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/')
+    )
+}
+
+/// Writes the start time of each round (which has one) to an iCalendar file.
+/// Each event lasts `duration` minutes.
+pub async fn export_ics(auth: Auth, to: &str, duration: i64) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let mut rounds = get_rounds(&auth, manager).await;
+    rounds.sort_by_key(|round| round.seq);
+
+    let events = rounds
+        .iter()
+        .filter_map(|round| {
+            let start = round_start(round)?.with_timezone(&Utc);
+            Some(IcsEvent {
+                uid: ics_uid(&auth, &format!("round-{}", round.seq)),
+                start,
+                end: start + Duration::minutes(duration),
+                summary: format!("{} ({})", round.name.as_str(), auth.tournament_slug),
+                description: format!("{}/{}/", auth.tabbycat_url, auth.tournament_slug),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    std::fs::write(to, to_ics(&auth.tournament_slug, &events, Utc::now())).unwrap();
+    info!("Wrote {} round(s) to {to}", events.len());
+}
+
+/// The role of the adjudicator (by URL) on a panel, if they are on it.
+pub fn judge_role(panel: &DebateAdjudicator, judge: &str) -> Option<&'static str> {
+    if panel.chair.as_deref() == Some(judge) {
+        Some("chair")
+    } else if panel.panellists.iter().any(|p| p == judge) {
+        Some("panellist")
+    } else if panel.trainees.iter().any(|t| t == judge) {
+        Some("trainee")
+    } else {
+        None
+    }
+}
+
+/// Writes an iCalendar file with an event for each round (which has a start
+/// time and a draw) that `judge` is allocated in, giving their room, role,
+/// the teams and the rest of the panel.
+pub async fn export_judge_schedule(auth: Auth, judge: &str, to: &str, duration: i64) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (mut rounds, judges, teams, venues) = tokio::join!(
+        get_rounds(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+    );
+    rounds.sort_by_key(|round| round.seq);
+
+    let Some(judge) = judges
+        .iter()
+        .find(|j| names_match(&j.name, judge) || j.id.to_string() == judge.trim())
+    else {
+        error!("No adjudicator named {judge} exists");
+        exit(1);
+    };
+    let judge_name = |url: &String| {
+        judges
+            .iter()
+            .find(|j| &j.url == url)
+            .map(|j| j.name.clone())
+            .unwrap_or_else(|| url.clone())
+    };
+
+    let mut events = Vec::new();
+    for round in &rounds {
+        let pairings = pairings_of_round(&auth, round, manager.clone()).await;
+        let Some((pairing, panel, role)) = pairings.iter().find_map(|pairing| {
+            let panel = pairing.adjudicators.as_ref()?;
+            Some((pairing, panel, judge_role(panel, &judge.url)?))
+        }) else {
+            continue;
+        };
+        let Some(start) = round_start(round) else {
+            warn!(
+                "{} has no start time (set one with `tabbycat schedule set`), so it was skipped",
+                round.name.as_str()
+            );
+            continue;
+        };
+        let start = start.with_timezone(&Utc);
+
+        let room = pairing
+            .venue
+            .as_ref()
+            .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+            .map(|venue| venue.name.as_str().to_string())
+            .unwrap_or_else(|| format!("Debate {}", pairing.id));
+        let debate_teams = pairing
+            .teams
+            .iter()
+            .map(|debate_team| {
+                teams
+                    .iter()
+                    .find(|team| team.url == debate_team.team)
+                    .map(|team| team.short_name.clone())
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        let rest_of_panel = panel
+            .chair
+            .iter()
+            .map(|chair| (chair, " (chair)"))
+            .chain(panel.panellists.iter().map(|panellist| (panellist, "")))
+            .chain(panel.trainees.iter().map(|trainee| (trainee, " (trainee)")))
+            .filter(|(url, _)| **url != judge.url)
+            .map(|(url, role)| format!("{}{role}", judge_name(url)))
+            .collect::<Vec<_>>();
+
+        events.push(IcsEvent {
+            uid: ics_uid(&auth, &format!("round-{}-judge-{}", round.seq, judge.id)),
+            start,
+            end: start + Duration::minutes(duration),
+            summary: format!("{}: {role} in {room}", round.name.as_str()),
+            description: format!(
+                "Teams: {}\nPanel: {}",
+                debate_teams.join(" vs "),
+                rest_of_panel.join(", ")
+            ),
+        });
+    }
+
+    std::fs::write(
+        to,
+        to_ics(
+            &format!("{} ({})", judge.name, auth.tournament_slug),
+            &events,
+            Utc::now(),
+        ),
+    )
+    .unwrap();
+    info!("Wrote {} round(s) for {} to {to}", events.len(), judge.name);
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_start() {
+    // a Thursday
+    let today = NaiveDate::from_ymd_opt(2025, 10, 16).unwrap();
+
+    assert_eq!(
+        parse_start("Sat 10:00", today),
+        NaiveDate::from_ymd_opt(2025, 10, 18)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+    );
+    assert_eq!(parse_start("thu 09:30", today), today.and_hms_opt(9, 30, 0));
+    assert_eq!(parse_start("14:15", today), today.and_hms_opt(14, 15, 0));
+    assert_eq!(
+        parse_start("2025-10-20 08:00", today),
+        NaiveDate::from_ymd_opt(2025, 10, 20)
+            .unwrap()
+            .and_hms_opt(8, 0, 0)
+    );
+    assert_eq!(parse_start("Someday 10:00", today), None);
+}
+
+#[cfg(test)]
+#[test]
+fn test_to_ics() {
+    let start = DateTime::parse_from_rfc3339("2025-10-18T09:00:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    let ics = to_ics(
+        "wudc",
+        &[IcsEvent {
+            uid: "wudc-round-1@example.com".to_string(),
+            start,
+            end: start + Duration::minutes(60),
+            summary: "Round 1, Open".to_string(),
+            description: String::new(),
+        }],
+        start,
+    );
+
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(ics.contains("DTSTART:20251018T090000Z\r\n"));
+    assert!(ics.contains("DTEND:20251018T100000Z\r\n"));
+    assert!(ics.contains("SUMMARY:Round 1\\, Open\r\n"));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_judge_role() {
+    let panel = DebateAdjudicator {
+        chair: Some("a".to_string()),
+        panellists: vec!["b".to_string()],
+        trainees: vec!["c".to_string()],
+    };
+    assert_eq!(judge_role(&panel, "a"), Some("chair"));
+    assert_eq!(judge_role(&panel, "b"), Some("panellist"));
+    assert_eq!(judge_role(&panel, "c"), Some("trainee"));
+    assert_eq!(judge_role(&panel, "d"), None);
+}
@@ -1,6 +1,6 @@
 use tracing::{Level, info, span};
 
-use crate::Auth;
+use crate::{Auth, audit_log};
 
 /// Adds conflicts that Tabbycat often fails to create. These can be missing
 /// (for example) if a team's institution is added using the edit database
@@ -35,16 +35,15 @@ pub fn do_make_sensible_conflicts(auth: Auth) {
         {
             let mut conflicts = team.institution_conflicts.clone();
             conflicts.push(inst);
-            let patched_team: tabbycat_api::types::Team = attohttpc::patch(team.url)
-                .header("Authorization", format!("Token {}", auth.api_key))
-                .json(&serde_json::json!({
+            let patched_team: tabbycat_api::types::Team = audit_log::patch_json(
+                &team.url,
+                &auth,
+                &serde_json::json!({
                     "institution_conflicts": conflicts
-                }))
-                .unwrap()
-                .send()
-                .unwrap()
-                .json()
-                .unwrap();
+                }),
+            )
+            .json()
+            .unwrap();
             let original_team = teams
                 .iter_mut()
                 .find(|team| team.url == patched_team.url)
@@ -77,16 +76,15 @@ pub fn do_make_sensible_conflicts(auth: Auth) {
         {
             let mut t = judge.team_conflicts;
             t.push(inst);
-            let adj: tabbycat_api::types::Adjudicator = attohttpc::patch(judge.url)
-                .header("Authorization", format!("Token {}", auth.api_key))
-                .json(&serde_json::json!({
+            let adj: tabbycat_api::types::Adjudicator = audit_log::patch_json(
+                &judge.url,
+                &auth,
+                &serde_json::json!({
                     "institution_conflicts": t
-                }))
-                .unwrap()
-                .send()
-                .unwrap()
-                .json()
-                .unwrap();
+                }),
+            )
+            .json()
+            .unwrap();
             let judge = judges
                 .iter_mut()
                 .find(|judge| judge.url == adj.url)
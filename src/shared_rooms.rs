@@ -0,0 +1,239 @@
+//! Sharing rooms between two tournaments which run at the same time in the
+//! same building (on the same Tabbycat site): a room used by a debate in one
+//! tournament is marked as unavailable in the other, for each round which
+//! overlaps with that debate's round, and is made available again once it is
+//! no longer used.
+
+use std::{collections::HashSet, process::exit};
+
+use chrono::{DateTime, Duration, Local};
+use serde::Deserialize;
+use tabbycat_api::types::{Round, Venue};
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_availabilities, get_rounds, get_venues, pairings_of_round, set_availability},
+    import::read_rows,
+    names::names_match,
+    request_manager::RequestManager,
+    schedule::round_start,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+struct SharedRoomRow {
+    /// The name of the room in this tournament.
+    name: String,
+    /// The name of the room in the other tournament, if it is different.
+    #[serde(default)]
+    other_name: Option<String>,
+}
+
+/// A room which both tournaments use.
+#[derive(Debug, Clone)]
+struct SharedRoom {
+    name: String,
+    /// The URL of the room in each tournament.
+    urls: [String; 2],
+}
+
+/// Whether two rounds, which start at `a` and `b` and each last `duration`,
+/// take place at the same time.
+fn overlaps(a: DateTime<Local>, b: DateTime<Local>, duration: Duration) -> bool {
+    a < b + duration && b < a + duration
+}
+
+/// Finds the rooms listed in the shared rooms file in both tournaments,
+/// warning about any which are missing from either.
+fn match_rooms(rows: &[SharedRoomRow], venues: [&[Venue]; 2], slugs: [&str; 2]) -> Vec<SharedRoom> {
+    let find = |venues: &[Venue], name: &str| {
+        venues
+            .iter()
+            .find(|venue| names_match(venue.name.as_str(), name))
+            .map(|venue| venue.url.clone())
+    };
+
+    let mut rooms = Vec::new();
+    for row in rows {
+        let other_name = row.other_name.as_deref().unwrap_or(&row.name);
+        match (find(venues[0], &row.name), find(venues[1], other_name)) {
+            (Some(url), Some(other_url)) => rooms.push(SharedRoom {
+                name: row.name.clone(),
+                urls: [url, other_url],
+            }),
+            (None, _) => warn!("There is no room {:?} in {}", row.name, slugs[0]),
+            (_, None) => warn!("There is no room {other_name:?} in {}", slugs[1]),
+        }
+    }
+    rooms
+}
+
+/// Sets the availability of the shared rooms in each round of tournament `to`
+/// (0 or 1) which overlaps with a round of the other tournament: the rooms
+/// used by a debate in an overlapping round are unavailable, and the rest are
+/// available (so that rooms freed up by the other tournament can be used
+/// again).
+async fn sync(
+    to: usize,
+    auths: [&Auth; 2],
+    rounds: [&[Round]; 2],
+    rooms: &[SharedRoom],
+    duration: Duration,
+    manager: RequestManager,
+) {
+    let from = 1 - to;
+
+    // the shared rooms used by each round of the other tournament
+    let mut used_by = Vec::new();
+    for round in rounds[from] {
+        let Some(start) = round_start(round) else {
+            continue;
+        };
+        let pairings = pairings_of_round(auths[from], round, manager.clone()).await;
+        let used = rooms
+            .iter()
+            .filter(|room| {
+                pairings
+                    .iter()
+                    .any(|pairing| pairing.venue.as_ref() == Some(&room.urls[from]))
+            })
+            .collect::<Vec<_>>();
+        used_by.push((round, start, used));
+    }
+
+    for other in rounds[to] {
+        let Some(other_start) = round_start(other) else {
+            continue;
+        };
+        let overlapping = used_by
+            .iter()
+            .filter(|(_, start, _)| overlaps(*start, other_start, duration))
+            .collect::<Vec<_>>();
+        if overlapping.is_empty() {
+            continue;
+        }
+
+        let available = get_availabilities(auths[to], other, manager.clone()).await;
+        let other_pairings = pairings_of_round(auths[to], other, manager.clone()).await;
+        let mut blocked = HashSet::new();
+        for (round, _, used) in &overlapping {
+            for room in used {
+                if other_pairings
+                    .iter()
+                    .any(|pairing| pairing.venue.as_ref() == Some(&room.urls[to]))
+                {
+                    error!(
+                        "{} is used by both {} {} and {} {}; please move one of the debates",
+                        room.name,
+                        auths[from].tournament_slug,
+                        round.abbreviation.as_str(),
+                        auths[to].tournament_slug,
+                        other.abbreviation.as_str()
+                    );
+                }
+                blocked.insert(&room.urls[to]);
+            }
+        }
+
+        let urls = rooms.iter().map(|room| &room.urls[to]);
+        let block = urls
+            .clone()
+            .filter(|url| blocked.contains(url) && available.contains(*url))
+            .cloned()
+            .collect::<Vec<_>>();
+        let free = urls
+            .filter(|url| !blocked.contains(url) && !available.contains(*url))
+            .cloned()
+            .collect::<Vec<_>>();
+        for (is_available, urls) in [(false, block), (true, free)] {
+            if urls.is_empty() {
+                continue;
+            }
+            set_availability(auths[to], other, &urls, is_available, manager.clone()).await;
+            info!(
+                "Marked {} room(s) as {} in {} {}",
+                urls.len(),
+                if is_available {
+                    "available"
+                } else {
+                    "unavailable"
+                },
+                auths[to].tournament_slug,
+                other.abbreviation.as_str()
+            );
+        }
+    }
+}
+
+/// Keeps the availability of the rooms listed in `csv` (with a `name` column,
+/// and an `other_name` column for rooms named differently in the other
+/// tournament) in sync between this tournament and the tournament `with` on
+/// the same site. Rounds overlap if they start less than `duration` minutes
+/// apart. This should be run again whenever a draw is generated or rooms are
+/// moved in either tournament.
+pub async fn share(auth: Auth, with: &str, csv: &str, duration: i64) {
+    let manager = RequestManager::new(&auth.api_key);
+    let other = Auth {
+        tournament_slug: with.to_string(),
+        ..auth.clone()
+    };
+    if other.tournament_slug == auth.tournament_slug {
+        error!("Rooms can only be shared with a different tournament.");
+        exit(1);
+    }
+
+    let rows = read_rows::<SharedRoomRow>(&Some(csv.to_string()));
+    let (rounds, other_rounds, venues, other_venues) = tokio::join!(
+        get_rounds(&auth, manager.clone()),
+        get_rounds(&other, manager.clone()),
+        get_venues(&auth, manager.clone()),
+        get_venues(&other, manager.clone()),
+    );
+    let rooms = match_rooms(
+        &rows,
+        [&venues, &other_venues],
+        [&auth.tournament_slug, with],
+    );
+    if rooms.is_empty() {
+        error!("None of the rooms in {csv} exist in both tournaments.");
+        exit(1);
+    }
+
+    let unscheduled = rounds
+        .iter()
+        .chain(&other_rounds)
+        .filter(|round| round_start(round).is_none())
+        .count();
+    if unscheduled > 0 {
+        warn!(
+            "{unscheduled} round(s) have no start time, so are not checked for overlaps (set one \
+            with `tabbycat schedule set`)"
+        );
+    }
+
+    let auths = [&auth, &other];
+    let rounds = [rounds.as_slice(), other_rounds.as_slice()];
+    let duration = Duration::minutes(duration);
+    for to in [0, 1] {
+        sync(to, auths, rounds, &rooms, duration, manager.clone()).await;
+    }
+    info!("Shared {} room(s) with {with}", rooms.len());
+}
+
+#[cfg(test)]
+#[test]
+fn test_overlaps() {
+    use chrono::TimeZone;
+
+    let at = |hour: u32, minute: u32| {
+        Local
+            .with_ymd_and_hms(2025, 10, 18, hour, minute, 0)
+            .unwrap()
+    };
+    let duration = Duration::minutes(90);
+
+    assert!(overlaps(at(9, 0), at(10, 0), duration));
+    assert!(overlaps(at(10, 0), at(9, 0), duration));
+    assert!(!overlaps(at(9, 0), at(10, 30), duration));
+    assert!(!overlaps(at(9, 0), at(13, 0), duration));
+}
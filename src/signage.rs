@@ -0,0 +1,208 @@
+use std::{collections::HashMap, path::Path, process::exit};
+
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::{
+    Auth,
+    api_utils::{get_judges, get_round_and_pairings, get_teams, get_venues},
+    debate_flags::DebateFlags,
+    request_manager::RequestManager,
+};
+
+#[derive(Serialize)]
+pub struct RoomTeam {
+    pub name: String,
+    pub side: Option<String>,
+    pub speakers: Vec<String>,
+}
+
+/// Everything needed to print material (signage, ballots, etc) for a single
+/// room.
+#[derive(Serialize)]
+pub struct RoomContext {
+    /// The ID of the debate.
+    pub debate: i64,
+    pub round: String,
+    pub room: String,
+    pub teams: Vec<RoomTeam>,
+    pub chair: Option<String>,
+    pub panellists: Vec<String>,
+    pub trainees: Vec<String>,
+    /// Only provided once the motions have been released.
+    pub motions: Vec<String>,
+    /// The flags set on the debate with `draw flag` (e.g. "needs runner").
+    pub flags: Vec<String>,
+}
+
+/// Collects the [`RoomContext`] for each debate in the given round.
+pub async fn room_contexts(auth: &Auth, round: &str, manager: RequestManager) -> Vec<RoomContext> {
+    let ((round, pairings), teams, judges, venues) = tokio::join!(
+        get_round_and_pairings(round, auth, manager.clone()),
+        get_teams(auth, manager.clone()),
+        get_judges(auth, manager.clone()),
+        get_venues(auth, manager.clone()),
+    );
+
+    let round_json = serde_json::to_value(&round).unwrap();
+    let motions = if round_json["motions_released"].as_bool() == Some(true) {
+        round_json["motions"]
+            .as_array()
+            .map(|motions| {
+                motions
+                    .iter()
+                    .filter_map(|motion| motion["text"].as_str().map(|t| t.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    } else {
+        vec![]
+    };
+
+    let flags = DebateFlags::load(auth);
+    let judge_name = |url: &String| {
+        judges
+            .iter()
+            .find(|judge| &judge.url == url)
+            .map(|judge| judge.name.clone())
+            .unwrap_or_default()
+    };
+
+    pairings
+        .iter()
+        .map(|pairing| RoomContext {
+            debate: pairing.id,
+            round: round.name.as_str().to_string(),
+            room: pairing
+                .venue
+                .as_ref()
+                .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+                .map(|venue| venue.name.as_str().to_string())
+                .unwrap_or_else(|| format!("Debate {}", pairing.id)),
+            teams: pairing
+                .teams
+                .iter()
+                .map(|debate_team| {
+                    let team = teams.iter().find(|t| t.url == debate_team.team);
+                    RoomTeam {
+                        name: team.map(|t| t.short_name.clone()).unwrap_or_default(),
+                        side: serde_json::to_value(&debate_team.side)
+                            .ok()
+                            .and_then(|side| side.as_str().map(|s| s.to_string())),
+                        speakers: team
+                            .map(|t| t.speakers.iter().map(|s| s.name.to_string()).collect())
+                            .unwrap_or_default(),
+                    }
+                })
+                .collect(),
+            chair: pairing
+                .adjudicators
+                .as_ref()
+                .and_then(|adjs| adjs.chair.as_ref())
+                .map(judge_name),
+            panellists: pairing
+                .adjudicators
+                .as_ref()
+                .map(|adjs| adjs.panellists.iter().map(judge_name).collect())
+                .unwrap_or_default(),
+            trainees: pairing
+                .adjudicators
+                .as_ref()
+                .map(|adjs| adjs.trainees.iter().map(judge_name).collect())
+                .unwrap_or_default(),
+            motions: motions.clone(),
+            flags: flags.get(pairing.id).to_vec(),
+        })
+        .collect()
+}
+
+/// Turns a room name into something which can safely be used as a file name.
+fn file_stem(room: &str) -> String {
+    room.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The file names (without the extension) for each room, which are the names
+/// of the rooms, followed by the ID of the debate for rooms whose names would
+/// otherwise give the same file name.
+fn file_stems(rooms: &[RoomContext]) -> Vec<String> {
+    let mut counts = HashMap::new();
+    for room in rooms {
+        *counts.entry(file_stem(&room.room)).or_insert(0) += 1;
+    }
+    rooms
+        .iter()
+        .map(|room| {
+            let stem = file_stem(&room.room);
+            if counts[&stem] > 1 {
+                format!("{stem}_{}", room.debate)
+            } else {
+                stem
+            }
+        })
+        .collect()
+}
+
+/// Renders one file per room from a Tera template (see
+/// https://keats.github.io/tera/docs/ for the syntax). The template has access
+/// to `debate` (the ID of the debate), `round`, `room`, `teams` (each with a
+/// `name`, `side` and `speakers`), `chair`, `panellists`, `trainees`, `motions`
+/// and `flags`. HTML (and XML) templates are escaped automatically.
+pub async fn export_signage(auth: Auth, round: &str, template: &str, out: &str) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let template_source = std::fs::read_to_string(template).unwrap_or_else(|e| {
+        error!("Could not read template {template}: {e}");
+        exit(1);
+    });
+    let extension = Path::new(template)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_else(|| "html".to_string());
+
+    // Tera decides whether to escape a template by the extension of its name
+    let name = format!("signage.{extension}");
+    let mut tera = tera::Tera::default();
+    if let Err(e) = tera.add_raw_template(&name, &template_source) {
+        error!("Could not parse template {template}: {e}");
+        exit(1);
+    }
+
+    let rooms = room_contexts(&auth, round, manager).await;
+
+    std::fs::create_dir_all(out).unwrap();
+
+    for (room, stem) in rooms.iter().zip(file_stems(&rooms)) {
+        let rendered = tera
+            .render(&name, &tera::Context::from_serialize(room).unwrap())
+            .unwrap_or_else(|e| {
+                error!("Could not render signage for {}: {e}", room.room);
+                exit(1);
+            });
+
+        let path = Path::new(out).join(format!("{stem}.{extension}"));
+        std::fs::write(&path, rendered).unwrap();
+        info!("Wrote signage for {} to {}", room.room, path.display());
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_file_stems() {
+    let room = |debate: i64, room: &str| RoomContext {
+        debate,
+        round: "Round 1".to_string(),
+        room: room.to_string(),
+        teams: vec![],
+        chair: None,
+        panellists: vec![],
+        trainees: vec![],
+        motions: vec![],
+        flags: vec![],
+    };
+    assert_eq!(
+        file_stems(&[room(1, "Room 1"), room(2, "Room/1"), room(3, "Hall")]),
+        vec!["Room_1_1", "Room_1_2", "Hall"]
+    );
+}
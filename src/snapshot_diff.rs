@@ -0,0 +1,280 @@
+use std::{collections::HashMap, process::exit};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use tabbycat_api::types::RoundPairing;
+use tracing::error;
+
+use crate::{
+    Auth,
+    api_utils::{get_judges, get_round, get_rounds, get_teams, get_venues, pairings_of_round},
+    request_manager::RequestManager,
+    save_panels::{match_rooms, pairing_order, panel_of, read_snapshot},
+};
+
+fn role_name(role: char) -> &'static str {
+    match role {
+        'c' => "chair",
+        't' => "trainee",
+        _ => "panellist",
+    }
+}
+
+fn side_of(pairing: &RoundPairing, team: &str) -> String {
+    pairing
+        .teams
+        .iter()
+        .find(|debate_team| debate_team.team == team)
+        .and_then(|debate_team| serde_json::to_value(&debate_team.side).ok())
+        .and_then(|side| side.as_str().map(|side| side.to_uppercase()))
+        .unwrap_or_default()
+}
+
+/// The changes between the draws `before` and `after`, as a list of rooms (in
+/// the order of `after`) with the teams and adjudicators who were added to,
+/// removed from or moved within each. `name` gives the name of a room, team or
+/// adjudicator from their URL.
+fn diff(
+    before: &[RoundPairing],
+    after: &[RoundPairing],
+    name: impl Fn(&String) -> String,
+) -> Vec<(String, Vec<String>)> {
+    let room = |pairing: &RoundPairing| {
+        pairing
+            .venue
+            .as_ref()
+            .map(&name)
+            .unwrap_or_else(|| format!("Debate {}", pairing.id))
+    };
+    // where each judge and team is in a draw
+    let locate = |draw: &[RoundPairing]| {
+        let mut judges = HashMap::new();
+        let mut teams = HashMap::new();
+        for pairing in draw {
+            for (url, role) in panel_of(pairing.adjudicators.as_ref()) {
+                judges.insert(url.clone(), (room(pairing), role));
+            }
+            for debate_team in &pairing.teams {
+                teams.insert(debate_team.team.clone(), room(pairing));
+            }
+        }
+        (judges, teams)
+    };
+    let (judges_before, teams_before) = locate(before);
+    let (judges_after, teams_after) = locate(after);
+
+    let (matched, unmatched) = match_rooms(before, after);
+    let mut rooms = Vec::new();
+    for (j, new) in after.iter().enumerate() {
+        let Some(&(i, _)) = matched.iter().find(|(_, live)| *live == j) else {
+            rooms.push((room(new), vec!["new debate".to_string()]));
+            continue;
+        };
+        let old = &before[i];
+        let mut changes = Vec::new();
+
+        if room(old) != room(new) {
+            changes.push(format!("moved from {}", room(old)));
+        }
+
+        let old_teams = old.teams.iter().map(|t| &t.team).collect::<Vec<_>>();
+        for debate_team in &new.teams {
+            let team = &debate_team.team;
+            if !old_teams.contains(&team) {
+                let from = teams_before
+                    .get(team)
+                    .map(|room| format!(" (from {room})"))
+                    .unwrap_or_default();
+                changes.push(format!("+ {}{from}", name(team)));
+            } else if side_of(old, team) != side_of(new, team) {
+                changes.push(format!(
+                    "{}: {} → {}",
+                    name(team),
+                    side_of(old, team),
+                    side_of(new, team)
+                ));
+            }
+        }
+        for team in old_teams {
+            if !new.teams.iter().any(|t| &t.team == team) {
+                let to = teams_after
+                    .get(team)
+                    .map(|room| format!(" (to {room})"))
+                    .unwrap_or_default();
+                changes.push(format!("- {}{to}", name(team)));
+            }
+        }
+
+        let old_panel = panel_of(old.adjudicators.as_ref()).collect::<Vec<_>>();
+        let new_panel = panel_of(new.adjudicators.as_ref()).collect::<Vec<_>>();
+        for (judge, role) in &new_panel {
+            match old_panel.iter().find(|(old_judge, _)| old_judge == judge) {
+                Some((_, old_role)) if old_role != role => changes.push(format!(
+                    "{}: {} → {}",
+                    name(judge),
+                    role_name(*old_role),
+                    role_name(*role)
+                )),
+                Some(_) => (),
+                None => {
+                    let from = judges_before
+                        .get(*judge)
+                        .map(|(room, _)| format!(", from {room}"))
+                        .unwrap_or_default();
+                    changes.push(format!("+ {} ({}{from})", name(judge), role_name(*role)));
+                }
+            }
+        }
+        for (judge, role) in &old_panel {
+            if !new_panel.iter().any(|(new_judge, _)| new_judge == judge) {
+                let to = judges_after
+                    .get(*judge)
+                    .map(|(room, _)| format!(", to {room}"))
+                    .unwrap_or_default();
+                changes.push(format!("- {} ({}{to})", name(judge), role_name(*role)));
+            }
+        }
+
+        if !changes.is_empty() {
+            rooms.push((room(new), changes));
+        }
+    }
+    for i in unmatched {
+        rooms.push((room(&before[i]), vec!["debate removed".to_string()]));
+    }
+    rooms
+}
+
+fn print_diff(rooms: Vec<(String, Vec<String>)>) {
+    if rooms.is_empty() {
+        println!("The draws are the same.");
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .set_header(vec!["Room", "Changes"]);
+    for (room, changes) in rooms {
+        table.add_row(vec![room, changes.join("\n")]);
+    }
+    println!("{table}");
+}
+
+/// Prints the differences between two draws saved by `save-allocs`.
+pub fn diff_snapshots(a: &str, b: &str) {
+    let (before, mut names) = read_snapshot(a);
+    let (after, names_after) = read_snapshot(b);
+    names.extend(names_after);
+
+    print_diff(diff(&before, &after, |url| {
+        names.get(url).cloned().unwrap_or_else(|| url.clone())
+    }));
+}
+
+/// Prints the differences between a draw saved by `save-allocs` and the
+/// current draw of the same round (or of `round`, if given).
+pub async fn diff_live(auth: Auth, snapshot: &str, round: Option<String>) {
+    let manager = RequestManager::new(&auth.api_key);
+    let (before, saved_names) = read_snapshot(snapshot);
+
+    let round = match round {
+        Some(round) => get_round(&round, &auth, manager.clone()).await,
+        None => {
+            let rounds = get_rounds(&auth, manager.clone()).await;
+            let found = before.first().and_then(|pairing| {
+                rounds
+                    .iter()
+                    .find(|round| pairing.url.starts_with(&round.links.pairing))
+            });
+            match found {
+                Some(round) => round.clone(),
+                None => {
+                    error!("Could not tell which round {snapshot} is from; please pass --round.");
+                    exit(1);
+                }
+            }
+        }
+    };
+
+    let (mut after, teams, judges, venues) = tokio::join!(
+        pairings_of_round(&auth, &round, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+    );
+    after.sort_by_key(pairing_order);
+
+    let mut names = saved_names;
+    names.extend(
+        teams
+            .iter()
+            .map(|team| (team.url.clone(), team.short_name.to_string())),
+    );
+    names.extend(
+        judges
+            .iter()
+            .map(|judge| (judge.url.clone(), judge.name.clone())),
+    );
+    names.extend(
+        venues
+            .iter()
+            .map(|venue| (venue.url.clone(), venue.name.as_str().to_string())),
+    );
+
+    print_diff(diff(&before, &after, |url| {
+        names.get(url).cloned().unwrap_or_else(|| url.clone())
+    }));
+}
+
+#[cfg(test)]
+#[test]
+fn test_diff() {
+    use serde_json::json;
+
+    let pairing = |id: i64, room: &str, teams: [&str; 2], chair: &str, panellists: &[&str]| {
+        serde_json::from_value::<RoundPairing>(json!({
+            "id": id,
+            "url": format!("https://example.com/pairings/{id}"),
+            "venue": room,
+            "teams": [
+                { "team": teams[0], "side": "aff", "flags": [] },
+                { "team": teams[1], "side": "neg", "flags": [] },
+            ],
+            "adjudicators": { "chair": chair, "panellists": panellists, "trainees": [] },
+            "barcode": null,
+            "_links": { "ballots": format!("https://example.com/pairings/{id}/ballots") },
+            "sides_confirmed": true
+        }))
+        .unwrap()
+    };
+    let before = vec![
+        pairing(1, "R1", ["a", "b"], "Jo", &["Sam"]),
+        pairing(2, "R2", ["c", "d"], "Kim", &[]),
+    ];
+    let name = |url: &String| url.clone();
+
+    assert!(diff(&before, &before, name).is_empty());
+
+    let after = vec![
+        pairing(1, "R1", ["a", "b"], "Sam", &[]),
+        pairing(2, "R2", ["c", "d"], "Kim", &["Jo"]),
+    ];
+    assert_eq!(
+        diff(&before, &after, name),
+        vec![
+            (
+                "R1".to_string(),
+                vec![
+                    "Sam: panellist → chair".to_string(),
+                    "- Jo (chair, to R2)".to_string()
+                ]
+            ),
+            (
+                "R2".to_string(),
+                vec!["+ Jo (panellist, from R1)".to_string()]
+            ),
+        ]
+    );
+}
@@ -0,0 +1,624 @@
+use std::{collections::BTreeMap, process::exit};
+
+use comfy_table::{Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use serde::Deserialize;
+use serde_json::Value;
+use tabbycat_api::types::{DrawStatusEnum, Team};
+use tracing::{error, info};
+
+use crate::{
+    Auth,
+    anonymise::Anonymiser,
+    api_utils::{
+        get_ballots, get_preference, get_rounds, get_speaker_categories, get_teams,
+        pairings_of_round,
+    },
+    dispatch_req::json_of_resp,
+    request_manager::RequestManager,
+    tabular::{Column, TableFormat, column_names, write_table},
+};
+
+/// A single row of the (team or speaker) standings returned by Tabbycat.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Standing {
+    #[serde(default)]
+    pub team: Option<String>,
+    #[serde(default)]
+    pub speaker: Option<String>,
+    pub rank: Option<u32>,
+    #[serde(default)]
+    pub tied: bool,
+    pub metrics: Vec<Metric>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Metric {
+    pub metric: String,
+    pub value: Option<f64>,
+}
+
+async fn get_standings(auth: &Auth, path: &str, manager: RequestManager) -> Vec<Standing> {
+    let url = format!(
+        "{}/api/v1/tournaments/{}/{path}",
+        auth.tabbycat_url, auth.tournament_slug
+    );
+    let resp = manager
+        .send_request(|| manager.client.get(&url).build().unwrap())
+        .await;
+
+    json_of_resp(resp).await
+}
+
+pub async fn get_team_standings(auth: &Auth, manager: RequestManager) -> Vec<Standing> {
+    get_standings(auth, "teams/standings", manager).await
+}
+
+pub async fn get_speaker_standings(auth: &Auth, manager: RequestManager) -> Vec<Standing> {
+    get_standings(auth, "speakers/standings", manager).await
+}
+
+pub async fn get_reply_standings(auth: &Auth, manager: RequestManager) -> Vec<Standing> {
+    get_standings(auth, "speakers/standings/replies", manager).await
+}
+
+/// Builds the header and rows of a standings table. The rank column is taken
+/// from Tabbycat, followed by the columns produced by `describe` and then one
+/// column per metric. With `explain`, a final column shows which metric
+/// separates each row from the one above it (see [`explain_standings`]).
+fn standings_table(
+    standings: &[Standing],
+    columns: &[&str],
+    explain: bool,
+    describe: impl Fn(&Standing) -> Vec<String>,
+) -> (Vec<Column>, Vec<Vec<String>>) {
+    let mut header = vec![Column::text("rank")];
+    header.extend(columns.iter().map(|c| Column::text(*c)));
+    if let Some(first) = standings.first() {
+        header.extend(first.metrics.iter().map(|m| Column::number(&m.metric)));
+    }
+    if explain {
+        header.push(Column::text("separated_by"));
+    }
+    let explanations = explain_standings(standings);
+
+    let rows = standings
+        .iter()
+        .zip(explanations)
+        .map(|(standing, explanation)| {
+            let mut row = vec![
+                standing
+                    .rank
+                    .map(|rank| format_rank(rank as usize, standing.tied))
+                    .unwrap_or_default(),
+            ];
+            row.extend(describe(standing));
+            row.extend(
+                standing
+                    .metrics
+                    .iter()
+                    .map(|m| m.value.map(|v| v.to_string()).unwrap_or_default()),
+            );
+            if explain {
+                row.push(explanation);
+            }
+            row
+        })
+        .collect();
+
+    (header, rows)
+}
+
+/// Fetches either the team standings or (if `replies` is set) the reply
+/// speaker standings, formatted as a table.
+pub async fn fetch_standings_table(
+    auth: &Auth,
+    replies: bool,
+    explain: bool,
+    anon: Anonymiser,
+    manager: RequestManager,
+) -> (Vec<Column>, Vec<Vec<String>>) {
+    let (teams, standings) = tokio::join!(get_teams(auth, manager.clone()), async {
+        if replies {
+            get_reply_standings(auth, manager.clone()).await
+        } else {
+            get_team_standings(auth, manager.clone()).await
+        }
+    });
+
+    if replies {
+        standings_table(&standings, &["name", "team"], explain, |standing| {
+            let url = standing.speaker.as_deref().unwrap_or_default();
+            teams
+                .iter()
+                .find_map(|team| {
+                    team.speakers
+                        .iter()
+                        .find(|speaker| speaker.url == url)
+                        .map(|speaker| {
+                            vec![
+                                anon.name("Speaker", &speaker.url, &speaker.name),
+                                anon.name("Team", &team.url, &team.short_name),
+                            ]
+                        })
+                })
+                .unwrap_or_else(|| vec![anon.name("Speaker", url, url), String::new()])
+        })
+    } else {
+        standings_table(&standings, &["team"], explain, |standing| {
+            let url = standing.team.as_deref().unwrap_or_default();
+            vec![
+                teams
+                    .iter()
+                    .find(|team| team.url == url)
+                    .map(|team| anon.name("Team", &team.url, &team.short_name))
+                    .unwrap_or_else(|| anon.name("Team", url, url)),
+            ]
+        })
+    }
+}
+
+pub async fn view_standings(auth: Auth, replies: bool, explain: bool) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (header, rows) =
+        fetch_standings_table(&auth, replies, explain, Anonymiser::default(), manager).await;
+
+    if rows.is_empty() {
+        println!("No standings are available yet.");
+        return;
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(column_names(&header));
+    for row in rows {
+        table.add_row(row);
+    }
+
+    println!("{table}");
+}
+
+pub async fn export_standings(
+    auth: Auth,
+    replies: bool,
+    explain: bool,
+    to: &str,
+    format: TableFormat,
+    anon: Anonymiser,
+) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (header, rows) = fetch_standings_table(&auth, replies, explain, anon, manager).await;
+
+    write_table(to, format, &header, &rows);
+
+    info!(
+        "Saved {} standings ({} rows) into {:?} file {}",
+        if replies { "reply speaker" } else { "team" },
+        rows.len(),
+        format,
+        to
+    );
+}
+
+/// Recomputes ranks for a (filtered) list of standings, which must already be
+/// in order. Standings with identical metrics share a rank. Returns the rank
+/// of each standing and whether it is tied with another.
+pub fn rank_standings(standings: &[Standing]) -> Vec<(usize, bool)> {
+    let same = |a: &Standing, b: &Standing| {
+        a.metrics.iter().map(|m| m.value).collect::<Vec<_>>()
+            == b.metrics.iter().map(|m| m.value).collect::<Vec<_>>()
+    };
+
+    let mut ranks = Vec::with_capacity(standings.len());
+    for (i, standing) in standings.iter().enumerate() {
+        let rank = if i > 0 && same(&standings[i - 1], standing) {
+            ranks.last().map(|(rank, _)| *rank).unwrap()
+        } else {
+            i + 1
+        };
+        let tied = (i > 0 && same(&standings[i - 1], standing))
+            || standings
+                .get(i + 1)
+                .map(|next| same(standing, next))
+                .unwrap_or(false);
+        ranks.push((rank, tied));
+    }
+    ranks
+}
+
+/// For each standing, describes the first metric (in order of precedence)
+/// which separates it from the standing above, e.g. `speaks: 151.5 vs 150`.
+pub fn explain_standings(standings: &[Standing]) -> Vec<String> {
+    let format_value = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+
+    standings
+        .iter()
+        .enumerate()
+        .map(|(i, standing)| {
+            let Some(above) = i.checked_sub(1).map(|i| &standings[i]) else {
+                return String::new();
+            };
+            above
+                .metrics
+                .iter()
+                .zip(&standing.metrics)
+                .find(|(a, b)| a.value != b.value)
+                .map(|(a, b)| {
+                    format!(
+                        "{}: {} vs {}",
+                        b.metric,
+                        format_value(a.value),
+                        format_value(b.value)
+                    )
+                })
+                .unwrap_or_else(|| "tied on all metrics".to_string())
+        })
+        .collect()
+}
+
+fn format_rank(rank: usize, tied: bool) -> String {
+    if tied {
+        format!("{rank}=")
+    } else {
+        rank.to_string()
+    }
+}
+
+/// Exports the speaker tab (optionally only for the speakers in a given
+/// speaker category, e.g. `esl`) to a CSV file.
+pub async fn export_speakers(
+    auth: Auth,
+    category: Option<String>,
+    to: &str,
+    format: TableFormat,
+    anon: Anonymiser,
+) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (standings, teams, categories) = tokio::join!(
+        get_speaker_standings(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_speaker_categories(&auth, manager.clone()),
+    );
+
+    let category = category.map(|key| {
+        categories
+            .iter()
+            .find(|cat| {
+                cat.slug.as_str().eq_ignore_ascii_case(&key)
+                    || cat.name.as_str().eq_ignore_ascii_case(&key)
+            })
+            .unwrap_or_else(|| {
+                error!("No speaker category found matching {key}");
+                exit(1);
+            })
+            .clone()
+    });
+
+    let (header, rows) = speaker_tab(
+        standings,
+        &teams,
+        category.as_ref().map(|cat| cat.url.as_str()),
+        anon,
+    );
+
+    write_table(to, format, &header, &rows);
+    info!(
+        "Saved {} speaker tab ({} speakers) into {:?} file {}",
+        category
+            .map(|cat| cat.name.as_str().to_string())
+            .unwrap_or_else(|| "open".to_string()),
+        rows.len(),
+        format,
+        to
+    );
+}
+
+/// Builds the speaker tab (optionally only for the speakers in the speaker
+/// category with URL `category`) from the speaker standings. Speakers are
+/// ranked both within the category and overall (`open_rank`).
+pub fn speaker_tab(
+    standings: Vec<Standing>,
+    teams: &[Team],
+    category: Option<&str>,
+    anon: Anonymiser,
+) -> (Vec<Column>, Vec<Vec<String>>) {
+    let speaker_of = |url: &str| {
+        teams.iter().find_map(|team| {
+            team.speakers
+                .iter()
+                .find(|speaker| speaker.url == url)
+                .map(|speaker| (speaker, team))
+        })
+    };
+
+    let standings = standings
+        .into_iter()
+        .filter(|standing| {
+            let Some((speaker, _)) = standing.speaker.as_deref().and_then(speaker_of) else {
+                return false;
+            };
+            category
+                .map(|cat| speaker.categories.iter().any(|url| url == cat))
+                .unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
+
+    let mut header = vec![
+        Column::text("rank"),
+        Column::text("open_rank"),
+        Column::text("name"),
+        Column::text("team"),
+    ];
+    if let Some(first) = standings.first() {
+        header.extend(first.metrics.iter().map(|m| Column::number(&m.metric)));
+    }
+
+    let mut rows = Vec::new();
+    for (standing, (rank, tied)) in standings.iter().zip(rank_standings(&standings)) {
+        let (speaker, team) = speaker_of(standing.speaker.as_deref().unwrap()).unwrap();
+
+        let mut record = vec![
+            format_rank(rank, tied),
+            standing
+                .rank
+                .map(|rank| format_rank(rank as usize, standing.tied))
+                .unwrap_or_default(),
+            anon.name("Speaker", &speaker.url, &speaker.name),
+            anon.name("Team", &team.url, &team.short_name),
+        ];
+        record.extend(
+            standing
+                .metrics
+                .iter()
+                .map(|m| m.value.map(|v| v.to_string()).unwrap_or_default()),
+        );
+        rows.push(record);
+    }
+
+    (header, rows)
+}
+
+/// The score each speaker received in a debate, from the sheets of its
+/// confirmed ballot. Where a debate has several sheets (one per voting
+/// adjudicator), each speaker's score is the mean across the sheets, as in
+/// Tabbycat's own tab. Replies (speeches after the first
+/// `substantive_speakers`) and iron-person "ghost" speeches are not counted.
+fn speech_scores(sheets: &[Value], substantive_speakers: usize) -> BTreeMap<String, f64> {
+    let mut scores: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for sheet in sheets {
+        for team in sheet["teams"].as_array().into_iter().flatten() {
+            for speech in team["speeches"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .take(substantive_speakers)
+            {
+                if speech["ghost"].as_bool() == Some(true) {
+                    continue;
+                }
+                let (Some(speaker), Some(score)) =
+                    (speech["speaker"].as_str(), speech["score"].as_f64())
+                else {
+                    continue;
+                };
+                scores.entry(speaker.to_string()).or_default().push(score);
+            }
+        }
+    }
+    scores
+        .into_iter()
+        .map(|(speaker, scores)| (speaker, scores.iter().sum::<f64>() / scores.len() as f64))
+        .collect()
+}
+
+/// Builds the speaker tab in "wide" format: each speaker's rank, name and team,
+/// then one column per round (named by `rounds`) holding their score in that
+/// round (empty if they did not speak), then the metrics from the standings.
+fn speaker_rounds_table(
+    standings: &[Standing],
+    teams: &[Team],
+    rounds: &[(String, BTreeMap<String, f64>)],
+    anon: Anonymiser,
+) -> (Vec<Column>, Vec<Vec<String>>) {
+    let mut header = vec![
+        Column::text("rank"),
+        Column::text("name"),
+        Column::text("team"),
+    ];
+    header.extend(rounds.iter().map(|(round, _)| Column::number(round)));
+    if let Some(first) = standings.first() {
+        header.extend(first.metrics.iter().map(|m| Column::number(&m.metric)));
+    }
+
+    let mut rows = Vec::new();
+    for standing in standings {
+        let url = standing.speaker.as_deref().unwrap_or_default();
+        let Some((speaker, team)) = teams.iter().find_map(|team| {
+            team.speakers
+                .iter()
+                .find(|speaker| speaker.url == url)
+                .map(|speaker| (speaker, team))
+        }) else {
+            continue;
+        };
+
+        let mut row = vec![
+            standing
+                .rank
+                .map(|rank| format_rank(rank as usize, standing.tied))
+                .unwrap_or_default(),
+            anon.name("Speaker", &speaker.url, &speaker.name),
+            anon.name("Team", &team.url, &team.short_name),
+        ];
+        row.extend(rounds.iter().map(|(_, scores)| {
+            scores
+                .get(url)
+                .map(|score| score.to_string())
+                .unwrap_or_default()
+        }));
+        row.extend(
+            standing
+                .metrics
+                .iter()
+                .map(|m| m.value.map(|v| v.to_string()).unwrap_or_default()),
+        );
+        rows.push(row);
+    }
+
+    (header, rows)
+}
+
+/// Exports the speaker tab with each speaker's substantive score in every
+/// preliminary round (taken from the confirmed ballots), so that the tab's
+/// totals, averages, tie-breaks and dropped rounds can be checked by hand.
+pub async fn export_speaker_rounds(auth: Auth, to: &str, format: TableFormat, anon: Anonymiser) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (standings, teams, mut rounds, substantive) = tokio::join!(
+        get_speaker_standings(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_rounds(&auth, manager.clone()),
+        get_preference(&auth, "debate_rules__substantive_speakers", manager.clone()),
+    );
+    rounds.sort_by_key(|round| round.seq);
+    let substantive_speakers = substantive
+        .as_u64()
+        .or_else(|| substantive.as_str().and_then(|n| n.parse().ok()))
+        .map(|n| n as usize)
+        .unwrap_or(usize::MAX);
+
+    let mut round_scores = Vec::new();
+    for round in rounds.iter().filter(|round| {
+        // elimination rounds do not count towards the speaker tab
+        serde_json::to_value(round).unwrap()["stage"].as_str() == Some("P")
+            && matches!(round.draw_status, Some(DrawStatusEnum::R))
+    }) {
+        let mut scores = BTreeMap::new();
+        for pairing in pairings_of_round(&auth, round, manager.clone()).await {
+            for ballot in get_ballots(&auth, &pairing, manager.clone()).await {
+                if ballot["confirmed"].as_bool() != Some(true) {
+                    continue;
+                }
+                let sheets = ballot["result"]["sheets"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                scores.extend(speech_scores(&sheets, substantive_speakers));
+            }
+        }
+        round_scores.push((round.abbreviation.as_str().to_string(), scores));
+    }
+
+    let (header, rows) = speaker_rounds_table(&standings, &teams, &round_scores, anon);
+
+    write_table(to, format, &header, &rows);
+    info!(
+        "Saved the scores of {} speakers in {} rounds into {:?} file {}",
+        rows.len(),
+        round_scores.len(),
+        format,
+        to
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_rank_standings() {
+    let standing = |total: f64| Standing {
+        team: None,
+        speaker: None,
+        rank: None,
+        tied: false,
+        metrics: vec![Metric {
+            metric: "total".to_string(),
+            value: Some(total),
+        }],
+    };
+
+    let standings = vec![
+        standing(160.0),
+        standing(155.0),
+        standing(155.0),
+        standing(150.0),
+    ];
+
+    assert_eq!(
+        rank_standings(&standings),
+        vec![(1, false), (2, true), (2, true), (4, false)]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_explain_standings() {
+    let standing = |wins: f64, speaks: f64| Standing {
+        team: None,
+        speaker: None,
+        rank: None,
+        tied: false,
+        metrics: vec![
+            Metric {
+                metric: "wins".to_string(),
+                value: Some(wins),
+            },
+            Metric {
+                metric: "speaks".to_string(),
+                value: Some(speaks),
+            },
+        ],
+    };
+
+    let standings = vec![
+        standing(5.0, 400.0),
+        standing(4.0, 410.0),
+        standing(4.0, 405.5),
+        standing(4.0, 405.5),
+    ];
+
+    assert_eq!(
+        explain_standings(&standings),
+        vec![
+            "".to_string(),
+            "wins: 5 vs 4".to_string(),
+            "speaks: 410 vs 405.5".to_string(),
+            "tied on all metrics".to_string(),
+        ]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_speech_scores() {
+    let sheet = |a1: f64, a2: f64| {
+        serde_json::json!({
+            "teams": [
+                {
+                    "side": "aff",
+                    "speeches": [
+                        { "speaker": "a1", "score": a1 },
+                        { "speaker": "a2", "score": a2 },
+                        { "speaker": "a1", "score": 37.0 },
+                    ]
+                },
+                {
+                    "side": "neg",
+                    "speeches": [
+                        { "speaker": "n1", "score": 70.0 },
+                        { "speaker": "n1", "score": 71.0, "ghost": true },
+                    ]
+                }
+            ]
+        })
+    };
+
+    let scores = speech_scores(&[sheet(75.0, 72.0), sheet(76.0, 72.0)], 2);
+    assert_eq!(scores["a1"], 75.5);
+    assert_eq!(scores["a2"], 72.0);
+    assert_eq!(scores["n1"], 70.0);
+    assert_eq!(scores.len(), 3);
+}
@@ -0,0 +1,303 @@
+//! Suggesting judge moves which strengthen the panels of debates with live
+//! teams (teams which may or may not break), by swapping judges with the
+//! panels of debates in which no team is live.
+
+use std::{collections::HashSet, process::exit};
+
+use tabbycat_api::types::{Adjudicator, RoundPairing, Venue};
+use tracing::{error, warn};
+
+use crate::{
+    Auth,
+    api_utils::{get_judges, get_round_and_pairings, get_rounds, get_teams, get_venues},
+    break_live::{is_prelim, live_teams, remaining_prelims},
+    conflicts::Conflicts,
+    request_manager::RequestManager,
+};
+
+/// A voting judge (the chair or a panellist) on a panel.
+#[derive(Debug, Clone)]
+struct Seat {
+    url: String,
+    id: i64,
+    name: String,
+    score: f64,
+    chair: bool,
+}
+
+/// A debate in the round being allocated.
+#[derive(Debug, Clone)]
+struct Debate {
+    room: String,
+    /// The URLs of the teams in the debate.
+    teams: Vec<String>,
+    /// How many of the teams are live.
+    live: usize,
+    judges: Vec<Seat>,
+}
+
+impl Debate {
+    fn strength(&self) -> Option<f64> {
+        (!self.judges.is_empty()).then(|| {
+            self.judges.iter().map(|judge| judge.score).sum::<f64>() / self.judges.len() as f64
+        })
+    }
+
+    /// Whether `judge` can take the seat of `replacing` on this panel
+    /// without conflicting with a team or one of the other judges.
+    fn can_seat(&self, judge: &Seat, replacing: &Seat, conflicts: &Conflicts) -> bool {
+        !self
+            .teams
+            .iter()
+            .any(|team| conflicts.judge_team(&judge.url, team))
+            && !self
+                .judges
+                .iter()
+                .filter(|other| other.url != replacing.url)
+                .any(|other| conflicts.judge_judge(&judge.url, &other.url))
+    }
+}
+
+/// A swap of a judge on the panel of a live debate with a stronger judge (in
+/// the same role) from a debate in which no team is live.
+#[derive(Debug, Clone)]
+struct Move {
+    live_debate: usize,
+    dead_debate: usize,
+    weaker: Seat,
+    stronger: Seat,
+}
+
+/// Finds the swap which most improves the panel of a live debate (weighted by
+/// the number of live teams in it), among judges who have not been moved yet.
+fn best_move(debates: &[Debate], conflicts: &Conflicts, moved: &HashSet<String>) -> Option<Move> {
+    let mut best: Option<(f64, Move)> = None;
+    for (i, live) in debates.iter().enumerate().filter(|(_, d)| d.live > 0) {
+        for (j, dead) in debates.iter().enumerate().filter(|(_, d)| d.live == 0) {
+            for weaker in live.judges.iter().filter(|seat| !moved.contains(&seat.url)) {
+                for stronger in dead.judges.iter().filter(|seat| {
+                    !moved.contains(&seat.url)
+                        && seat.chair == weaker.chair
+                        && seat.score > weaker.score
+                }) {
+                    if !live.can_seat(stronger, weaker, conflicts)
+                        || !dead.can_seat(weaker, stronger, conflicts)
+                    {
+                        continue;
+                    }
+                    let gain = (stronger.score - weaker.score) * live.live as f64
+                        / live.judges.len() as f64;
+                    if best.as_ref().is_none_or(|(best_gain, _)| gain > *best_gain) {
+                        best = Some((
+                            gain,
+                            Move {
+                                live_debate: i,
+                                dead_debate: j,
+                                weaker: weaker.clone(),
+                                stronger: stronger.clone(),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    best.map(|(_, best)| best)
+}
+
+/// Greedily suggests (at most `max_moves`) swaps, each of which makes the
+/// biggest remaining improvement to the panels of the live debates. Each
+/// judge is moved at most once, and `debates` is updated as though the swaps
+/// had been made.
+fn suggest(debates: &mut [Debate], conflicts: &Conflicts, max_moves: usize) -> Vec<Move> {
+    let mut moves = Vec::new();
+    let mut moved = HashSet::new();
+    while moves.len() < max_moves {
+        let Some(best) = best_move(debates, conflicts, &moved) else {
+            break;
+        };
+        for (debate, from, to) in [
+            (best.live_debate, &best.weaker, &best.stronger),
+            (best.dead_debate, &best.stronger, &best.weaker),
+        ] {
+            let seat = debates[debate]
+                .judges
+                .iter_mut()
+                .find(|seat| seat.url == from.url)
+                .unwrap();
+            *seat = to.clone();
+        }
+        moved.insert(best.weaker.url.clone());
+        moved.insert(best.stronger.url.clone());
+        moves.push(best);
+    }
+    moves
+}
+
+/// The mean panel score of the debates with a live team.
+fn mean_live_strength(debates: &[Debate]) -> f64 {
+    let strengths = debates
+        .iter()
+        .filter(|debate| debate.live > 0)
+        .filter_map(Debate::strength)
+        .collect::<Vec<_>>();
+    strengths.iter().sum::<f64>() / strengths.len().max(1) as f64
+}
+
+fn debates_of(
+    pairings: &[RoundPairing],
+    judges: &[Adjudicator],
+    venues: &[Venue],
+    live: &HashSet<String>,
+) -> Vec<Debate> {
+    let seat = |url: &String, chair: bool| {
+        let judge = judges.iter().find(|judge| &judge.url == url)?;
+        Some(Seat {
+            url: url.clone(),
+            id: judge.id,
+            name: judge.name.clone(),
+            score: serde_json::to_value(judge).unwrap()["base_score"]
+                .as_f64()
+                .unwrap_or(0.0),
+            chair,
+        })
+    };
+
+    pairings
+        .iter()
+        .map(|pairing| {
+            let teams = pairing
+                .teams
+                .iter()
+                .map(|team| team.team.clone())
+                .collect::<Vec<_>>();
+            Debate {
+                room: pairing
+                    .venue
+                    .as_ref()
+                    .and_then(|url| venues.iter().find(|venue| &venue.url == url))
+                    .map(|venue| venue.name.as_str().to_string())
+                    .unwrap_or_else(|| format!("debate {}", pairing.id)),
+                live: teams.iter().filter(|team| live.contains(*team)).count(),
+                teams,
+                judges: pairing
+                    .adjudicators
+                    .iter()
+                    .flat_map(|panel| {
+                        panel
+                            .chair
+                            .iter()
+                            .filter_map(|url| seat(url, true))
+                            .chain(panel.panellists.iter().filter_map(|url| seat(url, false)))
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Suggests (at most `max_moves`) swaps of judges between debates in which no
+/// team can still change whether it breaks and debates with a live team, which
+/// give the live debates stronger panels (by judges' base scores) without
+/// creating any conflicts. Chairs are only swapped with chairs, and panellists
+/// with panellists.
+///
+/// The swaps are printed as `tabbycat draw-swap` commands (one per line, with
+/// the judges' names in a trailing comment), so that they can be reviewed and
+/// then run as a batch, e.g. by piping them into `sh`.
+pub async fn suggest_moves(auth: Auth, round: &str, max_moves: usize, break_size: Option<usize>) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((round, pairings), rounds, teams, judges, venues) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_rounds(&auth, manager.clone()),
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+    );
+
+    if !is_prelim(&round) {
+        error!(
+            "{} is not a preliminary round, so no team is live.",
+            round.name.as_str()
+        );
+        exit(1);
+    }
+    let remaining = remaining_prelims(&rounds, Some(&round));
+
+    let live = live_teams(&auth, &teams, remaining, break_size, manager.clone()).await;
+    let mut debates = debates_of(&pairings, &judges, &venues, &live);
+    if debates.iter().all(|debate| debate.judges.is_empty()) {
+        error!("No judges have been allocated in {}.", round.name.as_str());
+        exit(1);
+    }
+    if debates.iter().all(|debate| debate.live > 0) {
+        warn!("Every debate has a live team, so there are no judges to move.");
+    }
+
+    let before = mean_live_strength(&debates);
+    let conflicts = Conflicts::new(&teams, &judges);
+    let moves = suggest(&mut debates, &conflicts, max_moves);
+    let after = mean_live_strength(&debates);
+
+    println!(
+        "# {} move(s) for {}: the mean panel score of the {} live debate(s) goes from {before:.2} \
+        to {after:.2}",
+        moves.len(),
+        round.name.as_str(),
+        debates.iter().filter(|debate| debate.live > 0).count(),
+    );
+    for m in &moves {
+        println!(
+            "tabbycat draw-swap {} {} {}  # {} ({}, {:.1}) <-> {} ({}, {:.1})",
+            round.seq,
+            m.weaker.id,
+            m.stronger.id,
+            m.weaker.name,
+            debates[m.live_debate].room,
+            m.weaker.score,
+            m.stronger.name,
+            debates[m.dead_debate].room,
+            m.stronger.score,
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_suggest() {
+    let seat = |id: i64, score: f64, chair: bool| Seat {
+        url: format!("/adjudicators/{id}"),
+        id,
+        name: format!("Judge {id}"),
+        score,
+        chair,
+    };
+    let debate = |live: usize, judges: Vec<Seat>| Debate {
+        room: String::new(),
+        teams: Vec::new(),
+        live,
+        judges,
+    };
+    let mut debates = vec![
+        debate(2, vec![seat(1, 2.0, true), seat(2, 3.0, false)]),
+        debate(0, vec![seat(3, 4.0, true), seat(4, 5.0, false)]),
+        debate(1, vec![seat(5, 4.5, true)]),
+    ];
+
+    let moves = suggest(&mut debates, &Conflicts::default(), 10);
+    // the first debate takes both judges of the dead debate; the chair of
+    // the third debate is already stronger than the one it could swap with
+    assert_eq!(moves.len(), 2);
+    let ids = |debate: &Debate| debate.judges.iter().map(|seat| seat.id).collect::<Vec<_>>();
+    assert_eq!(ids(&debates[0]), vec![3, 4]);
+    assert_eq!(ids(&debates[1]), vec![1, 2]);
+    assert_eq!(ids(&debates[2]), vec![5]);
+
+    // no more than `max_moves` are suggested
+    let mut debates = vec![
+        debate(1, vec![seat(1, 2.0, true), seat(2, 3.0, false)]),
+        debate(0, vec![seat(3, 4.0, true), seat(4, 5.0, false)]),
+    ];
+    assert_eq!(suggest(&mut debates, &Conflicts::default(), 1).len(), 1);
+}
@@ -0,0 +1,162 @@
+use std::{process::exit, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, BooleanArray, Float64Array, StringArray},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+use parquet::arrow::ArrowWriter;
+use tracing::error;
+
+/// The file formats which tabular exports can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    Csv,
+    Parquet,
+}
+
+impl TableFormat {
+    pub fn parse(format: &str) -> TableFormat {
+        match format.to_ascii_lowercase().as_str() {
+            "csv" => TableFormat::Csv,
+            "parquet" => TableFormat::Parquet,
+            _ => {
+                error!("Invalid format `{format}` expected either csv or parquet");
+                exit(1);
+            }
+        }
+    }
+}
+
+/// The type of the values in a column of an exported table. CSV files are
+/// untyped, but Parquet columns are stored with this type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Text,
+    Number,
+    Bool,
+}
+
+/// A column of an exported table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub kind: ColumnType,
+}
+
+impl Column {
+    pub fn text(name: impl Into<String>) -> Column {
+        Column {
+            name: name.into(),
+            kind: ColumnType::Text,
+        }
+    }
+
+    pub fn number(name: impl Into<String>) -> Column {
+        Column {
+            name: name.into(),
+            kind: ColumnType::Number,
+        }
+    }
+
+    pub fn bool(name: impl Into<String>) -> Column {
+        Column {
+            name: name.into(),
+            kind: ColumnType::Bool,
+        }
+    }
+}
+
+/// The names of `columns`, e.g. for the header of a table shown in the
+/// terminal.
+pub fn column_names(columns: &[Column]) -> Vec<String> {
+    columns.iter().map(|column| column.name.clone()).collect()
+}
+
+/// Writes a table (its columns and rows of cells) to `path`. When writing
+/// Parquet, each column is stored with the type given by the exporter, and
+/// empty cells are stored as nulls.
+pub fn write_table(path: &str, format: TableFormat, columns: &[Column], rows: &[Vec<String>]) {
+    match format {
+        TableFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path).unwrap();
+            writer.write_record(column_names(columns)).unwrap();
+            for row in rows {
+                writer.write_record(row).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        TableFormat::Parquet => {
+            let (fields, arrays): (Vec<_>, Vec<_>) = columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| {
+                    let cells = rows
+                        .iter()
+                        .map(|row| row.get(i).map(|cell| cell.as_str()).unwrap_or_default())
+                        .collect::<Vec<_>>();
+                    let (data_type, array) = typed_column(column.kind, &cells);
+                    (Field::new(&column.name, data_type, true), array)
+                })
+                .unzip();
+
+            let schema = Arc::new(Schema::new(fields));
+            let batch = RecordBatch::try_new(schema.clone(), arrays).unwrap();
+
+            let file = std::fs::File::create(path).unwrap();
+            let mut writer = ArrowWriter::try_new(file, schema, None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+        }
+    }
+}
+
+/// Converts the cells of a column to an Arrow array of the given type. Cells
+/// which are empty (or not of that type) are stored as nulls.
+fn typed_column(kind: ColumnType, cells: &[&str]) -> (DataType, ArrayRef) {
+    match kind {
+        ColumnType::Number => {
+            let values = cells
+                .iter()
+                .map(|cell| cell.parse::<f64>().ok())
+                .collect::<Float64Array>();
+            (DataType::Float64, Arc::new(values))
+        }
+        ColumnType::Bool => {
+            let values = cells
+                .iter()
+                .map(|cell| match *cell {
+                    "true" => Some(true),
+                    "false" => Some(false),
+                    _ => None,
+                })
+                .collect::<BooleanArray>();
+            (DataType::Boolean, Arc::new(values))
+        }
+        ColumnType::Text => {
+            let values = cells
+                .iter()
+                .map(|cell| (!cell.is_empty()).then_some(*cell))
+                .collect::<StringArray>();
+            (DataType::Utf8, Arc::new(values))
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_typed_column() {
+    use arrow::array::Array;
+
+    let (data_type, array) = typed_column(ColumnType::Number, &["1", "", "2.5"]);
+    assert_eq!(data_type, DataType::Float64);
+    assert_eq!(array.null_count(), 1);
+    assert_eq!(
+        typed_column(ColumnType::Bool, &["true", "false", ""]).0,
+        DataType::Boolean
+    );
+    // e.g. room names which happen to be numbers are kept as text
+    let (data_type, array) = typed_column(ColumnType::Text, &["101", "", "true"]);
+    assert_eq!(data_type, DataType::Utf8);
+    assert_eq!(array.null_count(), 1);
+}
@@ -0,0 +1,74 @@
+//! Fitting tables to the width of the terminal, and showing long output
+//! through a pager.
+
+use std::{
+    io::{IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+use comfy_table::Table;
+use tracing::warn;
+
+/// The pager used with `--pager` if `$PAGER` is not set.
+const DEFAULT_PAGER: &str = "less -R";
+
+/// How tables are printed (set by `--width` and `--pager`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableOutput {
+    /// The width to draw tables at. If not given, tables are fitted to the
+    /// terminal (or to `$COLUMNS`, if the output is not a terminal).
+    pub width: Option<u16>,
+    /// Show the output through `$PAGER`.
+    pub pager: bool,
+}
+
+impl TableOutput {
+    fn width(&self) -> Option<u16> {
+        self.width.or_else(|| {
+            // (when the output is a terminal, `comfy_table` finds its width)
+            if std::io::stdout().is_terminal() {
+                return None;
+            }
+            std::env::var("COLUMNS").ok()?.trim().parse().ok()
+        })
+    }
+
+    /// Fits `table` to the output.
+    pub fn fit(&self, table: &mut Table) {
+        if let Some(width) = self.width() {
+            table.set_width(width);
+        }
+    }
+
+    /// Prints `text`, through the pager if one was asked for (and the output
+    /// is a terminal).
+    pub fn show(&self, text: &str) {
+        if !self.pager || !std::io::stdout().is_terminal() {
+            print!("{text}");
+            return;
+        }
+
+        let pager = std::env::var("PAGER")
+            .ok()
+            .filter(|pager| !pager.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+        let mut words = pager.split_whitespace();
+        let program = words.next().unwrap();
+        let child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("Could not run the pager `{pager}` ({e}), so printing the output instead");
+                print!("{text}");
+                return;
+            }
+        };
+
+        // the pager closes its input if it is quit before reaching the end
+        let _ = child.stdin.take().unwrap().write_all(text.as_bytes());
+        let _ = child.wait();
+    }
+}
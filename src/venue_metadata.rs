@@ -0,0 +1,91 @@
+use std::{collections::BTreeMap, process::exit};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::names::names_match;
+
+/// The file (in the current working directory) in which information about
+/// rooms which Tabbycat does not store is kept.
+pub const VENUE_METADATA_FILE: &str = "venue-metadata.json";
+
+/// Information about a single room which Tabbycat has no field for.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct VenueMetadata {
+    /// The number of people the room can hold.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<u32>,
+}
+
+/// The metadata of every room, by room name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct VenueMetadataStore {
+    rooms: BTreeMap<String, VenueMetadata>,
+}
+
+impl VenueMetadataStore {
+    /// Loads the store from the working directory, or returns an empty store
+    /// if it does not exist yet.
+    pub fn load() -> VenueMetadataStore {
+        let text = match std::fs::read_to_string(VENUE_METADATA_FILE) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return VenueMetadataStore::default();
+            }
+            Err(e) => {
+                error!("Could not read {VENUE_METADATA_FILE}: {e}");
+                exit(1)
+            }
+        };
+        serde_json::from_str(&text).unwrap_or_else(|e| {
+            error!("Your {VENUE_METADATA_FILE} file is malformed: {e}");
+            exit(1)
+        })
+    }
+
+    pub fn save(&self) {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        if let Err(e) = std::fs::write(VENUE_METADATA_FILE, json) {
+            error!("Could not write {VENUE_METADATA_FILE}: {e}");
+            exit(1);
+        }
+    }
+
+    /// The metadata of the room called `name` (compared using
+    /// [`names_match`]).
+    pub fn get(&self, name: &str) -> Option<&VenueMetadata> {
+        self.rooms
+            .iter()
+            .find(|(room, _)| names_match(room, name))
+            .map(|(_, metadata)| metadata)
+    }
+
+    /// Returns the metadata of the room called `name`, adding it if it is not
+    /// in the store yet.
+    pub fn entry(&mut self, name: &str) -> &mut VenueMetadata {
+        let key = self
+            .rooms
+            .keys()
+            .find(|room| names_match(room, name))
+            .cloned()
+            .unwrap_or_else(|| name.trim().to_string());
+        self.rooms.entry(key).or_default()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_venue_metadata_store() {
+    let mut store: VenueMetadataStore =
+        serde_json::from_str(r#"{ "Room 1": { "capacity": 20 } }"#).unwrap();
+    assert_eq!(store.get(" room 1").unwrap().capacity, Some(20));
+    assert!(store.get("Room 2").is_none());
+
+    store.entry("ROOM 1").capacity = Some(25);
+    store.entry("Hall ").capacity = Some(300);
+    assert_eq!(
+        serde_json::to_value(&store).unwrap(),
+        serde_json::json!({ "Hall": { "capacity": 300 }, "Room 1": { "capacity": 25 } })
+    );
+}
@@ -1,45 +1,140 @@
-use std::process::exit;
+use std::{
+    collections::{HashMap, HashSet},
+    process::exit,
+};
 
-use comfy_table::{Cell, Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
+use comfy_table::{Cell, Color, Table, modifiers::UTF8_ROUND_CORNERS, presets::UTF8_FULL};
 use itertools::Itertools;
+use serde_json::Value;
+use tabbycat_api::types::{Adjudicator, RoundPairing, Team, Venue};
 
 use crate::{
     Auth,
-    api_utils::{get_judges, get_round, get_teams},
+    api_utils::{
+        get_judges, get_round_and_pairings, get_rounds, get_teams, get_venues, pairing_in_room,
+        pairings_of_round,
+    },
+    conflicts::Conflicts,
+    debate_flags::DebateFlags,
     dispatch_req::json_of_resp,
+    i18n::{tr, tr_args},
+    names::name_contains,
     request_manager::RequestManager,
+    terminal::TableOutput,
 };
 
-pub async fn view_draw(round: &str, auth: Auth) {
-    let manager = RequestManager::new(&auth.api_key);
+/// Restricts which debates `view-draw` shows. A debate is shown if it matches
+/// every filter which is set.
+#[derive(Debug, Clone, Default)]
+pub struct DrawFilter {
+    /// The name of the room, or the ID of the debate.
+    pub room: Option<String>,
+    /// Part of the name of one of the judges.
+    pub judge: Option<String>,
+    /// Part of the (short or full) name of one of the teams.
+    pub team: Option<String>,
+}
 
-    let round = get_round(round, &auth, manager.clone()).await;
+impl DrawFilter {
+    fn matches(
+        &self,
+        pairing: &RoundPairing,
+        venues: &[Venue],
+        teams: &[Team],
+        judges: &[Adjudicator],
+    ) -> bool {
+        let room = self.room.as_ref().is_none_or(|room| {
+            pairing_in_room(std::slice::from_ref(pairing), venues, room).is_some()
+        });
+        let judge = self.judge.as_ref().is_none_or(|query| {
+            panel_urls(pairing)
+                .into_iter()
+                .filter_map(|url| judges.iter().find(|judge| &judge.url == url))
+                .any(|judge| name_contains(&judge.name, query))
+        });
+        let team = self.team.as_ref().is_none_or(|query| {
+            pairing
+                .teams
+                .iter()
+                .filter_map(|team| teams.iter().find(|t| t.url == team.team))
+                .any(|team| {
+                    name_contains(&team.short_name, query) || name_contains(&team.long_name, query)
+                })
+        });
+        room && judge && team
+    }
+}
 
-    let teams_in_debate: tabbycat_api::types::Preference = json_of_resp(
-        manager
-            .send_request(|| {
-                let url = format!(
-                    "{}/api/v1/tournaments/{}/preferences/{}",
-                    auth.tabbycat_url, auth.tournament_slug, "debate_rules__teams_in_debate"
-                );
-                manager.client.get(url).build().unwrap()
-            })
-            .await,
-    )
-    .await;
-    let teams_in_debate = teams_in_debate.value.as_i64().unwrap();
+/// The URLs of the judges on a panel (the chair, panellists and trainees).
+fn panel_urls(pairing: &RoundPairing) -> Vec<&String> {
+    pairing
+        .adjudicators
+        .iter()
+        .flat_map(|panel| {
+            panel
+                .chair
+                .iter()
+                .chain(&panel.panellists)
+                .chain(&panel.trainees)
+        })
+        .collect()
+}
 
-    let pairings: Vec<tabbycat_api::types::RoundPairing> = json_of_resp(
-        manager
-            .send_request(|| {
-                let url = &round.links.pairing;
-                manager.client.get(url).build().unwrap()
-            })
-            .await,
-    )
-    .await;
+/// The strength of a panel: the mean score of its voting judges.
+fn panel_strength(scores: &[f64]) -> Option<f64> {
+    (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64)
+}
 
-    let teams = get_teams(&auth, manager.clone()).await;
+/// Shows a JSON value from the API in a table cell.
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(display).join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// One line of a panel, e.g. `Sam Smith (c, id 12)`. The role and ID are
+/// joined with non-breaking spaces, so that if the line has to be wrapped they
+/// stay together (with the end of the name).
+fn judge_line(name: &str, role: Option<char>, id: impl std::fmt::Display) -> String {
+    let tag = match role {
+        Some(role) => format!("({role},\u{a0}id\u{a0}{id})"),
+        None => format!("(id\u{a0}{id})"),
+    };
+    format!("{name}\u{a0}{tag}")
+}
+
+pub async fn view_draw(
+    round: &str,
+    auth: Auth,
+    filter: DrawFilter,
+    detail: bool,
+    output: TableOutput,
+) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let ((round, pairings), rounds, teams_in_debate, teams, judges, venues) = tokio::join!(
+        get_round_and_pairings(round, &auth, manager.clone()),
+        get_rounds(&auth, manager.clone()),
+        async {
+            let resp = manager
+                .send_request(|| {
+                    let url = format!(
+                        "{}/api/v1/tournaments/{}/preferences/{}",
+                        auth.tabbycat_url, auth.tournament_slug, "debate_rules__teams_in_debate"
+                    );
+                    manager.client.get(url).build().unwrap()
+                })
+                .await;
+            json_of_resp::<tabbycat_api::types::Preference>(resp).await
+        },
+        get_teams(&auth, manager.clone()),
+        get_judges(&auth, manager.clone()),
+        get_venues(&auth, manager.clone()),
+    );
+    let teams_in_debate = teams_in_debate.value.as_i64().unwrap();
 
     let name_of_team = |url: &str| -> String {
         teams
@@ -50,35 +145,70 @@ pub async fn view_draw(round: &str, auth: Auth) {
             .clone()
     };
 
-    let judges = get_judges(&auth, manager).await;
-
     let name_of_judge = |url: &str| -> tabbycat_api::types::Adjudicator {
         judges.iter().find(|team| team.url == url).unwrap().clone()
     };
 
     if pairings.is_empty() {
-        println!("No draw for this round");
+        println!("{}", tr("draw-none"));
 
         return;
     }
 
+    let pairings = pairings
+        .iter()
+        .filter(|pairing| filter.matches(pairing, &venues, &teams, &judges))
+        .sorted_by_key(|pairing| pairing.id)
+        .collect::<Vec<_>>();
+    if pairings.is_empty() {
+        println!("No debates in this round match");
+
+        return;
+    }
+
+    let conflicts = Conflicts::new(&teams, &judges);
+    let flags = DebateFlags::load(&auth);
+    // the teams each judge has judged in earlier rounds
+    let mut judged: HashMap<String, HashSet<String>> = HashMap::new();
+    for earlier in rounds.iter().filter(|earlier| earlier.seq < round.seq) {
+        for pairing in pairings_of_round(&auth, earlier, manager.clone()).await {
+            for judge in panel_urls(&pairing) {
+                judged
+                    .entry(judge.clone())
+                    .or_default()
+                    .extend(pairing.teams.iter().map(|team| team.team.clone()));
+            }
+        }
+    }
+
+    let room_of = |pairing: &RoundPairing| {
+        pairing
+            .venue
+            .as_ref()
+            .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+            .map(|venue| venue.name.to_string())
+            .unwrap_or_default()
+    };
+
     let headers = {
         let mut headers = Vec::new();
-        headers.push("id");
-        headers.push("Nb");
+        headers.push(tr("draw-id"));
+        headers.push(tr("room"));
+        headers.push(tr("draw-nb"));
         if teams_in_debate == 2 {
-            headers.push("Prop");
-            headers.push("Opp");
+            headers.push(tr("draw-prop"));
+            headers.push(tr("draw-opp"));
         } else if teams_in_debate == 4 {
-            headers.push("OG");
-            headers.push("OO");
-            headers.push("CG");
-            headers.push("CO");
+            headers.push(tr("draw-og"));
+            headers.push(tr("draw-oo"));
+            headers.push(tr("draw-cg"));
+            headers.push(tr("draw-co"));
         } else {
             println!("Error: bad number of teams (should be 2 or 4, not {teams_in_debate})!");
             exit(1);
         }
-        headers.push("Panel");
+        headers.push(tr("draw-panel"));
+        headers.push(tr("draw-strength"));
         headers
     };
 
@@ -89,14 +219,29 @@ pub async fn view_draw(round: &str, auth: Auth) {
         .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
         .apply_modifier(UTF8_ROUND_CORNERS)
         .set_header(headers);
+    output.fit(&mut table);
 
-    for pairing in pairings.iter().sorted_by_key(|pairing| pairing.id) {
+    for pairing in &pairings {
         let mut cells = Vec::new();
 
         cells.push(Cell::new(pairing.id));
+        // flags set with `draw flag` are shown under the name of the room
+        let room_cell_contents = std::iter::once(room_of(pairing))
+            .chain(
+                flags
+                    .get(pairing.id)
+                    .iter()
+                    .map(|flag| format!("\u{2691}\u{a0}{flag}")),
+            )
+            .join("\n");
+        cells.push(if flags.get(pairing.id).is_empty() {
+            Cell::new(room_cell_contents)
+        } else {
+            Cell::new(room_cell_contents).fg(Color::Magenta)
+        });
 
         cells.push(if matches!(pairing.sides_confirmed, Some(false) | None) {
-            Cell::new("Sides not confirmed!".to_string()).bg(comfy_table::Color::Yellow)
+            Cell::new(tr("draw-sides-not-confirmed")).bg(comfy_table::Color::Yellow)
         } else {
             Cell::new(String::new())
         });
@@ -120,7 +265,7 @@ pub async fn view_draw(round: &str, auth: Auth) {
             for team in &pairing.teams {
                 match team.side {
                     Some(tabbycat_api::types::DebateTeamSide::Variant1(side)) => {
-                        cells[2 + match side {
+                        cells[3 + match side {
                             tabbycat_api::types::DebateTeamSideVariant1::Aff => 0,
                             tabbycat_api::types::DebateTeamSideVariant1::Neg => 1,
                             tabbycat_api::types::DebateTeamSideVariant1::Cg => 2,
@@ -133,33 +278,170 @@ pub async fn view_draw(round: &str, auth: Auth) {
             }
         }
 
-        let mut judge_cell_contents = String::new();
-        if let Some(judges) = &pairing.adjudicators {
-            let mut prev = false;
-            if let Some(chair) = &judges.chair {
-                let judge = (name_of_judge)(chair);
-                judge_cell_contents += &format!("{} (c, id {})", judge.name, judge.id);
-                prev = true;
+        // judges are marked if they conflict with a team or judge in the
+        // room, or have judged one of the teams before
+        let panel = panel_urls(pairing);
+        let mut conflicted = false;
+        let mut seen_before = false;
+        let mut notes = |judge: &str| {
+            let clashes = pairing
+                .teams
+                .iter()
+                .filter(|team| conflicts.judge_team(judge, &team.team))
+                .map(|team| (name_of_team)(&team.team))
+                .chain(
+                    panel
+                        .iter()
+                        .filter(|other| other.as_str() != judge)
+                        .filter(|other| conflicts.judge_judge(judge, other))
+                        .map(|other| (name_of_judge)(other).name),
+                )
+                .collect::<Vec<_>>();
+            let seen = pairing
+                .teams
+                .iter()
+                .filter(|team| {
+                    judged
+                        .get(judge)
+                        .is_some_and(|teams| teams.contains(&team.team))
+                })
+                .map(|team| (name_of_team)(&team.team))
+                .collect::<Vec<_>>();
+
+            let mut notes = String::new();
+            if !clashes.is_empty() {
+                conflicted = true;
+                notes += &format!(
+                    "\n  {}",
+                    tr_args(
+                        "draw-conflicts-with",
+                        [("names", clashes.join(", ").into())]
+                    )
+                );
             }
-            for panelist in &judges.panellists {
-                let judge = (name_of_judge)(panelist);
-                if prev {
-                    judge_cell_contents += "\n----\n";
-                }
-                judge_cell_contents += &format!("{} (id {})", judge.name, judge.id);
+            if !seen.is_empty() {
+                seen_before = true;
+                notes += &format!(
+                    "\n  {}",
+                    tr_args("draw-judged-before", [("names", seen.join(", ").into())])
+                );
             }
-            for trainee in &judges.trainees {
-                let judge = (name_of_judge)(trainee);
-                if prev {
-                    judge_cell_contents += "\n";
-                }
-                judge_cell_contents += &format!("{} (t, id {})", judge.name, judge.id);
+            notes
+        };
+
+        // one judge per line (with any notes on the lines below), so that
+        // large draws stay compact
+        let mut judge_lines = Vec::new();
+        if let Some(judges) = &pairing.adjudicators {
+            let roles = judges
+                .chair
+                .iter()
+                .map(|url| (url, Some('c')))
+                .chain(judges.panellists.iter().map(|url| (url, None)))
+                .chain(judges.trainees.iter().map(|url| (url, Some('t'))));
+            for (url, role) in roles {
+                let judge = (name_of_judge)(url);
+                judge_lines.push(format!(
+                    "{}{}",
+                    judge_line(&judge.name, role, judge.id),
+                    notes(url)
+                ));
             }
         }
-        cells.push(Cell::new(judge_cell_contents));
+        let judge_cell_contents = judge_lines.join("\n");
+        cells.push(if conflicted {
+            Cell::new(judge_cell_contents).fg(Color::Red)
+        } else if seen_before {
+            Cell::new(judge_cell_contents).fg(Color::Yellow)
+        } else {
+            Cell::new(judge_cell_contents)
+        });
+
+        let scores = pairing
+            .adjudicators
+            .iter()
+            .flat_map(|panel| panel.chair.iter().chain(&panel.panellists))
+            .filter_map(|url| {
+                serde_json::to_value((name_of_judge)(url)).unwrap()["base_score"].as_f64()
+            })
+            .collect::<Vec<_>>();
+        cells.push(Cell::new(
+            panel_strength(&scores)
+                .map(|strength| format!("{strength:.1}"))
+                .unwrap_or_default(),
+        ));
 
         table.add_row(cells);
     }
 
-    println!("{table}");
+    let mut out = format!("{table}\n");
+
+    if detail {
+        for pairing in &pairings {
+            let json = serde_json::to_value(pairing).unwrap();
+            let team_flags = pairing
+                .teams
+                .iter()
+                .zip(json["teams"].as_array().into_iter().flatten())
+                .map(|(team, team_json)| {
+                    format!(
+                        "{}: {}",
+                        (name_of_team)(&team.team),
+                        display(&team_json["flags"])
+                    )
+                })
+                .join("\n");
+
+            let mut table = Table::new();
+            table
+                .load_preset(UTF8_FULL)
+                .set_content_arrangement(comfy_table::ContentArrangement::Dynamic)
+                .apply_modifier(UTF8_ROUND_CORNERS)
+                .set_header(vec![
+                    tr_args("debate", [("id", pairing.id.into())]),
+                    room_of(pairing),
+                ]);
+            output.fit(&mut table);
+            for (field, value) in [
+                ("draw-bracket", display(&json["bracket"])),
+                ("draw-room-rank", display(&json["room_rank"])),
+                ("draw-importance", display(&json["importance"])),
+                ("draw-result-status", display(&json["result_status"])),
+                ("draw-sides-confirmed", display(&json["sides_confirmed"])),
+                (
+                    "draw-flags",
+                    json["flags"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(display)
+                        .chain(flags.get(pairing.id).iter().cloned())
+                        .join(", "),
+                ),
+                ("draw-team-flags", team_flags),
+            ] {
+                table.add_row(vec![tr(field), value]);
+            }
+            out += &format!("{table}\n");
+        }
+    }
+
+    output.show(&out);
+}
+
+#[cfg(test)]
+#[test]
+fn test_name_contains() {
+    assert!(name_contains("Alex Smith", "smith"));
+    assert!(name_contains("Alex Smith", " Alex S "));
+    assert!(!name_contains("Alex Smith", "Jones"));
+
+    assert_eq!(panel_strength(&[4.0, 3.0, 3.5]), Some(3.5));
+    assert_eq!(panel_strength(&[]), None);
+
+    assert_eq!(
+        judge_line("Sam de Smith", Some('c'), 12),
+        "Sam de Smith\u{a0}(c,\u{a0}id\u{a0}12)"
+    );
+    assert_eq!(judge_line("Kim", None, 3), "Kim\u{a0}(id\u{a0}3)");
 }
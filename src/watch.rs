@@ -0,0 +1,172 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use serde_json::json;
+use tracing::{error, info, warn};
+
+use crate::{
+    Auth,
+    api_utils::{
+        get_round, get_venues, try_get_ballots, try_get_feedbacks, try_get_judges,
+        try_pairings_of_round,
+    },
+    request_manager::RequestManager,
+};
+
+/// Posts a message to a Slack- or Discord-compatible webhook (Slack reads the
+/// `text` field, and Discord the `content` field).
+pub async fn notify(webhook: &str, message: &str) {
+    // note: this deliberately doesn't use `RequestManager`, which would attach
+    // the Tabbycat API key to the request
+    let res = reqwest::Client::new()
+        .post(webhook)
+        .json(&json!({
+            "text": message,
+            "content": message,
+        }))
+        .send()
+        .await;
+
+    match res {
+        Ok(res) if res.status().is_success() => (),
+        Ok(res) => error!(
+            "Webhook returned {}: {}",
+            res.status(),
+            res.text().await.unwrap_or_default()
+        ),
+        Err(e) => error!("Failed to send webhook notification: {e}"),
+    }
+}
+
+/// Watches for new ballots (in `round`, if provided) and feedback, posting a
+/// notification to `webhook` whenever something is submitted. Feedback with a
+/// score below `low_score` is additionally flagged. Runs until interrupted.
+pub async fn watch(
+    auth: Auth,
+    webhook: &str,
+    round: Option<String>,
+    low_score: Option<f64>,
+    interval: u64,
+) {
+    let manager = RequestManager::new(&auth.api_key);
+
+    let (round, venues) = tokio::join!(
+        async {
+            match round {
+                Some(round) => Some(get_round(&round, &auth, manager.clone()).await),
+                None => None,
+            }
+        },
+        get_venues(&auth, manager.clone()),
+    );
+
+    let mut seen_feedback: Option<HashSet<i64>> = None;
+    // the ballots seen in each debate (by its ID), which is only added once
+    // the ballots of the debate have been fetched successfully
+    let mut seen_ballots: HashMap<i64, HashSet<String>> = HashMap::new();
+
+    info!("Watching for new submissions (checking every {interval} seconds)");
+
+    loop {
+        let (feedbacks, judges) = tokio::join!(
+            try_get_feedbacks(&auth, manager.clone()),
+            try_get_judges(&auth, manager.clone())
+        );
+        // a failed request is tried again at the next poll
+        let fetched = match (feedbacks, judges) {
+            (Ok(feedbacks), Ok(judges)) => Some((feedbacks, judges)),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("Could not fetch the feedback (will retry): {e}");
+                None
+            }
+        };
+
+        let mut messages = Vec::new();
+
+        if let Some((feedbacks, judges)) = fetched {
+            let first_poll = seen_feedback.is_none();
+            let seen = seen_feedback.get_or_insert_with(HashSet::new);
+            for feedback in &feedbacks {
+                if !seen.insert(feedback.id) || first_poll {
+                    continue;
+                }
+
+                let judge = judges
+                    .iter()
+                    .find(|judge| judge.url == feedback.adjudicator)
+                    .map(|judge| judge.name.clone())
+                    .unwrap_or_else(|| feedback.adjudicator.clone());
+                let score = serde_json::to_value(feedback).unwrap()["score"].as_f64();
+
+                messages.push(format!(
+                    "New feedback on {judge}{}",
+                    score.map(|s| format!(" (score {s})")).unwrap_or_default()
+                ));
+                if let (Some(score), Some(low_score)) = (score, low_score)
+                    && score < low_score
+                {
+                    messages.push(format!(
+                        ":warning: Low feedback score for {judge}: {score} (threshold is \
+                        {low_score})"
+                    ));
+                }
+            }
+        }
+
+        if let Some(round) = &round {
+            let pairings = match try_pairings_of_round(round, manager.clone()).await {
+                Ok(pairings) => pairings,
+                Err(e) => {
+                    warn!(
+                        "Could not fetch the draw of {} (will retry): {e}",
+                        round.name.as_str()
+                    );
+                    Vec::new()
+                }
+            };
+
+            for pairing in &pairings {
+                // a failed request is tried again at the next poll
+                let ballots = match try_get_ballots(&auth, pairing, manager.clone()).await {
+                    Ok(ballots) => ballots,
+                    Err(e) => {
+                        warn!(
+                            "Could not fetch the ballots for debate {} (will retry): {e}",
+                            pairing.id
+                        );
+                        continue;
+                    }
+                };
+
+                let first_poll = !seen_ballots.contains_key(&pairing.id);
+                let seen = seen_ballots.entry(pairing.id).or_default();
+                let room = pairing
+                    .venue
+                    .as_ref()
+                    .and_then(|venue| venues.iter().find(|v| &v.url == venue))
+                    .map(|venue| venue.name.as_str().to_string())
+                    .unwrap_or_else(|| format!("debate {}", pairing.id));
+
+                for ballot in ballots {
+                    let key = ballot["url"]
+                        .as_str()
+                        .map(|url| url.to_string())
+                        .unwrap_or_else(|| ballot["id"].to_string());
+                    if !seen.insert(key) || first_poll {
+                        continue;
+                    }
+                    messages.push(format!("New ballot for {room} ({})", round.name.as_str()));
+                }
+            }
+        }
+
+        for message in messages {
+            info!("{message}");
+            notify(webhook, &message).await;
+        }
+
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
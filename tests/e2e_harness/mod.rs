@@ -0,0 +1,315 @@
+//! The harness for the end-to-end tests in `tests/`, which run the installed
+//! CLI against a Tabbycat instance started with Docker compose. It is only
+//! visible to the integration tests (the crate has no library to export it
+//! from), so new test files use it with `mod e2e_harness;`.
+
+// not every test uses every helper
+#![allow(dead_code)]
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::json;
+
+/// The address the Tabbycat instance started by [`start_instance`] listens on.
+pub const TABBYCAT_URL: &str = "http://localhost:8000";
+
+#[derive(Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Auth {
+    pub tabbycat_url: String,
+    pub tournament_slug: String,
+    pub api_key: String,
+}
+
+/// Sets up logging for the tests (this may only be called once).
+pub fn init_tracing() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_timer(tracing_subscriber::fmt::time::uptime())
+        .with_target(false)
+        .with_ansi(true)
+        .init();
+}
+
+/// Runs `python tabbycat/manage.py <args>` in the Tabbycat container (with
+/// the given environment variables).
+fn manage(args: &[&str], env_vars: &[(&str, &str)]) -> Command {
+    let mut command = Command::new("docker");
+    command.args(["compose", "run"]);
+    for (name, value) in env_vars {
+        command.args(["--env", &format!("{name}={value}")]);
+    }
+    command
+        .args(["web", "python", "tabbycat/manage.py"])
+        .args(args);
+    command
+}
+
+/// Clones Tabbycat, starts it with Docker compose (with a fresh database,
+/// unless running in CI) and creates a superuser. Returns the API key of the
+/// superuser.
+pub fn start_instance() -> String {
+    Command::new("git")
+        .args([
+            "clone",
+            "--depth=1",
+            "https://github.com/tabbycatDebate/tabbycat",
+        ])
+        .status()
+        .expect("Failed to clone repository");
+
+    env::set_current_dir("tabbycat").expect("Failed to change directory");
+    Command::new("docker")
+        .args(["compose", "-f", "docker-compose.yml", "up", "--detach"])
+        .status()
+        .expect("Failed to start docker containers");
+
+    loop {
+        match attohttpc::get(TABBYCAT_URL).send() {
+            Ok(res) if res.status().is_success() || res.status().is_redirection() => break,
+            _ => (),
+        }
+    }
+
+    if std::env::var("CI") == Err(std::env::VarError::NotPresent) {
+        manage(&["reset_db", "--no-input"], &[])
+            .status()
+            .expect("Failed to reset database");
+
+        tracing::trace!("Finished reset_db");
+
+        manage(&["migrate", "--no-input"], &[])
+            .status()
+            .expect("Failed to reset database");
+    }
+
+    manage(
+        &["createsuperuser", "--noinput"],
+        &[
+            ("DJANGO_SUPERUSER_PASSWORD", "test"),
+            ("DJANGO_SUPERUSER_EMAIL", "email@example.com"),
+            ("DJANGO_SUPERUSER_USERNAME", "user"),
+        ],
+    )
+    .status()
+    .expect("Failed to create superuser");
+
+    let output = manage(&["dumpdata", "authtoken.token"], &[])
+        .output()
+        .expect("Failed to get auth token");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(stdout.lines().last().unwrap().trim()).unwrap();
+    let api_key = json[0]["pk"].as_str().unwrap().to_string();
+
+    env::set_current_dir("..").expect("Failed to change back to original directory");
+    api_key
+}
+
+/// Creates a tournament called `slug`, and saves its credentials (as
+/// `tabbycat set` would) so that the CLI uses it.
+pub fn create_tournament(api_key: &str, slug: &str) -> Auth {
+    let auth = Auth {
+        tabbycat_url: TABBYCAT_URL.to_string(),
+        tournament_slug: slug.to_string(),
+        api_key: api_key.to_string(),
+    };
+
+    let home_dir = dirs::home_dir().expect("Could not determine home directory");
+    std::fs::write(home_dir.join(".tabbycat"), toml::to_string(&auth).unwrap()).unwrap();
+
+    attohttpc::post(format!("{TABBYCAT_URL}/api/v1/tournaments"))
+        .header("Authorization", format!("Token {api_key}"))
+        .json(&json!({
+            "name": slug,
+            "slug": slug
+        }))
+        .unwrap()
+        .send()
+        .unwrap();
+
+    auth
+}
+
+/// Installs the CLI (from the working directory) so that tests can run it.
+pub fn install_cli() {
+    Command::new("cargo")
+        .args(["install", "--path", "."])
+        .status()
+        .expect("Failed to install package");
+}
+
+/// Runs the CLI with `args`, returning whether it succeeded.
+pub fn tabbycat(args: &[&str]) -> bool {
+    Command::new("tabbycat")
+        .args(args)
+        .status()
+        .expect("Failed to run tabbycat")
+        .success()
+}
+
+/// Imports the fixture data in `fixtures` into the tournament (with any extra
+/// arguments to `tabbycat import`).
+pub fn seed(fixtures: &Fixtures, extra_args: &[&str]) {
+    let paths = [
+        ("--judges-csv", &fixtures.judges),
+        ("--teams-csv", &fixtures.teams),
+        ("--institutions-csv", &fixtures.institutions),
+        ("--clashes-csv", &fixtures.clashes),
+    ];
+    let mut args = vec!["import"];
+    for (flag, path) in &paths {
+        args.push(*flag);
+        args.push(path.to_str().unwrap());
+    }
+    args.extend(extra_args);
+    assert!(tabbycat(&args), "Failed to import data");
+}
+
+/// Fetches `endpoint` (e.g. `teams`) of the tournament from the API.
+pub fn get<T: DeserializeOwned>(auth: &Auth, endpoint: &str) -> T {
+    attohttpc::get(format!(
+        "{}/api/v1/tournaments/{}/{endpoint}",
+        auth.tabbycat_url, auth.tournament_slug
+    ))
+    .header("Authorization", format!("Token {}", auth.api_key))
+    .send()
+    .unwrap()
+    .json()
+    .unwrap()
+}
+
+/// Sends a `POST` request to `endpoint` (e.g. `teams`) of the tournament.
+pub fn post(auth: &Auth, endpoint: &str, body: &serde_json::Value) {
+    attohttpc::post(format!(
+        "{}/api/v1/tournaments/{}/{endpoint}",
+        auth.tabbycat_url, auth.tournament_slug
+    ))
+    .json(body)
+    .unwrap()
+    .header("Authorization", format!("Token {}", auth.api_key))
+    .send()
+    .unwrap();
+}
+
+/// The CSV files to import a tournament from.
+#[derive(Clone, Debug)]
+pub struct Fixtures {
+    pub institutions: PathBuf,
+    pub teams: PathBuf,
+    pub judges: PathBuf,
+    pub clashes: PathBuf,
+}
+
+impl Fixtures {
+    /// The fixtures in `data/` (88 teams and 80 judges).
+    pub fn bp88team() -> Fixtures {
+        Fixtures::in_dir(Path::new("data"))
+    }
+
+    fn in_dir(dir: &Path) -> Fixtures {
+        Fixtures {
+            institutions: dir.join("institutions.csv"),
+            teams: dir.join("teams.csv"),
+            judges: dir.join("judges.csv"),
+            clashes: dir.join("clashes.csv"),
+        }
+    }
+
+    /// Generates a tournament with `teams` teams of `speakers` speakers and
+    /// `judges` judges, spread across institutions of (at most) four teams
+    /// each, in a new directory under the system's temporary directory. Each
+    /// judge is clashed with the institution after their own.
+    pub fn generate(teams: usize, speakers: usize, judges: usize) -> Fixtures {
+        let dir = env::temp_dir().join(format!("tabbycat-fixtures-{teams}-{speakers}-{judges}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fixtures = Fixtures::in_dir(&dir);
+
+        let institutions = teams.div_ceil(4).max(1);
+        let institution = |i: usize| format!("Inst{}", i % institutions + 1);
+
+        let mut writer = csv::Writer::from_path(&fixtures.institutions).unwrap();
+        writer
+            .write_record(["short_code", "region", "full_name"])
+            .unwrap();
+        for i in 0..institutions {
+            writer
+                .write_record([
+                    institution(i),
+                    "Region".to_string(),
+                    format!("University {}", i + 1),
+                ])
+                .unwrap();
+        }
+        writer.flush().unwrap();
+
+        let mut writer = csv::Writer::from_path(&fixtures.teams).unwrap();
+        let mut header = vec![
+            "institution".to_string(),
+            "full_name".to_string(),
+            "use_institution_prefix".to_string(),
+            "categories".to_string(),
+        ];
+        for s in 1..=speakers {
+            for column in ["name", "email", "gender", "categories"] {
+                header.push(format!("speaker{s}_{column}"));
+            }
+        }
+        writer.write_record(&header).unwrap();
+        for t in 0..teams {
+            let mut row = vec![
+                institution(t / 4),
+                format!("Team {}", t + 1),
+                "TRUE".to_string(),
+                "open".to_string(),
+            ];
+            for s in 1..=speakers {
+                row.extend([
+                    format!("Speaker {}-{s}", t + 1),
+                    format!("speaker{}-{s}@example.com", t + 1),
+                    String::new(),
+                    String::new(),
+                ]);
+            }
+            writer.write_record(&row).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let mut writer = csv::Writer::from_path(&fixtures.judges).unwrap();
+        writer
+            .write_record(["name", "email", "gender", "institution", "is_ca", "is_ia"])
+            .unwrap();
+        for j in 0..judges {
+            writer
+                .write_record([
+                    format!("Judge {}", j + 1),
+                    format!("judge{}@example.com", j + 1),
+                    String::new(),
+                    institution(j),
+                    "FALSE".to_string(),
+                    "FALSE".to_string(),
+                ])
+                .unwrap();
+        }
+        writer.flush().unwrap();
+
+        // the clashes file has no header row
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_path(&fixtures.clashes)
+            .unwrap();
+        for j in 0..judges.min(teams) {
+            writer
+                .write_record([format!("Judge {}", j + 1), institution(j + 1)])
+                .unwrap();
+        }
+        writer.flush().unwrap();
+
+        fixtures
+    }
+}